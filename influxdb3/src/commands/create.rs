@@ -1,12 +1,16 @@
 use base64::engine::general_purpose::URL_SAFE_NO_PAD as B64;
 use base64::Engine as _;
+use influxdb3_client::FieldType;
 use rand::rngs::OsRng;
 use rand::RngCore;
+use secrecy::ExposeSecret;
 use sha2::Digest;
 use sha2::Sha512;
 use std::error::Error;
 use std::str;
 
+use super::common::InfluxDb3Config;
+
 #[derive(Debug, clap::Parser)]
 pub struct Config {
     #[clap(subcommand)]
@@ -15,11 +19,169 @@ pub struct Config {
 
 #[derive(Debug, clap::Parser)]
 pub enum SubCommand {
+    /// Create a new database
+    Database(DatabaseConfig),
+    /// Declare a new table in a database, before any data is written to it
+    Table(TableConfig),
+    /// Create a new auth token
     Token,
+    /// Create a new last value cache on a table
+    #[clap(name = "last-cache")]
+    LastCache(LastCacheConfig),
+}
+
+#[derive(Debug, clap::Parser)]
+pub struct DatabaseConfig {
+    /// Common InfluxDB 3.0 config
+    #[clap(flatten)]
+    influxdb3_config: InfluxDb3Config,
+
+    /// How long data is kept in the database, e.g., "30d", otherwise, data is kept indefinitely
+    #[clap(long = "retention-period")]
+    retention_period: Option<String>,
+
+    /// The maximum number of tables in the database, otherwise, only the server's limit applies
+    #[clap(long = "max-tables")]
+    max_tables: Option<usize>,
+
+    /// The maximum number of columns per table in the database, otherwise, only the server's
+    /// limit applies
+    #[clap(long = "max-columns-per-table")]
+    max_columns_per_table: Option<usize>,
+
+    /// Only accept writes to tables and columns that have been declared with `create table`
+    #[clap(long = "strict-schema")]
+    strict_schema: bool,
+}
+
+#[derive(Debug, clap::Parser)]
+pub struct TableConfig {
+    /// Common InfluxDB 3.0 config
+    #[clap(flatten)]
+    influxdb3_config: InfluxDb3Config,
+
+    /// The name of the table
+    #[clap(short = 't', long = "table")]
+    table: String,
+
+    /// A comma-separated list of the table's tags
+    #[clap(long = "tags", value_delimiter = ',')]
+    tags: Vec<String>,
+
+    /// A comma-separated list of the table's fields and their types, given as `name:type`, where
+    /// the type is one of: utf8, int64, uint64, float64, bool
+    #[clap(long = "fields", value_delimiter = ',', value_parser = parse_field)]
+    fields: Vec<(String, FieldType)>,
+
+    /// A comma-separated list of the members of the table's series key, if given, the table uses
+    /// the v3 data model
+    #[clap(long = "series-key", value_delimiter = ',')]
+    series_key: Option<Vec<String>>,
+
+    /// Accept writes of integer values to float fields, and of float values to integer fields,
+    /// by widening the field to a float, rather than rejecting them
+    #[clap(long = "widen-numeric-fields")]
+    widen_numeric_fields: bool,
 }
 
-pub fn command(config: Config) -> Result<(), Box<dyn Error>> {
+fn parse_field(s: &str) -> Result<(String, FieldType), String> {
+    let (name, field_type) = s
+        .split_once(':')
+        .ok_or_else(|| format!("invalid field '{s}', expected `name:type`"))?;
+    Ok((name.to_string(), field_type.parse()?))
+}
+
+#[derive(Debug, clap::Parser)]
+pub struct LastCacheConfig {
+    /// Common InfluxDB 3.0 config
+    #[clap(flatten)]
+    influxdb3_config: InfluxDb3Config,
+
+    /// The table to create the cache on
+    #[clap(short = 't', long = "table")]
+    table: String,
+
+    /// Give a name for the cache, otherwise, one will be generated from the table and key columns
+    #[clap(long = "cache-name")]
+    cache_name: Option<String>,
+
+    /// A comma-separated list of columns to use as keys in the cache, otherwise, the table's
+    /// tags will be used
+    #[clap(long = "key-columns", value_delimiter = ',')]
+    key_columns: Option<Vec<String>>,
+
+    /// A comma-separated list of columns to store as values in the cache, otherwise, all
+    /// non-key columns will be used
+    #[clap(long = "value-columns", value_delimiter = ',')]
+    value_columns: Option<Vec<String>>,
+
+    /// The number of entries per unique key to store in the cache, from 1 to 10, defaults to 1
+    #[clap(long = "count")]
+    count: Option<usize>,
+}
+
+pub async fn command(config: Config) -> Result<(), Box<dyn Error>> {
     match config.cmd {
+        SubCommand::Database(DatabaseConfig {
+            influxdb3_config:
+                InfluxDb3Config {
+                    host_url,
+                    database_name,
+                    auth_token,
+                },
+            retention_period,
+            max_tables,
+            max_columns_per_table,
+            strict_schema,
+        }) => {
+            let mut client = influxdb3_client::Client::new(host_url)?;
+            if let Some(t) = auth_token {
+                client = client.with_auth_token(t.expose_secret());
+            }
+            let mut req = client.api_v3_configure_database_create(&database_name);
+            if let Some(retention_period) = retention_period {
+                req = req.retention_period(retention_period);
+            }
+            if let Some(max_tables) = max_tables {
+                req = req.max_tables(max_tables);
+            }
+            if let Some(max_columns_per_table) = max_columns_per_table {
+                req = req.max_columns_per_table(max_columns_per_table);
+            }
+            req.strict_schema(strict_schema).send().await?;
+            println!("database created: {database_name}");
+        }
+        SubCommand::Table(TableConfig {
+            influxdb3_config:
+                InfluxDb3Config {
+                    host_url,
+                    database_name,
+                    auth_token,
+                },
+            table,
+            tags,
+            fields,
+            series_key,
+            widen_numeric_fields,
+        }) => {
+            let mut client = influxdb3_client::Client::new(host_url)?;
+            if let Some(t) = auth_token {
+                client = client.with_auth_token(t.expose_secret());
+            }
+            let mut req = client
+                .api_v3_configure_table_create(database_name, &table)
+                .tags(tags);
+            for (name, field_type) in fields {
+                req = req.field(name, field_type);
+            }
+            if let Some(series_key) = series_key {
+                req = req.series_key(series_key);
+            }
+            req.widen_numeric_fields(widen_numeric_fields)
+                .send()
+                .await?;
+            println!("table created: {table}");
+        }
         SubCommand::Token => {
             let token = {
                 let mut token = String::from("apiv3_");
@@ -39,6 +201,48 @@ pub fn command(config: Config) -> Result<(), Box<dyn Error>> {
                 hashed = hex::encode(&Sha512::digest(&token)[..])
             );
         }
+        SubCommand::LastCache(LastCacheConfig {
+            influxdb3_config:
+                InfluxDb3Config {
+                    host_url,
+                    database_name,
+                    auth_token,
+                },
+            table,
+            cache_name,
+            key_columns,
+            value_columns,
+            count,
+        }) => {
+            let mut client = influxdb3_client::Client::new(host_url)?;
+            if let Some(t) = auth_token {
+                client = client.with_auth_token(t.expose_secret());
+            }
+            let mut req = client.api_v3_configure_last_cache_create(database_name, table);
+            if let Some(name) = cache_name {
+                req = req.name(name);
+            }
+            if let Some(key_columns) = key_columns {
+                req = req.key_columns(key_columns);
+            }
+            if let Some(value_columns) = value_columns {
+                req = req.value_columns(value_columns);
+            }
+            if let Some(count) = count {
+                req = req.count(count);
+            }
+            let created = req.send().await?;
+            println!(
+                "new cache created: {name}\n\
+                key columns: {keys}\n\
+                value columns: {values}\n\
+                count: {count}",
+                name = created.name,
+                keys = created.key_columns.join(", "),
+                values = created.value_columns.join(", "),
+                count = created.count,
+            );
+        }
     }
     Ok(())
 }
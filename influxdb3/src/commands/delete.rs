@@ -0,0 +1,116 @@
+use secrecy::ExposeSecret;
+use std::error::Error;
+
+use super::common::InfluxDb3Config;
+
+#[derive(Debug, clap::Parser)]
+pub struct Config {
+    #[clap(subcommand)]
+    cmd: SubCommand,
+}
+
+#[derive(Debug, clap::Parser)]
+pub enum SubCommand {
+    /// Delete a database, along with all of its data
+    Database(DatabaseConfig),
+
+    /// Delete a table from a database, along with all of its data
+    Table(TableConfig),
+
+    /// Delete a last value cache from a table
+    #[clap(name = "last-cache")]
+    LastCache(LastCacheConfig),
+}
+
+#[derive(Debug, clap::Parser)]
+pub struct DatabaseConfig {
+    /// Common InfluxDB 3.0 config
+    #[clap(flatten)]
+    influxdb3_config: InfluxDb3Config,
+}
+
+#[derive(Debug, clap::Parser)]
+pub struct TableConfig {
+    /// Common InfluxDB 3.0 config
+    #[clap(flatten)]
+    influxdb3_config: InfluxDb3Config,
+
+    /// The table to delete
+    #[clap(short = 't', long = "table")]
+    table: String,
+}
+
+#[derive(Debug, clap::Parser)]
+pub struct LastCacheConfig {
+    /// Common InfluxDB 3.0 config
+    #[clap(flatten)]
+    influxdb3_config: InfluxDb3Config,
+
+    /// The table that the cache is on
+    #[clap(short = 't', long = "table")]
+    table: String,
+
+    /// The name of the cache to delete
+    #[clap(long = "cache-name")]
+    cache_name: String,
+}
+
+pub async fn command(config: Config) -> Result<(), Box<dyn Error>> {
+    match config.cmd {
+        SubCommand::Database(DatabaseConfig {
+            influxdb3_config:
+                InfluxDb3Config {
+                    host_url,
+                    database_name,
+                    auth_token,
+                },
+        }) => {
+            let mut client = influxdb3_client::Client::new(host_url)?;
+            if let Some(t) = auth_token {
+                client = client.with_auth_token(t.expose_secret());
+            }
+            client
+                .api_v3_configure_database_delete(&database_name)
+                .await?;
+            println!("database {database_name} deleted successfully");
+        }
+        SubCommand::Table(TableConfig {
+            influxdb3_config:
+                InfluxDb3Config {
+                    host_url,
+                    database_name,
+                    auth_token,
+                },
+            table,
+        }) => {
+            let mut client = influxdb3_client::Client::new(host_url)?;
+            if let Some(t) = auth_token {
+                client = client.with_auth_token(t.expose_secret());
+            }
+            client
+                .api_v3_configure_table_delete(database_name, &table)
+                .await?;
+            println!("table {table} deleted successfully");
+        }
+        SubCommand::LastCache(LastCacheConfig {
+            influxdb3_config:
+                InfluxDb3Config {
+                    host_url,
+                    database_name,
+                    auth_token,
+                },
+            table,
+            cache_name,
+        }) => {
+            let mut client = influxdb3_client::Client::new(host_url)?;
+            if let Some(t) = auth_token {
+                client = client.with_auth_token(t.expose_secret());
+            }
+            client
+                .api_v3_configure_last_cache_delete(database_name, table, cache_name)
+                .await?;
+            println!("last cache deleted successfully");
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,83 @@
+use clap::Parser;
+use secrecy::ExposeSecret;
+use tokio::{
+    fs::File,
+    io::{self, AsyncReadExt},
+};
+
+use super::common::InfluxDb3Config;
+
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum Error {
+    #[error(transparent)]
+    Client(#[from] influxdb3_client::Error),
+
+    #[error("error reading file: {0}")]
+    Io(#[from] io::Error),
+}
+
+pub(crate) type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, Parser)]
+pub struct Config {
+    #[clap(subcommand)]
+    cmd: SubCommand,
+}
+
+#[derive(Debug, Parser)]
+pub enum SubCommand {
+    /// Import a parquet file of historical data into a table
+    ///
+    /// The schema of the file is validated against the table, which is created if it doesn't
+    /// exist. The rows are sorted, deduplicated and persisted directly, so they are queryable
+    /// once the import is done.
+    Parquet(ParquetConfig),
+}
+
+#[derive(Debug, Parser)]
+pub struct ParquetConfig {
+    /// Common InfluxDB 3.0 config
+    #[clap(flatten)]
+    influxdb3_config: InfluxDb3Config,
+
+    /// The table to import the data into
+    #[clap(short = 't', long = "table")]
+    table: String,
+
+    /// File path of the parquet file to import
+    #[clap(short = 'f', long = "file")]
+    file_path: String,
+}
+
+pub(crate) async fn command(config: Config) -> Result<()> {
+    match config.cmd {
+        SubCommand::Parquet(ParquetConfig {
+            influxdb3_config:
+                InfluxDb3Config {
+                    host_url,
+                    database_name,
+                    auth_token,
+                },
+            table,
+            file_path,
+        }) => {
+            let mut client = influxdb3_client::Client::new(host_url)?;
+            if let Some(t) = auth_token {
+                client = client.with_auth_token(t.expose_secret());
+            }
+
+            let mut f = File::open(file_path).await?;
+            let mut parquet = Vec::new();
+            f.read_to_end(&mut parquet).await?;
+
+            let imported = client
+                .api_v3_import_parquet(database_name, &table, parquet)
+                .await?;
+            println!(
+                "imported {} rows into table {table}",
+                imported.segment_row_count
+            );
+        }
+    }
+    Ok(())
+}
@@ -14,10 +14,12 @@ use influxdb3_server::{
     auth::AllOrNothingAuthorizer, builder::ServerBuilder, query_executor::QueryExecutorImpl, serve,
     CommonServerState,
 };
+use influxdb3_write::catalog::CatalogLimits;
 use influxdb3_write::persister::PersisterImpl;
-use influxdb3_write::wal::WalImpl;
-use influxdb3_write::write_buffer::WriteBufferImpl;
-use influxdb3_write::SegmentDuration;
+use influxdb3_write::wal::{ObjectStoreWal, WalFlushConfig, WalImpl, WalRecoveryMode, WalSyncMode};
+use influxdb3_write::write_buffer::backpressure::BackpressureConfig;
+use influxdb3_write::write_buffer::{WriteBufferImpl, WriteTimeWindow};
+use influxdb3_write::{SegmentDuration, Wal};
 use iox_query::exec::{DedicatedExecutor, Executor, ExecutorConfig};
 use iox_time::SystemProvider;
 use ioxd_common::reexport::trace_http::ctx::TraceHeaderParser;
@@ -102,6 +104,58 @@ pub struct Config {
     #[clap(long = "wal-directory", env = "INFLUXDB3_WAL_DIRECTORY", action)]
     pub wal_directory: Option<PathBuf>,
 
+    /// Write the write ahead log to the configured object store, under the wal/ prefix, instead
+    /// of a local directory
+    #[clap(
+        long = "wal-object-store",
+        env = "INFLUXDB3_WAL_OBJECT_STORE",
+        conflicts_with = "wal_directory",
+        action
+    )]
+    pub wal_object_store: bool,
+
+    /// How to handle a WAL segment with a block that can't be read on startup, which happens
+    /// when the server crashes part way through writing to the WAL. Valid values: strict, to fail
+    /// startup; truncate, to drop everything from the first unreadable block on; and
+    /// skip-segment, to not load the segment at all.
+    #[clap(
+        long = "wal-recovery-mode",
+        env = "INFLUXDB3_WAL_RECOVERY_MODE",
+        default_value = "truncate",
+        action
+    )]
+    pub wal_recovery_mode: WalRecoveryMode,
+
+    /// When the WAL is fsync'd. Writes are only acknowledged once they are durable under the
+    /// mode. Valid values: always, to fsync every flushed batch of writes; interval=<duration>,
+    /// e.g. interval=100ms, to fsync at most once per interval; and none, to leave it to the OS.
+    #[clap(
+        long = "wal-sync-mode",
+        env = "INFLUXDB3_WAL_SYNC_MODE",
+        default_value = "always",
+        action
+    )]
+    pub wal_sync_mode: WalSyncMode,
+
+    /// How long writes are buffered before they are flushed to the WAL as a batch
+    #[clap(
+        long = "wal-flush-interval",
+        env = "INFLUXDB3_WAL_FLUSH_INTERVAL",
+        default_value = "10ms",
+        action
+    )]
+    pub wal_flush_interval: humantime::Duration,
+
+    /// The number of buffered writes at which they are flushed to the WAL without waiting for
+    /// the flush interval
+    #[clap(
+        long = "wal-max-batch-size",
+        env = "INFLUXDB3_WAL_MAX_BATCH_SIZE",
+        default_value = "10000",
+        action
+    )]
+    pub wal_max_batch_size: usize,
+
     /// The address on which InfluxDB will serve HTTP API requests
     #[clap(
     long = "http-bind",
@@ -177,6 +231,79 @@ pub struct Config {
         action
     )]
     pub buffer_mem_limit_mb: usize,
+
+    /// The number of writes waiting to be flushed to the WAL at which new writes are rejected
+    /// with a 429 response, asking clients to retry later. At most 10000 writes can be queued.
+    #[clap(
+        long = "max-queued-writes",
+        env = "INFLUXDB3_MAX_QUEUED_WRITES",
+        default_value = "10000",
+        action
+    )]
+    pub max_queued_writes: usize,
+
+    /// The size of the open segments in the write buffer at which new writes are rejected with a
+    /// 503 response, asking clients to retry once the buffer has been persisted to free up
+    /// memory. If not set, writes aren't rejected because of the buffer size.
+    ///
+    /// Reaching `--buffer-mem-limit-mb` starts persisting the buffer to shed memory, so this
+    /// should be set well above it, to only reject writes while persistence can't keep up.
+    #[clap(
+        long = "buffer-mem-reject-mb",
+        env = "INFLUXDB3_BUFFER_MEM_REJECT_MB",
+        action
+    )]
+    pub buffer_mem_reject_mb: Option<usize>,
+
+    /// Lines with timestamps more than this long before the server time are rejected, e.g.,
+    /// "30d". If not set, lines are accepted however old they are.
+    #[clap(long = "max-write-age", env = "INFLUXDB3_MAX_WRITE_AGE", action)]
+    pub max_write_age: Option<humantime::Duration>,
+
+    /// Lines with timestamps more than this long after the server time are rejected, e.g.,
+    /// "1h". If not set, lines are accepted however far in the future they are.
+    #[clap(long = "max-write-future", env = "INFLUXDB3_MAX_WRITE_FUTURE", action)]
+    pub max_write_future: Option<humantime::Duration>,
+
+    /// The maximum number of databases that can be created on the server.
+    #[clap(
+        long = "max-databases",
+        env = "INFLUXDB3_MAX_DATABASES",
+        default_value = "5",
+        action
+    )]
+    pub max_databases: usize,
+
+    /// The maximum number of tables that can be created on the server, across all databases.
+    /// Databases can be configured with a lower limit.
+    #[clap(
+        long = "max-tables",
+        env = "INFLUXDB3_MAX_TABLES",
+        default_value = "2000",
+        action
+    )]
+    pub max_tables: usize,
+
+    /// The maximum number of columns per table, including the time column. Databases can be
+    /// configured with a lower limit.
+    #[clap(
+        long = "max-columns-per-table",
+        env = "INFLUXDB3_MAX_COLUMNS_PER_TABLE",
+        default_value = "500",
+        action
+    )]
+    pub max_columns_per_table: usize,
+
+    /// The number of unique key column values held by each last cache. Once a cache holds more,
+    /// the keys whose most recent row is the oldest are evicted, and the cache no longer returns
+    /// rows for them.
+    #[clap(
+        long = "last-cache-max-keys",
+        env = "INFLUXDB3_LAST_CACHE_MAX_KEYS",
+        default_value = "100000",
+        action
+    )]
+    pub last_cache_max_keys: usize,
 }
 
 /// If `p` does not exist, try to create it as a directory.
@@ -273,10 +400,28 @@ pub async fn command(config: Config) -> Result<()> {
         *config.http_bind_address,
     )?;
     let persister = Arc::new(PersisterImpl::new(Arc::clone(&object_store)));
-    let wal: Option<Arc<WalImpl>> = config
-        .wal_directory
-        .map(|dir| WalImpl::new(dir).map(Arc::new))
-        .transpose()?;
+    let wal_flush_config = WalFlushConfig {
+        flush_interval: config.wal_flush_interval.into(),
+        max_batch_size: config.wal_max_batch_size,
+        sync_mode: config.wal_sync_mode,
+    };
+    let wal: Option<Arc<Box<dyn Wal>>> = if config.wal_object_store {
+        let wal =
+            ObjectStoreWal::new(Arc::clone(&object_store))?.with_flush_config(wal_flush_config);
+        Some(Arc::new(Box::new(wal) as Box<dyn Wal>))
+    } else {
+        config
+            .wal_directory
+            .map(|dir| {
+                WalImpl::new(dir).map(|wal| {
+                    let wal = wal
+                        .with_recovery_mode(config.wal_recovery_mode)
+                        .with_flush_config(wal_flush_config);
+                    Arc::new(Box::new(wal) as Box<dyn Wal>)
+                })
+            })
+            .transpose()?
+    };
 
     let time_provider = Arc::new(SystemProvider::new());
     let write_buffer = Arc::new(
@@ -286,14 +431,30 @@ pub async fn command(config: Config) -> Result<()> {
             Arc::clone(&time_provider),
             config.segment_duration,
             Arc::clone(&exec),
+            Arc::clone(&metrics),
             config.buffer_mem_limit_mb,
+            CatalogLimits {
+                max_databases: config.max_databases,
+                max_tables: config.max_tables,
+                max_columns_per_table: config.max_columns_per_table,
+            },
         )
-        .await?,
+        .await?
+        .with_backpressure_config(BackpressureConfig {
+            max_queued_writes: config.max_queued_writes,
+            max_buffer_mem_mb: config.buffer_mem_reject_mb,
+        })
+        .with_write_time_window(WriteTimeWindow {
+            max_write_age: config.max_write_age.map(Into::into),
+            max_write_future: config.max_write_future.map(Into::into),
+        })
+        .with_last_cache_max_keys(config.last_cache_max_keys),
     );
     let query_executor = Arc::new(QueryExecutorImpl::new(
         write_buffer.catalog(),
         Arc::clone(&write_buffer),
         Arc::clone(&exec),
+        Arc::clone(&time_provider) as _,
         Arc::clone(&metrics),
         Arc::new(config.datafusion_config),
         10,
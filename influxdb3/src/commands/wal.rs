@@ -0,0 +1,336 @@
+use std::path::PathBuf;
+
+use clap::{Parser, ValueEnum};
+use influxdb3_write::wal::{self, WalImpl, WalSegmentReaderImpl};
+use influxdb3_write::{ArrowWriteOp, SegmentFile, Wal, WalOp, WalOpBatch, WalSegmentReader};
+use serde_json::json;
+
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum Error {
+    #[error("wal directory {0:?} does not exist")]
+    WalDirectoryDoesntExist(PathBuf),
+
+    #[error(transparent)]
+    Wal(#[from] wal::Error),
+
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("error serializing to json: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("error decoding the record batches of an arrow write: {0}")]
+    ArrowWrite(String),
+
+    #[error("found corruption in {corrupt} of {total} segment files")]
+    Corruption { corrupt: usize, total: usize },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, Parser)]
+pub struct Config {
+    #[clap(subcommand)]
+    cmd: SubCommand,
+}
+
+#[derive(Debug, Parser)]
+pub enum SubCommand {
+    /// List the segment files in the WAL directory along with their time ranges and sizes
+    List(ListConfig),
+
+    /// Print a summary of every batch of operations in the WAL segment files
+    Dump(DumpConfig),
+
+    /// Read every block in the WAL segment files and report any corruption
+    ///
+    /// Exits with a non-zero status if any segment file is corrupt.
+    Verify(VerifyConfig),
+}
+
+#[derive(Debug, Parser)]
+pub struct WalConfig {
+    /// The directory containing the WAL segment files
+    #[clap(long = "wal-directory", env = "INFLUXDB3_WAL_DIRECTORY", action)]
+    wal_directory: PathBuf,
+
+    /// Only inspect the segment file with this id
+    #[clap(long = "segment-id")]
+    segment_id: Option<u32>,
+}
+
+#[derive(Debug, Parser)]
+pub struct ListConfig {
+    #[clap(flatten)]
+    wal_config: WalConfig,
+}
+
+#[derive(Debug, Parser)]
+pub struct DumpConfig {
+    #[clap(flatten)]
+    wal_config: WalConfig,
+
+    /// The format in which to output the batches
+    ///
+    /// `json` outputs one JSON object per batch.
+    #[clap(value_enum, long = "fmt", default_value = "pretty")]
+    output_format: Format,
+}
+
+#[derive(Debug, Parser)]
+pub struct VerifyConfig {
+    #[clap(flatten)]
+    wal_config: WalConfig,
+}
+
+#[derive(Debug, ValueEnum, Clone, Copy)]
+#[clap(rename_all = "snake_case")]
+enum Format {
+    Pretty,
+    Json,
+}
+
+impl WalConfig {
+    fn segment_files(&self) -> Result<Vec<SegmentFile>> {
+        // opening the wal would create the directory, which an inspection tool shouldn't do
+        if !self.wal_directory.is_dir() {
+            return Err(Error::WalDirectoryDoesntExist(self.wal_directory.clone()));
+        }
+        let wal = WalImpl::new(self.wal_directory.clone())?;
+        let mut segment_files = wal.segment_files()?;
+        if let Some(segment_id) = self.segment_id {
+            segment_files.retain(|f| f.segment_id.as_u32() == segment_id);
+        }
+        Ok(segment_files)
+    }
+
+    fn open_reader(&self, segment_file: &SegmentFile) -> Result<WalSegmentReaderImpl> {
+        Ok(WalSegmentReaderImpl::new(
+            self.wal_directory.clone(),
+            segment_file.segment_id,
+        )?)
+    }
+}
+
+pub(crate) async fn command(config: Config) -> Result<()> {
+    match config.cmd {
+        SubCommand::List(ListConfig { wal_config }) => list(&wal_config),
+        SubCommand::Dump(DumpConfig {
+            wal_config,
+            output_format,
+        }) => dump(&wal_config, output_format),
+        SubCommand::Verify(VerifyConfig { wal_config }) => verify(&wal_config),
+    }
+}
+
+fn list(wal_config: &WalConfig) -> Result<()> {
+    println!(
+        "{:>10}  {:<8}  {:<25}  {:<25}  {:>12}",
+        "segment", "version", "start_time", "end_time", "size_bytes"
+    );
+    for segment_file in wal_config.segment_files()? {
+        let size_bytes = std::fs::metadata(&segment_file.path)?.len();
+        match wal_config.open_reader(&segment_file) {
+            Ok(reader) => {
+                let range = reader.header().range;
+                println!(
+                    "{:>10}  {:<8}  {:<25}  {:<25}  {:>12}{}",
+                    segment_file.segment_id.as_u32(),
+                    reader.version().to_string(),
+                    range.start_time.date_time().to_rfc3339(),
+                    range.end_time.date_time().to_rfc3339(),
+                    size_bytes,
+                    if range.contains_data_outside_range {
+                        "  (contains data outside range)"
+                    } else {
+                        ""
+                    },
+                );
+            }
+            Err(e) => println!(
+                "{:>10}  unreadable header: {e}",
+                segment_file.segment_id.as_u32()
+            ),
+        }
+    }
+    Ok(())
+}
+
+fn dump(wal_config: &WalConfig, format: Format) -> Result<()> {
+    for segment_file in wal_config.segment_files()? {
+        let segment_id = segment_file.segment_id.as_u32();
+        let mut reader = wal_config.open_reader(&segment_file)?;
+        if let Format::Pretty = format {
+            let range = reader.header().range;
+            println!(
+                "segment {segment_id} ({}): {} to {}",
+                reader.version(),
+                range.start_time.date_time().to_rfc3339(),
+                range.end_time.date_time().to_rfc3339(),
+            );
+        }
+        while let Some(batch) = reader.next_batch()? {
+            match format {
+                Format::Pretty => print_batch(&batch)?,
+                Format::Json => println!("{}", batch_json(segment_id, &batch)?),
+            }
+        }
+    }
+    Ok(())
+}
+
+fn print_batch(batch: &WalOpBatch) -> Result<()> {
+    println!(
+        "  batch {}: {} ops",
+        batch.sequence_number.as_u32(),
+        batch.ops.len()
+    );
+    for op in &batch.ops {
+        match op {
+            WalOp::LpWrite(write) => println!(
+                "    lp write: db {}, {} lines, precision {:?}",
+                write.db_name,
+                line_count(&write.lp),
+                write.precision
+            ),
+            WalOp::ParquetWrite(write) => println!(
+                "    parquet write: db {}, table {}, {} rows, {}",
+                write.db_name, write.table_name, write.row_count, write.path
+            ),
+            WalOp::Catalog(op) => println!(
+                "    catalog op: db {}, {}",
+                op.db_name(),
+                serde_json::to_string(op)?
+            ),
+            WalOp::Delete(op) => println!(
+                "    delete: db {}, table {}, {}",
+                op.db_name,
+                op.table_name,
+                serde_json::to_string(&op.predicate)?
+            ),
+            WalOp::ArrowWrite(write) => println!(
+                "    arrow write: db {}, table {}, {} rows",
+                write.db_name,
+                write.table_name,
+                arrow_row_count(write)?
+            ),
+        }
+    }
+    Ok(())
+}
+
+fn batch_json(segment_id: u32, batch: &WalOpBatch) -> Result<String> {
+    let ops = batch
+        .ops
+        .iter()
+        .map(|op| {
+            Ok(match op {
+                WalOp::LpWrite(write) => json!({
+                    "op": "lp_write",
+                    "db_name": write.db_name,
+                    "lines": line_count(&write.lp),
+                    "precision": write.precision,
+                }),
+                WalOp::ParquetWrite(write) => json!({
+                    "op": "parquet_write",
+                    "db_name": write.db_name,
+                    "table_name": write.table_name,
+                    "path": write.path,
+                    "row_count": write.row_count,
+                    "size_bytes": write.size_bytes,
+                }),
+                WalOp::Catalog(op) => json!({
+                    "op": "catalog",
+                    "db_name": op.db_name(),
+                    "catalog_op": op,
+                }),
+                WalOp::Delete(op) => json!({
+                    "op": "delete",
+                    "db_name": op.db_name,
+                    "table_name": op.table_name,
+                    "predicate": op.predicate,
+                }),
+                WalOp::ArrowWrite(write) => json!({
+                    "op": "arrow_write",
+                    "db_name": write.db_name,
+                    "table_name": write.table_name,
+                    "row_count": arrow_row_count(write)?,
+                }),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(serde_json::to_string(&json!({
+        "segment_id": segment_id,
+        "sequence_number": batch.sequence_number.as_u32(),
+        "ops": ops,
+    }))?)
+}
+
+fn arrow_row_count(write: &ArrowWriteOp) -> Result<usize> {
+    let batches = write
+        .record_batches()
+        .map_err(|e| Error::ArrowWrite(e.to_string()))?;
+    Ok(batches.iter().map(|batch| batch.num_rows()).sum())
+}
+
+fn line_count(lp: &str) -> usize {
+    lp.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .count()
+}
+
+fn verify(wal_config: &WalConfig) -> Result<()> {
+    let segment_files = wal_config.segment_files()?;
+    let mut corrupt = 0;
+    for segment_file in &segment_files {
+        let segment_id = segment_file.segment_id.as_u32();
+        let path = segment_file.path.display();
+        let mut reader = match wal_config.open_reader(segment_file) {
+            Ok(reader) => reader,
+            Err(e) => {
+                println!("segment {segment_id} ({path}): CORRUPT: unable to read header: {e}");
+                corrupt += 1;
+                continue;
+            }
+        };
+
+        let mut blocks = 0;
+        let mut errors = 0;
+        loop {
+            let offset = reader.offset()?;
+            match reader.next_batch() {
+                Ok(Some(_)) => blocks += 1,
+                Ok(None) => break,
+                Err(e) => {
+                    errors += 1;
+                    println!(
+                        "segment {segment_id} ({path}): block {blocks} at byte offset {offset}: {e}"
+                    );
+                    // the whole block was read, so the next one can still be checked
+                    if matches!(e, wal::Error::ChecksumMismatch { .. }) {
+                        blocks += 1;
+                        continue;
+                    }
+                    break;
+                }
+            }
+        }
+
+        if errors == 0 {
+            println!("segment {segment_id} ({path}): OK, {blocks} blocks");
+        } else {
+            println!("segment {segment_id} ({path}): CORRUPT, {errors} errors");
+            corrupt += 1;
+        }
+    }
+
+    if corrupt > 0 {
+        return Err(Error::Corruption {
+            corrupt,
+            total: segment_files.len(),
+        });
+    }
+    Ok(())
+}
@@ -1,4 +1,4 @@
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use secrecy::ExposeSecret;
 use tokio::{
     fs::File,
@@ -26,16 +26,35 @@ pub struct Config {
     influxdb3_config: InfluxDb3Config,
 
     /// File path to load the write data from
-    ///
-    /// Currently, only files containing line protocol are supported.
     #[clap(short = 'f', long = "file")]
     file_path: String,
 
+    /// The format of the write data
+    ///
+    /// JSON data is either an array of rows, or newline delimited JSON with a row on each line.
+    /// Each row is an object with its `table`, `tags`, `fields` and `timestamp`.
+    #[clap(value_enum, long = "format", default_value = "lp")]
+    format: Format,
+
     /// Flag to request the server accept partial writes
     ///
     /// Invalid lines in the input data will be ignored by the server.
     #[clap(long = "accept-partial")]
     accept_partial_writes: bool,
+
+    /// The number of times to retry the write if the server is overloaded
+    ///
+    /// The server responds to writes it can't accept yet with how long to wait before retrying.
+    #[clap(long = "max-retries", default_value = "3")]
+    max_retries: usize,
+}
+
+#[derive(Debug, ValueEnum, Clone, Copy)]
+enum Format {
+    /// Line protocol
+    Lp,
+    /// Rows of JSON
+    Json,
 }
 
 pub(crate) async fn command(config: Config) -> Result<()> {
@@ -53,7 +72,11 @@ pub(crate) async fn command(config: Config) -> Result<()> {
     let mut writes = Vec::new();
     f.read_to_end(&mut writes).await?;
 
-    let mut req = client.api_v3_write_lp(database_name);
+    let mut req = match config.format {
+        Format::Lp => client.api_v3_write_lp(database_name),
+        Format::Json => client.api_v3_write_json(database_name),
+    }
+    .max_retries(config.max_retries);
     if config.accept_partial_writes {
         req = req.accept_partial(true);
     }
@@ -27,8 +27,11 @@ use trogging::{
 mod commands {
     pub(crate) mod common;
     pub mod create;
+    pub mod delete;
+    pub mod import;
     pub mod query;
     pub mod serve;
+    pub mod wal;
     pub mod write;
 }
 
@@ -87,6 +90,15 @@ enum Command {
 
     /// Create new resources
     Create(commands::create::Config),
+
+    /// Delete existing resources
+    Delete(commands::delete::Config),
+
+    /// Import historical data from files
+    Import(commands::import::Config),
+
+    /// Inspect and verify the segment files in a WAL directory
+    Wal(commands::wal::Config),
 }
 
 fn main() -> Result<(), std::io::Error> {
@@ -133,11 +145,29 @@ fn main() -> Result<(), std::io::Error> {
                 }
             }
             Some(Command::Create(config)) => {
-                if let Err(e) = commands::create::command(config) {
+                if let Err(e) = commands::create::command(config).await {
                     eprintln!("Create command failed: {e}");
                     std::process::exit(ReturnCode::Failure as _)
                 }
             }
+            Some(Command::Delete(config)) => {
+                if let Err(e) = commands::delete::command(config).await {
+                    eprintln!("Delete command failed: {e}");
+                    std::process::exit(ReturnCode::Failure as _)
+                }
+            }
+            Some(Command::Import(config)) => {
+                if let Err(e) = commands::import::command(config).await {
+                    eprintln!("Import command failed: {e}");
+                    std::process::exit(ReturnCode::Failure as _)
+                }
+            }
+            Some(Command::Wal(config)) => {
+                if let Err(e) = commands::wal::command(config).await {
+                    eprintln!("Wal command failed: {e}");
+                    std::process::exit(ReturnCode::Failure as _)
+                }
+            }
         }
     });
 
@@ -0,0 +1,514 @@
+use crate::TestServer;
+use influxdb3_client::Precision;
+use pretty_assertions::assert_eq;
+use serde_json::{json, Value};
+
+#[tokio::test]
+async fn api_v3_configure_last_cache() {
+    let server = TestServer::spawn().await;
+    let client = reqwest::Client::new();
+    let url = format!(
+        "{base}/api/v3/configure/last_cache",
+        base = server.client_addr()
+    );
+
+    server
+        .write_lp_to_db("foo", "cpu,host=a,region=us usage=0.9 1", Precision::Second)
+        .await
+        .unwrap();
+
+    // Create a cache on the table:
+    let resp = client
+        .post(&url)
+        .json(&json!({
+            "db": "foo",
+            "table": "cpu",
+            "key_columns": ["host"],
+            "value_columns": ["usage"],
+            "count": 2
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(reqwest::StatusCode::CREATED, resp.status());
+    assert_eq!(
+        json!({
+            "name": "cpu_host_last_cache",
+            "key_columns": ["host"],
+            "value_columns": ["usage"],
+            "count": 2
+        }),
+        resp.json::<Value>().await.unwrap()
+    );
+
+    // Creating a cache with the same name is a conflict:
+    let resp = client
+        .post(&url)
+        .json(&json!({
+            "db": "foo",
+            "table": "cpu",
+            "key_columns": ["host"]
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(reqwest::StatusCode::CONFLICT, resp.status());
+
+    // Using a column that does not exist is a bad request:
+    let resp = client
+        .post(&url)
+        .json(&json!({
+            "db": "foo",
+            "table": "cpu",
+            "key_columns": ["zone"]
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(reqwest::StatusCode::BAD_REQUEST, resp.status());
+
+    // Creating a cache on a table that does not exist is not found:
+    let resp = client
+        .post(&url)
+        .json(&json!({
+            "db": "foo",
+            "table": "mem"
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(reqwest::StatusCode::NOT_FOUND, resp.status());
+
+    // List the caches for the database:
+    let resp = client
+        .get(&url)
+        .query(&[("db", "foo")])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(reqwest::StatusCode::OK, resp.status());
+    assert_eq!(
+        json!([{
+            "table": "cpu",
+            "name": "cpu_host_last_cache",
+            "key_columns": ["host"],
+            "value_columns": ["usage"],
+            "count": 2
+        }]),
+        resp.json::<Value>().await.unwrap()
+    );
+
+    // Writes after the cache was created are available through the cache:
+    server
+        .write_lp_to_db(
+            "foo",
+            "cpu,host=a,region=us usage=0.8 2\n\
+            cpu,host=b,region=us usage=0.7 2\n\
+            cpu,host=a,region=us usage=0.6 3",
+            Precision::Second,
+        )
+        .await
+        .unwrap();
+    let resp = server
+        .api_v3_query_sql(&[
+            ("db", "foo"),
+            (
+                "q",
+                "SELECT * FROM last_cache('cpu') ORDER BY host, time DESC",
+            ),
+            ("format", "pretty"),
+        ])
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert_eq!(
+        "+------+-------+---------------------+\n\
+        | host | usage | time                |\n\
+        +------+-------+---------------------+\n\
+        | a    | 0.6   | 1970-01-01T00:00:03 |\n\
+        | a    | 0.8   | 1970-01-01T00:00:02 |\n\
+        | b    | 0.7   | 1970-01-01T00:00:02 |\n\
+        +------+-------+---------------------+",
+        resp
+    );
+
+    // Delete the cache, and then try deleting it again:
+    let params = [
+        ("db", "foo"),
+        ("table", "cpu"),
+        ("name", "cpu_host_last_cache"),
+    ];
+    let resp = client.delete(&url).query(&params).send().await.unwrap();
+    assert_eq!(reqwest::StatusCode::OK, resp.status());
+    let resp = client.delete(&url).query(&params).send().await.unwrap();
+    assert_eq!(reqwest::StatusCode::NOT_FOUND, resp.status());
+
+    // No caches are left:
+    let resp = client
+        .get(&url)
+        .query(&[("db", "foo"), ("table", "cpu")])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(json!([]), resp.json::<Value>().await.unwrap());
+}
+
+#[tokio::test]
+async fn api_v3_configure_database() {
+    let server = TestServer::spawn().await;
+    let client = reqwest::Client::new();
+    let url = format!(
+        "{base}/api/v3/configure/database",
+        base = server.client_addr()
+    );
+
+    // Create a database with a retention period:
+    let resp = client
+        .post(&url)
+        .json(&json!({
+            "db": "foo",
+            "retention_period": "1h"
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(reqwest::StatusCode::CREATED, resp.status());
+
+    // Creating it again is a conflict:
+    let resp = client
+        .post(&url)
+        .json(&json!({ "db": "foo" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(reqwest::StatusCode::CONFLICT, resp.status());
+
+    // An invalid retention period is a bad request:
+    let resp = client
+        .post(&url)
+        .json(&json!({
+            "db": "bar",
+            "retention_period": "forever"
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(reqwest::StatusCode::BAD_REQUEST, resp.status());
+
+    // Updating a database that does not exist is not found:
+    let resp = client
+        .put(&url)
+        .json(&json!({
+            "db": "bar",
+            "retention_period": "1d"
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(reqwest::StatusCode::NOT_FOUND, resp.status());
+
+    let retention_policies = || async {
+        server
+            .api_v3_query_influxql(&[("q", "SHOW RETENTION POLICIES"), ("format", "pretty")])
+            .await
+            .text()
+            .await
+            .unwrap()
+    };
+    let one_hour = "+---------------+---------+---------------+\n\
+        | iox::database | name    | duration      |\n\
+        +---------------+---------+---------------+\n\
+        | foo           | autogen | 3600000000000 |\n\
+        +---------------+---------+---------------+";
+    assert_eq!(one_hour, retention_policies().await);
+
+    // Updating the limits leaves the retention period as it is:
+    let resp = client
+        .put(&url)
+        .json(&json!({
+            "db": "foo",
+            "max_tables": 10
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(reqwest::StatusCode::OK, resp.status());
+    assert_eq!(one_hour, retention_policies().await);
+
+    // Remove the retention period:
+    let resp = client
+        .put(&url)
+        .json(&json!({
+            "db": "foo",
+            "retention_period": "none"
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(reqwest::StatusCode::OK, resp.status());
+    assert_eq!(
+        "+---------------+---------+----------+\n\
+        | iox::database | name    | duration |\n\
+        +---------------+---------+----------+\n\
+        | foo           | autogen |          |\n\
+        +---------------+---------+----------+",
+        retention_policies().await
+    );
+
+    // Delete the database:
+    let resp = client
+        .delete(&url)
+        .query(&[("db", "foo")])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(reqwest::StatusCode::OK, resp.status());
+
+    // Deleting it again is not found:
+    let resp = client
+        .delete(&url)
+        .query(&[("db", "foo")])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(reqwest::StatusCode::NOT_FOUND, resp.status());
+
+    // Not providing a database is a bad request:
+    let resp = client.delete(&url).send().await.unwrap();
+    assert_eq!(reqwest::StatusCode::BAD_REQUEST, resp.status());
+
+    // The database can be created again once deleted:
+    let resp = client
+        .post(&url)
+        .json(&json!({ "db": "foo" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(reqwest::StatusCode::CREATED, resp.status());
+}
+
+#[tokio::test]
+async fn api_v3_configure_table_delete() {
+    let server = TestServer::spawn().await;
+    let client = reqwest::Client::new();
+    let url = format!("{base}/api/v3/configure/table", base = server.client_addr());
+
+    server
+        .write_lp_to_db(
+            "foo",
+            "cpu,host=a usage=0.9 1\n\
+            mem,host=a usage=0.5 1\n\
+            disk,host=a used=10 1",
+            Precision::Second,
+        )
+        .await
+        .unwrap();
+
+    // Delete a table:
+    let resp = client
+        .delete(&url)
+        .query(&[("db", "foo"), ("table", "cpu")])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(reqwest::StatusCode::OK, resp.status());
+
+    // Deleting it again is not found:
+    let resp = client
+        .delete(&url)
+        .query(&[("db", "foo"), ("table", "cpu")])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(reqwest::StatusCode::NOT_FOUND, resp.status());
+
+    // Not providing the table is a bad request:
+    let resp = client
+        .delete(&url)
+        .query(&[("db", "foo")])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(reqwest::StatusCode::BAD_REQUEST, resp.status());
+
+    // Delete a table using InfluxQL:
+    let resp = server
+        .api_v3_query_influxql(&[("db", "foo"), ("q", "DROP MEASUREMENT mem")])
+        .await;
+    assert_eq!(reqwest::StatusCode::OK, resp.status());
+
+    let resp = server
+        .api_v3_query_influxql(&[
+            ("db", "foo"),
+            ("q", "SHOW MEASUREMENTS"),
+            ("format", "pretty"),
+        ])
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert_eq!(
+        "+------------------+------+\n\
+        | iox::measurement | name |\n\
+        +------------------+------+\n\
+        | measurements     | disk |\n\
+        +------------------+------+",
+        resp
+    );
+}
+
+#[tokio::test]
+async fn api_v3_configure_table_create() {
+    let server = TestServer::spawn().await;
+    let client = reqwest::Client::new();
+    let db_url = format!(
+        "{base}/api/v3/configure/database",
+        base = server.client_addr()
+    );
+    let url = format!("{base}/api/v3/configure/table", base = server.client_addr());
+
+    // Declaring a table in a database that does not exist is not found:
+    let table_body = json!({
+        "db": "foo",
+        "table": "cpu",
+        "tags": ["host"],
+        "fields": [
+            {"name": "usage", "type": "float64"},
+            {"name": "count", "type": "uint64"}
+        ]
+    });
+    let resp = client.post(&url).json(&table_body).send().await.unwrap();
+    assert_eq!(reqwest::StatusCode::NOT_FOUND, resp.status());
+
+    // Create a database with a strict schema, and declare a table in it:
+    let resp = client
+        .post(&db_url)
+        .json(&json!({"db": "foo", "strict_schema": true}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(reqwest::StatusCode::CREATED, resp.status());
+    let resp = client.post(&url).json(&table_body).send().await.unwrap();
+    assert_eq!(reqwest::StatusCode::CREATED, resp.status());
+
+    // Declaring the table again is a conflict:
+    let resp = client.post(&url).json(&table_body).send().await.unwrap();
+    assert_eq!(reqwest::StatusCode::CONFLICT, resp.status());
+
+    // An invalid table definition is a bad request:
+    let resp = client
+        .post(&url)
+        .json(&json!({
+            "db": "foo",
+            "table": "mem",
+            "tags": ["host"],
+            "series_key": ["region"]
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(reqwest::StatusCode::BAD_REQUEST, resp.status());
+
+    // Writes to the declared table are accepted, while writes to undeclared tables or columns
+    // are rejected:
+    server
+        .write_lp_to_db("foo", "cpu,host=a usage=0.9,count=1u 1", Precision::Second)
+        .await
+        .unwrap();
+    for lp in ["cpu,host=a,region=us usage=0.9 2", "mem,host=a used=5 2"] {
+        let Err(influxdb3_client::Error::ApiError { code, message }) =
+            server.write_lp_to_db("foo", lp, Precision::Second).await
+        else {
+            panic!("write to an undeclared table or column was accepted: {lp}");
+        };
+        assert_eq!(reqwest::StatusCode::BAD_REQUEST, code);
+        assert!(message.contains("strict schema"), "{message}");
+    }
+
+    let resp = server
+        .api_v3_query_sql(&[
+            ("db", "foo"),
+            ("q", "SELECT * FROM cpu"),
+            ("format", "pretty"),
+        ])
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert_eq!(
+        "+-------+------+---------------------+-------+\n\
+        | count | host | time                | usage |\n\
+        +-------+------+---------------------+-------+\n\
+        | 1     | a    | 1970-01-01T00:00:01 | 0.9   |\n\
+        +-------+------+---------------------+-------+",
+        resp
+    );
+}
+
+#[tokio::test]
+async fn api_v3_configure_table_update() {
+    let server = TestServer::spawn().await;
+    let client = reqwest::Client::new();
+    let url = format!("{base}/api/v3/configure/table", base = server.client_addr());
+
+    server
+        .write_lp_to_db("foo", "cpu,host=a usage=1i 1", Precision::Second)
+        .await
+        .unwrap();
+
+    // Updating a table that does not exist is not found:
+    let resp = client
+        .put(&url)
+        .json(&json!({"db": "foo", "table": "mem", "widen_numeric_fields": true}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(reqwest::StatusCode::NOT_FOUND, resp.status());
+
+    // A float written to the integer field is rejected until widening is enabled:
+    let Err(influxdb3_client::Error::ApiError { code, .. }) = server
+        .write_lp_to_db("foo", "cpu,host=a usage=1.5 2", Precision::Second)
+        .await
+    else {
+        panic!("write with a conflicting field type was accepted");
+    };
+    assert_eq!(reqwest::StatusCode::BAD_REQUEST, code);
+
+    let resp = client
+        .put(&url)
+        .json(&json!({"db": "foo", "table": "cpu", "widen_numeric_fields": true}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(reqwest::StatusCode::OK, resp.status());
+    server
+        .write_lp_to_db(
+            "foo",
+            "cpu,host=a usage=1.5 2\ncpu,host=a usage=2i 3",
+            Precision::Second,
+        )
+        .await
+        .unwrap();
+
+    let resp = server
+        .api_v3_query_sql(&[
+            ("db", "foo"),
+            ("q", "SELECT * FROM cpu"),
+            ("format", "pretty"),
+        ])
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert_eq!(
+        "+------+---------------------+-------+\n\
+        | host | time                | usage |\n\
+        +------+---------------------+-------+\n\
+        | a    | 1970-01-01T00:00:01 | 1.0   |\n\
+        | a    | 1970-01-01T00:00:02 | 1.5   |\n\
+        | a    | 1970-01-01T00:00:03 | 2.0   |\n\
+        +------+---------------------+-------+",
+        resp
+    );
+}
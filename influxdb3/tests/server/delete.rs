@@ -0,0 +1,114 @@
+use crate::TestServer;
+use influxdb3_client::Precision;
+use pretty_assertions::assert_eq;
+use serde_json::json;
+
+#[tokio::test]
+async fn api_v3_delete() {
+    let server = TestServer::spawn().await;
+    let client = reqwest::Client::new();
+    let url = format!("{base}/api/v3/delete", base = server.client_addr());
+
+    server
+        .write_lp_to_db(
+            "foo",
+            "cpu,host=a,region=us-east usage=0.9 1\n\
+            cpu,host=a,region=us-west usage=0.8 2\n\
+            cpu,host=b,region=us-east usage=0.7 3\n\
+            cpu,host=a,region=us-east usage=0.6 4",
+            Precision::Second,
+        )
+        .await
+        .unwrap();
+
+    // Deleting from a table that does not exist is not found:
+    let resp = client
+        .post(&url)
+        .json(&json!({"db": "foo", "table": "mem"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(reqwest::StatusCode::NOT_FOUND, resp.status());
+
+    // An unparseable predicate, time, or a tag that is not in the table is a bad request:
+    for body in [
+        json!({"db": "foo", "table": "cpu", "predicate": "host=\"a\" OR host=\"b\""}),
+        json!({"db": "foo", "table": "cpu", "start": "yesterday"}),
+        json!({"db": "foo", "table": "cpu", "predicate": "usage=\"0.9\""}),
+        json!({
+            "db": "foo",
+            "table": "cpu",
+            "start": "1970-01-01T00:00:03Z",
+            "stop": "1970-01-01T00:00:02Z"
+        }),
+    ] {
+        let resp = client.post(&url).json(&body).send().await.unwrap();
+        assert_eq!(reqwest::StatusCode::BAD_REQUEST, resp.status(), "{body}");
+    }
+
+    // Delete the rows for host a in us-east up to 3s:
+    let resp = client
+        .post(&url)
+        .json(&json!({
+            "db": "foo",
+            "table": "cpu",
+            "stop": "1970-01-01T00:00:03Z",
+            "predicate": "host=\"a\" AND region=\"us-east\""
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(reqwest::StatusCode::OK, resp.status());
+
+    let resp = server
+        .api_v3_query_sql(&[
+            ("db", "foo"),
+            (
+                "q",
+                "SELECT host, region, time, usage FROM cpu ORDER BY time",
+            ),
+            ("format", "pretty"),
+        ])
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert_eq!(
+        "+------+---------+---------------------+-------+\n\
+        | host | region  | time                | usage |\n\
+        +------+---------+---------------------+-------+\n\
+        | a    | us-west | 1970-01-01T00:00:02 | 0.8   |\n\
+        | b    | us-east | 1970-01-01T00:00:03 | 0.7   |\n\
+        | a    | us-east | 1970-01-01T00:00:04 | 0.6   |\n\
+        +------+---------+---------------------+-------+",
+        resp
+    );
+
+    // Delete everything that remains in the table:
+    let resp = client
+        .post(&url)
+        .json(&json!({"db": "foo", "table": "cpu"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(reqwest::StatusCode::OK, resp.status());
+
+    let resp = server
+        .api_v3_query_sql(&[
+            ("db", "foo"),
+            ("q", "SELECT COUNT(*) FROM cpu"),
+            ("format", "pretty"),
+        ])
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert_eq!(
+        "+----------+\n\
+        | COUNT(*) |\n\
+        +----------+\n\
+        | 0        |\n\
+        +----------+",
+        resp
+    );
+}
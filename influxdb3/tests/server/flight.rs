@@ -1,6 +1,15 @@
+use std::sync::Arc;
+
+use arrow::array::{
+    ArrayRef, DictionaryArray, Float64Array, StringArray, TimestampNanosecondArray,
+};
+use arrow::datatypes::{Int32Type, Schema};
+use arrow::record_batch::RecordBatch;
+use arrow_flight::encode::{DictionaryHandling, FlightDataEncoderBuilder};
 use arrow_flight::sql::SqlInfo;
-use arrow_flight::Ticket;
+use arrow_flight::{FlightDescriptor, Ticket};
 use arrow_util::assert_batches_sorted_eq;
+use futures::TryStreamExt;
 use influxdb3_client::Precision;
 use test_helpers::assert_contains;
 
@@ -217,3 +226,129 @@ async fn flight_influxql() {
         );
     }
 }
+
+#[tokio::test]
+async fn flight_do_put() {
+    let server = TestServer::spawn().await;
+
+    let batch = RecordBatch::try_from_iter([
+        (
+            "host",
+            Arc::new(DictionaryArray::<Int32Type>::from_iter(["s1", "s2"])) as ArrayRef,
+        ),
+        (
+            "usage",
+            Arc::new(Float64Array::from(vec![0.9, 0.5])) as ArrayRef,
+        ),
+        (
+            "time",
+            Arc::new(TimestampNanosecondArray::from(vec![1, 2])) as ArrayRef,
+        ),
+    ])
+    .unwrap();
+
+    let mut client = server.flight_client().await;
+    // keep the host column dictionary encoded, so that it's written as a tag
+    let flight_data = FlightDataEncoderBuilder::new()
+        .with_dictionary_handling(DictionaryHandling::Resend)
+        .with_flight_descriptor(Some(FlightDescriptor::new_path(vec![
+            "foo".to_string(),
+            "cpu".to_string(),
+        ])))
+        .build(futures::stream::iter([Ok(batch)]));
+    let results: Vec<_> = client
+        .do_put(flight_data)
+        .await
+        .unwrap()
+        .try_collect()
+        .await
+        .unwrap();
+    assert_eq!(results.len(), 1);
+
+    let mut sql_client = server.flight_sql_client("foo").await;
+    let response = sql_client
+        .query("SELECT host, time, usage FROM cpu")
+        .await
+        .unwrap();
+    let batches = collect_stream(response).await;
+    assert_batches_sorted_eq!(
+        [
+            "+------+--------------------------------+-------+",
+            "| host | time                           | usage |",
+            "+------+--------------------------------+-------+",
+            "| s1   | 1970-01-01T00:00:00.000000001Z | 0.9   |",
+            "| s2   | 1970-01-01T00:00:00.000000002Z | 0.5   |",
+            "+------+--------------------------------+-------+",
+        ],
+        &batches
+    );
+
+    // a batch that doesn't match the table's schema is rejected
+    let batch = RecordBatch::try_from_iter([
+        (
+            "usage",
+            Arc::new(StringArray::from(vec!["high"])) as ArrayRef,
+        ),
+        (
+            "time",
+            Arc::new(TimestampNanosecondArray::from(vec![3])) as ArrayRef,
+        ),
+    ])
+    .unwrap();
+    let flight_data = FlightDataEncoderBuilder::new()
+        .with_flight_descriptor(Some(FlightDescriptor::new_path(vec![
+            "foo".to_string(),
+            "cpu".to_string(),
+        ])))
+        .build(futures::stream::iter([Ok(batch)]));
+    let error = match client.do_put(flight_data).await {
+        Ok(results) => results.try_collect::<Vec<_>>().await.unwrap_err(),
+        Err(e) => e,
+    };
+    assert_contains!(error.to_string(), "column usage has type");
+
+    // the descriptor must name the database and table
+    let flight_data = FlightDataEncoderBuilder::new()
+        .with_flight_descriptor(Some(FlightDescriptor::new_path(vec!["foo".to_string()])))
+        .build(futures::stream::iter([Ok(RecordBatch::new_empty(
+            Arc::new(Schema::empty()),
+        ))]));
+    let error = match client.do_put(flight_data).await {
+        Ok(results) => results.try_collect::<Vec<_>>().await.unwrap_err(),
+        Err(e) => e,
+    };
+    assert_contains!(error.to_string(), "database and table names");
+}
+
+#[tokio::test]
+async fn flight_do_put_request_size_limit() {
+    let server = TestServer::configure()
+        .max_http_request_size(4096)
+        .spawn()
+        .await;
+    let mut client = server.flight_client().await;
+
+    // a write whose batches decode to more than the limit is rejected
+    let batch = RecordBatch::try_from_iter([
+        (
+            "usage",
+            Arc::new(Float64Array::from(vec![0.5; 300])) as ArrayRef,
+        ),
+        (
+            "time",
+            Arc::new(TimestampNanosecondArray::from_iter_values(0..300)) as ArrayRef,
+        ),
+    ])
+    .unwrap();
+    let flight_data = FlightDataEncoderBuilder::new()
+        .with_flight_descriptor(Some(FlightDescriptor::new_path(vec![
+            "foo".to_string(),
+            "cpu".to_string(),
+        ])))
+        .build(futures::stream::iter([Ok(batch.clone()), Ok(batch)]));
+    let error = match client.do_put(flight_data).await {
+        Ok(results) => results.try_collect::<Vec<_>>().await.unwrap_err(),
+        Err(e) => e,
+    };
+    assert_contains!(error.to_string(), "max request size (4096 bytes) exceeded");
+}
@@ -0,0 +1,92 @@
+use std::process::Command;
+use std::sync::Arc;
+
+use arrow::array::{ArrayRef, DictionaryArray, Float64Array, TimestampMillisecondArray};
+use arrow::datatypes::Int32Type;
+use arrow::record_batch::RecordBatch;
+use assert_cmd::cargo::CommandCargoExt;
+use parquet::arrow::ArrowWriter;
+use pretty_assertions::assert_eq;
+
+use crate::TestServer;
+
+#[tokio::test]
+async fn import_parquet() {
+    let server = TestServer::spawn().await;
+
+    // the file has a duplicate row, which is only imported once
+    let batch = RecordBatch::try_from_iter([
+        (
+            "host",
+            Arc::new(DictionaryArray::<Int32Type>::from_iter(["a", "a", "b"])) as ArrayRef,
+        ),
+        (
+            "usage",
+            Arc::new(Float64Array::from(vec![0.5, 0.5, 0.9])) as ArrayRef,
+        ),
+        (
+            "time",
+            Arc::new(TimestampMillisecondArray::from(vec![1_000, 1_000, 2_000])) as ArrayRef,
+        ),
+    ])
+    .unwrap();
+    let dir = test_helpers::tmp_dir().unwrap();
+    let path = dir.path().join("cpu.parquet");
+    let file = std::fs::File::create(&path).unwrap();
+    let mut writer = ArrowWriter::try_new(file, batch.schema(), None).unwrap();
+    writer.write(&batch).unwrap();
+    writer.close().unwrap();
+
+    let output = Command::cargo_bin("influxdb3")
+        .unwrap()
+        .args([
+            "import",
+            "parquet",
+            "--host",
+            &server.client_addr(),
+            "--dbname",
+            "foo",
+            "--table",
+            "cpu",
+            "--file",
+            path.to_str().unwrap(),
+        ])
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(output.status.success(), "{stdout}");
+    assert_eq!("imported 2 rows into table cpu\n", stdout);
+
+    let resp = server
+        .api_v3_query_sql(&[
+            ("db", "foo"),
+            ("q", "SELECT host, time, usage FROM cpu ORDER BY time"),
+            ("format", "pretty"),
+        ])
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert_eq!(
+        "+------+---------------------+-------+\n\
+        | host | time                | usage |\n\
+        +------+---------------------+-------+\n\
+        | a    | 1970-01-01T00:00:01 | 0.5   |\n\
+        | b    | 1970-01-01T00:00:02 | 0.9   |\n\
+        +------+---------------------+-------+",
+        resp
+    );
+
+    // a body that isn't a parquet file is a bad request
+    let resp = reqwest::Client::new()
+        .post(format!(
+            "{base}/api/v3/import/parquet",
+            base = server.client_addr()
+        ))
+        .query(&[("db", "foo"), ("table", "cpu")])
+        .body("not parquet")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(reqwest::StatusCode::BAD_REQUEST, resp.status());
+}
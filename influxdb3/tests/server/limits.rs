@@ -1,4 +1,5 @@
-use crate::TestServer;
+use crate::{collect_stream, TestServer};
+use arrow_util::assert_batches_sorted_eq;
 use hyper::StatusCode;
 use influxdb3_client::Error;
 use influxdb3_client::Precision;
@@ -80,3 +81,74 @@ async fn limits() -> Result<(), Error> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn configured_limits() -> Result<(), Error> {
+    let server = TestServer::configure()
+        .catalog_limits(2, 3, 4)
+        .spawn()
+        .await;
+    let client = influxdb3_client::Client::new(server.client_addr())?;
+
+    // Override the server's limits for a single database
+    client
+        .api_v3_configure_database_create("foo")
+        .max_tables(1)
+        .max_columns_per_table(3)
+        .send()
+        .await?;
+
+    server
+        .write_lp_to_db("foo", "cpu a=1,b=2 1\n", Precision::Nanosecond)
+        .await?;
+    let Err(Error::ApiError { code, message }) = server
+        .write_lp_to_db("foo", "cpu a=1,b=2,c=3 2\n", Precision::Nanosecond)
+        .await
+    else {
+        panic!("Did not error when exceeding the database's columns limit");
+    };
+    assert_eq!(code, StatusCode::UNPROCESSABLE_ENTITY);
+    assert!(message.contains("limit of 2 columns"), "{message}");
+    let Err(Error::ApiError { code, message }) = server
+        .write_lp_to_db("foo", "mem a=1 1\n", Precision::Nanosecond)
+        .await
+    else {
+        panic!("Did not error when exceeding the database's tables limit");
+    };
+    assert_eq!(code, StatusCode::UNPROCESSABLE_ENTITY);
+    assert!(message.contains("limit of 1 tables"), "{message}");
+
+    // The server's limits apply to the other databases
+    server
+        .write_lp_to_db("bar", "cpu a=1,b=2,c=3 1\n", Precision::Nanosecond)
+        .await?;
+    let Err(Error::ApiError { code, message }) = server
+        .write_lp_to_db("baz", "cpu a=1 1\n", Precision::Nanosecond)
+        .await
+    else {
+        panic!("Did not error when exceeding the server's databases limit");
+    };
+    assert_eq!(code, StatusCode::UNPROCESSABLE_ENTITY);
+    assert!(message.contains("limit of 2 databases"), "{message}");
+
+    let mut flight_client = server.flight_sql_client("foo").await;
+    let response = flight_client
+        .query("SELECT * FROM system.limits")
+        .await
+        .unwrap();
+    let batches = collect_stream(response).await;
+    assert_batches_sorted_eq!(
+        [
+            "+-------------------+--------------+----------------+-----------------+",
+            "| limit_name        | server_limit | database_limit | effective_limit |",
+            "+-------------------+--------------+----------------+-----------------+",
+            "| columns_per_table | 4            | 3              | 3               |",
+            "| databases         | 2            |                | 2               |",
+            "| tables            | 3            | 1              | 1               |",
+            "+-------------------+--------------+----------------+-----------------+",
+        ],
+        &batches
+    );
+
+    Ok(())
+}
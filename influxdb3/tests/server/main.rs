@@ -14,17 +14,26 @@ use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
 use reqwest::Response;
 
 mod auth;
+mod configure;
+mod delete;
 mod flight;
+mod import;
 mod limits;
+mod otlp;
 mod ping;
+mod prom;
 mod query;
 mod system_tables;
+mod wal;
 mod write;
 
 /// Configuration for a [`TestServer`]
 #[derive(Debug, Default)]
 pub struct TestConfig {
     auth_token: Option<(String, String)>,
+    catalog_limits: Option<[String; 3]>,
+    write_time_window: Option<[String; 2]>,
+    max_http_request_size: Option<String>,
 }
 
 impl TestConfig {
@@ -38,6 +47,35 @@ impl TestConfig {
         self
     }
 
+    /// Set the catalog limits for this [`TestServer`]
+    pub fn catalog_limits(
+        mut self,
+        max_databases: usize,
+        max_tables: usize,
+        max_columns_per_table: usize,
+    ) -> Self {
+        self.catalog_limits =
+            Some([max_databases, max_tables, max_columns_per_table].map(|l| l.to_string()));
+        self
+    }
+
+    /// Set the window of line timestamps accepted by writes to this [`TestServer`], as human
+    /// readable durations, e.g., "1h"
+    pub fn write_time_window<S: Into<String>, R: Into<String>>(
+        mut self,
+        max_write_age: S,
+        max_write_future: R,
+    ) -> Self {
+        self.write_time_window = Some([max_write_age.into(), max_write_future.into()]);
+        self
+    }
+
+    /// Set the maximum size of requests to this [`TestServer`], in bytes
+    pub fn max_http_request_size(mut self, max_http_request_size: usize) -> Self {
+        self.max_http_request_size = Some(max_http_request_size.to_string());
+        self
+    }
+
     /// Spawn a new [`TestServer`] with this configuration
     ///
     /// This will run the `influxdb3 serve` command, and bind its HTTP
@@ -51,6 +89,27 @@ impl TestConfig {
         if let Some((token, _)) = &self.auth_token {
             args.append(&mut vec!["--bearer-token", token]);
         }
+        if let Some([max_databases, max_tables, max_columns_per_table]) = &self.catalog_limits {
+            args.append(&mut vec![
+                "--max-databases",
+                max_databases,
+                "--max-tables",
+                max_tables,
+                "--max-columns-per-table",
+                max_columns_per_table,
+            ]);
+        }
+        if let Some([max_write_age, max_write_future]) = &self.write_time_window {
+            args.append(&mut vec![
+                "--max-write-age",
+                max_write_age,
+                "--max-write-future",
+                max_write_future,
+            ]);
+        }
+        if let Some(max_http_request_size) = &self.max_http_request_size {
+            args.append(&mut vec!["--max-http-request-size", max_http_request_size]);
+        }
         args
     }
 }
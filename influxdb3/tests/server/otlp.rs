@@ -0,0 +1,83 @@
+use pretty_assertions::assert_eq;
+use reqwest::StatusCode;
+
+use crate::TestServer;
+
+/// An OTLP metrics export from the OpenTelemetry SDK, with a gauge, a sum, a histogram and an
+/// exponential histogram, each from an instrument of the `checkout` service
+const EXPORT_METRICS_REQUEST: &[u8] = include_bytes!("testdata/otlp_metrics.bin");
+
+/// The response to [`EXPORT_METRICS_REQUEST`], whose exponential histogram data point is rejected
+const EXPORT_METRICS_RESPONSE: &[u8] = include_bytes!("testdata/otlp_metrics_response.bin");
+
+#[tokio::test]
+async fn otlp_metrics() {
+    let server = TestServer::spawn().await;
+    let client = reqwest::Client::new();
+
+    let resp = client
+        .post(format!("{base}/v1/metrics", base = server.client_addr()))
+        .query(&[("db", "otel")])
+        .header("Content-Type", "application/x-protobuf")
+        .body(EXPORT_METRICS_REQUEST)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::OK, resp.status());
+    assert_eq!("application/x-protobuf", resp.headers()["Content-Type"]);
+    assert_eq!(
+        EXPORT_METRICS_RESPONSE,
+        resp.bytes().await.unwrap().as_ref()
+    );
+
+    let queries = [
+        (
+            "SELECT \"cpu.mode\", \"service.name\", time, value \
+            FROM \"process.cpu.utilization\" ORDER BY \"cpu.mode\"",
+            "+----------+--------------+---------------------+-------+\n\
+            | cpu.mode | service.name | time                | value |\n\
+            +----------+--------------+---------------------+-------+\n\
+            | system   | checkout     | 2023-11-14T22:13:20 | 0.125 |\n\
+            | user     | checkout     | 2023-11-14T22:13:20 | 0.25  |\n\
+            +----------+--------------+---------------------+-------+",
+        ),
+        (
+            "SELECT \"http.request.method\", \"http.response.status_code\", time, value \
+            FROM \"http.server.request.count\"",
+            "+---------------------+---------------------------+---------------------+-------+\n\
+            | http.request.method | http.response.status_code | time                | value |\n\
+            +---------------------+---------------------------+---------------------+-------+\n\
+            | GET                 | 200                       | 2023-11-14T22:13:20 | 42    |\n\
+            +---------------------+---------------------------+---------------------+-------+",
+        ),
+        (
+            "SELECT count, sum, min, max, \"le_0.1\", \"le_0.25\", \"le_0.5\" \
+            FROM \"http.server.request.duration\"",
+            "+-------+-----+------+------+--------+---------+--------+\n\
+            | count | sum | min  | max  | le_0.1 | le_0.25 | le_0.5 |\n\
+            +-------+-----+------+------+--------+---------+--------+\n\
+            | 5     | 1.5 | 0.05 | 0.75 | 1      | 3       | 4      |\n\
+            +-------+-----+------+------+--------+---------+--------+",
+        ),
+    ];
+    for (query, expected) in queries {
+        let resp = server
+            .api_v3_query_sql(&[("db", "otel"), ("q", query), ("format", "pretty")])
+            .await
+            .text()
+            .await
+            .unwrap();
+        assert_eq!(expected, resp, "{query}");
+    }
+
+    // OTLP encoded as JSON isn't supported
+    let resp = client
+        .post(format!("{base}/v1/metrics", base = server.client_addr()))
+        .query(&[("db", "otel")])
+        .header("Content-Type", "application/json")
+        .body("{}")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::UNSUPPORTED_MEDIA_TYPE, resp.status());
+}
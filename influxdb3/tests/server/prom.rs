@@ -0,0 +1,114 @@
+use pretty_assertions::assert_eq;
+use reqwest::StatusCode;
+
+use crate::TestServer;
+
+/// A remote write request from Prometheus, with two series of `http_requests_total`, and a
+/// series of `up` that ends with a staleness marker
+const REMOTE_WRITE_REQUEST: &[u8] = include_bytes!("testdata/prom_remote_write.bin");
+
+/// A remote read request from Prometheus, for the `http_requests_total` series with a `code`
+/// matching `2..`
+const REMOTE_READ_REQUEST: &[u8] = include_bytes!("testdata/prom_remote_read.bin");
+
+/// The decompressed response to [`REMOTE_READ_REQUEST`]
+const REMOTE_READ_RESPONSE: &[u8] = include_bytes!("testdata/prom_remote_read_response.bin");
+
+#[tokio::test]
+async fn prom_remote_write_and_read() {
+    let server = TestServer::spawn().await;
+    let client = reqwest::Client::new();
+
+    let resp = client
+        .post(format!(
+            "{base}/api/v1/prom/write",
+            base = server.client_addr()
+        ))
+        .query(&[("db", "prom")])
+        .header("Content-Encoding", "snappy")
+        .header("Content-Type", "application/x-protobuf")
+        .header("X-Prometheus-Remote-Write-Version", "0.1.0")
+        .body(REMOTE_WRITE_REQUEST)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::NO_CONTENT, resp.status());
+
+    let resp = server
+        .api_v3_query_sql(&[
+            ("db", "prom"),
+            (
+                "q",
+                "SELECT code, handler, instance, job, time, value \
+                FROM http_requests_total ORDER BY code, time",
+            ),
+            ("format", "pretty"),
+        ])
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert_eq!(
+        "+------+---------------+----------------+------------+---------------------+-------+\n\
+        | code | handler       | instance       | job        | time                | value |\n\
+        +------+---------------+----------------+------------+---------------------+-------+\n\
+        | 200  | /api/v1/query | localhost:9090 | prometheus | 2023-11-14T22:13:20 | 10.0  |\n\
+        | 200  | /api/v1/query | localhost:9090 | prometheus | 2023-11-14T22:13:35 | 12.0  |\n\
+        | 500  | /api/v1/query | localhost:9090 | prometheus | 2023-11-14T22:13:20 | 1.0   |\n\
+        +------+---------------+----------------+------------+---------------------+-------+",
+        resp
+    );
+
+    // the staleness marker isn't written
+    let resp = server
+        .api_v3_query_sql(&[
+            ("db", "prom"),
+            ("q", "SELECT instance, job, time, value FROM up"),
+            ("format", "pretty"),
+        ])
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert_eq!(
+        "+----------------+------------+---------------------+-------+\n\
+        | instance       | job        | time                | value |\n\
+        +----------------+------------+---------------------+-------+\n\
+        | localhost:9090 | prometheus | 2023-11-14T22:13:20 | 1.0   |\n\
+        +----------------+------------+---------------------+-------+",
+        resp
+    );
+
+    let resp = client
+        .post(format!(
+            "{base}/api/v1/prom/read",
+            base = server.client_addr()
+        ))
+        .query(&[("db", "prom")])
+        .header("Content-Encoding", "snappy")
+        .header("Content-Type", "application/x-protobuf")
+        .header("X-Prometheus-Remote-Read-Version", "0.1.0")
+        .body(REMOTE_READ_REQUEST)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::OK, resp.status());
+    assert_eq!("snappy", resp.headers()["Content-Encoding"]);
+    assert_eq!("application/x-protobuf", resp.headers()["Content-Type"]);
+    let body = resp.bytes().await.unwrap();
+    let body = snap::raw::Decoder::new().decompress_vec(&body).unwrap();
+    assert_eq!(REMOTE_READ_RESPONSE, body.as_slice());
+
+    // a body that isn't snappy compressed is a bad request
+    let resp = client
+        .post(format!(
+            "{base}/api/v1/prom/write",
+            base = server.client_addr()
+        ))
+        .query(&[("db", "prom")])
+        .body("not snappy")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::BAD_REQUEST, resp.status());
+}
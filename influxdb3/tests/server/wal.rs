@@ -0,0 +1,120 @@
+use std::path::Path;
+use std::process::{Command, Output};
+
+use assert_cmd::cargo::CommandCargoExt;
+use influxdb3_write::wal::WalImpl;
+use influxdb3_write::{LpWriteOp, Precision, SegmentDuration, SegmentId, SegmentRange, Wal, WalOp};
+use iox_time::Time;
+
+fn write_segment(dir: &Path, segment_id: u32) {
+    let wal = WalImpl::new(dir).unwrap();
+    let range = SegmentRange::from_time_and_duration(
+        Time::from_timestamp_nanos(0),
+        SegmentDuration::new_5m(),
+        false,
+    );
+    let mut writer = wal
+        .new_segment_writer(SegmentId::new(segment_id), range)
+        .unwrap();
+    for db_name in ["foo", "bar"] {
+        writer
+            .write_batch(vec![WalOp::LpWrite(LpWriteOp {
+                db_name: db_name.to_string(),
+                lp: "cpu,host=a usage=1\ncpu,host=b usage=2\n".to_string(),
+                default_time: 0,
+                precision: Precision::Nanosecond,
+            })])
+            .unwrap();
+    }
+}
+
+fn run_wal_command(args: &[&str], dir: &Path) -> Output {
+    Command::cargo_bin("influxdb3")
+        .unwrap()
+        .arg("wal")
+        .args(args)
+        .args(["--wal-directory", dir.to_str().unwrap()])
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn wal_list_and_dump() {
+    let dir = test_helpers::tmp_dir().unwrap();
+    write_segment(dir.path(), 1);
+    write_segment(dir.path(), 2);
+
+    let output = run_wal_command(&["list"], dir.path());
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 3, "{stdout}");
+    assert!(lines[1].trim_start().starts_with("1  idb3.002"), "{stdout}");
+    assert!(lines[1].contains("1970-01-01T00:00:00+00:00"), "{stdout}");
+    assert!(lines[2].trim_start().starts_with("2  idb3.002"), "{stdout}");
+
+    let output = run_wal_command(&["dump", "--segment-id", "2", "--fmt", "json"], dir.path());
+    assert!(output.status.success());
+    let batches: Vec<serde_json::Value> = String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(
+        batches,
+        vec![
+            serde_json::json!({
+                "segment_id": 2,
+                "sequence_number": 1,
+                "ops": [{"op": "lp_write", "db_name": "foo", "lines": 2, "precision": "nanosecond"}],
+            }),
+            serde_json::json!({
+                "segment_id": 2,
+                "sequence_number": 2,
+                "ops": [{"op": "lp_write", "db_name": "bar", "lines": 2, "precision": "nanosecond"}],
+            }),
+        ]
+    );
+}
+
+#[test]
+fn wal_verify_reports_corruption() {
+    let dir = test_helpers::tmp_dir().unwrap();
+    write_segment(dir.path(), 1);
+    write_segment(dir.path(), 2);
+
+    let output = run_wal_command(&["verify"], dir.path());
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("OK, 2 blocks"), "{stdout}");
+
+    // corrupt the checksum of the second block of the second segment
+    let path = WalImpl::new(dir.path()).unwrap().segment_files().unwrap()[1]
+        .path
+        .clone();
+    let mut bytes = std::fs::read(&path).unwrap();
+    let header_len = u16::from_be_bytes([bytes[8], bytes[9]]) as usize;
+    let first_block = 10 + header_len;
+    let first_block_len =
+        u32::from_be_bytes(bytes[first_block + 4..first_block + 8].try_into().unwrap()) as usize;
+    let second_block = first_block + 8 + first_block_len;
+    bytes[second_block] ^= 0xff;
+    std::fs::write(&path, bytes).unwrap();
+
+    let output = run_wal_command(&["verify"], dir.path());
+    assert!(!output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("segment 1 "), "{stdout}");
+    assert!(
+        stdout.contains(&format!(
+            "segment 2 ({}): block 1 at byte offset {second_block}: checksum mismatch",
+            path.display()
+        )),
+        "{stdout}"
+    );
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("found corruption in 1 of 2 segment files"),
+        "{stderr}"
+    );
+}
@@ -1,6 +1,8 @@
 use hyper::StatusCode;
 use influxdb3_client::Precision;
 use pretty_assertions::assert_eq;
+use serde_json::Value;
+use std::time::{SystemTime, UNIX_EPOCH};
 use test_helpers::assert_contains;
 
 use crate::TestServer;
@@ -429,3 +431,158 @@ async fn writes_with_different_schema_should_fail() {
         "the request should hae failed with an API Error"
     );
 }
+
+#[tokio::test]
+async fn writes_outside_time_window_are_rejected() {
+    let server = TestServer::configure()
+        .write_time_window("1h", "10m")
+        .spawn()
+        .await;
+    let client = reqwest::Client::new();
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+
+    let resp = client
+        .post(format!(
+            "{base}/api/v3/write_lp",
+            base = server.client_addr()
+        ))
+        .query(&[
+            ("db", "foo"),
+            ("precision", "second"),
+            ("accept_partial", "true"),
+        ])
+        .body(format!(
+            "cpu,host=a usage=0.5 {now}\n\
+            cpu,host=b usage=0.6 {old}\n\
+            cpu,host=c usage=0.7 {future}\n\
+            cpu,host=d usage=0.8",
+            old = now - 2 * 3600,
+            future = now + 3600,
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: Value = resp.json().await.unwrap();
+    let invalid_lines = body["data"].as_array().unwrap();
+    assert_eq!(invalid_lines.len(), 2);
+    assert_eq!(invalid_lines[0]["line_number"], 2);
+    assert_contains!(
+        invalid_lines[0]["error_message"].as_str().unwrap(),
+        "max write age of 1h"
+    );
+    assert_eq!(invalid_lines[1]["line_number"], 3);
+    assert_contains!(
+        invalid_lines[1]["error_message"].as_str().unwrap(),
+        "max write future of 10m"
+    );
+
+    let resp = server
+        .api_v3_query_sql(&[
+            ("db", "foo"),
+            ("q", "SELECT host, usage FROM cpu ORDER BY host"),
+            ("format", "pretty"),
+        ])
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert_eq!(
+        "+------+-------+\n\
+        | host | usage |\n\
+        +------+-------+\n\
+        | a    | 0.5   |\n\
+        | d    | 0.8   |\n\
+        +------+-------+",
+        resp
+    );
+}
+
+#[tokio::test]
+async fn api_v3_write_json() {
+    let server = TestServer::spawn().await;
+    let client = reqwest::Client::new();
+
+    let resp = client
+        .post(format!(
+            "{base}/api/v3/write_json",
+            base = server.client_addr()
+        ))
+        .query(&[("db", "foo"), ("precision", "second")])
+        .body(
+            r#"[
+                {"table": "cpu", "tags": {"host": "a"}, "fields": {"usage": 0.5}, "timestamp": 1},
+                {"table": "cpu", "tags": {"host": "b"}, "fields": {"usage": "high"}, "timestamp": 2},
+                {"table": "cpu", "tags": {"host": "c"}, "fields": {}},
+                {"table": "cpu", "tags": {"host": "d"}, "fields": {"usage": 0.7}, "timestamp": 3}
+            ]"#,
+        )
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: Value = resp.json().await.unwrap();
+    let invalid_lines = body["data"].as_array().unwrap();
+    assert_eq!(invalid_lines.len(), 2);
+    // errors from validating the rows refer to the rows, rather than the line protocol they're
+    // converted to:
+    assert_eq!(invalid_lines[0]["line_number"], 2);
+    assert_eq!(
+        invalid_lines[0]["original_line"],
+        r#"{"fields":{"usage":"high"},"table":"cpu","tags":{"host":"b"},"timestamp":2}"#
+    );
+    assert_contains!(
+        invalid_lines[0]["error_message"].as_str().unwrap(),
+        "invalid field value"
+    );
+    assert_eq!(invalid_lines[1]["line_number"], 3);
+    assert_eq!(
+        invalid_lines[1]["error_message"],
+        "the row must have at least one field with a value"
+    );
+
+    // newline delimited JSON, without accepting a partial write:
+    let resp = client
+        .post(format!(
+            "{base}/api/v3/write_json",
+            base = server.client_addr()
+        ))
+        .query(&[
+            ("db", "foo"),
+            ("precision", "second"),
+            ("accept_partial", "false"),
+        ])
+        .body(
+            "{\"table\": \"cpu\", \"tags\": {\"host\": \"e\"}, \"fields\": {\"usage\": 0.9}, \"timestamp\": 4}\n\
+            {\"table\": \"cpu\", \"tags\": {\"host\": \"f\"}, \"fields\": {\"usage\": [1]}}\n",
+        )
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["line_number"], 2);
+
+    let resp = server
+        .api_v3_query_sql(&[
+            ("db", "foo"),
+            ("q", "SELECT host, usage, time FROM cpu ORDER BY host"),
+            ("format", "pretty"),
+        ])
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert_eq!(
+        "+------+-------+---------------------+\n\
+        | host | usage | time                |\n\
+        +------+-------+---------------------+\n\
+        | a    | 0.5   | 1970-01-01T00:00:01 |\n\
+        | d    | 0.7   | 1970-01-01T00:00:03 |\n\
+        +------+-------+---------------------+",
+        resp
+    );
+}
@@ -1,8 +1,8 @@
-use std::{collections::HashMap, fmt::Display, string::FromUtf8Error};
+use std::{collections::HashMap, fmt::Display, string::FromUtf8Error, time::Duration};
 
 use bytes::Bytes;
 use iox_query_params::StatementParam;
-use reqwest::{Body, IntoUrl, StatusCode};
+use reqwest::{header::RETRY_AFTER, Body, IntoUrl, StatusCode};
 use secrecy::{ExposeSecret, Secret};
 use serde::{Deserialize, Serialize};
 use url::Url;
@@ -22,6 +22,18 @@ pub enum Error {
     #[error("failed to send /ping request: {0}")]
     PingSend(#[source] reqwest::Error),
 
+    #[error("failed to send /api/v3/configure/database request: {0}")]
+    ConfigureDatabaseSend(#[source] reqwest::Error),
+
+    #[error("failed to send /api/v3/configure/table request: {0}")]
+    ConfigureTableSend(#[source] reqwest::Error),
+
+    #[error("failed to send /api/v3/configure/last_cache request: {0}")]
+    ConfigureLastCacheSend(#[source] reqwest::Error),
+
+    #[error("failed to send /api/v3/import/parquet request: {0}")]
+    ImportParquetSend(#[source] reqwest::Error),
+
     #[error("failed to read the API response bytes: {0}")]
     Bytes(#[source] reqwest::Error),
 
@@ -122,13 +134,44 @@ impl Client {
     pub fn api_v3_write_lp<S: Into<String>>(&self, db: S) -> WriteRequestBuilder<'_, NoBody> {
         WriteRequestBuilder {
             client: self,
+            path: "/api/v3/write_lp",
             db: db.into(),
             precision: None,
             accept_partial: None,
+            max_retries: 0,
             body: NoBody,
         }
     }
 
+    /// Compose a request to the `/api/v3/write_json` API
+    ///
+    /// The body is either a JSON array of rows, or newline delimited JSON with a row on each
+    /// line. Each row is an object with its `table`, `tags`, `fields` and `timestamp`.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use influxdb3_client::Client;
+    /// # use influxdb3_client::Precision;
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    /// let client = Client::new("http://localhost:8181")?;
+    /// client
+    ///     .api_v3_write_json("db_name")
+    ///     .precision(Precision::Second)
+    ///     .body(r#"{"table": "cpu", "tags": {"host": "s1"}, "fields": {"usage": 0.5}, "timestamp": 1}"#)
+    ///     .send()
+    ///     .await
+    ///     .expect("send write_json request");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn api_v3_write_json<S: Into<String>>(&self, db: S) -> WriteRequestBuilder<'_, NoBody> {
+        WriteRequestBuilder {
+            path: "/api/v3/write_json",
+            ..self.api_v3_write_lp(db)
+        }
+    }
+
     /// Compose a request to the `/api/v3/query_sql` API
     ///
     /// # Example
@@ -191,6 +234,223 @@ impl Client {
         }
     }
 
+    /// Compose a request to create a new database using the `/api/v3/configure/database` API
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use influxdb3_client::Client;
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    /// let client = Client::new("http://localhost:8181")?;
+    /// client
+    ///     .api_v3_configure_database_create("db_name")
+    ///     .retention_period("30d")
+    ///     .max_tables(100)
+    ///     .send()
+    ///     .await
+    ///     .expect("send create database request");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn api_v3_configure_database_create<D: Into<String>>(
+        &self,
+        db: D,
+    ) -> CreateDatabaseRequestBuilder<'_> {
+        CreateDatabaseRequestBuilder {
+            client: self,
+            db: db.into(),
+            retention_period: None,
+            max_tables: None,
+            max_columns_per_table: None,
+            strict_schema: false,
+        }
+    }
+
+    /// Delete a database using the `/api/v3/configure/database` API
+    pub async fn api_v3_configure_database_delete(
+        &self,
+        db: impl Into<String> + Send,
+    ) -> Result<()> {
+        let url = self.base_url.join("/api/v3/configure/database")?;
+        let mut req = self.http_client.delete(url).query(&[("db", db.into())]);
+        if let Some(token) = &self.auth_token {
+            req = req.bearer_auth(token.expose_secret());
+        }
+        let resp = req.send().await.map_err(Error::ConfigureDatabaseSend)?;
+        let status = resp.status();
+        match status {
+            StatusCode::OK => Ok(()),
+            code => Err(Error::ApiError {
+                code,
+                message: resp.text().await.map_err(Error::Text)?,
+            }),
+        }
+    }
+
+    /// Compose a request to declare a new table using the `/api/v3/configure/table` API
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use influxdb3_client::{Client, FieldType};
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    /// let client = Client::new("http://localhost:8181")?;
+    /// client
+    ///     .api_v3_configure_table_create("db_name", "cpu")
+    ///     .tags(["host", "region"])
+    ///     .field("usage", FieldType::Float64)
+    ///     .send()
+    ///     .await
+    ///     .expect("send create table request");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn api_v3_configure_table_create<D: Into<String>, T: Into<String>>(
+        &self,
+        db: D,
+        table: T,
+    ) -> CreateTableRequestBuilder<'_> {
+        CreateTableRequestBuilder {
+            client: self,
+            db: db.into(),
+            table: table.into(),
+            tags: vec![],
+            fields: vec![],
+            series_key: None,
+            widen_numeric_fields: false,
+        }
+    }
+
+    /// Delete a table using the `/api/v3/configure/table` API
+    pub async fn api_v3_configure_table_delete(
+        &self,
+        db: impl Into<String> + Send,
+        table: impl Into<String> + Send,
+    ) -> Result<()> {
+        let url = self.base_url.join("/api/v3/configure/table")?;
+        let mut req = self
+            .http_client
+            .delete(url)
+            .query(&[("db", db.into()), ("table", table.into())]);
+        if let Some(token) = &self.auth_token {
+            req = req.bearer_auth(token.expose_secret());
+        }
+        let resp = req.send().await.map_err(Error::ConfigureTableSend)?;
+        let status = resp.status();
+        match status {
+            StatusCode::OK => Ok(()),
+            code => Err(Error::ApiError {
+                code,
+                message: resp.text().await.map_err(Error::Text)?,
+            }),
+        }
+    }
+
+    /// Compose a request to create a last cache using the `/api/v3/configure/last_cache` API
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use influxdb3_client::Client;
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    /// let client = Client::new("http://localhost:8181")?;
+    /// let created = client
+    ///     .api_v3_configure_last_cache_create("db_name", "cpu")
+    ///     .key_columns(["host"])
+    ///     .value_columns(["usage"])
+    ///     .count(5)
+    ///     .send()
+    ///     .await
+    ///     .expect("send create last cache request");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn api_v3_configure_last_cache_create<D: Into<String>, T: Into<String>>(
+        &self,
+        db: D,
+        table: T,
+    ) -> CreateLastCacheRequestBuilder<'_> {
+        CreateLastCacheRequestBuilder {
+            client: self,
+            db: db.into(),
+            table: table.into(),
+            name: None,
+            key_columns: None,
+            value_columns: None,
+            count: None,
+        }
+    }
+
+    /// Delete a last cache using the `/api/v3/configure/last_cache` API
+    pub async fn api_v3_configure_last_cache_delete(
+        &self,
+        db: impl Into<String> + Send,
+        table: impl Into<String> + Send,
+        name: impl Into<String> + Send,
+    ) -> Result<()> {
+        let url = self.base_url.join("/api/v3/configure/last_cache")?;
+        let params = DeleteLastCacheParams {
+            db: db.into(),
+            table: table.into(),
+            name: name.into(),
+        };
+        let mut req = self.http_client.delete(url).query(&params);
+        if let Some(token) = &self.auth_token {
+            req = req.bearer_auth(token.expose_secret());
+        }
+        let resp = req.send().await.map_err(Error::ConfigureLastCacheSend)?;
+        let status = resp.status();
+        match status {
+            StatusCode::OK => Ok(()),
+            code => Err(Error::ApiError {
+                code,
+                message: resp.text().await.map_err(Error::Text)?,
+            }),
+        }
+    }
+
+    /// Import a parquet file into a table using the `/api/v3/import/parquet` API
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use influxdb3_client::Client;
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    /// let client = Client::new("http://localhost:8181")?;
+    /// let parquet = std::fs::read("cpu.parquet")?;
+    /// let imported = client
+    ///     .api_v3_import_parquet("db_name", "cpu", parquet)
+    ///     .await
+    ///     .expect("send import parquet request");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn api_v3_import_parquet(
+        &self,
+        db: impl Into<String> + Send,
+        table: impl Into<String> + Send,
+        parquet: impl Into<Body> + Send,
+    ) -> Result<ParquetImportResponse> {
+        let url = self.base_url.join("/api/v3/import/parquet")?;
+        let mut req = self
+            .http_client
+            .post(url)
+            .query(&[("db", db.into()), ("table", table.into())])
+            .body(parquet);
+        if let Some(token) = &self.auth_token {
+            req = req.bearer_auth(token.expose_secret());
+        }
+        let resp = req.send().await.map_err(Error::ImportParquetSend)?;
+        let status = resp.status();
+        match status {
+            StatusCode::OK => resp.json().await.map_err(Error::Json),
+            code => Err(Error::ApiError {
+                code,
+                message: resp.text().await.map_err(Error::Text)?,
+            }),
+        }
+    }
+
     /// Send a `/ping` request to the target `influxdb3` server to check its
     /// status and gather `version` and `revision` information
     pub async fn ping(&self) -> Result<PingResponse> {
@@ -262,15 +522,17 @@ pub enum Precision {
     Nanosecond,
 }
 
-/// Builder type for composing a request to `/api/v3/write_lp`
+/// Builder type for composing a request to `/api/v3/write_lp` or `/api/v3/write_json`
 ///
-/// Produced by [`Client::api_v3_write_lp`]
+/// Produced by [`Client::api_v3_write_lp`] or [`Client::api_v3_write_json`]
 #[derive(Debug)]
 pub struct WriteRequestBuilder<'c, B> {
     client: &'c Client,
+    path: &'static str,
     db: String,
     precision: Option<Precision>,
     accept_partial: Option<bool>,
+    max_retries: usize,
     body: B,
 }
 
@@ -286,19 +548,31 @@ impl<'c, B> WriteRequestBuilder<'c, B> {
         self.accept_partial = Some(set_to);
         self
     }
+
+    /// Set the number of times the write is retried if the server rejects it because it is
+    /// overloaded, i.e., with a `429` or `503` response. Before each retry, the client waits for
+    /// as long as the response's `Retry-After` header asks it to, or one second if it has none.
+    ///
+    /// Writes with a streaming body can't be retried. Defaults to 0.
+    pub fn max_retries(mut self, set_to: usize) -> Self {
+        self.max_retries = set_to;
+        self
+    }
 }
 
 impl<'c> WriteRequestBuilder<'c, NoBody> {
-    /// Set the body of the request to the `/api/v3/write_lp` API
+    /// Set the body of the request to the `/api/v3/write_lp` or `/api/v3/write_json` API
     ///
     /// This essentially wraps `reqwest`'s [`body`][reqwest::RequestBuilder::body]
     /// method, and puts the responsibility on the caller for now.
     pub fn body<T: Into<Body>>(self, body: T) -> WriteRequestBuilder<'c, Body> {
         WriteRequestBuilder {
             client: self.client,
+            path: self.path,
             db: self.db,
             precision: self.precision,
             accept_partial: self.accept_partial,
+            max_retries: self.max_retries,
             body: body.into(),
         }
     }
@@ -307,30 +581,63 @@ impl<'c> WriteRequestBuilder<'c, NoBody> {
 impl<'c> WriteRequestBuilder<'c, Body> {
     /// Send the request to the server
     pub async fn send(self) -> Result<()> {
-        let url = self.client.base_url.join("/api/v3/write_lp")?;
+        let url = self.client.base_url.join(self.path)?;
         let params = WriteParams::from(&self);
         let mut req = self.client.http_client.post(url).query(&params);
         if let Some(token) = &self.client.auth_token {
             req = req.bearer_auth(token.expose_secret());
         }
-        let resp = req
-            .body(self.body)
-            .send()
-            .await
-            .map_err(Error::WriteLpSend)?;
-        let status = resp.status();
-        let content = resp.bytes().await.map_err(Error::Bytes)?;
-        match status {
-            // TODO - handle the OK response content, return to caller, etc.
-            StatusCode::OK => Ok(()),
-            code => Err(Error::ApiError {
-                code,
-                message: String::from_utf8(content.to_vec())?,
-            }),
+        let mut req = req.body(self.body);
+        let mut retries = 0;
+        loop {
+            // keep a copy of the request in case it has to be retried:
+            let retry_req = if retries < self.max_retries {
+                req.try_clone()
+            } else {
+                None
+            };
+            let resp = req.send().await.map_err(Error::WriteLpSend)?;
+            let status = resp.status();
+            let retry_after = retry_after(&resp);
+            let content = resp.bytes().await.map_err(Error::Bytes)?;
+            match (status, retry_req) {
+                // TODO - handle the OK response content, return to caller, etc.
+                (StatusCode::OK, _) => return Ok(()),
+                (
+                    StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE,
+                    Some(retry_req),
+                ) => {
+                    tokio::time::sleep(retry_after.unwrap_or(DEFAULT_RETRY_AFTER)).await;
+                    req = retry_req;
+                    retries += 1;
+                }
+                (code, _) => {
+                    return Err(Error::ApiError {
+                        code,
+                        message: String::from_utf8(content.to_vec())?,
+                    })
+                }
+            }
         }
     }
 }
 
+/// How long to wait before retrying a write rejected by a server that didn't say how long to wait
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(1);
+
+/// Get the time to wait before retrying a request from the `Retry-After` header of its response,
+/// if it is given in seconds
+fn retry_after(resp: &reqwest::Response) -> Option<Duration> {
+    resp.headers()
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+        .map(Duration::from_secs)
+}
+
 #[doc(hidden)]
 /// Typestate type for [`WriteRequestBuilder`]
 #[derive(Debug, Copy, Clone)]
@@ -496,6 +803,336 @@ impl<'c> QueryRequestBuilder<'c> {
     }
 }
 
+/// Builder type for composing a request to create a database
+///
+/// Produced by [`Client::api_v3_configure_database_create`]
+#[derive(Debug)]
+pub struct CreateDatabaseRequestBuilder<'c> {
+    client: &'c Client,
+    db: String,
+    retention_period: Option<String>,
+    max_tables: Option<usize>,
+    max_columns_per_table: Option<usize>,
+    strict_schema: bool,
+}
+
+impl<'c> CreateDatabaseRequestBuilder<'c> {
+    /// Specify the retention period in a human readable form, e.g., `"30d"`, otherwise, data in
+    /// the database is kept indefinitely
+    pub fn retention_period(mut self, retention_period: impl Into<String>) -> Self {
+        self.retention_period = Some(retention_period.into());
+        self
+    }
+
+    /// Specify the maximum number of tables in the database, otherwise, only the server's limit
+    /// applies
+    pub fn max_tables(mut self, max_tables: usize) -> Self {
+        self.max_tables = Some(max_tables);
+        self
+    }
+
+    /// Specify the maximum number of columns per table in the database, otherwise, only the
+    /// server's limit applies
+    pub fn max_columns_per_table(mut self, max_columns_per_table: usize) -> Self {
+        self.max_columns_per_table = Some(max_columns_per_table);
+        self
+    }
+
+    /// Only accept writes to tables and columns that have been declared with
+    /// [`Client::api_v3_configure_table_create`]
+    pub fn strict_schema(mut self, strict_schema: bool) -> Self {
+        self.strict_schema = strict_schema;
+        self
+    }
+
+    /// Send the request to `/api/v3/configure/database`
+    pub async fn send(self) -> Result<()> {
+        let url = self.client.base_url.join("/api/v3/configure/database")?;
+        let params = ConfigureDatabaseParams {
+            db: &self.db,
+            retention_period: self.retention_period.as_deref(),
+            max_tables: self.max_tables,
+            max_columns_per_table: self.max_columns_per_table,
+            strict_schema: self.strict_schema,
+        };
+        let mut req = self.client.http_client.post(url).json(&params);
+        if let Some(token) = &self.client.auth_token {
+            req = req.bearer_auth(token.expose_secret());
+        }
+        let resp = req.send().await.map_err(Error::ConfigureDatabaseSend)?;
+        let status = resp.status();
+        match status {
+            StatusCode::CREATED => Ok(()),
+            code => Err(Error::ApiError {
+                code,
+                message: resp.text().await.map_err(Error::Text)?,
+            }),
+        }
+    }
+}
+
+/// Builder type for composing a request to declare a table
+///
+/// Produced by [`Client::api_v3_configure_table_create`]
+#[derive(Debug)]
+pub struct CreateTableRequestBuilder<'c> {
+    client: &'c Client,
+    db: String,
+    table: String,
+    tags: Vec<String>,
+    fields: Vec<(String, FieldType)>,
+    series_key: Option<Vec<String>>,
+    widen_numeric_fields: bool,
+}
+
+impl<'c> CreateTableRequestBuilder<'c> {
+    /// Specify the tags of the table
+    pub fn tags<I: IntoIterator<Item: Into<String>>>(mut self, tags: I) -> Self {
+        self.tags = tags.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Add a field of the given type to the table
+    pub fn field(mut self, name: impl Into<String>, field_type: FieldType) -> Self {
+        self.fields.push((name.into(), field_type));
+        self
+    }
+
+    /// Specify the series key of the table, which makes it use the v3 data model, otherwise, the
+    /// table uses the v1 data model
+    pub fn series_key<I: IntoIterator<Item: Into<String>>>(mut self, series_key: I) -> Self {
+        self.series_key = Some(series_key.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Accept writes of integer values to float fields, and of float values to integer fields,
+    /// by widening the field to a float, rather than rejecting them
+    pub fn widen_numeric_fields(mut self, widen_numeric_fields: bool) -> Self {
+        self.widen_numeric_fields = widen_numeric_fields;
+        self
+    }
+
+    /// Send the request to `/api/v3/configure/table`
+    pub async fn send(self) -> Result<()> {
+        let url = self.client.base_url.join("/api/v3/configure/table")?;
+        let params = CreateTableParams {
+            db: &self.db,
+            table: &self.table,
+            tags: &self.tags,
+            fields: self
+                .fields
+                .iter()
+                .map(|(name, field_type)| FieldParams {
+                    name,
+                    field_type: *field_type,
+                })
+                .collect(),
+            series_key: self.series_key.as_deref(),
+            widen_numeric_fields: self.widen_numeric_fields,
+        };
+        let mut req = self.client.http_client.post(url).json(&params);
+        if let Some(token) = &self.client.auth_token {
+            req = req.bearer_auth(token.expose_secret());
+        }
+        let resp = req.send().await.map_err(Error::ConfigureTableSend)?;
+        let status = resp.status();
+        match status {
+            StatusCode::CREATED => Ok(()),
+            code => Err(Error::ApiError {
+                code,
+                message: resp.text().await.map_err(Error::Text)?,
+            }),
+        }
+    }
+}
+
+/// The type of a field declared with [`Client::api_v3_configure_table_create`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FieldType {
+    Utf8,
+    Int64,
+    UInt64,
+    Float64,
+    Bool,
+}
+
+impl std::str::FromStr for FieldType {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "utf8" => Ok(Self::Utf8),
+            "int64" => Ok(Self::Int64),
+            "uint64" => Ok(Self::UInt64),
+            "float64" => Ok(Self::Float64),
+            "bool" => Ok(Self::Bool),
+            _ => Err(format!(
+                "invalid field type '{s}', expected one of: utf8, int64, uint64, float64, bool"
+            )),
+        }
+    }
+}
+
+/// Builder type for composing a request to create a last cache
+///
+/// Produced by [`Client::api_v3_configure_last_cache_create`]
+#[derive(Debug)]
+pub struct CreateLastCacheRequestBuilder<'c> {
+    client: &'c Client,
+    db: String,
+    table: String,
+    name: Option<String>,
+    key_columns: Option<Vec<String>>,
+    value_columns: Option<Vec<String>>,
+    count: Option<usize>,
+}
+
+impl<'c> CreateLastCacheRequestBuilder<'c> {
+    /// Specify the name of the cache, otherwise, one will be generated by the server
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Specify the key columns of the cache, otherwise, the table's tags will be used
+    pub fn key_columns<I: IntoIterator<Item: Into<String>>>(mut self, columns: I) -> Self {
+        self.key_columns = Some(columns.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Specify the value columns of the cache, otherwise, all non-key columns will be used
+    pub fn value_columns<I: IntoIterator<Item: Into<String>>>(mut self, columns: I) -> Self {
+        self.value_columns = Some(columns.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Specify the number of values to cache per key, otherwise, one will be used
+    pub fn count(mut self, count: usize) -> Self {
+        self.count = Some(count);
+        self
+    }
+
+    /// Send the request to `/api/v3/configure/last_cache`
+    pub async fn send(self) -> Result<LastCacheCreatedResponse> {
+        let url = self.client.base_url.join("/api/v3/configure/last_cache")?;
+        let params = CreateLastCacheParams::from(&self);
+        let mut req = self.client.http_client.post(url).json(&params);
+        if let Some(token) = &self.client.auth_token {
+            req = req.bearer_auth(token.expose_secret());
+        }
+        let resp = req.send().await.map_err(Error::ConfigureLastCacheSend)?;
+        let status = resp.status();
+        match status {
+            StatusCode::CREATED => resp.json().await.map_err(Error::Json),
+            code => Err(Error::ApiError {
+                code,
+                message: resp.text().await.map_err(Error::Text)?,
+            }),
+        }
+    }
+}
+
+/// The body of the request to create a last cache with the `/api/v3/configure/last_cache` API
+#[derive(Debug, Serialize)]
+struct CreateLastCacheParams<'a> {
+    db: &'a str,
+    table: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    key_columns: Option<&'a [String]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    value_columns: Option<&'a [String]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    count: Option<usize>,
+}
+
+impl<'a> From<&'a CreateLastCacheRequestBuilder<'a>> for CreateLastCacheParams<'a> {
+    fn from(builder: &'a CreateLastCacheRequestBuilder<'a>) -> Self {
+        Self {
+            db: &builder.db,
+            table: &builder.table,
+            name: builder.name.as_deref(),
+            key_columns: builder.key_columns.as_deref(),
+            value_columns: builder.value_columns.as_deref(),
+            count: builder.count,
+        }
+    }
+}
+
+/// The response of the `/api/v3/configure/last_cache` API when a cache is created
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct LastCacheCreatedResponse {
+    /// The name of the cache
+    pub name: String,
+    /// The columns used as keys in the cache
+    pub key_columns: Vec<String>,
+    /// The columns whose values are stored in the cache
+    pub value_columns: Vec<String>,
+    /// The number of values stored for each key
+    pub count: usize,
+}
+
+/// The response of the `/api/v3/import/parquet` API, which describes the segment the imported
+/// rows were persisted in
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ParquetImportResponse {
+    /// The id of the segment
+    pub segment_id: u32,
+    /// The size of the parquet files written for the import, in bytes
+    pub segment_parquet_size_bytes: u64,
+    /// The number of rows imported, after duplicates were removed
+    pub segment_row_count: u64,
+    /// The min time of the imported rows, in nanoseconds
+    pub segment_min_time: i64,
+    /// The max time of the imported rows, in nanoseconds
+    pub segment_max_time: i64,
+}
+
+/// The body of the request to the `/api/v3/configure/database` API
+#[derive(Debug, Serialize)]
+struct ConfigureDatabaseParams<'a> {
+    db: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    retention_period: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_tables: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_columns_per_table: Option<usize>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    strict_schema: bool,
+}
+
+/// The body of the request to declare a table with the `/api/v3/configure/table` API
+#[derive(Debug, Serialize)]
+struct CreateTableParams<'a> {
+    db: &'a str,
+    table: &'a str,
+    tags: &'a [String],
+    fields: Vec<FieldParams<'a>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    series_key: Option<&'a [String]>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    widen_numeric_fields: bool,
+}
+
+/// A typed field in the body of the request to declare a table
+#[derive(Debug, Serialize)]
+struct FieldParams<'a> {
+    name: &'a str,
+    #[serde(rename = "type")]
+    field_type: FieldType,
+}
+
+/// Query parameters for deleting a last cache from the `/api/v3/configure/last_cache` API
+#[derive(Debug, Serialize)]
+struct DeleteLastCacheParams {
+    db: String,
+    table: String,
+    name: String,
+}
+
 /// Query parameters for the `/api/v3/query_sql` API
 #[derive(Debug, Serialize)]
 pub struct QueryParams<'a> {
@@ -546,9 +1183,13 @@ pub enum Format {
 #[cfg(test)]
 mod tests {
     use mockito::{Matcher, Server};
+    use reqwest::StatusCode;
     use serde_json::json;
 
-    use crate::{Client, Format, Precision};
+    use crate::{
+        Client, Error, FieldType, Format, LastCacheCreatedResponse, ParquetImportResponse,
+        Precision,
+    };
 
     #[tokio::test]
     async fn api_v3_write_lp() {
@@ -587,6 +1228,97 @@ mod tests {
         mock.assert_async().await;
     }
 
+    #[tokio::test]
+    async fn api_v3_write_json() {
+        let db = "stats";
+        let body = r#"[{"table": "cpu", "tags": {"host": "s1"}, "fields": {"usage": 0.5}}]"#;
+
+        let mut mock_server = Server::new_async().await;
+        let mock = mock_server
+            .mock("POST", "/api/v3/write_json")
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("precision".into(), "second".into()),
+                Matcher::UrlEncoded("db".into(), db.into()),
+            ]))
+            .match_body(body)
+            .create_async()
+            .await;
+
+        let client = Client::new(mock_server.url()).expect("create client");
+
+        client
+            .api_v3_write_json(db)
+            .precision(Precision::Second)
+            .body(body)
+            .send()
+            .await
+            .expect("send write_json request");
+
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn api_v3_write_lp_retries() {
+        let db = "stats";
+        let body = "cpu,host=s1 usage=0.5";
+
+        let mut mock_server = Server::new_async().await;
+        let overloaded = mock_server
+            .mock("POST", "/api/v3/write_lp")
+            .match_body(body)
+            .with_status(429)
+            .with_header("Retry-After", "0")
+            .expect(2)
+            .create_async()
+            .await;
+        let ok = mock_server
+            .mock("POST", "/api/v3/write_lp")
+            .match_body(body)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let client = Client::new(mock_server.url()).expect("create client");
+
+        // the write is retried until the server accepts it:
+        client
+            .api_v3_write_lp(db)
+            .max_retries(2)
+            .body(body)
+            .send()
+            .await
+            .expect("send write_lp request");
+
+        overloaded.assert_async().await;
+        ok.assert_async().await;
+
+        let mut mock_server = Server::new_async().await;
+        let overloaded = mock_server
+            .mock("POST", "/api/v3/write_lp")
+            .with_status(503)
+            .with_header("Retry-After", "0")
+            .expect(2)
+            .create_async()
+            .await;
+
+        let client = Client::new(mock_server.url()).expect("create client");
+
+        // the error is returned once the retries are used up:
+        let err = client
+            .api_v3_write_lp(db)
+            .max_retries(1)
+            .body(body)
+            .send()
+            .await
+            .expect_err("write should fail");
+        assert!(
+            matches!(err, Error::ApiError { code, .. } if code == StatusCode::SERVICE_UNAVAILABLE),
+            "unexpected error: {err:?}"
+        );
+
+        overloaded.assert_async().await;
+    }
+
     #[tokio::test]
     async fn api_v3_query_sql() {
         let token = "super-secret-token";
@@ -784,4 +1516,243 @@ mod tests {
 
         r.expect("sent request successfully");
     }
+
+    #[tokio::test]
+    async fn api_v3_configure_database_create() {
+        let mut mock_server = Server::new_async().await;
+        let mock = mock_server
+            .mock("POST", "/api/v3/configure/database")
+            .match_body(Matcher::Json(json!({
+                "db": "stats",
+                "retention_period": "30d",
+                "max_tables": 10,
+                "max_columns_per_table": 20
+            })))
+            .with_status(201)
+            .create_async()
+            .await;
+
+        let client = Client::new(mock_server.url()).expect("create client");
+
+        client
+            .api_v3_configure_database_create("stats")
+            .retention_period("30d")
+            .max_tables(10)
+            .max_columns_per_table(20)
+            .send()
+            .await
+            .expect("send create database request");
+
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn api_v3_configure_table_create() {
+        let mut mock_server = Server::new_async().await;
+        let mock = mock_server
+            .mock("POST", "/api/v3/configure/table")
+            .match_body(Matcher::Json(json!({
+                "db": "stats",
+                "table": "cpu",
+                "tags": ["host"],
+                "fields": [
+                    {"name": "usage", "type": "float64"},
+                    {"name": "count", "type": "uint64"}
+                ],
+                "series_key": ["host"]
+            })))
+            .with_status(201)
+            .create_async()
+            .await;
+
+        let client = Client::new(mock_server.url()).expect("create client");
+
+        client
+            .api_v3_configure_table_create("stats", "cpu")
+            .tags(["host"])
+            .field("usage", FieldType::Float64)
+            .field("count", "uint64".parse().unwrap())
+            .series_key(["host"])
+            .send()
+            .await
+            .expect("send create table request");
+
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn api_v3_configure_last_cache_create() {
+        let token = "super-secret-token";
+        let db = "stats";
+        let table = "cpu";
+        let response = json!({
+            "name": "cpu_host_last_cache",
+            "key_columns": ["host"],
+            "value_columns": ["usage"],
+            "count": 5
+        });
+
+        let mut mock_server = Server::new_async().await;
+        let mock = mock_server
+            .mock("POST", "/api/v3/configure/last_cache")
+            .match_header("Authorization", format!("Bearer {token}").as_str())
+            .match_body(Matcher::Json(json!({
+                "db": db,
+                "table": table,
+                "key_columns": ["host"],
+                "value_columns": ["usage"],
+                "count": 5
+            })))
+            .with_status(201)
+            .with_body(response.to_string())
+            .create_async()
+            .await;
+
+        let client = Client::new(mock_server.url())
+            .expect("create client")
+            .with_auth_token(token);
+
+        let created = client
+            .api_v3_configure_last_cache_create(db, table)
+            .key_columns(["host"])
+            .value_columns(["usage"])
+            .count(5)
+            .send()
+            .await
+            .expect("send create last cache request");
+
+        mock.assert_async().await;
+
+        assert_eq!(
+            LastCacheCreatedResponse {
+                name: "cpu_host_last_cache".into(),
+                key_columns: vec!["host".into()],
+                value_columns: vec!["usage".into()],
+                count: 5,
+            },
+            created
+        );
+    }
+
+    #[tokio::test]
+    async fn api_v3_configure_database_delete() {
+        let mut mock_server = Server::new_async().await;
+        let mock = mock_server
+            .mock("DELETE", "/api/v3/configure/database")
+            .match_query(Matcher::UrlEncoded("db".into(), "stats".into()))
+            .with_status(200)
+            .create_async()
+            .await;
+
+        let client = Client::new(mock_server.url()).expect("create client");
+
+        client
+            .api_v3_configure_database_delete("stats")
+            .await
+            .expect("send delete database request");
+
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn api_v3_configure_table_delete() {
+        let mut mock_server = Server::new_async().await;
+        let mock = mock_server
+            .mock("DELETE", "/api/v3/configure/table")
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("db".into(), "stats".into()),
+                Matcher::UrlEncoded("table".into(), "cpu".into()),
+            ]))
+            .with_status(200)
+            .create_async()
+            .await;
+
+        let client = Client::new(mock_server.url()).expect("create client");
+
+        client
+            .api_v3_configure_table_delete("stats", "cpu")
+            .await
+            .expect("send delete table request");
+
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn api_v3_configure_last_cache_delete() {
+        let db = "stats";
+        let table = "cpu";
+        let name = "cpu_host_last_cache";
+
+        let mut mock_server = Server::new_async().await;
+        let mock = mock_server
+            .mock("DELETE", "/api/v3/configure/last_cache")
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("db".into(), db.into()),
+                Matcher::UrlEncoded("table".into(), table.into()),
+                Matcher::UrlEncoded("name".into(), name.into()),
+            ]))
+            .with_status(200)
+            .create_async()
+            .await;
+
+        let client = Client::new(mock_server.url()).expect("create client");
+
+        client
+            .api_v3_configure_last_cache_delete(db, table, name)
+            .await
+            .expect("send delete last cache request");
+
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn api_v3_import_parquet() {
+        let token = "super-secret-token";
+        let parquet = b"PAR1 not really".to_vec();
+        let response = json!({
+            "segment_id": 7,
+            "segment_wal_size_bytes": 0,
+            "segment_parquet_size_bytes": 1024,
+            "segment_row_count": 3,
+            "segment_min_time": 10,
+            "segment_max_time": 30,
+            "databases": {}
+        });
+
+        let mut mock_server = Server::new_async().await;
+        let mock = mock_server
+            .mock("POST", "/api/v3/import/parquet")
+            .match_header("Authorization", format!("Bearer {token}").as_str())
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("db".into(), "stats".into()),
+                Matcher::UrlEncoded("table".into(), "cpu".into()),
+            ]))
+            .match_body(parquet.clone())
+            .with_status(200)
+            .with_body(response.to_string())
+            .create_async()
+            .await;
+
+        let client = Client::new(mock_server.url())
+            .expect("create client")
+            .with_auth_token(token);
+
+        let imported = client
+            .api_v3_import_parquet("stats", "cpu", parquet)
+            .await
+            .expect("send import parquet request");
+
+        mock.assert_async().await;
+
+        assert_eq!(
+            ParquetImportResponse {
+                segment_id: 7,
+                segment_parquet_size_bytes: 1024,
+                segment_row_count: 3,
+                segment_min_time: 10,
+                segment_max_time: 30,
+            },
+            imported
+        );
+    }
 }
@@ -131,6 +131,9 @@ impl<W, Q, P, T>
         Server {
             common_state: self.common_state,
             http,
+            write_buffer: self.write_buffer.0,
+            time_provider: self.time_provider.0,
+            max_request_size: self.max_request_size,
             persister,
             authorizer,
         }
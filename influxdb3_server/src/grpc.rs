@@ -1,14 +1,307 @@
 use std::sync::Arc;
+use std::task::{ready, Context, Poll};
 
+use arrow::record_batch::RecordBatch;
+use arrow_flight::decode::FlightRecordBatchStream;
+use arrow_flight::error::FlightError;
+use arrow_flight::flight_descriptor::DescriptorType;
 use arrow_flight::flight_service_server::{
     FlightService as Flight, FlightServiceServer as FlightServer,
 };
+use arrow_flight::{
+    Action, ActionType, Criteria, Empty, FlightData, FlightDescriptor, FlightInfo,
+    HandshakeRequest, HandshakeResponse, PollInfo, PutResult, SchemaResult, Ticket,
+};
+use async_trait::async_trait;
 use authz::Authorizer;
+use data_types::NamespaceName;
+use futures::future::Either;
+use futures::stream::{self, BoxStream};
+use futures::{StreamExt, TryStreamExt};
+use influxdb3_write::{write_buffer, WriteBuffer};
 use iox_query::QueryDatabase;
+use iox_time::TimeProvider;
+use observability_deps::tracing::info;
+use tonic::metadata::MetadataMap;
+use tonic::{Request, Response, Status, Streaming};
+use tower::Service;
+
+/// The path of the Flight `DoPut` call, which is served by the [`FlightWriteService`]
+const DO_PUT_PATH: &str = "/arrow.flight.protocol.FlightService/DoPut";
 
-pub(crate) fn make_flight_server<Q: QueryDatabase>(
+pub(crate) fn make_flight_server<Q: QueryDatabase, W: WriteBuffer, T: TimeProvider>(
     server: Arc<Q>,
+    write_buffer: Arc<W>,
+    time_provider: Arc<T>,
+    max_request_bytes: usize,
     authz: Option<Arc<dyn Authorizer>>,
-) -> FlightServer<impl Flight> {
-    service_grpc_flight::make_server(server, authz)
+) -> FlightRouter<FlightServer<impl Flight>, FlightServer<FlightWriteService<W, T>>> {
+    FlightRouter {
+        query: service_grpc_flight::make_server(server, authz.clone()),
+        write: FlightServer::new(FlightWriteService {
+            write_buffer,
+            time_provider,
+            max_request_bytes,
+            authz,
+        }),
+    }
+}
+
+/// Routes `DoPut` calls to the Flight service that writes to the write buffer, and all other
+/// calls to the Flight service that serves queries
+#[derive(Debug, Clone)]
+pub(crate) struct FlightRouter<Q, W> {
+    query: Q,
+    write: W,
+}
+
+impl<Q, W, B> Service<hyper::Request<B>> for FlightRouter<Q, W>
+where
+    Q: Service<hyper::Request<B>>,
+    W: Service<hyper::Request<B>, Response = Q::Response, Error = Q::Error>,
+{
+    type Response = Q::Response;
+    type Error = Q::Error;
+    type Future = Either<Q::Future, W::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        ready!(self.query.poll_ready(cx))?;
+        self.write.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: hyper::Request<B>) -> Self::Future {
+        if req.uri().path() == DO_PUT_PATH {
+            Either::Right(self.write.call(req))
+        } else {
+            Either::Left(self.query.call(req))
+        }
+    }
+}
+
+/// A Flight service for writes, where a client streams record batches for a table with `DoPut`.
+/// The flight descriptor of the stream is a path of the database and table names.
+///
+/// The batches are validated against, or merged into, the table's schema, and written to the
+/// WAL and buffered without being converted to line protocol.
+#[derive(Debug)]
+pub(crate) struct FlightWriteService<W, T> {
+    write_buffer: Arc<W>,
+    time_provider: Arc<T>,
+    /// The limit on the size of the decoded batches of a write, the same as for HTTP requests
+    max_request_bytes: usize,
+    authz: Option<Arc<dyn Authorizer>>,
+}
+
+impl<W, T> FlightWriteService<W, T> {
+    async fn authorize(&self, metadata: &MetadataMap) -> Result<(), Status> {
+        let Some(authz) = &self.authz else {
+            return Ok(());
+        };
+        let token = bearer_token(metadata)?;
+        authz.permissions(token, &[]).await.map_err(|e| match e {
+            authz::Error::Forbidden => Status::permission_denied(e.to_string()),
+            _ => Status::unauthenticated(e.to_string()),
+        })?;
+        Ok(())
+    }
+}
+
+/// The token of the request's `Bearer` authorization header, if it has one
+fn bearer_token(metadata: &MetadataMap) -> Result<Option<Vec<u8>>, Status> {
+    metadata
+        .get("authorization")
+        .map(|value| {
+            value
+                .to_str()
+                .ok()
+                .and_then(|value| value.strip_prefix("Bearer "))
+                .map(|token| token.as_bytes().to_vec())
+                .ok_or_else(|| Status::unauthenticated("malformed authorization header"))
+        })
+        .transpose()
+}
+
+/// The database and table names of a `DoPut` call, from the path of its flight descriptor
+fn write_target(descriptor: Option<&FlightDescriptor>) -> Result<(String, String), Status> {
+    match descriptor {
+        Some(descriptor) if descriptor.r#type() == DescriptorType::Path => {
+            match descriptor.path.as_slice() {
+                [db_name, table_name] => Ok((db_name.clone(), table_name.clone())),
+                _ => Err(Status::invalid_argument(format!(
+                    "the flight descriptor path must be the database and table names, got {:?}",
+                    descriptor.path
+                ))),
+            }
+        }
+        _ => Err(Status::invalid_argument(
+            "the first message of the stream must have a flight descriptor with the path of \
+            the database and table names",
+        )),
+    }
+}
+
+/// The size of the data in the batch's columns. The buffers the columns are decoded into are
+/// shared between them, so only the slices of the buffers that each column uses are counted.
+fn batch_size(batch: &RecordBatch) -> usize {
+    batch
+        .columns()
+        .iter()
+        .map(|column| {
+            column
+                .to_data()
+                .get_slice_memory_size()
+                .unwrap_or_else(|_| column.get_array_memory_size())
+        })
+        .sum()
+}
+
+fn write_error_status(error: write_buffer::Error) -> Status {
+    match error {
+        write_buffer::Error::InvalidArrowWrite(_)
+        | write_buffer::Error::DatabaseNameError(_)
+        | write_buffer::Error::CatalogUpdateError(_) => Status::invalid_argument(error.to_string()),
+        write_buffer::Error::WriteQueueFull { .. }
+        | write_buffer::Error::BufferMemoryLimitExceeded { .. } => {
+            Status::resource_exhausted(error.to_string())
+        }
+        _ => Status::internal(error.to_string()),
+    }
+}
+
+type FlightStream<T> = BoxStream<'static, Result<T, Status>>;
+
+#[async_trait]
+impl<W: WriteBuffer, T: TimeProvider> Flight for FlightWriteService<W, T> {
+    type HandshakeStream = FlightStream<HandshakeResponse>;
+    type ListFlightsStream = FlightStream<FlightInfo>;
+    type DoGetStream = FlightStream<FlightData>;
+    type DoPutStream = FlightStream<PutResult>;
+    type DoActionStream = FlightStream<arrow_flight::Result>;
+    type ListActionsStream = FlightStream<ActionType>;
+    type DoExchangeStream = FlightStream<FlightData>;
+
+    async fn do_put(
+        &self,
+        request: Request<Streaming<FlightData>>,
+    ) -> Result<Response<Self::DoPutStream>, Status> {
+        self.authorize(request.metadata()).await?;
+
+        let mut flight_data = request.into_inner();
+        let Some(first) = flight_data.message().await? else {
+            return Err(Status::invalid_argument("the stream has no messages"));
+        };
+        let (db_name, table_name) = write_target(first.flight_descriptor.as_ref())?;
+        info!("write_arrow to {}.{}", db_name, table_name);
+        let database =
+            NamespaceName::new(db_name).map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        let flight_data = stream::once(async { Ok(first) })
+            .chain(flight_data)
+            .map_err(FlightError::from);
+        let mut batch_stream = FlightRecordBatchStream::new_from_flight_data(flight_data);
+        let mut batches = Vec::new();
+        let mut write_bytes = 0;
+        while let Some(batch) = batch_stream.try_next().await.map_err(|e| match e {
+            FlightError::Tonic(status) => status,
+            e => Status::invalid_argument(e.to_string()),
+        })? {
+            // the batches are held in memory until they're all written, so the size of the write
+            // is limited as it's decoded
+            write_bytes += batch_size(&batch);
+            if write_bytes > self.max_request_bytes {
+                return Err(Status::resource_exhausted(format!(
+                    "max request size ({} bytes) exceeded",
+                    self.max_request_bytes
+                )));
+            }
+            batches.push(batch);
+        }
+
+        self.write_buffer
+            .write_arrow(database, &table_name, batches, self.time_provider.now())
+            .await
+            .map_err(write_error_status)?;
+
+        let result = stream::once(async { Ok::<_, Status>(PutResult::default()) });
+        Ok(Response::new(result.boxed()))
+    }
+
+    async fn handshake(
+        &self,
+        _request: Request<Streaming<HandshakeRequest>>,
+    ) -> Result<Response<Self::HandshakeStream>, Status> {
+        Err(Status::unimplemented(
+            "handshake is served by the query service",
+        ))
+    }
+
+    async fn list_flights(
+        &self,
+        _request: Request<Criteria>,
+    ) -> Result<Response<Self::ListFlightsStream>, Status> {
+        Err(Status::unimplemented(
+            "list_flights is served by the query service",
+        ))
+    }
+
+    async fn get_flight_info(
+        &self,
+        _request: Request<FlightDescriptor>,
+    ) -> Result<Response<FlightInfo>, Status> {
+        Err(Status::unimplemented(
+            "get_flight_info is served by the query service",
+        ))
+    }
+
+    async fn poll_flight_info(
+        &self,
+        _request: Request<FlightDescriptor>,
+    ) -> Result<Response<PollInfo>, Status> {
+        Err(Status::unimplemented(
+            "poll_flight_info is served by the query service",
+        ))
+    }
+
+    async fn get_schema(
+        &self,
+        _request: Request<FlightDescriptor>,
+    ) -> Result<Response<SchemaResult>, Status> {
+        Err(Status::unimplemented(
+            "get_schema is served by the query service",
+        ))
+    }
+
+    async fn do_get(
+        &self,
+        _request: Request<Ticket>,
+    ) -> Result<Response<Self::DoGetStream>, Status> {
+        Err(Status::unimplemented(
+            "do_get is served by the query service",
+        ))
+    }
+
+    async fn do_exchange(
+        &self,
+        _request: Request<Streaming<FlightData>>,
+    ) -> Result<Response<Self::DoExchangeStream>, Status> {
+        Err(Status::unimplemented("do_exchange is not supported"))
+    }
+
+    async fn do_action(
+        &self,
+        _request: Request<Action>,
+    ) -> Result<Response<Self::DoActionStream>, Status> {
+        Err(Status::unimplemented(
+            "do_action is served by the query service",
+        ))
+    }
+
+    async fn list_actions(
+        &self,
+        _request: Request<Empty>,
+    ) -> Result<Response<Self::ListActionsStream>, Status> {
+        Err(Status::unimplemented(
+            "list_actions is served by the query service",
+        ))
+    }
 }
@@ -2,29 +2,37 @@
 
 use crate::{query_executor, QueryKind};
 use crate::{CommonServerState, QueryExecutor};
+use arrow::datatypes::Schema as ArrowSchema;
 use arrow::record_batch::RecordBatch;
 use arrow::util::pretty;
 use authz::http::AuthorizationHeaderExtension;
 use authz::Authorizer;
 use bytes::{Bytes, BytesMut};
+use chrono::DateTime;
 use data_types::NamespaceName;
 use datafusion::error::DataFusionError;
 use datafusion::execution::memory_pool::UnboundedMemoryPool;
 use datafusion::execution::RecordBatchStream;
 use datafusion::physical_plan::SendableRecordBatchStream;
+use datafusion_util::MemoryStream;
 use futures::{StreamExt, TryStreamExt};
 use hyper::header::ACCEPT;
 use hyper::header::AUTHORIZATION;
 use hyper::header::CONTENT_ENCODING;
 use hyper::header::CONTENT_TYPE;
+use hyper::header::RETRY_AFTER;
 use hyper::http::HeaderValue;
 use hyper::HeaderMap;
 use hyper::{Body, Method, Request, Response, StatusCode};
 use influxdb3_process::{INFLUXDB3_GIT_HASH_SHORT, INFLUXDB3_VERSION};
+use influxdb3_write::catalog::DatabaseLimits;
 use influxdb3_write::catalog::Error as CatalogError;
+use influxdb3_write::catalog::LastCacheDefinition;
+use influxdb3_write::last_cache::Error as LastCacheError;
 use influxdb3_write::persister::TrackedMemoryArrowWriter;
 use influxdb3_write::write_buffer::Error as WriteBufferError;
 use influxdb3_write::BufferedWriteRequest;
+use influxdb3_write::DeletePredicate;
 use influxdb3_write::Precision;
 use influxdb3_write::WriteBuffer;
 use iox_http::write::single_tenant::SingleTenantRequestUnifier;
@@ -34,6 +42,7 @@ use iox_query_influxql_rewrite as rewrite;
 use iox_query_params::StatementParams;
 use iox_time::TimeProvider;
 use observability_deps::tracing::{debug, error, info};
+use schema::InfluxFieldType;
 use serde::de::DeserializeOwned;
 use serde::Deserialize;
 use serde::Serialize;
@@ -46,6 +55,8 @@ use std::sync::Arc;
 use thiserror::Error;
 use unicode_segmentation::UnicodeSegmentation;
 
+mod otlp;
+mod prom;
 mod v1;
 
 #[derive(Debug, Error)]
@@ -187,6 +198,36 @@ pub enum Error {
 
     #[error("v1 query API error: {0}")]
     V1Query(#[from] v1::QueryError),
+
+    #[error("prometheus remote API error: {0}")]
+    Prom(#[from] prom::PromError),
+
+    #[error("otlp API error: {0}")]
+    Otlp(#[from] otlp::OtlpError),
+
+    /// Missing parameters for the last cache API
+    #[error("missing query parameters 'db' and 'table'")]
+    MissingLastCacheParams,
+
+    /// Missing parameters for the table configuration API
+    #[error("missing query parameters 'db' and 'table'")]
+    MissingTableParams,
+
+    /// Missing parameter for the database configuration API
+    #[error("missing query parameter 'db'")]
+    MissingDatabaseParam,
+
+    /// The provided retention period could not be parsed
+    #[error("invalid retention period: {0}")]
+    InvalidRetentionPeriod(#[from] humantime::DurationError),
+
+    /// The start or stop time of a delete request could not be parsed
+    #[error("invalid delete time range: {0}")]
+    InvalidDeleteTime(String),
+
+    /// The predicate of a delete request could not be parsed
+    #[error("invalid delete predicate: {0}")]
+    InvalidDeletePredicate(String),
 }
 
 #[derive(Debug, Error)]
@@ -212,9 +253,9 @@ impl Error {
     fn into_response(self) -> Response<Body> {
         match self {
             Self::WriteBuffer(WriteBufferError::CatalogUpdateError(
-                err @ (CatalogError::TooManyDbs
-                | CatalogError::TooManyColumns
-                | CatalogError::TooManyTables),
+                err @ (CatalogError::TooManyDbs { .. }
+                | CatalogError::TooManyColumns { .. }
+                | CatalogError::TooManyTables { .. }),
             )) => {
                 let err: ErrorMessage<()> = ErrorMessage {
                     error: err.to_string(),
@@ -263,6 +304,122 @@ impl Error {
                     .body(body)
                     .unwrap()
             }
+            Self::WriteBuffer(
+                err @ (WriteBufferError::DatabaseNotFound { .. }
+                | WriteBufferError::TableNotFound { .. }
+                | WriteBufferError::LastCacheNotFound { .. }),
+            ) => {
+                let err: ErrorMessage<()> = ErrorMessage {
+                    error: err.to_string(),
+                    data: None,
+                };
+                let serialized = serde_json::to_string(&err).unwrap();
+                let body = Body::from(serialized);
+                Response::builder()
+                    .status(StatusCode::NOT_FOUND)
+                    .body(body)
+                    .unwrap()
+            }
+            Self::WriteBuffer(WriteBufferError::LastCacheError(
+                err @ LastCacheError::CacheAlreadyExists { .. },
+            )) => {
+                let err: ErrorMessage<()> = ErrorMessage {
+                    error: err.to_string(),
+                    data: None,
+                };
+                let serialized = serde_json::to_string(&err).unwrap();
+                let body = Body::from(serialized);
+                Response::builder()
+                    .status(StatusCode::CONFLICT)
+                    .body(body)
+                    .unwrap()
+            }
+            Self::WriteBuffer(WriteBufferError::LastCacheError(
+                err @ (LastCacheError::ColumnDoesNotExist { .. }
+                | LastCacheError::InvalidKeyColumn { .. }),
+            )) => {
+                let err: ErrorMessage<()> = ErrorMessage {
+                    error: err.to_string(),
+                    data: None,
+                };
+                let serialized = serde_json::to_string(&err).unwrap();
+                let body = Body::from(serialized);
+                Response::builder()
+                    .status(StatusCode::BAD_REQUEST)
+                    .body(body)
+                    .unwrap()
+            }
+            Self::WriteBuffer(WriteBufferError::CatalogUpdateError(
+                err @ (CatalogError::InvalidLastCacheSize
+                | CatalogError::InvalidTableDefinition { .. }),
+            )) => {
+                let err: ErrorMessage<()> = ErrorMessage {
+                    error: err.to_string(),
+                    data: None,
+                };
+                let serialized = serde_json::to_string(&err).unwrap();
+                let body = Body::from(serialized);
+                Response::builder()
+                    .status(StatusCode::BAD_REQUEST)
+                    .body(body)
+                    .unwrap()
+            }
+            Self::WriteBuffer(WriteBufferError::CatalogUpdateError(
+                err @ (CatalogError::DatabaseAlreadyExists { .. }
+                | CatalogError::TableAlreadyExists { .. }),
+            )) => {
+                let err: ErrorMessage<()> = ErrorMessage {
+                    error: err.to_string(),
+                    data: None,
+                };
+                let serialized = serde_json::to_string(&err).unwrap();
+                let body = Body::from(serialized);
+                Response::builder()
+                    .status(StatusCode::CONFLICT)
+                    .body(body)
+                    .unwrap()
+            }
+            Self::WriteBuffer(err @ WriteBufferError::WriteQueueFull { .. }) => {
+                backpressure_response(StatusCode::TOO_MANY_REQUESTS, err)
+            }
+            Self::WriteBuffer(err @ WriteBufferError::BufferMemoryLimitExceeded { .. }) => {
+                backpressure_response(StatusCode::SERVICE_UNAVAILABLE, err)
+            }
+            Self::WriteBuffer(
+                err @ (WriteBufferError::InvalidDeletePredicate(_)
+                | WriteBufferError::InvalidJson(_)
+                | WriteBufferError::InvalidParquetImport(_)),
+            ) => {
+                let err: ErrorMessage<()> = ErrorMessage {
+                    error: err.to_string(),
+                    data: None,
+                };
+                let serialized = serde_json::to_string(&err).unwrap();
+                let body = Body::from(serialized);
+                Response::builder()
+                    .status(StatusCode::BAD_REQUEST)
+                    .body(body)
+                    .unwrap()
+            }
+            Self::MissingLastCacheParams
+            | Self::MissingTableParams
+            | Self::MissingDatabaseParam
+            | Self::InvalidRetentionPeriod(_)
+            | Self::InvalidDeleteTime(_)
+            | Self::InvalidDeletePredicate(_)
+            | Self::Prom(_)
+            | Self::Otlp(otlp::OtlpError::Decode(_)) => {
+                let err: ErrorMessage<()> = ErrorMessage {
+                    error: self.to_string(),
+                    data: None,
+                };
+                let serialized = serde_json::to_string(&err).unwrap();
+                let body = Body::from(serialized);
+                Response::builder()
+                    .status(StatusCode::BAD_REQUEST)
+                    .body(body)
+                    .unwrap()
+            }
             Self::UnsupportedMethod => {
                 let err: ErrorMessage<()> = ErrorMessage {
                     error: self.to_string(),
@@ -275,6 +432,18 @@ impl Error {
                     .body(body)
                     .unwrap()
             }
+            Self::Otlp(otlp::OtlpError::UnsupportedContentType(_)) => {
+                let err: ErrorMessage<()> = ErrorMessage {
+                    error: self.to_string(),
+                    data: None,
+                };
+                let serialized = serde_json::to_string(&err).unwrap();
+                let body = Body::from(serialized);
+                Response::builder()
+                    .status(StatusCode::UNSUPPORTED_MEDIA_TYPE)
+                    .body(body)
+                    .unwrap()
+            }
             _ => {
                 let body = Body::from(self.to_string());
                 Response::builder()
@@ -286,6 +455,25 @@ impl Error {
     }
 }
 
+/// The response to a write rejected because the write buffer is saturated, which tells the client
+/// how long to wait before retrying
+fn backpressure_response(status: StatusCode, err: WriteBufferError) -> Response<Body> {
+    let retry_after = err.retry_after().unwrap_or_default();
+    let err: ErrorMessage<()> = ErrorMessage {
+        error: err.to_string(),
+        data: None,
+    };
+    let serialized = serde_json::to_string(&err).unwrap();
+    let body = Body::from(serialized);
+    // Retry-After is in whole seconds, so round up:
+    let retry_after_secs = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+    Response::builder()
+        .status(status)
+        .header(RETRY_AFTER, retry_after_secs)
+        .body(body)
+        .unwrap()
+}
+
 pub type Result<T, E = Error> = std::result::Result<T, E>;
 
 #[derive(Debug)]
@@ -386,6 +574,58 @@ where
         }
     }
 
+    async fn write_json(&self, req: Request<Body>) -> Result<Response<Body>> {
+        let query = req.uri().query().ok_or(Error::MissingWriteParams)?;
+        let params: WriteParams = serde_urlencoded::from_str(query)?;
+        validate_db_name(&params.db, false)?;
+        info!("write_json to {}", params.db);
+
+        let body = self.read_body(req).await?;
+        let body = std::str::from_utf8(&body).map_err(Error::NonUtf8Body)?;
+
+        let database = NamespaceName::new(params.db)?;
+
+        let default_time = self.time_provider.now();
+
+        let result = self
+            .write_buffer
+            .write_json(
+                database,
+                body,
+                default_time,
+                params.accept_partial,
+                params.precision,
+            )
+            .await?;
+
+        if result.invalid_lines.is_empty() {
+            Ok(Response::new(Body::empty()))
+        } else {
+            Err(Error::PartialLpWrite(result))
+        }
+    }
+
+    async fn import_parquet(&self, req: Request<Body>) -> Result<Response<Body>> {
+        let query = req.uri().query().ok_or(Error::MissingTableParams)?;
+        let ParquetImportRequest { db, table } = serde_urlencoded::from_str(query)?;
+        validate_db_name(&db, false)?;
+        info!(%db, %table, "handling import_parquet");
+
+        let body = self.read_body(req).await?;
+        let database = NamespaceName::new(db)?;
+
+        let segment = self
+            .write_buffer
+            .import_parquet(database, &table, body)
+            .await?;
+
+        Response::builder()
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(serde_json::to_string(&segment)?))
+            .map_err(Into::into)
+    }
+
     async fn query_sql(&self, req: Request<Body>) -> Result<Response<Body>> {
         let QueryRequest {
             database,
@@ -458,6 +698,290 @@ where
         Ok(Response::new(Body::from(body)))
     }
 
+    async fn configure_database_create(&self, req: Request<Body>) -> Result<Response<Body>> {
+        let body = self.read_body(req).await?;
+        let DatabaseConfigureRequest {
+            db,
+            retention_period,
+            limits,
+            strict_schema,
+        } = serde_json::from_slice(body.as_ref())?;
+        validate_db_name(&db, false)?;
+        let retention_period = retention_period
+            .as_deref()
+            .map(humantime::parse_duration)
+            .transpose()?;
+        let strict_schema = strict_schema.unwrap_or_default();
+
+        info!(
+            %db,
+            ?retention_period,
+            ?limits,
+            strict_schema,
+            "handling configure_database_create"
+        );
+
+        self.write_buffer
+            .create_database(&db, retention_period, limits, strict_schema)
+            .await?;
+
+        Response::builder()
+            .status(StatusCode::CREATED)
+            .body(Body::empty())
+            .map_err(Into::into)
+    }
+
+    async fn configure_database_update(&self, req: Request<Body>) -> Result<Response<Body>> {
+        let body = self.read_body(req).await?;
+        let DatabaseConfigureRequest {
+            db,
+            retention_period,
+            limits,
+            strict_schema,
+        } = serde_json::from_slice(body.as_ref())?;
+        // a retention period of "none" removes it, so data is kept indefinitely
+        let retention_period = retention_period
+            .as_deref()
+            .map(|period| match period {
+                "none" => Ok(None),
+                period => humantime::parse_duration(period).map(Some),
+            })
+            .transpose()?;
+        let current_limits = self
+            .write_buffer
+            .catalog()
+            .db_schema(&db)
+            .ok_or_else(|| WriteBufferError::DatabaseNotFound {
+                db_name: db.clone(),
+            })?
+            .limits();
+
+        info!(
+            %db,
+            ?retention_period,
+            ?limits,
+            ?strict_schema,
+            "handling configure_database_update"
+        );
+
+        // only the settings given in the request are changed
+        if let Some(retention_period) = retention_period {
+            self.write_buffer
+                .set_retention_period(&db, retention_period)
+                .await?;
+        }
+        if limits != DatabaseLimits::default() {
+            let limits = DatabaseLimits {
+                max_tables: limits.max_tables.or(current_limits.max_tables),
+                max_columns_per_table: limits
+                    .max_columns_per_table
+                    .or(current_limits.max_columns_per_table),
+            };
+            self.write_buffer.set_database_limits(&db, limits).await?;
+        }
+        if let Some(strict_schema) = strict_schema {
+            self.write_buffer
+                .set_strict_schema(&db, strict_schema)
+                .await?;
+        }
+
+        Ok(Response::new(Body::empty()))
+    }
+
+    async fn configure_database_delete(&self, req: Request<Body>) -> Result<Response<Body>> {
+        let query = req.uri().query().ok_or(Error::MissingDatabaseParam)?;
+        let DatabaseDeleteRequest { db } = serde_urlencoded::from_str(query)?;
+
+        info!(%db, "handling configure_database_delete");
+
+        self.write_buffer.delete_database(&db).await?;
+
+        Ok(Response::new(Body::empty()))
+    }
+
+    async fn configure_table_create(&self, req: Request<Body>) -> Result<Response<Body>> {
+        let body = self.read_body(req).await?;
+        let TableCreateRequest {
+            db,
+            table,
+            tags,
+            fields,
+            series_key,
+            widen_numeric_fields,
+        } = serde_json::from_slice(body.as_ref())?;
+
+        info!(%db, %table, "handling configure_table_create");
+
+        let fields = fields
+            .into_iter()
+            .map(|FieldDefinition { name, data_type }| (name, data_type.into()))
+            .collect();
+        self.write_buffer
+            .create_table(&db, &table, tags, fields, series_key, widen_numeric_fields)
+            .await?;
+
+        Response::builder()
+            .status(StatusCode::CREATED)
+            .body(Body::empty())
+            .map_err(Into::into)
+    }
+
+    async fn configure_table_update(&self, req: Request<Body>) -> Result<Response<Body>> {
+        let body = self.read_body(req).await?;
+        let TableUpdateRequest {
+            db,
+            table,
+            widen_numeric_fields,
+        } = serde_json::from_slice(body.as_ref())?;
+
+        info!(%db, %table, widen_numeric_fields, "handling configure_table_update");
+
+        self.write_buffer
+            .set_table_field_widening(&db, &table, widen_numeric_fields)
+            .await?;
+
+        Ok(Response::new(Body::empty()))
+    }
+
+    async fn configure_table_delete(&self, req: Request<Body>) -> Result<Response<Body>> {
+        let query = req.uri().query().ok_or(Error::MissingTableParams)?;
+        let TableDeleteRequest { db, table } = serde_urlencoded::from_str(query)?;
+
+        info!(%db, %table, "handling configure_table_delete");
+
+        self.write_buffer.delete_table(&db, &table).await?;
+
+        Ok(Response::new(Body::empty()))
+    }
+
+    async fn delete_rows(&self, req: Request<Body>) -> Result<Response<Body>> {
+        let body = self.read_body(req).await?;
+        let DeleteRequest {
+            db,
+            table,
+            start,
+            stop,
+            predicate,
+        } = serde_json::from_slice(body.as_ref())?;
+        let min_time = start
+            .as_deref()
+            .map(parse_delete_time)
+            .transpose()?
+            .unwrap_or(i64::MIN);
+        let max_time = stop
+            .as_deref()
+            .map(parse_delete_time)
+            .transpose()?
+            .unwrap_or(i64::MAX);
+        let tags = predicate
+            .as_deref()
+            .map(parse_delete_predicate)
+            .transpose()?
+            .unwrap_or_default();
+
+        info!(%db, %table, min_time, max_time, ?tags, "handling delete_rows");
+
+        self.write_buffer
+            .delete_rows(
+                &db,
+                &table,
+                DeletePredicate {
+                    min_time,
+                    max_time,
+                    tags,
+                },
+            )
+            .await?;
+
+        Ok(Response::new(Body::empty()))
+    }
+
+    async fn configure_last_cache_create(&self, req: Request<Body>) -> Result<Response<Body>> {
+        let body = self.read_body(req).await?;
+        let LastCacheCreateRequest {
+            db,
+            table,
+            name,
+            key_columns,
+            value_columns,
+            count,
+        } = serde_json::from_slice(body.as_ref())?;
+
+        info!(%db, %table, ?name, "handling configure_last_cache_create");
+
+        let definition = self
+            .write_buffer
+            .create_last_cache(
+                &db,
+                &table,
+                name.as_deref(),
+                count,
+                key_columns,
+                value_columns,
+            )
+            .await?;
+
+        Response::builder()
+            .status(StatusCode::CREATED)
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(serde_json::to_string(&definition)?))
+            .map_err(Into::into)
+    }
+
+    fn configure_last_cache_list(&self, req: Request<Body>) -> Result<Response<Body>> {
+        let query = req.uri().query().ok_or(Error::MissingLastCacheParams)?;
+        let LastCacheListRequest { db, table } = serde_urlencoded::from_str(query)?;
+
+        let db_schema = self.write_buffer.catalog().db_schema(&db).ok_or_else(|| {
+            WriteBufferError::DatabaseNotFound {
+                db_name: db.clone(),
+            }
+        })?;
+        let table_names = match table {
+            Some(table) if db_schema.table_exists(&table) => vec![table],
+            Some(table) => {
+                return Err(WriteBufferError::TableNotFound {
+                    db_name: db,
+                    table_name: table,
+                }
+                .into())
+            }
+            None => db_schema.table_names(),
+        };
+        let caches = table_names
+            .iter()
+            .filter_map(|table_name| db_schema.get_table(table_name))
+            .flat_map(|table_def| {
+                table_def
+                    .last_caches
+                    .iter()
+                    .map(move |definition| LastCacheListEntry {
+                        table: &table_def.name,
+                        definition,
+                    })
+            })
+            .collect::<Vec<_>>();
+
+        Response::builder()
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(serde_json::to_string(&caches)?))
+            .map_err(Into::into)
+    }
+
+    async fn configure_last_cache_delete(&self, req: Request<Body>) -> Result<Response<Body>> {
+        let query = req.uri().query().ok_or(Error::MissingLastCacheParams)?;
+        let LastCacheDeleteRequest { db, table, name } = serde_urlencoded::from_str(query)?;
+
+        info!(%db, %table, %name, "handling configure_last_cache_delete");
+
+        self.write_buffer
+            .delete_last_cache(&db, &table, &name)
+            .await?;
+
+        Ok(Response::new(Body::empty()))
+    }
+
     /// Parse the request's body into raw bytes, applying the configured size
     /// limits and decoding any content encoding.
     async fn read_body(&self, req: hyper::Request<Body>) -> Result<Bytes> {
@@ -608,6 +1132,20 @@ where
             }
         };
 
+        if let Some(table_name) = statement.drop_measurement() {
+            let Some(database) = database else {
+                return Err(Error::InfluxqlNoDatabase);
+            };
+            info!(%database, %table_name, "handling DROP MEASUREMENT");
+            self.write_buffer
+                .delete_table(&database, &table_name)
+                .await?;
+            return Ok(Box::pin(MemoryStream::new_with_schema(
+                vec![],
+                Arc::new(ArrowSchema::empty()),
+            )));
+        }
+
         if statement.statement().is_show_databases() {
             self.query_executor.show_databases()
         } else if statement.statement().is_show_retention_policies() {
@@ -862,6 +1400,230 @@ async fn record_batch_stream_to_body(
     .map(Body::from)
 }
 
+/// Request body for creating a database, or updating its settings
+///
+/// When a database is updated, only the settings given in the request are changed.
+#[derive(Debug, Deserialize)]
+struct DatabaseConfigureRequest {
+    db: String,
+    /// The retention period, in a human readable form, e.g., "30d", if omitted when creating a
+    /// database, data in it is kept indefinitely. An update can remove the retention period by
+    /// setting it to "none".
+    retention_period: Option<String>,
+    /// Overrides of the server's catalog limits for the database, if omitted when creating a
+    /// database, the server's limits apply
+    #[serde(flatten)]
+    limits: DatabaseLimits,
+    /// If set, writes are only accepted for tables and columns that have been declared with
+    /// the `/api/v3/configure/table` API
+    strict_schema: Option<bool>,
+}
+
+/// Query parameters for deleting a database
+#[derive(Debug, Deserialize)]
+struct DatabaseDeleteRequest {
+    db: String,
+}
+
+/// Request body for declaring a new table
+#[derive(Debug, Deserialize)]
+struct TableCreateRequest {
+    db: String,
+    table: String,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    fields: Vec<FieldDefinition>,
+    /// If provided, the table uses the v3 data model, with this series key
+    series_key: Option<Vec<String>>,
+    /// If set, writes of integer or unsigned integer values to a float field, or float values to
+    /// an integer field, are accepted and stored as floats, rather than being rejected
+    #[serde(default)]
+    widen_numeric_fields: bool,
+}
+
+/// Request body for updating the settings of a table
+#[derive(Debug, Deserialize)]
+struct TableUpdateRequest {
+    db: String,
+    table: String,
+    #[serde(default)]
+    widen_numeric_fields: bool,
+}
+
+/// A typed field declared for a new table
+#[derive(Debug, Deserialize)]
+struct FieldDefinition {
+    name: String,
+    #[serde(rename = "type")]
+    data_type: FieldDataType,
+}
+
+/// The types that can be declared for a field
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum FieldDataType {
+    Utf8,
+    Int64,
+    UInt64,
+    Float64,
+    Bool,
+}
+
+impl From<FieldDataType> for InfluxFieldType {
+    fn from(data_type: FieldDataType) -> Self {
+        match data_type {
+            FieldDataType::Utf8 => Self::String,
+            FieldDataType::Int64 => Self::Integer,
+            FieldDataType::UInt64 => Self::UInteger,
+            FieldDataType::Float64 => Self::Float,
+            FieldDataType::Bool => Self::Boolean,
+        }
+    }
+}
+
+/// Query parameters for deleting a table
+#[derive(Debug, Deserialize)]
+struct TableDeleteRequest {
+    db: String,
+    table: String,
+}
+
+/// Query parameters for importing a parquet file into a table
+#[derive(Debug, Deserialize)]
+struct ParquetImportRequest {
+    db: String,
+    table: String,
+}
+
+/// Request body for deleting rows from a table
+#[derive(Debug, Deserialize)]
+struct DeleteRequest {
+    db: String,
+    table: String,
+    /// The start of the time range to delete, inclusive, as an RFC3339 timestamp, if omitted,
+    /// the range is unbounded
+    start: Option<String>,
+    /// The end of the time range to delete, inclusive, as an RFC3339 timestamp, if omitted, the
+    /// range is unbounded
+    stop: Option<String>,
+    /// Tag values that rows must have to be deleted, e.g., `host="a" AND region="us-east"`
+    predicate: Option<String>,
+}
+
+/// Parse an RFC3339 timestamp from a delete request into nanoseconds since the epoch
+fn parse_delete_time(s: &str) -> Result<i64> {
+    DateTime::parse_from_rfc3339(s)
+        .map_err(|e| Error::InvalidDeleteTime(format!("{s}: {e}")))?
+        .timestamp_nanos_opt()
+        .ok_or_else(|| Error::InvalidDeleteTime(format!("{s}: out of range")))
+}
+
+/// Parse a delete predicate of the form `tag1="value1" AND tag2="value2"` into pairs of tag name
+/// and value. Quotes around names and values are optional, and can be double or single quotes.
+/// Names and values with whitespace or an `=` in them must be quoted.
+fn parse_delete_predicate(s: &str) -> Result<Vec<(String, String)>> {
+    let invalid_term = |term: &str| {
+        Error::InvalidDeletePredicate(format!("expected tag=\"value\", got '{term}' in {s}"))
+    };
+    let mut tags = Vec::new();
+    let terms = split_unquoted(s, char::is_whitespace)
+        .ok_or_else(|| Error::InvalidDeletePredicate(format!("unterminated quote in {s}")))?;
+    let mut terms = terms.into_iter().filter(|term| !term.is_empty()).peekable();
+    if terms.peek().is_none() {
+        return Ok(tags);
+    }
+    loop {
+        let term = terms.next().ok_or_else(|| invalid_term(""))?;
+        let name = split_unquoted(term, |c| c == '=')
+            .filter(|parts| parts.len() > 1)
+            .map(|parts| parts[0])
+            .ok_or_else(|| invalid_term(term))?;
+        let value = &term[name.len() + 1..];
+        let (name, value) = (unquote(name), unquote(value));
+        if name.is_empty() || value.is_empty() {
+            return Err(invalid_term(term));
+        }
+        tags.push((name.to_string(), value.to_string()));
+        match terms.next() {
+            None => return Ok(tags),
+            Some(and) if and.eq_ignore_ascii_case("and") => continue,
+            Some(other) => {
+                return Err(Error::InvalidDeletePredicate(format!(
+                    "only AND is supported between tag values, got '{other}' in {s}"
+                )))
+            }
+        }
+    }
+}
+
+/// Split `s` at the characters matching `is_separator` that aren't within double or single
+/// quotes, returning `None` if a quote isn't closed
+fn split_unquoted(s: &str, is_separator: impl Fn(char) -> bool) -> Option<Vec<&str>> {
+    let mut parts = Vec::new();
+    let mut quote = None;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => (),
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if is_separator(c) => {
+                parts.push(&s[start..i]);
+                start = i + c.len_utf8();
+            }
+            None => (),
+        }
+    }
+    if quote.is_some() {
+        return None;
+    }
+    parts.push(&s[start..]);
+    Some(parts)
+}
+
+/// Strip the double or single quotes surrounding `s`, if it has them
+fn unquote(s: &str) -> &str {
+    ['"', '\'']
+        .into_iter()
+        .find_map(|q| s.strip_prefix(q).and_then(|s| s.strip_suffix(q)))
+        .unwrap_or(s)
+}
+
+/// Request body for creating a last cache
+#[derive(Debug, Deserialize)]
+struct LastCacheCreateRequest {
+    db: String,
+    table: String,
+    name: Option<String>,
+    key_columns: Option<Vec<String>>,
+    value_columns: Option<Vec<String>>,
+    count: Option<usize>,
+}
+
+/// Query parameters for listing last caches
+#[derive(Debug, Deserialize)]
+struct LastCacheListRequest {
+    db: String,
+    table: Option<String>,
+}
+
+/// Query parameters for deleting a last cache
+#[derive(Debug, Deserialize)]
+struct LastCacheDeleteRequest {
+    db: String,
+    table: String,
+    name: String,
+}
+
+/// An entry in the response to a last cache list request
+#[derive(Debug, Serialize)]
+struct LastCacheListEntry<'a> {
+    table: &'a str,
+    #[serde(flatten)]
+    definition: &'a LastCacheDefinition,
+}
+
 // This is a hack around the fact that bool default is false not true
 const fn true_fn() -> bool {
     true
@@ -950,11 +1712,41 @@ where
         }
         (Method::POST, "/api/v3/write") => http_server.write_v3(req).await,
         (Method::POST, "/api/v3/write_lp") => http_server.write_lp(req).await,
+        (Method::POST, "/api/v3/write_json") => http_server.write_json(req).await,
+        (Method::POST, "/api/v3/import/parquet") => http_server.import_parquet(req).await,
         (Method::GET | Method::POST, "/api/v3/query_sql") => http_server.query_sql(req).await,
         (Method::GET | Method::POST, "/api/v3/query_influxql") => {
             http_server.query_influxql(req).await
         }
         (Method::GET, "/query") => http_server.v1_query(req).await,
+        (Method::POST, "/api/v3/delete") => http_server.delete_rows(req).await,
+        (Method::POST, "/api/v3/configure/database") => {
+            http_server.configure_database_create(req).await
+        }
+        (Method::PUT, "/api/v3/configure/database") => {
+            http_server.configure_database_update(req).await
+        }
+        (Method::DELETE, "/api/v3/configure/database") => {
+            http_server.configure_database_delete(req).await
+        }
+        (_, "/api/v3/configure/database") => Err(Error::UnsupportedMethod),
+        (Method::POST, "/api/v3/configure/table") => http_server.configure_table_create(req).await,
+        (Method::PUT, "/api/v3/configure/table") => http_server.configure_table_update(req).await,
+        (Method::DELETE, "/api/v3/configure/table") => {
+            http_server.configure_table_delete(req).await
+        }
+        (_, "/api/v3/configure/table") => Err(Error::UnsupportedMethod),
+        (Method::POST, "/api/v3/configure/last_cache") => {
+            http_server.configure_last_cache_create(req).await
+        }
+        (Method::GET, "/api/v3/configure/last_cache") => http_server.configure_last_cache_list(req),
+        (Method::DELETE, "/api/v3/configure/last_cache") => {
+            http_server.configure_last_cache_delete(req).await
+        }
+        (_, "/api/v3/configure/last_cache") => Err(Error::UnsupportedMethod),
+        (Method::POST, "/api/v1/prom/write") => http_server.prom_write(req).await,
+        (Method::POST, "/api/v1/prom/read") => http_server.prom_read(req).await,
+        (Method::POST, "/v1/metrics") => http_server.otlp_metrics(req).await,
         (Method::GET, "/health" | "/api/v1/health") => http_server.health(),
         (Method::GET | Method::POST, "/ping") => http_server.ping(),
         (Method::GET, "/metrics") => http_server.handle_metrics(),
@@ -997,6 +1789,8 @@ fn legacy_write_error_to_response(e: WriteParseError) -> Response<Body> {
 
 #[cfg(test)]
 mod tests {
+    use super::parse_delete_predicate;
+    use super::parse_delete_time;
     use super::validate_db_name;
     use super::ValidateDbNameError;
 
@@ -1026,4 +1820,48 @@ mod tests {
         assert_validate_db_name!("_foo", false, Err(ValidateDbNameError::InvalidStartChar));
         assert_validate_db_name!("", false, Err(ValidateDbNameError::Empty));
     }
+
+    #[test]
+    fn test_parse_delete_predicate() {
+        assert!(parse_delete_predicate("").unwrap().is_empty());
+        assert_eq!(
+            parse_delete_predicate(r#"host="a" AND region=us-east"#).unwrap(),
+            vec![
+                ("host".to_string(), "a".to_string()),
+                ("region".to_string(), "us-east".to_string())
+            ]
+        );
+        assert_eq!(
+            parse_delete_predicate(r#""host"="a" and "region"="b""#).unwrap(),
+            vec![
+                ("host".to_string(), "a".to_string()),
+                ("region".to_string(), "b".to_string())
+            ]
+        );
+        assert_eq!(
+            parse_delete_predicate(r#"host='a b'  AND "data center"="us east=1""#).unwrap(),
+            vec![
+                ("host".to_string(), "a b".to_string()),
+                ("data center".to_string(), "us east=1".to_string())
+            ]
+        );
+        assert!(parse_delete_predicate("host").is_err());
+        assert!(parse_delete_predicate(r#"host="""#).is_err());
+        assert!(parse_delete_predicate("host='a b").is_err());
+        assert!(parse_delete_predicate(r#"host="a" OR host="b""#).is_err());
+        assert!(parse_delete_predicate(r#"host="a" AND"#).is_err());
+    }
+
+    #[test]
+    fn test_parse_delete_time() {
+        assert_eq!(
+            parse_delete_time("1970-01-01T00:00:01Z").unwrap(),
+            1_000_000_000
+        );
+        assert_eq!(
+            parse_delete_time("1970-01-01T01:00:00.000000001+01:00").unwrap(),
+            1
+        );
+        assert!(parse_delete_time("yesterday").is_err());
+    }
 }
@@ -0,0 +1,674 @@
+//! The OTLP/HTTP metrics API
+//!
+//! Accepts the protobuf encoded `ExportMetricsServiceRequest`s that OpenTelemetry SDKs and
+//! collectors export to `/v1/metrics`, as defined in [`metrics.proto`]. Each metric is stored in
+//! a table of its name, with the attributes of its resource and of each data point as tags:
+//!
+//! * a gauge or sum data point is a row with a `value` field, a float or integer as recorded
+//! * a histogram data point is a row with `count`, `sum`, `min` and `max` fields, and a
+//!   `le_<bound>` field for each bucket bound, with the cumulative count of values less than or
+//!   equal to it, like the buckets of a Prometheus histogram
+//!
+//! Data points that can't be written, such as those of exponential histograms and summaries,
+//! are rejected individually and reported in the partial success of the response.
+//!
+//! As with the Prometheus messages, only the fields that are used here are declared.
+//!
+//! [`metrics.proto`]: https://github.com/open-telemetry/opentelemetry-proto/blob/main/opentelemetry/proto/metrics/v1/metrics.proto
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use data_types::NamespaceName;
+use hyper::header::CONTENT_TYPE;
+use hyper::{Body, Request, Response, StatusCode};
+use influxdb3_write::{escape_lp, Precision, WriteBuffer};
+use iox_time::TimeProvider;
+use observability_deps::tracing::info;
+use prost::Message;
+use serde::Deserialize;
+
+use crate::QueryExecutor;
+
+use super::{validate_db_name, Error, HttpApi, Result};
+
+const PROTOBUF_CONTENT_TYPE: &str = "application/x-protobuf";
+
+/// The data point flag set when a data point has no value, e.g., because its series went stale
+const NO_RECORDED_VALUE_FLAG: u32 = 1;
+
+/// Errors in an OTLP export request
+#[derive(Debug, thiserror::Error)]
+pub enum OtlpError {
+    #[error("unsupported content type {0}, only application/x-protobuf is supported")]
+    UnsupportedContentType(String),
+
+    #[error("invalid protobuf body: {0}")]
+    Decode(#[from] prost::DecodeError),
+}
+
+impl<W, Q, T> HttpApi<W, Q, T>
+where
+    W: WriteBuffer,
+    Q: QueryExecutor,
+    T: TimeProvider,
+    Error: From<<Q as QueryExecutor>::Error>,
+{
+    /// Implements the OTLP/HTTP metrics export API
+    ///
+    /// Writes the data points of every metric in the request to the database given by the `db`
+    /// parameter. Data points that couldn't be written are counted in the response, along with
+    /// the reason the first of them was rejected.
+    pub(super) async fn otlp_metrics(&self, req: Request<Body>) -> Result<Response<Body>> {
+        let query = req.uri().query().ok_or(Error::MissingWriteParams)?;
+        let OtlpParams { db } = serde_urlencoded::from_str(query)?;
+        validate_db_name(&db, false)?;
+        info!(%db, "handling otlp_metrics");
+
+        if let Some(content_type) = req.headers().get(CONTENT_TYPE) {
+            let content_type = content_type.to_str()?;
+            if !content_type.starts_with(PROTOBUF_CONTENT_TYPE) {
+                return Err(OtlpError::UnsupportedContentType(content_type.to_string()).into());
+            }
+        }
+
+        let body = self.read_body(req).await?;
+        let request = ExportMetricsServiceRequest::decode(body).map_err(OtlpError::from)?;
+        let mut conversion = export_request_to_lp(&request);
+
+        if !conversion.lp.is_empty() {
+            let database = NamespaceName::new(db)?;
+            let default_time = self.time_provider.now();
+            let result = self
+                .write_buffer
+                .write_lp(
+                    database,
+                    &conversion.lp,
+                    default_time,
+                    true,
+                    Precision::Nanosecond,
+                )
+                .await?;
+            if let Some(line) = result.invalid_lines.first() {
+                conversion.reject(result.invalid_lines.len(), || line.error_message.clone());
+            }
+        }
+
+        let response = ExportMetricsServiceResponse {
+            partial_success: conversion.error_message.map(|error_message| {
+                ExportMetricsPartialSuccess {
+                    rejected_data_points: conversion.rejected_data_points,
+                    error_message,
+                }
+            }),
+        };
+        Ok(Response::builder()
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, PROTOBUF_CONTENT_TYPE)
+            .body(Body::from(response.encode_to_vec()))?)
+    }
+}
+
+/// The parameters of the OTLP metrics API
+#[derive(Debug, Deserialize)]
+struct OtlpParams {
+    db: String,
+}
+
+/// The line protocol converted from an export request, a line for each data point, along with
+/// the data points that couldn't be converted
+#[derive(Debug, Default)]
+struct Conversion {
+    lp: String,
+    rejected_data_points: i64,
+    /// Why the first rejected data point was rejected
+    error_message: Option<String>,
+}
+
+impl Conversion {
+    fn push_line(&mut self, line: Result<String, String>) {
+        match line {
+            Ok(line) => {
+                if !self.lp.is_empty() {
+                    self.lp.push('\n');
+                }
+                self.lp.push_str(&line);
+            }
+            Err(message) => self.reject(1, || message),
+        }
+    }
+
+    fn reject(&mut self, data_points: usize, message: impl FnOnce() -> String) {
+        self.rejected_data_points += data_points as i64;
+        if self.error_message.is_none() {
+            self.error_message = Some(message());
+        }
+    }
+}
+
+fn export_request_to_lp(request: &ExportMetricsServiceRequest) -> Conversion {
+    let mut conversion = Conversion::default();
+    for resource_metrics in &request.resource_metrics {
+        let resource_attributes = resource_metrics
+            .resource
+            .as_ref()
+            .map(|r| r.attributes.as_slice())
+            .unwrap_or_default();
+
+        for metric in resource_metrics
+            .scope_metrics
+            .iter()
+            .flat_map(|s| &s.metrics)
+        {
+            let name = metric.name.as_str();
+            if name.is_empty() {
+                let data_points = metric.data.as_ref().map_or(0, |d| d.data_points());
+                conversion.reject(data_points, || "a metric has no name".to_string());
+                continue;
+            }
+
+            match &metric.data {
+                Some(metric::Data::Gauge(Gauge { data_points }))
+                | Some(metric::Data::Sum(Sum { data_points })) => {
+                    for point in data_points {
+                        if point.flags & NO_RECORDED_VALUE_FLAG == 0 {
+                            conversion.push_line(number_data_point_line(
+                                name,
+                                resource_attributes,
+                                point,
+                            ));
+                        }
+                    }
+                }
+                Some(metric::Data::Histogram(Histogram { data_points })) => {
+                    for point in data_points {
+                        if point.flags & NO_RECORDED_VALUE_FLAG == 0 {
+                            conversion.push_line(histogram_data_point_line(
+                                name,
+                                resource_attributes,
+                                point,
+                            ));
+                        }
+                    }
+                }
+                Some(metric::Data::ExponentialHistogram(ExponentialHistogram { data_points })) => {
+                    conversion.reject(data_points.len(), || {
+                        format!("metric {name} is an exponential histogram, which isn't supported")
+                    });
+                }
+                Some(metric::Data::Summary(Summary { data_points })) => {
+                    conversion.reject(data_points.len(), || {
+                        format!("metric {name} is a summary, which isn't supported")
+                    });
+                }
+                None => {}
+            }
+        }
+    }
+    conversion
+}
+
+fn number_data_point_line(
+    name: &str,
+    resource_attributes: &[KeyValue],
+    point: &NumberDataPoint,
+) -> Result<String, String> {
+    let field = match point.value {
+        Some(number_data_point::Value::AsDouble(v)) if v.is_finite() => format!("value={v}"),
+        Some(number_data_point::Value::AsDouble(v)) => {
+            return Err(format!("metric {name} has a data point with value {v}"))
+        }
+        Some(number_data_point::Value::AsInt(v)) => format!("value={v}i"),
+        None => return Err(format!("metric {name} has a data point with no value")),
+    };
+    data_point_line(
+        name,
+        resource_attributes,
+        &point.attributes,
+        &field,
+        point.time_unix_nano,
+    )
+}
+
+fn histogram_data_point_line(
+    name: &str,
+    resource_attributes: &[KeyValue],
+    point: &HistogramDataPoint,
+) -> Result<String, String> {
+    let mut fields = format!("count={}u", point.count);
+    for (field, value) in [("sum", point.sum), ("min", point.min), ("max", point.max)] {
+        if let Some(value) = value.filter(|v| v.is_finite()) {
+            write!(fields, ",{field}={value}").unwrap();
+        }
+    }
+
+    // the last bucket count is for values above the last bound, which is the total count
+    if !point.bucket_counts.is_empty() {
+        if point.bucket_counts.len() != point.explicit_bounds.len() + 1 {
+            return Err(format!(
+                "metric {name} has a data point with {} bucket counts for {} bounds",
+                point.bucket_counts.len(),
+                point.explicit_bounds.len()
+            ));
+        }
+        let mut cumulative_count = 0;
+        for (bound, count) in point.explicit_bounds.iter().zip(&point.bucket_counts) {
+            cumulative_count += count;
+            write!(fields, ",le_{bound}={cumulative_count}u").unwrap();
+        }
+    }
+
+    data_point_line(
+        name,
+        resource_attributes,
+        &point.attributes,
+        &fields,
+        point.time_unix_nano,
+    )
+}
+
+/// Builds a line for a data point with the given fields. The data point's attributes take
+/// precedence over those of its resource, and attributes whose values are arrays, maps or bytes
+/// are left out.
+fn data_point_line(
+    name: &str,
+    resource_attributes: &[KeyValue],
+    attributes: &[KeyValue],
+    fields: &str,
+    time_unix_nano: u64,
+) -> Result<String, String> {
+    let mut tags = BTreeMap::new();
+    for attribute in resource_attributes.iter().chain(attributes) {
+        if let Some(value) = attribute.value.as_ref().and_then(AnyValue::to_tag_value) {
+            tags.insert(attribute.key.as_str(), value);
+        }
+    }
+
+    if name.contains(['\n', '\r']) {
+        return Err(format!("the metric name {name:?} can't contain newlines"));
+    }
+    // a line starting with '#' is a comment:
+    if name.starts_with('#') {
+        return Err(format!("the metric name {name} can't start with '#'"));
+    }
+    let mut line = escape_lp(name, &[',', ' ']);
+    for (key, value) in tags {
+        if key.is_empty() || value.is_empty() {
+            continue;
+        }
+        if key.contains(['\n', '\r']) || value.contains(['\n', '\r']) {
+            return Err(format!(
+                "the attribute {key:?} of metric {name} can't contain newlines"
+            ));
+        }
+        write!(
+            line,
+            ",{}={}",
+            escape_lp(key, &[',', '=', ' ']),
+            escape_lp(&value, &[',', '=', ' '])
+        )
+        .unwrap();
+    }
+    write!(line, " {fields}").unwrap();
+    if time_unix_nano > 0 {
+        write!(line, " {time_unix_nano}").unwrap();
+    }
+    Ok(line)
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct ExportMetricsServiceRequest {
+    #[prost(message, repeated, tag = "1")]
+    resource_metrics: Vec<ResourceMetrics>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct ExportMetricsServiceResponse {
+    #[prost(message, optional, tag = "1")]
+    partial_success: Option<ExportMetricsPartialSuccess>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct ExportMetricsPartialSuccess {
+    #[prost(int64, tag = "1")]
+    rejected_data_points: i64,
+    #[prost(string, tag = "2")]
+    error_message: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct ResourceMetrics {
+    #[prost(message, optional, tag = "1")]
+    resource: Option<Resource>,
+    #[prost(message, repeated, tag = "2")]
+    scope_metrics: Vec<ScopeMetrics>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct Resource {
+    #[prost(message, repeated, tag = "1")]
+    attributes: Vec<KeyValue>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct ScopeMetrics {
+    #[prost(message, repeated, tag = "2")]
+    metrics: Vec<Metric>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct Metric {
+    #[prost(string, tag = "1")]
+    name: String,
+    #[prost(oneof = "metric::Data", tags = "5, 7, 9, 10, 11")]
+    data: Option<metric::Data>,
+}
+
+mod metric {
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub(super) enum Data {
+        #[prost(message, tag = "5")]
+        Gauge(super::Gauge),
+        #[prost(message, tag = "7")]
+        Sum(super::Sum),
+        #[prost(message, tag = "9")]
+        Histogram(super::Histogram),
+        #[prost(message, tag = "10")]
+        ExponentialHistogram(super::ExponentialHistogram),
+        #[prost(message, tag = "11")]
+        Summary(super::Summary),
+    }
+
+    impl Data {
+        pub(super) fn data_points(&self) -> usize {
+            match self {
+                Self::Gauge(g) => g.data_points.len(),
+                Self::Sum(s) => s.data_points.len(),
+                Self::Histogram(h) => h.data_points.len(),
+                Self::ExponentialHistogram(h) => h.data_points.len(),
+                Self::Summary(s) => s.data_points.len(),
+            }
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct Gauge {
+    #[prost(message, repeated, tag = "1")]
+    data_points: Vec<NumberDataPoint>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct Sum {
+    #[prost(message, repeated, tag = "1")]
+    data_points: Vec<NumberDataPoint>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct Histogram {
+    #[prost(message, repeated, tag = "1")]
+    data_points: Vec<HistogramDataPoint>,
+}
+
+/// Only the number of data points of an exponential histogram is needed, to reject them
+#[derive(Clone, PartialEq, prost::Message)]
+struct ExponentialHistogram {
+    #[prost(message, repeated, tag = "1")]
+    data_points: Vec<UnsupportedDataPoint>,
+}
+
+/// Only the number of data points of a summary is needed, to reject them
+#[derive(Clone, PartialEq, prost::Message)]
+struct Summary {
+    #[prost(message, repeated, tag = "1")]
+    data_points: Vec<UnsupportedDataPoint>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct UnsupportedDataPoint {}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct NumberDataPoint {
+    #[prost(message, repeated, tag = "7")]
+    attributes: Vec<KeyValue>,
+    #[prost(fixed64, tag = "3")]
+    time_unix_nano: u64,
+    #[prost(oneof = "number_data_point::Value", tags = "4, 6")]
+    value: Option<number_data_point::Value>,
+    #[prost(uint32, tag = "8")]
+    flags: u32,
+}
+
+mod number_data_point {
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub(super) enum Value {
+        #[prost(double, tag = "4")]
+        AsDouble(f64),
+        #[prost(sfixed64, tag = "6")]
+        AsInt(i64),
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct HistogramDataPoint {
+    #[prost(message, repeated, tag = "9")]
+    attributes: Vec<KeyValue>,
+    #[prost(fixed64, tag = "3")]
+    time_unix_nano: u64,
+    #[prost(fixed64, tag = "4")]
+    count: u64,
+    #[prost(double, optional, tag = "5")]
+    sum: Option<f64>,
+    #[prost(fixed64, repeated, tag = "6")]
+    bucket_counts: Vec<u64>,
+    #[prost(double, repeated, tag = "7")]
+    explicit_bounds: Vec<f64>,
+    #[prost(uint32, tag = "10")]
+    flags: u32,
+    #[prost(double, optional, tag = "11")]
+    min: Option<f64>,
+    #[prost(double, optional, tag = "12")]
+    max: Option<f64>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct KeyValue {
+    #[prost(string, tag = "1")]
+    key: String,
+    #[prost(message, optional, tag = "2")]
+    value: Option<AnyValue>,
+}
+
+/// The array, map and bytes values aren't declared, so they decode as no value
+#[derive(Clone, PartialEq, prost::Message)]
+struct AnyValue {
+    #[prost(oneof = "any_value::Value", tags = "1, 2, 3, 4")]
+    value: Option<any_value::Value>,
+}
+
+impl AnyValue {
+    fn to_tag_value(&self) -> Option<String> {
+        match self.value.as_ref()? {
+            any_value::Value::StringValue(s) => Some(s.clone()),
+            any_value::Value::BoolValue(b) => Some(b.to_string()),
+            any_value::Value::IntValue(i) => Some(i.to_string()),
+            any_value::Value::DoubleValue(d) => Some(d.to_string()),
+        }
+    }
+}
+
+mod any_value {
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub(super) enum Value {
+        #[prost(string, tag = "1")]
+        StringValue(String),
+        #[prost(bool, tag = "2")]
+        BoolValue(bool),
+        #[prost(int64, tag = "3")]
+        IntValue(i64),
+        #[prost(double, tag = "4")]
+        DoubleValue(f64),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attribute(key: &str, value: any_value::Value) -> KeyValue {
+        KeyValue {
+            key: key.to_string(),
+            value: Some(AnyValue { value: Some(value) }),
+        }
+    }
+
+    fn string_attribute(key: &str, value: &str) -> KeyValue {
+        attribute(key, any_value::Value::StringValue(value.to_string()))
+    }
+
+    fn number_point(
+        attributes: Vec<KeyValue>,
+        value: Option<number_data_point::Value>,
+        flags: u32,
+    ) -> NumberDataPoint {
+        NumberDataPoint {
+            attributes,
+            time_unix_nano: 1_000,
+            value,
+            flags,
+        }
+    }
+
+    fn named_metric(name: &str, data: metric::Data) -> Metric {
+        Metric {
+            name: name.to_string(),
+            data: Some(data),
+        }
+    }
+
+    #[test]
+    fn converts_export_request_to_lp() {
+        let request = ExportMetricsServiceRequest {
+            resource_metrics: vec![ResourceMetrics {
+                resource: Some(Resource {
+                    attributes: vec![
+                        string_attribute("service.name", "checkout"),
+                        string_attribute("host", "a"),
+                    ],
+                }),
+                scope_metrics: vec![ScopeMetrics {
+                    metrics: vec![
+                        named_metric(
+                            "cpu utilization",
+                            metric::Data::Gauge(Gauge {
+                                data_points: vec![
+                                    // the data point's attribute overrides the resource's
+                                    number_point(
+                                        vec![
+                                            string_attribute("host", "b"),
+                                            attribute("core", any_value::Value::IntValue(1)),
+                                        ],
+                                        Some(number_data_point::Value::AsDouble(0.5)),
+                                        0,
+                                    ),
+                                    number_point(vec![], None, NO_RECORDED_VALUE_FLAG),
+                                    number_point(vec![], None, 0),
+                                ],
+                            }),
+                        ),
+                        named_metric(
+                            "requests",
+                            metric::Data::Sum(Sum {
+                                data_points: vec![number_point(
+                                    vec![attribute("ok", any_value::Value::BoolValue(true))],
+                                    Some(number_data_point::Value::AsInt(42)),
+                                    0,
+                                )],
+                            }),
+                        ),
+                        named_metric(
+                            "duration",
+                            metric::Data::Histogram(Histogram {
+                                data_points: vec![
+                                    HistogramDataPoint {
+                                        attributes: vec![],
+                                        time_unix_nano: 2_000,
+                                        count: 5,
+                                        sum: Some(1.5),
+                                        bucket_counts: vec![1, 2, 1, 1],
+                                        explicit_bounds: vec![0.1, 0.25, 0.5],
+                                        flags: 0,
+                                        min: Some(0.1),
+                                        max: None,
+                                    },
+                                    HistogramDataPoint {
+                                        attributes: vec![],
+                                        time_unix_nano: 2_000,
+                                        count: 5,
+                                        sum: None,
+                                        bucket_counts: vec![5],
+                                        explicit_bounds: vec![0.1],
+                                        flags: 0,
+                                        min: None,
+                                        max: None,
+                                    },
+                                ],
+                            }),
+                        ),
+                        named_metric(
+                            "latency",
+                            metric::Data::Summary(Summary {
+                                data_points: vec![UnsupportedDataPoint {}; 2],
+                            }),
+                        ),
+                    ],
+                }],
+            }],
+        };
+        let decoded =
+            ExportMetricsServiceRequest::decode(request.encode_to_vec().as_slice()).unwrap();
+
+        let conversion = export_request_to_lp(&decoded);
+        assert_eq!(
+            "cpu\\ utilization,core=1,host=b,service.name=checkout value=0.5 1000\n\
+            requests,host=a,ok=true,service.name=checkout value=42i 1000\n\
+            duration,host=a,service.name=checkout count=5u,sum=1.5,min=0.1,\
+            le_0.1=1u,le_0.25=3u,le_0.5=4u 2000",
+            conversion.lp
+        );
+        assert_eq!(4, conversion.rejected_data_points);
+        assert_eq!(
+            Some("metric cpu utilization has a data point with no value"),
+            conversion.error_message.as_deref()
+        );
+
+        // a metric whose lines would be comments is rejected
+        let request = ExportMetricsServiceRequest {
+            resource_metrics: vec![ResourceMetrics {
+                resource: None,
+                scope_metrics: vec![ScopeMetrics {
+                    metrics: vec![named_metric(
+                        "#requests",
+                        metric::Data::Sum(Sum {
+                            data_points: vec![
+                                number_point(
+                                    vec![],
+                                    Some(number_data_point::Value::AsInt(42)),
+                                    0,
+                                );
+                                2
+                            ],
+                        }),
+                    )],
+                }],
+            }],
+        };
+        let conversion = export_request_to_lp(&request);
+        assert!(conversion.lp.is_empty());
+        assert_eq!(2, conversion.rejected_data_points);
+        assert_eq!(
+            Some("the metric name #requests can't start with '#'"),
+            conversion.error_message.as_deref()
+        );
+    }
+}
@@ -0,0 +1,620 @@
+//! The Prometheus remote write and read APIs
+//!
+//! Prometheus sends snappy compressed `WriteRequest` and `ReadRequest` protobufs, as defined in
+//! its [`remote.proto`], and expects a snappy compressed `ReadResponse` back from a read. Each
+//! metric is stored in a table of its name, with the labels of a series as tags and its samples
+//! in a `value` float field.
+//!
+//! The messages are declared by hand, with only the fields that are used here, since prost
+//! skips any fields it doesn't know about when decoding.
+//!
+//! [`remote.proto`]: https://github.com/prometheus/prometheus/blob/main/prompb/remote.proto
+
+use std::fmt::Write;
+
+use arrow::array::{Array, AsArray};
+use arrow::compute::cast;
+use arrow::datatypes::{DataType, Float64Type, TimeUnit, TimestampMillisecondType};
+use arrow::record_batch::RecordBatch;
+use data_types::NamespaceName;
+use futures::TryStreamExt;
+use hyper::header::{CONTENT_ENCODING, CONTENT_TYPE};
+use hyper::{Body, Request, Response, StatusCode};
+use influxdb3_write::{escape_lp, Precision, WriteBuffer};
+use iox_time::TimeProvider;
+use observability_deps::tracing::info;
+use prost::Message;
+use schema::{InfluxColumnType, TIME_COLUMN_NAME};
+use serde::Deserialize;
+
+use crate::{QueryExecutor, QueryKind};
+
+use super::{validate_db_name, Error, HttpApi, Result};
+
+/// The label that holds the name of a metric, which is the table it's stored in
+const METRIC_NAME_LABEL: &str = "__name__";
+
+/// The field that holds the value of a sample
+const VALUE_FIELD_NAME: &str = "value";
+
+/// Errors in a Prometheus remote write or read request
+#[derive(Debug, thiserror::Error)]
+pub enum PromError {
+    #[error("invalid snappy compressed body: {0}")]
+    Snappy(#[from] snap::Error),
+
+    #[error("invalid protobuf body: {0}")]
+    Decode(#[from] prost::DecodeError),
+
+    #[error("a time series has no __name__ label")]
+    MissingMetricName,
+
+    #[error("the name and value of label {name:?} can't contain newlines")]
+    LabelWithNewline { name: String },
+
+    #[error("the metric name {0} can't start with '#'")]
+    InvalidMetricName(String),
+
+    #[error("a query must have an equality matcher for the __name__ label")]
+    MissingMetricNameMatcher,
+
+    #[error("unknown label matcher type {0}")]
+    UnknownMatcherType(i32),
+}
+
+impl<W, Q, T> HttpApi<W, Q, T>
+where
+    W: WriteBuffer,
+    Q: QueryExecutor,
+    T: TimeProvider,
+    Error: From<<Q as QueryExecutor>::Error>,
+{
+    /// Implements the Prometheus remote write API
+    ///
+    /// Writes the samples of every series in the request to the database given by the `db`
+    /// parameter, skipping any samples that aren't finite, such as staleness markers.
+    pub(super) async fn prom_write(&self, req: Request<Body>) -> Result<Response<Body>> {
+        let query = req.uri().query().ok_or(Error::MissingWriteParams)?;
+        let PromParams { db } = serde_urlencoded::from_str(query)?;
+        validate_db_name(&db, false)?;
+        info!(%db, "handling prom_write");
+
+        let body = self.read_snappy_body(req).await?;
+        let request = WriteRequest::decode(body.as_slice()).map_err(PromError::from)?;
+        let lp = write_request_to_lp(&request)?;
+        if lp.is_empty() {
+            return Ok(Response::builder()
+                .status(StatusCode::NO_CONTENT)
+                .body(Body::empty())?);
+        }
+
+        let database = NamespaceName::new(db)?;
+        let default_time = self.time_provider.now();
+        let result = self
+            .write_buffer
+            .write_lp(database, &lp, default_time, true, Precision::Millisecond)
+            .await?;
+
+        if result.invalid_lines.is_empty() {
+            Ok(Response::builder()
+                .status(StatusCode::NO_CONTENT)
+                .body(Body::empty())?)
+        } else {
+            Err(Error::PartialLpWrite(result))
+        }
+    }
+
+    /// Implements the Prometheus remote read API
+    ///
+    /// Each query in the request must select a metric by name, and can match its labels with
+    /// any of the matcher types. Series are returned as samples, whichever response types the
+    /// request accepts.
+    pub(super) async fn prom_read(&self, req: Request<Body>) -> Result<Response<Body>> {
+        let query = req.uri().query().ok_or(Error::MissingDatabaseParam)?;
+        let PromParams { db } = serde_urlencoded::from_str(query)?;
+        validate_db_name(&db, false)?;
+        info!(%db, "handling prom_read");
+
+        let body = self.read_snappy_body(req).await?;
+        let request = ReadRequest::decode(body.as_slice()).map_err(PromError::from)?;
+
+        let db_schema = self.write_buffer.catalog().db_schema(&db);
+        let mut response = ReadResponse::default();
+        for query in &request.queries {
+            let metric_name = query
+                .matchers
+                .iter()
+                .find(|m| m.r#type == MatchType::Eq as i32 && m.name == METRIC_NAME_LABEL)
+                .map(|m| m.value.as_str())
+                .ok_or(PromError::MissingMetricNameMatcher)?;
+
+            let Some(schema) = db_schema
+                .as_ref()
+                .and_then(|db_schema| db_schema.get_table_schema(metric_name))
+            else {
+                response.results.push(QueryResult::default());
+                continue;
+            };
+            let has_value = schema.iter().any(|(t, f)| {
+                matches!(t, InfluxColumnType::Field(_)) && f.name() == VALUE_FIELD_NAME
+            });
+            if !has_value {
+                response.results.push(QueryResult::default());
+                continue;
+            }
+            let mut tags = schema
+                .iter()
+                .filter(|(t, _)| matches!(t, InfluxColumnType::Tag))
+                .map(|(_, f)| f.name().to_string())
+                .collect::<Vec<_>>();
+            tags.sort();
+
+            let sql = read_query_sql(metric_name, &tags, query)?;
+            let batches: Vec<RecordBatch> = self
+                .query_executor
+                .query(&db, &sql, None, QueryKind::Sql, None, None)
+                .await?
+                .try_collect()
+                .await?;
+            response
+                .results
+                .push(batches_to_query_result(metric_name, &tags, &batches)?);
+        }
+
+        let body = snap::raw::Encoder::new()
+            .compress_vec(&response.encode_to_vec())
+            .map_err(PromError::from)?;
+        Ok(Response::builder()
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, "application/x-protobuf")
+            .header(CONTENT_ENCODING, "snappy")
+            .body(Body::from(body))?)
+    }
+
+    /// Reads and decompresses the snappy compressed body of a remote write or read request,
+    /// applying the configured size limit to the decompressed body as well
+    async fn read_snappy_body(&self, mut req: Request<Body>) -> Result<Vec<u8>> {
+        // the body is always snappy compressed, which `read_body` doesn't decode, so the
+        // header is dropped rather than rejected as an unacceptable encoding
+        req.headers_mut().remove(CONTENT_ENCODING);
+        let body = self.read_body(req).await?;
+
+        let len = snap::raw::decompress_len(&body).map_err(PromError::from)?;
+        if len > self.max_request_bytes {
+            return Err(Error::RequestSizeExceeded(self.max_request_bytes));
+        }
+        Ok(snap::raw::Decoder::new()
+            .decompress_vec(&body)
+            .map_err(PromError::from)?)
+    }
+}
+
+/// The parameters of the remote write and read APIs
+#[derive(Debug, Deserialize)]
+struct PromParams {
+    db: String,
+}
+
+/// Converts the samples of a remote write request to line protocol with millisecond
+/// timestamps, a line for each sample
+fn write_request_to_lp(request: &WriteRequest) -> Result<String, PromError> {
+    let mut lp = String::new();
+    for series in &request.timeseries {
+        let mut metric_name = None;
+        let mut tags = String::new();
+        for label in &series.labels {
+            if label.name.contains(['\n', '\r']) || label.value.contains(['\n', '\r']) {
+                return Err(PromError::LabelWithNewline {
+                    name: label.name.clone(),
+                });
+            }
+            if label.name == METRIC_NAME_LABEL {
+                metric_name = Some(label.value.as_str());
+            } else if !label.value.is_empty() {
+                // an empty label is the same as no label in Prometheus
+                write!(
+                    tags,
+                    ",{}={}",
+                    escape_lp(&label.name, &[',', '=', ' ']),
+                    escape_lp(&label.value, &[',', '=', ' '])
+                )
+                .unwrap();
+            }
+        }
+        let metric_name = metric_name
+            .filter(|name| !name.is_empty())
+            .ok_or(PromError::MissingMetricName)?;
+        // a line starting with '#' is a comment:
+        if metric_name.starts_with('#') {
+            return Err(PromError::InvalidMetricName(metric_name.to_string()));
+        }
+
+        for sample in series.samples.iter().filter(|s| s.value.is_finite()) {
+            if !lp.is_empty() {
+                lp.push('\n');
+            }
+            write!(
+                lp,
+                "{}{tags} {VALUE_FIELD_NAME}={} {}",
+                escape_lp(metric_name, &[',', ' ']),
+                sample.value,
+                sample.timestamp
+            )
+            .unwrap();
+        }
+    }
+    Ok(lp)
+}
+
+/// Builds the SQL query for the samples of a remote read query from a metric's table, which
+/// has the given tags. The tags are selected in order, followed by the value and time, sorted
+/// by series and then time.
+fn read_query_sql(table: &str, tags: &[String], query: &Query) -> Result<String, PromError> {
+    let mut sql = String::from("SELECT ");
+    for tag in tags {
+        write!(sql, "{}, ", quote_ident(tag)).unwrap();
+    }
+    write!(
+        sql,
+        "CAST({value} AS DOUBLE) AS {value}, {time} FROM {table} \
+        WHERE {time} >= to_timestamp_millis({start}) AND {time} <= to_timestamp_millis({end})",
+        value = quote_ident(VALUE_FIELD_NAME),
+        time = quote_ident(TIME_COLUMN_NAME),
+        table = quote_ident(table),
+        start = query.start_timestamp_ms,
+        end = query.end_timestamp_ms,
+    )
+    .unwrap();
+
+    for matcher in &query.matchers {
+        // a label that a series doesn't have matches as the empty string
+        let label = if matcher.name == METRIC_NAME_LABEL {
+            quote_literal(table)
+        } else if tags.contains(&matcher.name) {
+            format!("COALESCE({}, '')", quote_ident(&matcher.name))
+        } else {
+            "''".to_string()
+        };
+        let condition = match MatchType::try_from(matcher.r#type) {
+            Ok(MatchType::Eq) => format!("{label} = {}", quote_literal(&matcher.value)),
+            Ok(MatchType::Neq) => format!("{label} <> {}", quote_literal(&matcher.value)),
+            // Prometheus regular expressions are anchored at both ends
+            Ok(MatchType::Re) => format!(
+                "{label} ~ {}",
+                quote_literal(&format!("^(?:{})$", matcher.value))
+            ),
+            Ok(MatchType::Nre) => format!(
+                "{label} !~ {}",
+                quote_literal(&format!("^(?:{})$", matcher.value))
+            ),
+            Err(_) => return Err(PromError::UnknownMatcherType(matcher.r#type)),
+        };
+        write!(sql, " AND {condition}").unwrap();
+    }
+
+    sql.push_str(" ORDER BY ");
+    for tag in tags {
+        write!(sql, "{}, ", quote_ident(tag)).unwrap();
+    }
+    sql.push_str(&quote_ident(TIME_COLUMN_NAME));
+    Ok(sql)
+}
+
+fn quote_ident(s: &str) -> String {
+    format!("\"{}\"", s.replace('"', "\"\""))
+}
+
+fn quote_literal(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+/// Groups the rows returned by a query built by [`read_query_sql`] into time series, whose
+/// labels are sorted by name
+fn batches_to_query_result(
+    metric_name: &str,
+    tags: &[String],
+    batches: &[RecordBatch],
+) -> Result<QueryResult, arrow::error::ArrowError> {
+    // the tags are sorted, so the metric name label only has to be put in its place
+    let name_position = tags.partition_point(|tag| tag.as_str() < METRIC_NAME_LABEL);
+
+    let mut result = QueryResult::default();
+    for batch in batches {
+        let tag_columns = (0..tags.len())
+            .map(|i| cast(batch.column(i), &DataType::Utf8))
+            .collect::<Result<Vec<_>, _>>()?;
+        let tag_columns = tag_columns
+            .iter()
+            .map(|c| c.as_string::<i32>())
+            .collect::<Vec<_>>();
+        let values = batch.column(tags.len());
+        let values = values.as_primitive::<Float64Type>();
+        let times = cast(
+            batch.column(tags.len() + 1),
+            &DataType::Timestamp(TimeUnit::Millisecond, None),
+        )?;
+        let times = times.as_primitive::<TimestampMillisecondType>();
+
+        for row in 0..batch.num_rows() {
+            if values.is_null(row) {
+                continue;
+            }
+            let mut labels = Vec::with_capacity(tags.len() + 1);
+            for (i, (tag, column)) in tags.iter().zip(&tag_columns).enumerate() {
+                if i == name_position {
+                    labels.push(Label::new(METRIC_NAME_LABEL, metric_name));
+                }
+                if column.is_valid(row) && !column.value(row).is_empty() {
+                    labels.push(Label::new(tag, column.value(row)));
+                }
+            }
+            if name_position == tags.len() {
+                labels.push(Label::new(METRIC_NAME_LABEL, metric_name));
+            }
+            let sample = Sample {
+                value: values.value(row),
+                timestamp: times.value(row),
+            };
+
+            match result.timeseries.last_mut() {
+                Some(series) if series.labels == labels => series.samples.push(sample),
+                _ => result.timeseries.push(TimeSeries {
+                    labels,
+                    samples: vec![sample],
+                }),
+            }
+        }
+    }
+    Ok(result)
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct WriteRequest {
+    #[prost(message, repeated, tag = "1")]
+    timeseries: Vec<TimeSeries>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct TimeSeries {
+    #[prost(message, repeated, tag = "1")]
+    labels: Vec<Label>,
+    #[prost(message, repeated, tag = "2")]
+    samples: Vec<Sample>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct Label {
+    #[prost(string, tag = "1")]
+    name: String,
+    #[prost(string, tag = "2")]
+    value: String,
+}
+
+impl Label {
+    fn new(name: &str, value: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            value: value.to_string(),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct Sample {
+    #[prost(double, tag = "1")]
+    value: f64,
+    /// Milliseconds since the epoch
+    #[prost(int64, tag = "2")]
+    timestamp: i64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct ReadRequest {
+    #[prost(message, repeated, tag = "1")]
+    queries: Vec<Query>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct Query {
+    #[prost(int64, tag = "1")]
+    start_timestamp_ms: i64,
+    #[prost(int64, tag = "2")]
+    end_timestamp_ms: i64,
+    #[prost(message, repeated, tag = "3")]
+    matchers: Vec<LabelMatcher>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct LabelMatcher {
+    #[prost(enumeration = "MatchType", tag = "1")]
+    r#type: i32,
+    #[prost(string, tag = "2")]
+    name: String,
+    #[prost(string, tag = "3")]
+    value: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+enum MatchType {
+    Eq = 0,
+    Neq = 1,
+    Re = 2,
+    Nre = 3,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct ReadResponse {
+    #[prost(message, repeated, tag = "1")]
+    results: Vec<QueryResult>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct QueryResult {
+    #[prost(message, repeated, tag = "1")]
+    timeseries: Vec<TimeSeries>,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow::array::{ArrayRef, DictionaryArray, Float64Array, TimestampNanosecondArray};
+    use arrow::datatypes::Int32Type;
+
+    use super::*;
+
+    fn series(labels: &[(&str, &str)], samples: &[(f64, i64)]) -> TimeSeries {
+        TimeSeries {
+            labels: labels.iter().map(|(n, v)| Label::new(n, v)).collect(),
+            samples: samples
+                .iter()
+                .map(|&(value, timestamp)| Sample { value, timestamp })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn converts_write_request_to_lp() {
+        let request = WriteRequest {
+            timeseries: vec![
+                series(
+                    &[
+                        ("__name__", "http_requests_total"),
+                        ("code", "200"),
+                        ("handler", "/api v1"),
+                        ("empty", ""),
+                    ],
+                    &[(10.0, 1_000), (12.5, 2_000)],
+                ),
+                // the staleness marker is a NaN, which is skipped
+                series(
+                    &[("__name__", "up"), ("job", "prometheus")],
+                    &[(1.0, 1_000), (f64::NAN, 2_000), (f64::INFINITY, 3_000)],
+                ),
+            ],
+        };
+        let decoded = WriteRequest::decode(request.encode_to_vec().as_slice()).unwrap();
+        assert_eq!(
+            "http_requests_total,code=200,handler=/api\\ v1 value=10 1000\n\
+            http_requests_total,code=200,handler=/api\\ v1 value=12.5 2000\n\
+            up,job=prometheus value=1 1000",
+            write_request_to_lp(&decoded).unwrap()
+        );
+
+        // backslashes are escaped, so that a trailing one doesn't escape the separator after it
+        let request = WriteRequest {
+            timeseries: vec![series(
+                &[("__name__", "disk_free"), ("path", "C:\\"), ("drive", "C")],
+                &[(1.0, 1_000)],
+            )],
+        };
+        assert_eq!(
+            "disk_free,path=C:\\\\,drive=C value=1 1000",
+            write_request_to_lp(&request).unwrap()
+        );
+
+        let request = WriteRequest {
+            timeseries: vec![series(&[("job", "prometheus")], &[(1.0, 1_000)])],
+        };
+        assert!(matches!(
+            write_request_to_lp(&request),
+            Err(PromError::MissingMetricName)
+        ));
+
+        // a label name with a newline would start a line of its own
+        let request = WriteRequest {
+            timeseries: vec![series(
+                &[("__name__", "up"), ("a\nevil_table x=1", "b")],
+                &[(1.0, 1_000)],
+            )],
+        };
+        assert!(matches!(
+            write_request_to_lp(&request),
+            Err(PromError::LabelWithNewline { .. })
+        ));
+
+        // and a metric name starting with '#' would be a comment
+        let request = WriteRequest {
+            timeseries: vec![series(&[("__name__", "#up")], &[(1.0, 1_000)])],
+        };
+        assert!(matches!(
+            write_request_to_lp(&request),
+            Err(PromError::InvalidMetricName(_))
+        ));
+    }
+
+    #[test]
+    fn builds_read_query_sql() {
+        let query = Query {
+            start_timestamp_ms: 1_000,
+            end_timestamp_ms: 2_000,
+            matchers: vec![
+                LabelMatcher {
+                    r#type: MatchType::Eq as i32,
+                    name: "__name__".to_string(),
+                    value: "up".to_string(),
+                },
+                LabelMatcher {
+                    r#type: MatchType::Re as i32,
+                    name: "job".to_string(),
+                    value: "prom.*".to_string(),
+                },
+                LabelMatcher {
+                    r#type: MatchType::Neq as i32,
+                    name: "missing".to_string(),
+                    value: "it's".to_string(),
+                },
+            ],
+        };
+        assert_eq!(
+            "SELECT \"instance\", \"job\", CAST(\"value\" AS DOUBLE) AS \"value\", \"time\" \
+            FROM \"up\" WHERE \"time\" >= to_timestamp_millis(1000) \
+            AND \"time\" <= to_timestamp_millis(2000) AND 'up' = 'up' \
+            AND COALESCE(\"job\", '') ~ '^(?:prom.*)$' AND '' <> 'it''s' \
+            ORDER BY \"instance\", \"job\", \"time\"",
+            read_query_sql("up", &["instance".to_string(), "job".to_string()], &query).unwrap()
+        );
+    }
+
+    #[test]
+    fn groups_rows_into_time_series() {
+        let batch = RecordBatch::try_from_iter([
+            (
+                "code",
+                Arc::new(DictionaryArray::<Int32Type>::from_iter([
+                    Some("200"),
+                    Some("200"),
+                    None,
+                ])) as ArrayRef,
+            ),
+            (
+                "value",
+                Arc::new(Float64Array::from(vec![10.0, 12.0, 1.0])) as ArrayRef,
+            ),
+            (
+                "time",
+                Arc::new(TimestampNanosecondArray::from(vec![
+                    1_000_000_000,
+                    2_000_000_000,
+                    1_000_000_000,
+                ])) as ArrayRef,
+            ),
+        ])
+        .unwrap();
+
+        let result =
+            batches_to_query_result("http_requests_total", &["code".to_string()], &[batch])
+                .unwrap();
+        assert_eq!(
+            vec![
+                series(
+                    &[("__name__", "http_requests_total"), ("code", "200")],
+                    &[(10.0, 1_000), (12.0, 2_000)]
+                ),
+                series(&[("__name__", "http_requests_total")], &[(1.0, 1_000)]),
+            ],
+            result.timeseries
+        );
+    }
+}
@@ -118,6 +118,9 @@ impl CommonServerState {
 pub struct Server<W, Q, P, T> {
     common_state: CommonServerState,
     http: Arc<HttpApi<W, Q, T>>,
+    write_buffer: Arc<W>,
+    time_provider: Arc<T>,
+    max_request_size: usize,
     persister: Arc<P>,
     authorizer: Arc<dyn Authorizer>,
 }
@@ -180,6 +183,9 @@ where
 
     let grpc_service = trace_layer.clone().layer(make_flight_server(
         Arc::clone(&server.http.query_executor),
+        Arc::clone(&server.write_buffer),
+        Arc::clone(&server.time_provider),
+        server.max_request_size,
         Some(server.authorizer()),
     ));
     let rest_service = hyper::service::make_service_fn(|_| {
@@ -231,9 +237,12 @@ mod tests {
     use datafusion::parquet::data_type::AsBytes;
     use hyper::{body, Body, Client, Request, Response, StatusCode};
     use influxdb3_write::persister::PersisterImpl;
-    use influxdb3_write::SegmentDuration;
+    use influxdb3_write::write_buffer::backpressure::BackpressureConfig;
+    use influxdb3_write::{DatabaseManager, SegmentDuration};
     use iox_query::exec::{DedicatedExecutor, Executor, ExecutorConfig};
+    use iox_query::QueryDatabase;
     use iox_time::{MockProvider, Time};
+    use metric::{Attributes, Metric, U64Counter};
     use object_store::DynObjectStore;
     use parquet_file::storage::{ParquetStorage, StorageId};
     use pretty_assertions::assert_eq;
@@ -279,7 +288,9 @@ mod tests {
                 Arc::clone(&time_provider),
                 SegmentDuration::new_5m(),
                 Arc::clone(&exec),
+                Arc::clone(&metrics),
                 10000,
+                influxdb3_write::catalog::CatalogLimits::default(),
             )
             .await
             .unwrap(),
@@ -288,6 +299,7 @@ mod tests {
             write_buffer.catalog(),
             Arc::clone(&write_buffer),
             Arc::clone(&exec),
+            Arc::clone(&time_provider) as _,
             Arc::clone(&metrics),
             Arc::new(HashMap::new()),
             10,
@@ -404,6 +416,22 @@ mod tests {
             &[Buffer::from(1_u64.to_le_bytes().to_vec())]
         );
 
+        // the retention cutoff of queries is taken from the server's time provider
+        DatabaseManager::set_retention_period(
+            write_buffer.as_ref(),
+            "foo",
+            Some(std::time::Duration::from_secs(10)),
+        )
+        .await
+        .unwrap();
+        time_provider.set(Time::from_timestamp(100, 0).unwrap());
+        let namespace = query_executor
+            .namespace("foo", None, false)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(Some(90_000_000_000), namespace.retention_time_ns());
+
         shutdown.cancel();
     }
 
@@ -440,7 +468,9 @@ mod tests {
                 Arc::clone(&time_provider),
                 SegmentDuration::new_5m(),
                 Arc::clone(&exec),
+                Arc::clone(&metrics),
                 10000,
+                influxdb3_write::catalog::CatalogLimits::default(),
             )
             .await
             .unwrap(),
@@ -449,6 +479,7 @@ mod tests {
             write_buffer.catalog(),
             Arc::clone(&write_buffer),
             Arc::clone(&exec),
+            Arc::clone(&time_provider) as _,
             Arc::clone(&metrics),
             Arc::new(HashMap::new()),
             10,
@@ -648,7 +679,9 @@ mod tests {
                 Arc::clone(&time_provider),
                 SegmentDuration::new_5m(),
                 Arc::clone(&exec),
+                Arc::clone(&metrics),
                 10000,
+                influxdb3_write::catalog::CatalogLimits::default(),
             )
             .await
             .unwrap(),
@@ -657,6 +690,7 @@ mod tests {
             write_buffer.catalog(),
             Arc::clone(&write_buffer),
             Arc::clone(&exec),
+            Arc::clone(&time_provider) as _,
             Arc::clone(&metrics),
             Arc::new(HashMap::new()),
             10,
@@ -789,6 +823,108 @@ mod tests {
         shutdown.cancel();
     }
 
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn write_lp_backpressure() {
+        let addr = get_free_port();
+        let trace_header_parser = trace_http::ctx::TraceHeaderParser::new();
+        let metrics = Arc::new(metric::Registry::new());
+        let common_state =
+            crate::CommonServerState::new(Arc::clone(&metrics), None, trace_header_parser, addr)
+                .unwrap();
+        let object_store: Arc<DynObjectStore> = Arc::new(object_store::memory::InMemory::new());
+        let parquet_store =
+            ParquetStorage::new(Arc::clone(&object_store), StorageId::from("influxdb3"));
+        let exec = Arc::new(Executor::new_with_config_and_executor(
+            ExecutorConfig {
+                target_query_partitions: NonZeroUsize::new(1).unwrap(),
+                object_stores: [&parquet_store]
+                    .into_iter()
+                    .map(|store| (store.id(), Arc::clone(store.object_store())))
+                    .collect(),
+                metric_registry: Arc::clone(&metrics),
+                mem_pool_size: usize::MAX,
+            },
+            DedicatedExecutor::new_testing(),
+        ));
+        let persister = Arc::new(PersisterImpl::new(Arc::clone(&object_store)));
+        let time_provider = Arc::new(MockProvider::new(Time::from_timestamp_nanos(0)));
+
+        // no writes can be queued, so every write is rejected:
+        let write_buffer = Arc::new(
+            influxdb3_write::write_buffer::WriteBufferImpl::new(
+                Arc::clone(&persister),
+                None::<Arc<influxdb3_write::wal::WalImpl>>,
+                Arc::clone(&time_provider),
+                SegmentDuration::new_5m(),
+                Arc::clone(&exec),
+                Arc::clone(&metrics),
+                10000,
+                influxdb3_write::catalog::CatalogLimits::default(),
+            )
+            .await
+            .unwrap()
+            .with_backpressure_config(BackpressureConfig {
+                max_queued_writes: 0,
+                max_buffer_mem_mb: None,
+            }),
+        );
+        let query_executor = crate::query_executor::QueryExecutorImpl::new(
+            write_buffer.catalog(),
+            Arc::clone(&write_buffer),
+            Arc::clone(&exec),
+            Arc::clone(&time_provider) as _,
+            Arc::clone(&metrics),
+            Arc::new(HashMap::new()),
+            10,
+            10,
+        );
+
+        let server = ServerBuilder::new(common_state)
+            .write_buffer(Arc::clone(&write_buffer))
+            .query_executor(Arc::new(query_executor))
+            .persister(persister)
+            .authorizer(Arc::new(DefaultAuthorizer))
+            .time_provider(Arc::clone(&time_provider))
+            .build();
+        let frontend_shutdown = CancellationToken::new();
+        let shutdown = frontend_shutdown.clone();
+
+        tokio::spawn(async move { serve(server, frontend_shutdown).await });
+
+        let server = format!("http://{}", addr);
+        let resp = write_lp(
+            &server,
+            "foo",
+            "cpu,host=a val=1 1",
+            None,
+            true,
+            "nanosecond",
+        )
+        .await;
+
+        assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(resp.headers().get(hyper::header::RETRY_AFTER).unwrap(), "1");
+        let body =
+            String::from_utf8(body::to_bytes(resp.into_body()).await.unwrap().to_vec()).unwrap();
+        assert_eq!(
+            body,
+            "{\
+                \"error\":\"write queue is full with 0 writes waiting to be flushed to the wal, retry after 1s\",\
+                \"data\":null\
+            }"
+        );
+
+        let rejected = metrics
+            .get_instrument::<Metric<U64Counter>>("write_buffer_rejected_writes")
+            .unwrap()
+            .get_observer(&Attributes::from(&[("reason", "queue_full")]))
+            .unwrap()
+            .fetch();
+        assert_eq!(rejected, 1);
+
+        shutdown.cancel();
+    }
+
     pub(crate) async fn write_lp(
         server: impl Into<String> + Send,
         database: impl Into<String> + Send,
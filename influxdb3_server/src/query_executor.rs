@@ -2,7 +2,7 @@
 use crate::{QueryExecutor, QueryKind};
 use arrow::array::{
     ArrayRef, BooleanArray, DurationNanosecondArray, Int64Array, Int64Builder, StringBuilder,
-    StructArray, TimestampNanosecondArray,
+    StructArray, TimestampNanosecondArray, UInt64Array,
 };
 use arrow::datatypes::SchemaRef;
 use arrow::record_batch::RecordBatch;
@@ -24,6 +24,7 @@ use datafusion_util::config::DEFAULT_SCHEMA;
 use datafusion_util::MemoryStream;
 use influxdb3_write::{
     catalog::{Catalog, DatabaseSchema},
+    last_cache::{LastCacheFunction, LAST_CACHE_UDTF_NAME},
     WriteBuffer,
 };
 use iox_query::exec::{Executor, IOxSessionContext, QueryConfig};
@@ -38,6 +39,7 @@ use iox_query::{QueryChunk, QueryNamespace};
 use iox_query_influxql::frontend::planner::InfluxQLQueryPlanner;
 use iox_query_params::StatementParams;
 use iox_system_tables::{IoxSystemTable, SystemTableProvider};
+use iox_time::TimeProvider;
 use metric::Registry;
 use observability_deps::tracing::{debug, info};
 use schema::Schema;
@@ -60,13 +62,16 @@ pub struct QueryExecutorImpl<W> {
     datafusion_config: Arc<HashMap<String, String>>,
     query_execution_semaphore: Arc<InstrumentedAsyncSemaphore>,
     query_log: Arc<QueryLog>,
+    time_provider: Arc<dyn TimeProvider>,
 }
 
 impl<W: WriteBuffer> QueryExecutorImpl<W> {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         catalog: Arc<Catalog>,
         write_buffer: Arc<W>,
         exec: Arc<Executor>,
+        time_provider: Arc<dyn TimeProvider>,
         metrics: Arc<Registry>,
         datafusion_config: Arc<HashMap<String, String>>,
         concurrent_query_limit: usize,
@@ -78,10 +83,7 @@ impl<W: WriteBuffer> QueryExecutorImpl<W> {
         ));
         let query_execution_semaphore =
             Arc::new(semaphore_metrics.new_semaphore(concurrent_query_limit));
-        let query_log = Arc::new(QueryLog::new(
-            query_log_size,
-            Arc::new(iox_time::SystemProvider::new()),
-        ));
+        let query_log = Arc::new(QueryLog::new(query_log_size, Arc::clone(&time_provider)));
         Self {
             catalog,
             write_buffer,
@@ -89,6 +91,7 @@ impl<W: WriteBuffer> QueryExecutorImpl<W> {
             datafusion_config,
             query_execution_semaphore,
             query_log,
+            time_provider,
         }
     }
 }
@@ -179,7 +182,7 @@ impl<W: WriteBuffer> QueryExecutor for QueryExecutorImpl<W> {
     async fn show_retention_policies(
         &self,
         database: Option<&str>,
-        span_ctx: Option<SpanContext>,
+        _span_ctx: Option<SpanContext>,
     ) -> Result<SendableRecordBatchStream, Self::Error> {
         let mut databases = if let Some(db) = database {
             vec![db.to_owned()]
@@ -192,15 +195,14 @@ impl<W: WriteBuffer> QueryExecutor for QueryExecutorImpl<W> {
         let mut rows = Vec::with_capacity(databases.len());
         for database in databases {
             let db = self
-                .namespace(&database, span_ctx.child_span("get database"), false)
-                .await
-                .map_err(|_| Error::DatabaseNotFound {
-                    db_name: database.to_string(),
-                })?
+                .catalog
+                .db_schema(&database)
                 .ok_or_else(|| Error::DatabaseNotFound {
                     db_name: database.to_string(),
                 })?;
-            let duration = db.retention_time_ns();
+            let duration = db
+                .retention_period()
+                .map(|period| i64::try_from(period.as_nanos()).unwrap_or(i64::MAX));
             let (db_name, rp_name) = split_database_name(&database);
             rows.push(RetentionPolicyRow {
                 database: db_name,
@@ -315,6 +317,7 @@ impl<W: WriteBuffer> QueryDatabase for QueryExecutorImpl<W> {
             Arc::clone(&self.exec),
             Arc::clone(&self.datafusion_config),
             Arc::clone(&self.query_log),
+            Arc::clone(&self.time_provider),
         ))))
     }
 
@@ -338,6 +341,8 @@ pub struct Database<B> {
     datafusion_config: Arc<HashMap<String, String>>,
     query_log: Arc<QueryLog>,
     system_schema_provider: Arc<SystemSchemaProvider>,
+    /// Used for the current time, when data that has expired is left out of queries
+    time_provider: Arc<dyn TimeProvider>,
 }
 
 impl<B: WriteBuffer> Database<B> {
@@ -347,8 +352,10 @@ impl<B: WriteBuffer> Database<B> {
         exec: Arc<Executor>,
         datafusion_config: Arc<HashMap<String, String>>,
         query_log: Arc<QueryLog>,
+        time_provider: Arc<dyn TimeProvider>,
     ) -> Self {
         let system_schema_provider = Arc::new(SystemSchemaProvider::new(
+            db_schema.name.clone(),
             write_buffer.catalog(),
             Arc::clone(&query_log),
         ));
@@ -359,6 +366,7 @@ impl<B: WriteBuffer> Database<B> {
             datafusion_config,
             query_log,
             system_schema_provider,
+            time_provider,
         }
     }
 
@@ -370,6 +378,7 @@ impl<B: WriteBuffer> Database<B> {
             datafusion_config: Arc::clone(&db.datafusion_config),
             query_log: Arc::clone(&db.query_log),
             system_schema_provider: Arc::clone(&db.system_schema_provider),
+            time_provider: Arc::clone(&db.time_provider),
         }
     }
 
@@ -406,7 +415,8 @@ impl<B: WriteBuffer> QueryNamespace for Database<B> {
     }
 
     fn retention_time_ns(&self) -> Option<i64> {
-        None
+        self.db_schema
+            .retention_cutoff_ns(self.time_provider.now().timestamp_nanos())
     }
 
     fn record_query(
@@ -443,7 +453,15 @@ impl<B: WriteBuffer> QueryNamespace for Database<B> {
             cfg = cfg.with_config_option(k, v);
         }
 
-        cfg.build()
+        let ctx = cfg.build();
+        ctx.inner().register_udtf(
+            LAST_CACHE_UDTF_NAME,
+            Arc::new(LastCacheFunction::new(
+                &self.db_schema.name,
+                self.write_buffer.last_cache_provider(),
+            )),
+        );
+        ctx
     }
 }
 
@@ -566,6 +584,7 @@ impl<B: WriteBuffer> TableProvider for QueryTable<B> {
 pub const SYSTEM_SCHEMA: &str = "system";
 
 const QUERIES_TABLE: &str = "queries";
+const LIMITS_TABLE: &str = "limits";
 const _PARQUET_FILES_TABLE: &str = "parquet_files";
 
 struct SystemSchemaProvider {
@@ -584,12 +603,16 @@ impl std::fmt::Debug for SystemSchemaProvider {
 }
 
 impl SystemSchemaProvider {
-    fn new(_catalog: Arc<Catalog>, query_log: Arc<QueryLog>) -> Self {
+    fn new(db_name: String, catalog: Arc<Catalog>, query_log: Arc<QueryLog>) -> Self {
         let mut tables = HashMap::<&'static str, Arc<dyn TableProvider>>::new();
         let queries = Arc::new(SystemTableProvider::new(Arc::new(QueriesTable::new(
             query_log,
         ))));
         tables.insert(QUERIES_TABLE, queries);
+        let limits = Arc::new(SystemTableProvider::new(Arc::new(LimitsTable::new(
+            db_name, catalog,
+        ))));
+        tables.insert(LIMITS_TABLE, limits);
         Self { tables }
     }
 }
@@ -829,3 +852,98 @@ fn from_query_log_entries(
     let batch = RecordBatch::try_new(schema, columns)?;
     Ok(batch)
 }
+
+/// Shows the catalog limits of the server, the overrides of the database, if any, and the limits
+/// that are effectively applied to the database
+struct LimitsTable {
+    schema: SchemaRef,
+    db_name: String,
+    catalog: Arc<Catalog>,
+}
+
+impl LimitsTable {
+    fn new(db_name: String, catalog: Arc<Catalog>) -> Self {
+        Self {
+            schema: limits_schema(),
+            db_name,
+            catalog,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl IoxSystemTable for LimitsTable {
+    fn schema(&self) -> SchemaRef {
+        Arc::clone(&self.schema)
+    }
+
+    async fn scan(
+        &self,
+        _filters: Option<Vec<Expr>>,
+        _limit: Option<usize>,
+    ) -> Result<RecordBatch, DataFusionError> {
+        let server_limits = self.catalog.limits();
+        let db_limits = self
+            .catalog
+            .db_schema(&self.db_name)
+            .map(|db| db.limits())
+            .unwrap_or_default();
+
+        let rows = [
+            (
+                "databases",
+                server_limits.max_databases,
+                None,
+                server_limits.max_databases,
+            ),
+            (
+                "tables",
+                server_limits.max_tables,
+                db_limits.max_tables,
+                server_limits.max_tables_for(&db_limits),
+            ),
+            (
+                "columns_per_table",
+                server_limits.max_columns_per_table,
+                db_limits.max_columns_per_table,
+                server_limits.max_columns_per_table_for(&db_limits),
+            ),
+        ];
+
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(
+                rows.iter()
+                    .map(|(name, ..)| Some(*name))
+                    .collect::<StringArray>(),
+            ),
+            Arc::new(
+                rows.iter()
+                    .map(|(_, server, ..)| Some(*server as u64))
+                    .collect::<UInt64Array>(),
+            ),
+            Arc::new(
+                rows.iter()
+                    .map(|(_, _, database, _)| database.map(|l| l as u64))
+                    .collect::<UInt64Array>(),
+            ),
+            Arc::new(
+                rows.iter()
+                    .map(|(.., effective)| Some(*effective as u64))
+                    .collect::<UInt64Array>(),
+            ),
+        ];
+
+        Ok(RecordBatch::try_new(self.schema(), columns)?)
+    }
+}
+
+fn limits_schema() -> SchemaRef {
+    let columns = vec![
+        Field::new("limit_name", DataType::Utf8, false),
+        Field::new("server_limit", DataType::UInt64, false),
+        Field::new("database_limit", DataType::UInt64, true),
+        Field::new("effective_limit", DataType::UInt64, false),
+    ];
+
+    Arc::new(DatafusionSchema::new(columns))
+}
@@ -0,0 +1,87 @@
+//! Compares the encodings of the WAL segment file versions, i.e. JSON (`idb3.001`) and protobuf
+//! (`idb3.002`), on a batch of line protocol writes like the ones produced by the write buffer.
+//!
+//! Run with `cargo bench -p influxdb3_write --bench wal_encoding`.
+
+use influxdb3_write::wal::SegmentFileVersion;
+use influxdb3_write::{LpWriteOp, Precision, SequenceNumber, WalOp, WalOpBatch};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const DATABASES: usize = 10;
+const LINES_PER_WRITE: usize = 1_000;
+const ITERATIONS: u32 = 200;
+
+fn main() {
+    let batch = wal_op_batch();
+
+    for version in [SegmentFileVersion::V1, SegmentFileVersion::V2] {
+        let encoded = version.encode_batch(&batch).unwrap();
+        let compressed = snap::raw::Encoder::new()
+            .compress_vec(&encoded)
+            .unwrap()
+            .len();
+        assert_eq!(version.decode_batch(&encoded).unwrap(), batch);
+
+        let encode = time(|| {
+            black_box(version.encode_batch(black_box(&batch)).unwrap());
+        });
+        let decode = time(|| {
+            black_box(version.decode_batch(black_box(&encoded)).unwrap());
+        });
+
+        println!(
+            "{version:?}: {} bytes ({compressed} bytes compressed), encode {:?}/batch ({:.1} MiB/s), decode {:?}/batch ({:.1} MiB/s)",
+            encoded.len(),
+            encode,
+            throughput(encoded.len(), encode),
+            decode,
+            throughput(encoded.len(), decode),
+        );
+    }
+}
+
+fn wal_op_batch() -> WalOpBatch {
+    let ops = (0..DATABASES)
+        .map(|db| {
+            let lp = (0..LINES_PER_WRITE)
+                .map(|line| {
+                    format!(
+                        "cpu,region=us-west-{},host=server-{line} usage_user={}.5,usage_system={}i,active=true {}\n",
+                        line % 4,
+                        line % 100,
+                        line % 17,
+                        1_700_000_000_000_000_000_i64 + line as i64,
+                    )
+                })
+                .collect();
+            WalOp::LpWrite(LpWriteOp {
+                db_name: format!("db_{db}"),
+                lp,
+                default_time: 1_700_000_000_000_000_000,
+                precision: Precision::Nanosecond,
+            })
+        })
+        .collect();
+
+    WalOpBatch {
+        sequence_number: SequenceNumber::new(1),
+        ops,
+    }
+}
+
+fn time(mut f: impl FnMut()) -> Duration {
+    // warm up
+    for _ in 0..ITERATIONS / 10 {
+        f();
+    }
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        f();
+    }
+    start.elapsed() / ITERATIONS
+}
+
+fn throughput(bytes: usize, per_batch: Duration) -> f64 {
+    bytes as f64 / (1024.0 * 1024.0) / per_batch.as_secs_f64()
+}
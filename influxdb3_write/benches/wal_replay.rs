@@ -0,0 +1,173 @@
+//! Measures how long the write buffer takes to start up when it has to replay a large WAL, i.e.
+//! the time spent in `WriteBufferImpl::new` decoding the segments and applying them to the
+//! catalog and buffers.
+//!
+//! Run with `cargo bench -p influxdb3_write --bench wal_replay`. By default this writes 10 GiB of
+//! WAL segments to a temporary directory first, and needs enough memory to buffer all of it. Set
+//! `WAL_REPLAY_BENCH_GIB` to change the amount written, or `WAL_REPLAY_BENCH_DIR` to replay (and
+//! keep) the WAL in that directory, writing it only if it's empty.
+
+use influxdb3_write::catalog::CatalogLimits;
+use influxdb3_write::persister::PersisterImpl;
+use influxdb3_write::wal::WalImpl;
+use influxdb3_write::write_buffer::WriteBufferImpl;
+use influxdb3_write::{LpWriteOp, Precision, SegmentDuration, SegmentId, SegmentRange, Wal, WalOp};
+use iox_query::exec::{DedicatedExecutor, Executor, ExecutorConfig};
+use iox_time::{MockProvider, Time};
+use metric::{Attributes, Metric, U64Counter};
+use object_store::memory::InMemory;
+use object_store::ObjectStore;
+use parquet_file::storage::{ParquetStorage, StorageId};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
+
+const GIB: u64 = 1024 * 1024 * 1024;
+/// The size at which a segment is closed and the next one started
+const SEGMENT_BYTES: u64 = 256 * 1024 * 1024;
+const LINES_PER_WRITE: usize = 10_000;
+
+fn main() {
+    let wal_bytes = std::env::var("WAL_REPLAY_BENCH_GIB")
+        .map(|gib| {
+            gib.parse::<u64>()
+                .expect("WAL_REPLAY_BENCH_GIB must be an integer")
+                * GIB
+        })
+        .unwrap_or(10 * GIB);
+    let (dir, _tmp_dir) = match std::env::var("WAL_REPLAY_BENCH_DIR") {
+        Ok(dir) => (PathBuf::from(dir), None),
+        Err(_) => {
+            let tmp_dir = test_helpers::tmp_dir().unwrap();
+            (tmp_dir.path().to_path_buf(), Some(tmp_dir))
+        }
+    };
+
+    let wal = WalImpl::new(&dir).unwrap();
+    if wal.segment_files().unwrap().is_empty() {
+        let start = Instant::now();
+        write_wal(&wal, wal_bytes);
+        println!("wrote WAL to {dir:?} in {:?}", start.elapsed());
+    }
+    let (segments, bytes) = wal_size(&wal);
+    let last_segment_id = wal.segment_files().unwrap().last().unwrap().segment_id;
+    let last_segment_start = wal
+        .open_segment_reader(last_segment_id)
+        .unwrap()
+        .header()
+        .range
+        .start_time;
+
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let metric_registry = Arc::new(metric::Registry::default());
+    let start = Instant::now();
+    let write_buffer = runtime.block_on(WriteBufferImpl::new(
+        Arc::new(PersisterImpl::new(Arc::new(InMemory::new()))),
+        Some(Arc::new(wal)),
+        Arc::new(MockProvider::new(last_segment_start)),
+        SegmentDuration::new_5m(),
+        make_exec(),
+        Arc::clone(&metric_registry),
+        // 1 TiB, so that the buffer isn't persisted to free up memory while replaying
+        1024 * 1024,
+        CatalogLimits::default(),
+    ));
+    let elapsed = start.elapsed();
+    write_buffer.unwrap();
+
+    let ops = metric_registry
+        .get_instrument::<Metric<U64Counter>>("wal_replay_ops_replayed")
+        .unwrap()
+        .get_observer(&Attributes::from(&[]))
+        .unwrap()
+        .fetch();
+    println!(
+        "replayed {segments} segments, {ops} ops, {:.2} GiB in {elapsed:?} ({:.1} MiB/s)",
+        bytes as f64 / GIB as f64,
+        bytes as f64 / (1024.0 * 1024.0) / elapsed.as_secs_f64(),
+    );
+
+    // don't wait for the persistence of the replayed segments that was started
+    runtime.shutdown_background();
+}
+
+/// Writes segments of line protocol writes, one 5 minute segment after another, until the WAL
+/// is `wal_bytes` in size.
+fn write_wal(wal: &WalImpl, wal_bytes: u64) {
+    let mut range = SegmentRange::from_time_and_duration(
+        Time::from_timestamp_nanos(0),
+        SegmentDuration::new_5m(),
+        false,
+    );
+    let mut written = 0;
+    let mut segment_id = SegmentId::new(1);
+    while written < wal_bytes {
+        let mut writer = wal.new_segment_writer(segment_id, range).unwrap();
+        let mut write = 0;
+        while writer.bytes_written() < SEGMENT_BYTES && written + writer.bytes_written() < wal_bytes
+        {
+            writer
+                .write_batch(vec![WalOp::LpWrite(lp_write(range, write))])
+                .unwrap();
+            write += 1;
+        }
+        writer.sync().unwrap();
+        written += writer.bytes_written();
+
+        segment_id = segment_id.next();
+        range = range.next();
+    }
+}
+
+fn lp_write(range: SegmentRange, write: usize) -> LpWriteOp {
+    let start = range.start_time.timestamp_nanos();
+    let lp = (0..LINES_PER_WRITE)
+        .map(|line| {
+            format!(
+                "cpu,region=us-west-{},host=server-{} usage_user={}.5,usage_system={}i,active=true {}\n",
+                line % 4,
+                line % 1_000,
+                (write + line) % 100,
+                line % 17,
+                start + (write * LINES_PER_WRITE + line) as i64,
+            )
+        })
+        .collect();
+    LpWriteOp {
+        db_name: format!("db_{}", write % 10),
+        lp,
+        default_time: start,
+        precision: Precision::Nanosecond,
+    }
+}
+
+fn wal_size(wal: &WalImpl) -> (usize, u64) {
+    let segment_files = wal.segment_files().unwrap();
+    let bytes = segment_files.iter().map(|f| file_len(&f.path)).sum::<u64>();
+    (segment_files.len(), bytes)
+}
+
+fn file_len(path: &Path) -> u64 {
+    std::fs::metadata(path).unwrap().len()
+}
+
+fn make_exec() -> Arc<Executor> {
+    let object_store: Arc<dyn ObjectStore> = Arc::new(InMemory::new());
+    let parquet_store = ParquetStorage::new(object_store, StorageId::from("wal_replay_bench"));
+    Arc::new(Executor::new_with_config_and_executor(
+        ExecutorConfig {
+            target_query_partitions: NonZeroUsize::new(1).unwrap(),
+            object_stores: [&parquet_store]
+                .into_iter()
+                .map(|store| (store.id(), Arc::clone(store.object_store())))
+                .collect(),
+            metric_registry: Arc::new(metric::Registry::default()),
+            mem_pool_size: 1024 * 1024 * 1024,
+        },
+        DedicatedExecutor::new_testing(),
+    ))
+}
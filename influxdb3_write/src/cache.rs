@@ -89,6 +89,7 @@ impl ParquetCache {
                                 row_count: meta_data.num_rows as u64,
                                 min_time,
                                 max_time,
+                                tombstones: vec![],
                             },
                         );
                     })
@@ -101,6 +102,7 @@ impl ParquetCache {
                                 row_count: meta_data.num_rows as u64,
                                 min_time,
                                 max_time,
+                                tombstones: vec![],
                             },
                         )])
                     });
@@ -116,6 +118,7 @@ impl ParquetCache {
                             row_count: meta_data.num_rows as u64,
                             min_time,
                             max_time,
+                            tombstones: vec![],
                         },
                     )]),
                 )])
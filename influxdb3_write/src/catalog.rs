@@ -1,13 +1,14 @@
 //! Implementation of the Catalog that sits entirely in memory.
 
-use crate::SequenceNumber;
+use crate::{CatalogOp, SequenceNumber};
 use influxdb_line_protocol::FieldValue;
 use observability_deps::tracing::info;
 use parking_lot::RwLock;
 use schema::{InfluxColumnType, InfluxFieldType, Schema, SchemaBuilder};
 use serde::{Deserialize, Serialize, Serializer};
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::sync::Arc;
+use std::time::Duration;
 use thiserror::Error;
 
 mod serialize;
@@ -19,33 +20,113 @@ pub enum Error {
 
     #[error(
         "Update to schema would exceed number of columns per table limit of {} columns",
-        Catalog::NUM_COLUMNS_PER_TABLE_LIMIT - 1
+        .limit.saturating_sub(1)
     )]
-    TooManyColumns,
+    TooManyColumns { limit: usize },
 
-    #[error(
-        "Update to schema would exceed number of tables limit of {} tables",
-        Catalog::NUM_TABLES_LIMIT
-    )]
-    TooManyTables,
+    #[error("Update to schema would exceed number of tables limit of {limit} tables")]
+    TooManyTables { limit: usize },
 
-    #[error(
-        "Adding a new database would exceed limit of {} databases",
-        Catalog::NUM_DBS_LIMIT
-    )]
-    TooManyDbs,
+    #[error("Adding a new database would exceed limit of {limit} databases")]
+    TooManyDbs { limit: usize },
 
     #[error("last cache size must be from 1 to 10")]
     InvalidLastCacheSize,
+
+    #[error("database already exists: {db_name}")]
+    DatabaseAlreadyExists { db_name: String },
+
+    #[error("database not found: {db_name}")]
+    DatabaseNotFound { db_name: String },
+
+    #[error("table already exists: {table_name} in database {db_name}")]
+    TableAlreadyExists { db_name: String, table_name: String },
+
+    #[error("invalid definition for table {table_name}: {reason}")]
+    InvalidTableDefinition { table_name: String, reason: String },
 }
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
 
 pub const TIME_COLUMN_NAME: &str = "time";
 
+/// Limits on the size of the catalog, which are configured for the server
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CatalogLimits {
+    /// The maximum number of databases
+    pub max_databases: usize,
+    /// The maximum number of tables, across all databases
+    pub max_tables: usize,
+    /// The maximum number of columns per table, including the time column
+    pub max_columns_per_table: usize,
+}
+
+impl Default for CatalogLimits {
+    fn default() -> Self {
+        Self {
+            max_databases: Catalog::NUM_DBS_LIMIT,
+            max_tables: Catalog::NUM_TABLES_LIMIT,
+            max_columns_per_table: Catalog::NUM_COLUMNS_PER_TABLE_LIMIT,
+        }
+    }
+}
+
+impl CatalogLimits {
+    /// The maximum number of tables that the database can have, which is the lower of the
+    /// server's limit and the database's override, if it has one
+    pub fn max_tables_for(&self, db_limits: &DatabaseLimits) -> usize {
+        db_limits
+            .max_tables
+            .map_or(self.max_tables, |max| max.min(self.max_tables))
+    }
+
+    /// The maximum number of columns per table in the database, which is the lower of the server's
+    /// limit and the database's override, if it has one
+    pub fn max_columns_per_table_for(&self, db_limits: &DatabaseLimits) -> usize {
+        db_limits
+            .max_columns_per_table
+            .map_or(self.max_columns_per_table, |max| {
+                max.min(self.max_columns_per_table)
+            })
+    }
+}
+
+/// Overrides of the server's [`CatalogLimits`] for a single database
+///
+/// An override can only lower a limit: if it is above the server's limit, the server's limit
+/// applies instead.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DatabaseLimits {
+    /// The maximum number of tables in the database
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tables: Option<usize>,
+    /// The maximum number of columns per table in the database, including the time column
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_columns_per_table: Option<usize>,
+}
+
+impl DatabaseLimits {
+    fn is_empty(&self) -> bool {
+        self.max_tables.is_none() && self.max_columns_per_table.is_none()
+    }
+}
+
+/// The settings of a database that are recorded in a [`CatalogOp`] when the database is created
+/// or updated
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DatabaseSettings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retention_period: Option<Duration>,
+    #[serde(default, skip_serializing_if = "DatabaseLimits::is_empty")]
+    pub limits: DatabaseLimits,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub strict_schema: bool,
+}
+
 #[derive(Debug)]
 pub struct Catalog {
     inner: RwLock<InnerCatalog>,
+    limits: CatalogLimits,
 }
 
 impl Default for Catalog {
@@ -70,30 +151,44 @@ impl Serialize for Catalog {
 }
 
 impl Catalog {
-    /// Limit for the number of Databases that InfluxDB Edge can have
+    /// Default limit for the number of Databases that InfluxDB Edge can have
     pub(crate) const NUM_DBS_LIMIT: usize = 5;
-    /// Limit for the number of columns per table that InfluxDB Edge can have
+    /// Default limit for the number of columns per table that InfluxDB Edge can have
     pub(crate) const NUM_COLUMNS_PER_TABLE_LIMIT: usize = 500;
-    /// Limit for the number of tables across all DBs that InfluxDB Edge can have
+    /// Default limit for the number of tables across all DBs that InfluxDB Edge can have
     pub(crate) const NUM_TABLES_LIMIT: usize = 2000;
 
     pub fn new() -> Self {
         Self {
             inner: RwLock::new(InnerCatalog::new()),
+            limits: CatalogLimits::default(),
         }
     }
 
     pub fn from_inner(inner: InnerCatalog) -> Self {
         Self {
             inner: RwLock::new(inner),
+            limits: CatalogLimits::default(),
         }
     }
 
+    /// Set the limits that are enforced when the catalog is updated
+    pub fn with_limits(mut self, limits: CatalogLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    pub fn limits(&self) -> CatalogLimits {
+        self.limits
+    }
+
+    /// Replace the database with an updated version of it, returning the [`CatalogOp`]s that
+    /// describe the changes that were made, for writing to the WAL
     pub(crate) fn replace_database(
         &self,
         sequence: SequenceNumber,
         db: Arc<DatabaseSchema>,
-    ) -> Result<()> {
+    ) -> Result<Vec<CatalogOp>> {
         let mut inner = self.inner.write();
         if inner.sequence != sequence {
             info!("catalog updated elsewhere");
@@ -110,20 +205,31 @@ impl Catalog {
 
         num_tables += db.tables.len();
 
-        if num_tables > Self::NUM_TABLES_LIMIT {
-            return Err(Error::TooManyTables);
+        if num_tables > self.limits.max_tables {
+            return Err(Error::TooManyTables {
+                limit: self.limits.max_tables,
+            });
         }
 
+        let max_db_tables = self.limits.max_tables_for(&db.limits);
+        if db.tables.len() > max_db_tables {
+            return Err(Error::TooManyTables {
+                limit: max_db_tables,
+            });
+        }
+
+        let max_columns = self.limits.max_columns_per_table_for(&db.limits);
         for table in db.tables.values() {
-            if table.num_columns() > Self::NUM_COLUMNS_PER_TABLE_LIMIT {
-                return Err(Error::TooManyColumns);
+            if table.num_columns() > max_columns {
+                return Err(Error::TooManyColumns { limit: max_columns });
             }
         }
 
         info!("inserted/updated database in catalog: {}", db.name);
+        let ops = db.ops_since(inner.databases.get(&db.name).map(AsRef::as_ref));
         inner.sequence = inner.sequence.next();
         inner.databases.insert(db.name.clone(), db);
-        Ok(())
+        Ok(ops)
     }
 
     pub(crate) fn db_or_create(
@@ -143,8 +249,10 @@ impl Catalog {
             None => {
                 let mut inner = self.inner.write();
 
-                if inner.databases.len() >= Self::NUM_DBS_LIMIT {
-                    return Err(Error::TooManyDbs);
+                if inner.databases.len() >= self.limits.max_databases {
+                    return Err(Error::TooManyDbs {
+                        limit: self.limits.max_databases,
+                    });
                 }
 
                 info!("return new db {}", db_name);
@@ -157,11 +265,123 @@ impl Catalog {
         Ok((sequence, db))
     }
 
+    /// Create a new database in the catalog
+    ///
+    /// Unlike [`Catalog::db_or_create`], this will fail if the database already exists.
+    pub(crate) fn create_database(&self, db: DatabaseSchema) -> Result<Vec<CatalogOp>> {
+        let mut inner = self.inner.write();
+        if inner.databases.contains_key(&db.name) {
+            return Err(Error::DatabaseAlreadyExists { db_name: db.name });
+        }
+        if inner.databases.len() >= self.limits.max_databases {
+            return Err(Error::TooManyDbs {
+                limit: self.limits.max_databases,
+            });
+        }
+
+        info!("created db {}", db.name);
+        let ops = db.ops_since(None);
+        inner.sequence = inner.sequence.next();
+        inner.databases.insert(db.name.clone(), Arc::new(db));
+        Ok(ops)
+    }
+
+    /// Remove a database from the catalog, freeing up its slot against the database limit
+    ///
+    /// A tombstone is kept for the database, recording the time it was deleted, so that writes
+    /// to it that are replayed from the WAL can be identified with [`Catalog::db_deleted_after`].
+    pub(crate) fn delete_database(&self, db_name: &str, deleted_at_ns: i64) -> Result<CatalogOp> {
+        let mut inner = self.inner.write();
+        inner
+            .databases
+            .remove(db_name)
+            .ok_or_else(|| Error::DatabaseNotFound {
+                db_name: db_name.to_string(),
+            })?;
+
+        info!("deleted db {}", db_name);
+        inner.sequence = inner.sequence.next();
+        inner
+            .deleted_databases
+            .insert(db_name.to_string(), deleted_at_ns);
+        Ok(CatalogOp::DeleteDatabase {
+            db_name: db_name.to_string(),
+            deleted_at_ns,
+        })
+    }
+
+    /// Apply a [`CatalogOp`] that is being replayed from the WAL
+    ///
+    /// The op was validated when it was first applied, so limits are not enforced, and parts of
+    /// the op that are already reflected in the catalog, which is loaded from its last persisted
+    /// state before the WAL is replayed, are ignored.
+    pub(crate) fn apply_op(&self, op: &CatalogOp) {
+        let mut inner = self.inner.write();
+        match op {
+            CatalogOp::CreateDatabase { db_name, settings } => {
+                if inner.databases.contains_key(db_name) {
+                    return;
+                }
+                let mut db = DatabaseSchema::new(db_name);
+                db.set_settings(*settings);
+                inner.databases.insert(db_name.clone(), Arc::new(db));
+            }
+            CatalogOp::DeleteDatabase {
+                db_name,
+                deleted_at_ns,
+            } => {
+                inner.databases.remove(db_name);
+                let deleted_at = inner
+                    .deleted_databases
+                    .entry(db_name.clone())
+                    .or_insert(*deleted_at_ns);
+                *deleted_at = (*deleted_at).max(*deleted_at_ns);
+            }
+            op => {
+                // changes to the tables of a database that does not exist create it, in the same
+                // way that a write to the database would:
+                let mut db = inner
+                    .databases
+                    .get(op.db_name())
+                    .map(|db| db.as_ref().clone())
+                    .unwrap_or_else(|| DatabaseSchema::new(op.db_name()));
+                db.apply_op(op);
+                inner.databases.insert(db.name.clone(), Arc::new(db));
+            }
+        }
+        inner.sequence = inner.sequence.next();
+    }
+
+    /// Check if the database was deleted at or after the given time, in nanoseconds since the
+    /// epoch, meaning that a write made to it at that time belongs to the deleted database
+    pub(crate) fn db_deleted_after(&self, db_name: &str, time_ns: i64) -> bool {
+        self.inner
+            .read()
+            .deleted_databases
+            .get(db_name)
+            .is_some_and(|deleted_at_ns| time_ns <= *deleted_at_ns)
+    }
+
     pub fn db_schema(&self, name: &str) -> Option<Arc<DatabaseSchema>> {
         info!("db_schema {}", name);
         self.inner.read().databases.get(name).cloned()
     }
 
+    /// Get the [`DatabaseSchema`] for the given database, along with the catalog's current
+    /// [`SequenceNumber`], for making an update to the database through
+    /// [`Catalog::replace_database`]
+    pub(crate) fn db_schema_and_sequence(
+        &self,
+        name: &str,
+    ) -> Option<(SequenceNumber, Arc<DatabaseSchema>)> {
+        let inner = self.inner.read();
+        inner
+            .databases
+            .get(name)
+            .cloned()
+            .map(|db| (inner.sequence, db))
+    }
+
     pub fn sequence_number(&self) -> SequenceNumber {
         self.inner.read().sequence
     }
@@ -170,6 +390,17 @@ impl Catalog {
         self.inner.read().clone()
     }
 
+    /// Replace the state of the catalog with `updated`, a later version of its state `from`.
+    /// Returns `false`, leaving the catalog as it is, if its state is no longer `from`.
+    pub(crate) fn fast_forward(&self, from: &InnerCatalog, updated: InnerCatalog) -> bool {
+        let mut inner = self.inner.write();
+        if *inner != *from {
+            return false;
+        }
+        *inner = updated;
+        true
+    }
+
     pub fn list_databases(&self) -> Vec<String> {
         self.inner.read().databases.keys().cloned().collect()
     }
@@ -187,6 +418,10 @@ pub struct InnerCatalog {
     #[serde_as(as = "serde_with::MapPreventDuplicates<_, _>")]
     databases: HashMap<String, Arc<DatabaseSchema>>,
     sequence: SequenceNumber,
+    /// Map of deleted database names to the time they were deleted, in nanoseconds since the
+    /// epoch
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    deleted_databases: HashMap<String, i64>,
 }
 
 impl InnerCatalog {
@@ -194,6 +429,7 @@ impl InnerCatalog {
         Self {
             databases: HashMap::new(),
             sequence: SequenceNumber::new(0),
+            deleted_databases: HashMap::new(),
         }
     }
 
@@ -214,6 +450,20 @@ pub struct DatabaseSchema {
     /// The database is a map of tables
     #[serde_as(as = "serde_with::MapPreventDuplicates<_, _>")]
     pub(crate) tables: BTreeMap<String, TableDefinition>,
+    /// How long data is kept in the database before it is removed, if unset, data is kept
+    /// indefinitely
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) retention_period: Option<Duration>,
+    /// Map of deleted table names to the time they were deleted, in nanoseconds since the epoch
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) deleted_tables: BTreeMap<String, i64>,
+    /// Overrides of the server's limits for this database
+    #[serde(default, skip_serializing_if = "DatabaseLimits::is_empty")]
+    pub(crate) limits: DatabaseLimits,
+    /// If set, writes are rejected for tables or columns that have not been declared with
+    /// [`DatabaseSchema::create_table`]
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub(crate) strict_schema: bool,
 }
 
 impl DatabaseSchema {
@@ -221,9 +471,188 @@ impl DatabaseSchema {
         Self {
             name: name.into(),
             tables: BTreeMap::new(),
+            retention_period: None,
+            deleted_tables: BTreeMap::new(),
+            limits: DatabaseLimits::default(),
+            strict_schema: false,
+        }
+    }
+
+    pub fn retention_period(&self) -> Option<Duration> {
+        self.retention_period
+    }
+
+    pub fn limits(&self) -> DatabaseLimits {
+        self.limits
+    }
+
+    pub fn strict_schema(&self) -> bool {
+        self.strict_schema
+    }
+
+    pub fn settings(&self) -> DatabaseSettings {
+        DatabaseSettings {
+            retention_period: self.retention_period,
+            limits: self.limits,
+            strict_schema: self.strict_schema,
+        }
+    }
+
+    pub(crate) fn set_settings(&mut self, settings: DatabaseSettings) {
+        self.retention_period = settings.retention_period;
+        self.limits = settings.limits;
+        self.strict_schema = settings.strict_schema;
+    }
+
+    /// The [`CatalogOp`]s that bring the `previous` version of this database, or no database if
+    /// it did not exist, up to date with this version
+    pub(crate) fn ops_since(&self, previous: Option<&DatabaseSchema>) -> Vec<CatalogOp> {
+        let db_name = &self.name;
+        let Some(previous) = previous else {
+            let mut ops = vec![CatalogOp::CreateDatabase {
+                db_name: db_name.clone(),
+                settings: self.settings(),
+            }];
+            ops.extend(self.tables.values().map(|table| CatalogOp::CreateTable {
+                db_name: db_name.clone(),
+                table: table.clone(),
+            }));
+            return ops;
+        };
+
+        let mut ops = vec![];
+        if self.settings() != previous.settings() {
+            ops.push(CatalogOp::UpdateDatabase {
+                db_name: db_name.clone(),
+                settings: self.settings(),
+            });
+        }
+        // tables that were deleted in this version, which may have been created again since:
+        let mut deleted_tables = HashSet::new();
+        for (table_name, deleted_at_ns) in &self.deleted_tables {
+            if previous.deleted_tables.get(table_name) != Some(deleted_at_ns) {
+                deleted_tables.insert(table_name);
+                ops.push(CatalogOp::DeleteTable {
+                    db_name: db_name.clone(),
+                    table_name: table_name.clone(),
+                    deleted_at_ns: *deleted_at_ns,
+                });
+            }
+        }
+        for (table_name, table) in &self.tables {
+            match previous
+                .tables
+                .get(table_name)
+                .filter(|_| !deleted_tables.contains(&table_name))
+            {
+                Some(previous_table) => ops.extend(table.ops_since(db_name, previous_table)),
+                None => ops.push(CatalogOp::CreateTable {
+                    db_name: db_name.clone(),
+                    table: table.clone(),
+                }),
+            }
+        }
+        ops
+    }
+
+    /// Apply a [`CatalogOp`] for the tables of this database, see [`Catalog::apply_op`]
+    fn apply_op(&mut self, op: &CatalogOp) {
+        match op {
+            CatalogOp::UpdateDatabase { settings, .. } => self.set_settings(*settings),
+            CatalogOp::CreateTable { table, .. } => {
+                if !self.tables.contains_key(&table.name) {
+                    self.tables.insert(table.name.clone(), table.clone());
+                }
+            }
+            CatalogOp::DeleteTable {
+                table_name,
+                deleted_at_ns,
+                ..
+            } => {
+                self.tables.remove(table_name);
+                let deleted_at = self
+                    .deleted_tables
+                    .entry(table_name.clone())
+                    .or_insert(*deleted_at_ns);
+                *deleted_at = (*deleted_at).max(*deleted_at_ns);
+            }
+            CatalogOp::AddColumns {
+                table_name,
+                columns,
+                ..
+            } => {
+                let Some(table) = self.tables.get_mut(table_name) else {
+                    return;
+                };
+                let columns: Vec<(String, InfluxColumnType)> = columns
+                    .iter()
+                    .filter(|(name, _)| !table.column_exists(name))
+                    .map(|(name, data_type)| (name.clone(), (*data_type).into()))
+                    .collect();
+                if !columns.is_empty() {
+                    table.add_columns(columns);
+                }
+            }
+            CatalogOp::WidenFields {
+                table_name,
+                field_names,
+                ..
+            } => {
+                let Some(table) = self.tables.get_mut(table_name) else {
+                    return;
+                };
+                for field_name in field_names {
+                    if table
+                        .field_type_by_name(field_name)
+                        .is_some_and(|t| t != InfluxColumnType::Field(InfluxFieldType::Float))
+                    {
+                        table.widen_field_to_float(field_name);
+                    }
+                }
+            }
+            CatalogOp::UpdateTable {
+                table_name,
+                widen_numeric_fields,
+                ..
+            } => {
+                if let Some(table) = self.tables.get_mut(table_name) {
+                    table.widen_numeric_fields = *widen_numeric_fields;
+                }
+            }
+            CatalogOp::DefineLastCache {
+                table_name,
+                definition,
+                ..
+            } => {
+                if let Some(table) = self.tables.get_mut(table_name) {
+                    if table.get_last_cache(&definition.name).is_none() {
+                        table.add_last_cache(definition.clone());
+                    }
+                }
+            }
+            CatalogOp::DeleteLastCache {
+                table_name,
+                cache_name,
+                ..
+            } => {
+                if let Some(table) = self.tables.get_mut(table_name) {
+                    table.remove_last_cache(cache_name);
+                }
+            }
+            CatalogOp::CreateDatabase { .. } | CatalogOp::DeleteDatabase { .. } => {
+                unreachable!("database ops are applied by the catalog")
+            }
         }
     }
 
+    /// The time, in nanoseconds since the epoch, before which data in this database has expired,
+    /// given the current time `now_ns`, or `None` if the database has no retention period
+    pub fn retention_cutoff_ns(&self, now_ns: i64) -> Option<i64> {
+        self.retention_period.map(|period| {
+            now_ns.saturating_sub(i64::try_from(period.as_nanos()).unwrap_or(i64::MAX))
+        })
+    }
+
     pub fn get_table_schema(&self, table_name: &str) -> Option<&Schema> {
         self.tables.get(table_name).map(|table| &table.schema)
     }
@@ -239,6 +668,111 @@ impl DatabaseSchema {
     pub fn table_exists(&self, table_name: &str) -> bool {
         self.tables.contains_key(table_name)
     }
+
+    /// Declare a new table in the database, before any data has been written to it
+    ///
+    /// The table is given the `tags` and typed `fields`, along with a time column. If a
+    /// `series_key` is provided, the table uses the v3 data model, and its tags are the members
+    /// of the series key, so any `tags` given must also be in the series key.
+    ///
+    /// If `widen_numeric_fields` is set, writes with numeric field values that conflict with the
+    /// declared field types widen the fields to floats, instead of being rejected.
+    pub(crate) fn create_table(
+        &mut self,
+        table_name: &str,
+        tags: &[String],
+        fields: &[(String, InfluxFieldType)],
+        series_key: Option<&[String]>,
+        widen_numeric_fields: bool,
+    ) -> Result<()> {
+        let invalid = |reason: String| Error::InvalidTableDefinition {
+            table_name: table_name.to_string(),
+            reason,
+        };
+        if table_name.is_empty() {
+            return Err(invalid("the table name cannot be empty".to_string()));
+        }
+        if self.tables.contains_key(table_name) {
+            return Err(Error::TableAlreadyExists {
+                db_name: self.name.clone(),
+                table_name: table_name.to_string(),
+            });
+        }
+
+        let mut columns: Vec<(&str, InfluxColumnType)> = vec![];
+        if let Some(series_key) = series_key {
+            if let Some(tag) = tags.iter().find(|t| !series_key.contains(*t)) {
+                return Err(invalid(format!(
+                    "tag '{tag}' must be a member of the series key"
+                )));
+            }
+            columns.extend(
+                series_key
+                    .iter()
+                    .map(|sk| (sk.as_str(), InfluxColumnType::Tag)),
+            );
+        } else {
+            columns.extend(tags.iter().map(|t| (t.as_str(), InfluxColumnType::Tag)));
+        }
+        columns.extend(
+            fields
+                .iter()
+                .map(|(name, field_type)| (name.as_str(), InfluxColumnType::Field(*field_type))),
+        );
+        let mut names = HashSet::with_capacity(columns.len());
+        for (name, _) in &columns {
+            if name.is_empty() {
+                return Err(invalid("column names cannot be empty".to_string()));
+            }
+            if *name == TIME_COLUMN_NAME {
+                return Err(invalid(format!(
+                    "the '{TIME_COLUMN_NAME}' column is added to every table and cannot be \
+                    declared"
+                )));
+            }
+            if !names.insert(*name) {
+                return Err(invalid(format!(
+                    "column '{name}' was declared more than once"
+                )));
+            }
+        }
+        columns.push((TIME_COLUMN_NAME, InfluxColumnType::Timestamp));
+
+        let mut table = TableDefinition::new(table_name, columns, series_key);
+        table.widen_numeric_fields = widen_numeric_fields;
+        self.tables.insert(table_name.to_string(), table);
+
+        Ok(())
+    }
+
+    /// Remove a table from the database, returning its definition if it existed
+    ///
+    /// A tombstone is kept for the table, recording the time it was deleted, so that writes to it
+    /// that are replayed from the WAL can be identified with
+    /// [`DatabaseSchema::table_deleted_after`].
+    pub(crate) fn delete_table(
+        &mut self,
+        table_name: &str,
+        deleted_at_ns: i64,
+    ) -> Option<TableDefinition> {
+        let table = self.tables.remove(table_name)?;
+        self.deleted_tables
+            .insert(table_name.to_string(), deleted_at_ns);
+        Some(table)
+    }
+
+    /// Check if the table was deleted at or after the given time, in nanoseconds since the epoch,
+    /// meaning that a write made to it at that time belongs to the deleted table
+    pub(crate) fn table_deleted_after(&self, table_name: &str, time_ns: i64) -> bool {
+        self.deleted_tables
+            .get(table_name)
+            .is_some_and(|deleted_at_ns| time_ns <= *deleted_at_ns)
+    }
+
+    /// Check if any of the tables in the database have been deleted
+    pub(crate) fn has_deleted_tables(&self) -> bool {
+        !self.deleted_tables.is_empty()
+    }
 }
 
 #[derive(Debug, Eq, PartialEq, Clone)]
@@ -246,6 +780,9 @@ pub struct TableDefinition {
     pub name: String,
     pub schema: Schema,
     pub last_caches: Vec<LastCacheDefinition>,
+    /// If set, writes with a numeric field value that conflicts with the type of the field in the
+    /// schema widen the field to a float, instead of being rejected
+    pub(crate) widen_numeric_fields: bool,
 }
 
 impl TableDefinition {
@@ -277,6 +814,7 @@ impl TableDefinition {
             name,
             schema,
             last_caches: vec![],
+            widen_numeric_fields: false,
         }
     }
 
@@ -310,6 +848,89 @@ impl TableDefinition {
         self.schema = schema;
     }
 
+    /// Change the type of a numeric field to a float, keeping the rest of the schema as is
+    pub(crate) fn widen_field_to_float(&mut self, field_name: &str) {
+        let columns: Vec<(String, InfluxColumnType)> = self
+            .schema
+            .iter()
+            .map(|(col_type, field)| {
+                let col_type = if field.name() == field_name {
+                    InfluxColumnType::Field(InfluxFieldType::Float)
+                } else {
+                    col_type
+                };
+                (field.name().to_string(), col_type)
+            })
+            .collect();
+        let series_key = self
+            .schema
+            .series_key()
+            .map(|sk| sk.into_iter().map(ToString::to_string).collect::<Vec<_>>());
+        let widened = Self::new(self.name.as_str(), columns, series_key);
+        self.schema = widened.schema;
+    }
+
+    pub fn widens_numeric_fields(&self) -> bool {
+        self.widen_numeric_fields
+    }
+
+    /// The [`CatalogOp`]s that bring the `previous` version of this table up to date with this
+    /// version
+    fn ops_since(&self, db_name: &str, previous: &TableDefinition) -> Vec<CatalogOp> {
+        let mut ops = vec![];
+        let mut new_columns = vec![];
+        let mut widened_fields = vec![];
+        for (col_type, field) in self.schema.iter() {
+            match previous.field_type_by_name(field.name()) {
+                None => new_columns.push((field.name().to_string(), col_type.into())),
+                Some(previous_type) if previous_type != col_type => {
+                    widened_fields.push(field.name().to_string())
+                }
+                Some(_) => (),
+            }
+        }
+        if !new_columns.is_empty() {
+            ops.push(CatalogOp::AddColumns {
+                db_name: db_name.to_string(),
+                table_name: self.name.clone(),
+                columns: new_columns,
+            });
+        }
+        if !widened_fields.is_empty() {
+            ops.push(CatalogOp::WidenFields {
+                db_name: db_name.to_string(),
+                table_name: self.name.clone(),
+                field_names: widened_fields,
+            });
+        }
+        if self.widen_numeric_fields != previous.widen_numeric_fields {
+            ops.push(CatalogOp::UpdateTable {
+                db_name: db_name.to_string(),
+                table_name: self.name.clone(),
+                widen_numeric_fields: self.widen_numeric_fields,
+            });
+        }
+        for cache in &previous.last_caches {
+            if self.get_last_cache(&cache.name) != Some(cache) {
+                ops.push(CatalogOp::DeleteLastCache {
+                    db_name: db_name.to_string(),
+                    table_name: self.name.clone(),
+                    cache_name: cache.name.clone(),
+                });
+            }
+        }
+        for cache in &self.last_caches {
+            if previous.get_last_cache(&cache.name) != Some(cache) {
+                ops.push(CatalogOp::DefineLastCache {
+                    db_name: db_name.to_string(),
+                    table_name: self.name.clone(),
+                    definition: cache.clone(),
+                });
+            }
+        }
+        ops
+    }
+
     pub(crate) fn index_columns(&self) -> Vec<&str> {
         self.schema
             .iter()
@@ -337,13 +958,24 @@ impl TableDefinition {
     }
 
     /// Add a new last cache to this table definition
-    #[cfg(test)]
     pub(crate) fn add_last_cache<L>(&mut self, last_cache: L)
     where
         L: Into<LastCacheDefinition>,
     {
         self.last_caches.push(last_cache.into());
     }
+
+    /// Remove a last cache from this table definition, returning `true` if it existed
+    pub(crate) fn remove_last_cache(&mut self, name: &str) -> bool {
+        let len = self.last_caches.len();
+        self.last_caches.retain(|c| c.name != name);
+        self.last_caches.len() != len
+    }
+
+    /// Get the last cache with the given name, if it exists
+    pub fn get_last_cache(&self, name: &str) -> Option<&LastCacheDefinition> {
+        self.last_caches.iter().find(|c| c.name == name)
+    }
 }
 
 /// Defines a last cache in a given table and database
@@ -361,8 +993,7 @@ pub struct LastCacheDefinition {
 
 impl LastCacheDefinition {
     /// Create a new [`LastCacheDefinition`]
-    #[cfg(test)]
-    pub(crate) fn new<N, K, V>(
+    pub fn new<N, K, V>(
         name: N,
         key_columns: K,
         value_columns: V,
@@ -380,6 +1011,11 @@ impl LastCacheDefinition {
             count: count.try_into()?,
         })
     }
+
+    /// The number of last values held in the cache for each key
+    pub fn count(&self) -> usize {
+        self.count.into()
+    }
 }
 
 /// The maximum allowed size for a last cache
@@ -415,6 +1051,27 @@ impl From<LastCacheSize> for usize {
     }
 }
 
+/// The type that a field is widened to when a value of type `new` is written to it while it has
+/// type `existing`, or `None` if the types cannot be reconciled
+///
+/// Only numeric fields are widened, and conflicting numeric types are always widened to a float.
+pub(crate) fn widened_field_type(
+    existing: InfluxColumnType,
+    new: InfluxColumnType,
+) -> Option<InfluxColumnType> {
+    use InfluxFieldType::{Float, Integer, UInteger};
+    match (existing, new) {
+        (InfluxColumnType::Field(a), InfluxColumnType::Field(b))
+            if matches!(a, Float | Integer | UInteger)
+                && matches!(b, Float | Integer | UInteger) =>
+        {
+            Some(InfluxColumnType::Field(if a == b { a } else { Float }))
+        }
+        _ if existing == new => Some(existing),
+        _ => None,
+    }
+}
+
 pub fn influx_column_type_from_field_value(fv: &FieldValue<'_>) -> InfluxColumnType {
     match fv {
         FieldValue::I64(_) => InfluxColumnType::Field(InfluxFieldType::Integer),
@@ -441,6 +1098,10 @@ mod tests {
         let mut database = DatabaseSchema {
             name: "test_db".to_string(),
             tables: BTreeMap::new(),
+            retention_period: None,
+            deleted_tables: BTreeMap::new(),
+            limits: DatabaseLimits::default(),
+            strict_schema: false,
         };
         use InfluxColumnType::*;
         use InfluxFieldType::*;
@@ -573,6 +1234,10 @@ mod tests {
         let mut database = DatabaseSchema {
             name: "test".to_string(),
             tables: BTreeMap::new(),
+            retention_period: None,
+            deleted_tables: BTreeMap::new(),
+            limits: DatabaseLimits::default(),
+            strict_schema: false,
         };
         database.tables.insert(
             "test".into(),
@@ -602,6 +1267,10 @@ mod tests {
         let mut database = DatabaseSchema {
             name: "test_db".to_string(),
             tables: BTreeMap::new(),
+            retention_period: None,
+            deleted_tables: BTreeMap::new(),
+            limits: DatabaseLimits::default(),
+            strict_schema: false,
         };
         use InfluxColumnType::*;
         use InfluxFieldType::*;
@@ -642,6 +1311,10 @@ mod tests {
         let mut database = DatabaseSchema {
             name: "test_db".to_string(),
             tables: BTreeMap::new(),
+            retention_period: None,
+            deleted_tables: BTreeMap::new(),
+            limits: DatabaseLimits::default(),
+            strict_schema: false,
         };
         use InfluxColumnType::*;
         use InfluxFieldType::*;
@@ -673,4 +1346,384 @@ mod tests {
         let deserialized = Catalog::from_inner(deserialized_inner);
         assert_eq!(catalog, deserialized);
     }
+
+    #[test]
+    fn serialize_retention_period() {
+        let catalog = Catalog::new();
+        let mut database = DatabaseSchema::new("test_db");
+        database.retention_period = Some(Duration::from_secs(60 * 60 * 24));
+        catalog.create_database(database).unwrap();
+
+        let serialized = serde_json::to_string(&catalog).unwrap();
+        let deserialized_inner: InnerCatalog = serde_json::from_str(&serialized).unwrap();
+        let deserialized = Catalog::from_inner(deserialized_inner);
+        assert_eq!(catalog, deserialized);
+
+        let db = deserialized.db_schema("test_db").unwrap();
+        assert_eq!(
+            Some(Duration::from_secs(60 * 60 * 24)),
+            db.retention_period()
+        );
+        assert_eq!(
+            Some(1_000 - 60 * 60 * 24 * 1_000_000_000),
+            db.retention_cutoff_ns(1_000)
+        );
+
+        // creating the database again fails:
+        assert!(matches!(
+            catalog.create_database(DatabaseSchema::new("test_db")),
+            Err(Error::DatabaseAlreadyExists { .. })
+        ));
+    }
+
+    #[test]
+    fn delete_database_frees_slot_and_keeps_tombstone() {
+        let catalog = Catalog::new();
+        for i in 0..Catalog::NUM_DBS_LIMIT {
+            catalog
+                .create_database(DatabaseSchema::new(format!("db_{i}")))
+                .unwrap();
+        }
+        assert!(matches!(
+            catalog.create_database(DatabaseSchema::new("one_too_many")),
+            Err(Error::TooManyDbs { limit: 5 })
+        ));
+
+        let sequence = catalog.sequence_number();
+        catalog.delete_database("db_0", 1_000).unwrap();
+        assert!(!catalog.db_exists("db_0"));
+        assert_eq!(sequence.next(), catalog.sequence_number());
+        assert!(matches!(
+            catalog.delete_database("db_0", 2_000),
+            Err(Error::DatabaseNotFound { .. })
+        ));
+
+        // the slot is free again:
+        catalog
+            .create_database(DatabaseSchema::new("one_too_many"))
+            .unwrap();
+
+        // writes made before the delete belong to the deleted database:
+        assert!(catalog.db_deleted_after("db_0", 999));
+        assert!(catalog.db_deleted_after("db_0", 1_000));
+        assert!(!catalog.db_deleted_after("db_0", 1_001));
+        assert!(!catalog.db_deleted_after("db_1", 0));
+
+        // the tombstone survives serialization:
+        let serialized = serde_json::to_string(&catalog).unwrap();
+        let deserialized = Catalog::from_inner(serde_json::from_str(&serialized).unwrap());
+        assert_eq!(catalog, deserialized);
+        assert!(deserialized.db_deleted_after("db_0", 1_000));
+    }
+
+    #[test]
+    fn delete_table_keeps_tombstone() {
+        let mut database = DatabaseSchema::new("test_db");
+        database.tables.insert(
+            "cpu".into(),
+            TableDefinition::new(
+                "cpu",
+                [("time", InfluxColumnType::Timestamp)],
+                SeriesKey::None,
+            ),
+        );
+        assert!(!database.has_deleted_tables());
+
+        assert!(database.delete_table("cpu", 1_000).is_some());
+        assert!(database.delete_table("cpu", 2_000).is_none());
+        assert!(!database.table_exists("cpu"));
+        assert!(database.has_deleted_tables());
+        assert!(database.table_deleted_after("cpu", 1_000));
+        assert!(!database.table_deleted_after("cpu", 1_001));
+        assert!(!database.table_deleted_after("mem", 0));
+
+        let serialized = serde_json::to_string(&database).unwrap();
+        let deserialized: DatabaseSchema = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(database, deserialized);
+    }
+
+    #[test]
+    fn configurable_limits() {
+        let catalog = Catalog::new().with_limits(CatalogLimits {
+            max_databases: 1,
+            max_tables: 3,
+            max_columns_per_table: 3,
+        });
+        catalog.db_or_create("foo").unwrap();
+        let err = catalog.db_or_create("bar").unwrap_err();
+        assert_eq!(
+            "Adding a new database would exceed limit of 1 databases",
+            err.to_string()
+        );
+
+        use InfluxColumnType::*;
+        use InfluxFieldType::*;
+        let table = |name: &str, num_fields: usize| {
+            let mut columns = vec![("time".to_string(), Timestamp)];
+            columns.extend((0..num_fields).map(|i| (format!("f{i}"), Field(Float))));
+            TableDefinition::new(name, columns, SeriesKey::None)
+        };
+
+        // the server's limits apply:
+        let (sequence, db) = catalog.db_schema_and_sequence("foo").unwrap();
+        let mut new_db = db.as_ref().clone();
+        new_db.tables.insert("cpu".into(), table("cpu", 3));
+        let err = catalog
+            .replace_database(sequence, Arc::new(new_db))
+            .unwrap_err();
+        assert_eq!(
+            "Update to schema would exceed number of columns per table limit of 2 columns",
+            err.to_string()
+        );
+
+        // and can't be raised for the database:
+        let mut new_db = db.as_ref().clone();
+        new_db.limits = DatabaseLimits {
+            max_tables: Some(10),
+            max_columns_per_table: Some(10),
+        };
+        new_db.tables.insert("cpu".into(), table("cpu", 3));
+        let err = catalog
+            .replace_database(sequence, Arc::new(new_db))
+            .unwrap_err();
+        assert_eq!(
+            "Update to schema would exceed number of columns per table limit of 2 columns",
+            err.to_string()
+        );
+
+        // but they can be lowered:
+        let mut new_db = db.as_ref().clone();
+        new_db.limits = DatabaseLimits {
+            max_tables: Some(1),
+            max_columns_per_table: Some(2),
+        };
+        new_db.tables.insert("cpu".into(), table("cpu", 2));
+        let err = catalog
+            .replace_database(sequence, Arc::new(new_db.clone()))
+            .unwrap_err();
+        assert_eq!(
+            "Update to schema would exceed number of columns per table limit of 1 columns",
+            err.to_string()
+        );
+        new_db.tables.insert("cpu".into(), table("cpu", 1));
+        catalog
+            .replace_database(sequence, Arc::new(new_db.clone()))
+            .unwrap();
+
+        new_db.tables.insert("mem".into(), table("mem", 1));
+        let err = catalog
+            .replace_database(catalog.sequence_number(), Arc::new(new_db))
+            .unwrap_err();
+        assert_eq!(
+            "Update to schema would exceed number of tables limit of 1 tables",
+            err.to_string()
+        );
+
+        // the database limits are persisted with the catalog:
+        let serialized = serde_json::to_string(&catalog).unwrap();
+        let deserialized = Catalog::from_inner(serde_json::from_str(&serialized).unwrap());
+        assert_eq!(
+            Some(1),
+            deserialized.db_schema("foo").unwrap().limits().max_tables
+        );
+    }
+
+    #[test]
+    fn database_limits_only_lower_server_limits() {
+        let server = CatalogLimits {
+            max_databases: 1,
+            max_tables: 10,
+            max_columns_per_table: 10,
+        };
+        assert_eq!(10, server.max_tables_for(&DatabaseLimits::default()));
+        assert_eq!(
+            10,
+            server.max_columns_per_table_for(&DatabaseLimits::default())
+        );
+
+        let lower = DatabaseLimits {
+            max_tables: Some(5),
+            max_columns_per_table: Some(5),
+        };
+        assert_eq!(5, server.max_tables_for(&lower));
+        assert_eq!(5, server.max_columns_per_table_for(&lower));
+
+        let higher = DatabaseLimits {
+            max_tables: Some(20),
+            max_columns_per_table: Some(20),
+        };
+        assert_eq!(10, server.max_tables_for(&higher));
+        assert_eq!(10, server.max_columns_per_table_for(&higher));
+    }
+
+    #[test]
+    fn create_table() {
+        let mut database = DatabaseSchema::new("test_db");
+        let tags = ["host".to_string(), "region".to_string()];
+        let fields = [
+            ("usage".to_string(), InfluxFieldType::Float),
+            ("count".to_string(), InfluxFieldType::UInteger),
+        ];
+
+        database
+            .create_table("cpu", &tags, &fields, None, false)
+            .unwrap();
+        let cpu = database.get_table("cpu").unwrap();
+        assert!(!cpu.is_v3());
+        assert_eq!(
+            Some(InfluxColumnType::Field(InfluxFieldType::UInteger)),
+            cpu.field_type_by_name("count")
+        );
+        assert_eq!(vec!["host", "region"], cpu.index_columns());
+        assert_eq!(5, cpu.num_columns());
+
+        // the series key members are the tags of a v3 table:
+        database
+            .create_table("mem", &tags[..1], &fields[..1], Some(&tags), false)
+            .unwrap();
+        let mem = database.get_table("mem").unwrap();
+        assert_eq!(Some(vec!["host", "region"]), mem.schema().series_key());
+        assert_eq!(
+            Some(InfluxColumnType::Tag),
+            mem.field_type_by_name("region")
+        );
+
+        assert!(matches!(
+            database.create_table("cpu", &[], &[], None, false),
+            Err(Error::TableAlreadyExists { .. })
+        ));
+        assert_contains!(
+            database
+                .create_table("disk", &tags, &[], Some(&tags[..1]), false)
+                .unwrap_err()
+                .to_string(),
+            "tag 'region' must be a member of the series key"
+        );
+        assert_contains!(
+            database
+                .create_table("disk", &["time".to_string()], &[], None, false)
+                .unwrap_err()
+                .to_string(),
+            "the 'time' column is added to every table"
+        );
+        assert_contains!(
+            database
+                .create_table(
+                    "disk",
+                    &tags,
+                    &[("host".to_string(), InfluxFieldType::Float)],
+                    None,
+                    false,
+                )
+                .unwrap_err()
+                .to_string(),
+            "column 'host' was declared more than once"
+        );
+        assert!(!database.table_exists("disk"));
+    }
+
+    #[test]
+    fn replay_ops() {
+        let catalog = Catalog::new();
+        let mut database = DatabaseSchema::new("test_db");
+        database.retention_period = Some(Duration::from_secs(60));
+        let mut ops = catalog.create_database(database).unwrap();
+
+        let update = |f: &dyn Fn(&mut DatabaseSchema)| {
+            let (sequence, db) = catalog.db_schema_and_sequence("test_db").unwrap();
+            let mut db = db.as_ref().clone();
+            f(&mut db);
+            catalog.replace_database(sequence, Arc::new(db)).unwrap()
+        };
+        ops.extend(update(&|db| {
+            db.create_table(
+                "cpu",
+                &["host".to_string()],
+                &[("usage".to_string(), InfluxFieldType::Integer)],
+                None,
+                false,
+            )
+            .unwrap();
+            db.create_table("mem", &[], &[], None, false).unwrap();
+        }));
+        let table_ops = update(&|db| {
+            let cpu = db.tables.get_mut("cpu").unwrap();
+            cpu.add_columns(vec![
+                ("region".to_string(), InfluxColumnType::Tag),
+                (
+                    "free".to_string(),
+                    InfluxColumnType::Field(InfluxFieldType::Boolean),
+                ),
+            ]);
+            cpu.widen_numeric_fields = true;
+            cpu.widen_field_to_float("usage");
+            cpu.add_last_cache(
+                LastCacheDefinition::new("cpu_cache", ["host"], ["usage"], 1).unwrap(),
+            );
+        });
+        assert!(matches!(
+            table_ops.as_slice(),
+            [
+                CatalogOp::AddColumns { .. },
+                CatalogOp::WidenFields { .. },
+                CatalogOp::UpdateTable {
+                    widen_numeric_fields: true,
+                    ..
+                },
+                CatalogOp::DefineLastCache { .. },
+            ]
+        ));
+        ops.extend(table_ops);
+        ops.extend(update(&|db| {
+            db.delete_table("mem", 1_000);
+            db.strict_schema = true;
+        }));
+        ops.extend(update(&|db| {
+            db.tables
+                .get_mut("cpu")
+                .unwrap()
+                .remove_last_cache("cpu_cache");
+        }));
+
+        // replaying the ops restores the database:
+        let replayed = Catalog::new();
+        for op in &ops {
+            replayed.apply_op(op);
+        }
+        assert_eq!(catalog.db_schema("test_db"), replayed.db_schema("test_db"));
+
+        // and replaying them again, onto a catalog that already reflects them, has no effect:
+        for op in &ops {
+            replayed.apply_op(op);
+        }
+        assert_eq!(catalog.db_schema("test_db"), replayed.db_schema("test_db"));
+
+        // the ops survive a round trip through the WAL's serialization:
+        let serialized = serde_json::to_vec(&ops).unwrap();
+        let deserialized: Vec<CatalogOp> = serde_json::from_slice(&serialized).unwrap();
+        assert_eq!(ops, deserialized);
+    }
+
+    #[test]
+    fn fast_forward() {
+        let catalog = Catalog::new();
+        catalog.db_or_create("foo").unwrap();
+
+        let from = catalog.clone_inner();
+        let snapshot = Catalog::from_inner(from.clone());
+        snapshot.db_or_create("bar").unwrap();
+
+        assert!(catalog.fast_forward(&from, snapshot.clone_inner()));
+        assert_eq!(snapshot.clone_inner(), catalog.clone_inner());
+
+        // a snapshot taken before the catalog was last updated isn't applied:
+        let from = catalog.clone_inner();
+        let stale = Catalog::from_inner(from.clone());
+        stale.db_or_create("baz").unwrap();
+        catalog.db_or_create("qux").unwrap();
+
+        assert!(!catalog.fast_forward(&from, stale.clone_inner()));
+        assert!(catalog.db_exists("qux"));
+        assert!(!catalog.db_exists("baz"));
+    }
 }
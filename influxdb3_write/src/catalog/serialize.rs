@@ -41,6 +41,8 @@ struct TableSnapshot<'a> {
     cols: BTreeMap<&'a str, ColumnDefinition<'a>>,
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     last_caches: Vec<LastCacheSnapshot<'a>>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    widen_numeric_fields: bool,
 }
 
 /// Representation of Arrow's `DataType` for table snapshots.
@@ -153,6 +155,7 @@ impl<'a> From<&'a TableDefinition> for TableSnapshot<'a> {
             cols,
             key: keys,
             last_caches,
+            widen_numeric_fields: def.widen_numeric_fields,
         }
     }
 }
@@ -237,6 +240,7 @@ impl<'a> From<TableSnapshot<'a>> for TableDefinition {
             name,
             schema,
             last_caches,
+            widen_numeric_fields: snap.widen_numeric_fields,
         }
     }
 }
@@ -1,8 +1,15 @@
-use arrow::array::RecordBatch;
+use crate::write_buffer::deletes::remove_deleted_rows;
+use crate::DeletePredicate;
+use arrow::array::{new_null_array, RecordBatch, RecordBatchOptions};
+use arrow::compute::cast;
+use arrow::datatypes::SchemaRef;
 use data_types::{ChunkId, ChunkOrder, TransitionPartitionId};
-use datafusion::common::Statistics;
+use datafusion::common::{DataFusionError, Statistics};
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+use futures_util::stream::{self, StreamExt, TryStreamExt};
 use iox_query::chunk_statistics::ChunkStatistics;
 use iox_query::{QueryChunk, QueryChunkData};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
 use parquet_file::storage::ParquetExecInput;
 use schema::sort::SortKey;
 use schema::Schema;
@@ -71,6 +78,8 @@ pub struct ParquetChunk {
     pub(crate) id: ChunkId,
     pub(crate) chunk_order: ChunkOrder,
     pub(crate) parquet_exec: ParquetExecInput,
+    /// Deletes whose matching rows are filtered out of the file when it's scanned
+    pub(crate) tombstones: Vec<DeletePredicate>,
 }
 
 impl QueryChunk for ParquetChunk {
@@ -99,7 +108,22 @@ impl QueryChunk for ParquetChunk {
     }
 
     fn data(&self) -> QueryChunkData {
-        QueryChunkData::Parquet(self.parquet_exec.clone())
+        if self.tombstones.is_empty() {
+            return QueryChunkData::Parquet(self.parquet_exec.clone());
+        }
+
+        // the file is read when the stream is first polled, rather than scanned by the parquet
+        // exec, so that the deleted rows can be removed from it
+        let schema = Arc::clone(self.schema.inner());
+        let batches = read_undeleted_batches(
+            self.parquet_exec.clone(),
+            self.tombstones.clone(),
+            Arc::clone(&schema),
+        );
+        let stream = stream::once(batches)
+            .map_ok(|batches| stream::iter(batches.into_iter().map(Ok::<_, DataFusionError>)))
+            .try_flatten();
+        QueryChunkData::RecordBatches(Box::pin(RecordBatchStreamAdapter::new(schema, stream)))
     }
 
     fn chunk_type(&self) -> &str {
@@ -114,3 +138,39 @@ impl QueryChunk for ParquetChunk {
         self
     }
 }
+
+/// Reads the parquet file, returning its rows that don't match any of the tombstones, with the
+/// given schema
+async fn read_undeleted_batches(
+    parquet_exec: ParquetExecInput,
+    tombstones: Vec<DeletePredicate>,
+    schema: SchemaRef,
+) -> Result<Vec<RecordBatch>, DataFusionError> {
+    let bytes = parquet_exec
+        .object_store
+        .get(&parquet_exec.object_meta.location)
+        .await?
+        .bytes()
+        .await?;
+    ParquetRecordBatchReaderBuilder::try_new(bytes)?
+        .build()?
+        .map(|batch| {
+            let batch = remove_deleted_rows(batch?, &tombstones)?;
+            // the file may have been persisted before columns were added to the table, or fields
+            // were widened, so its columns are cast and filled in to match the table schema
+            let columns = schema
+                .fields()
+                .iter()
+                .map(|field| match batch.column_by_name(field.name()) {
+                    Some(column) => cast(column, field.data_type()),
+                    None => Ok(new_null_array(field.data_type(), batch.num_rows())),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(RecordBatch::try_new_with_options(
+                Arc::clone(&schema),
+                columns,
+                &RecordBatchOptions::new().with_row_count(Some(batch.num_rows())),
+            )?)
+        })
+        .collect()
+}
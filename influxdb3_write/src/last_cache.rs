@@ -0,0 +1,1328 @@
+//! An in-memory cache of the most recent values written to a table.
+//!
+//! Each cache is driven by a [`LastCacheDefinition`] in the catalog. For every distinct combination
+//! of values in the cache's key columns, the last `N` rows, by time, are held for the cache's value
+//! columns. Caches are updated on the write path once rows have been buffered, are rebuilt from the
+//! buffer when the server restarts, and can be queried from SQL using the [`LastCacheFunction`]
+//! table function, e.g.,
+//!
+//! ```sql
+//! SELECT * FROM last_cache('cpu', 'cpu_host_cache') WHERE host = 'a'
+//! ```
+//!
+//! The number of keys held by each cache is bounded by [`LastCacheProvider::set_max_keys`]: once
+//! a cache holds more, the keys whose most recent row is the oldest are evicted, and the cache no
+//! longer returns rows for them.
+
+use std::{
+    any::Any,
+    collections::{BTreeSet, HashMap, VecDeque},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use arrow::{
+    array::{
+        Array, ArrayRef, AsArray, BooleanBuilder, Float64Builder, Int64Builder, StringBuilder,
+        StringDictionaryBuilder, TimestampNanosecondBuilder, UInt64Builder,
+    },
+    compute::cast,
+    datatypes::{
+        DataType, Field as ArrowField, Float64Type, Int32Type, Int64Type, Schema as ArrowSchema,
+        SchemaRef as ArrowSchemaRef, TimestampNanosecondType, UInt64Type,
+    },
+    error::ArrowError,
+    record_batch::RecordBatch,
+};
+use async_trait::async_trait;
+use datafusion::{
+    common::{plan_err, Result as DataFusionResult, ScalarValue},
+    datasource::{function::TableFunctionImpl, MemTable, TableProvider, TableType},
+    error::DataFusionError,
+    execution::context::SessionState,
+    logical_expr::{Expr, TableProviderFilterPushDown},
+    physical_plan::ExecutionPlan,
+};
+use parking_lot::RwLock;
+use schema::{InfluxColumnType, InfluxFieldType, SchemaBuilder, TIME_COLUMN_NAME};
+use thiserror::Error;
+
+use crate::{
+    catalog::{Catalog, LastCacheDefinition, TableDefinition},
+    write_buffer::{Field, FieldData, Row, ValidSegmentedData},
+    DeletePredicate,
+};
+
+/// The name of the table function used to query a last cache
+pub const LAST_CACHE_UDTF_NAME: &str = "last_cache";
+
+/// The default number of unique keys held by each cache, beyond which the keys with the least
+/// recent rows are evicted
+pub const DEFAULT_LAST_CACHE_MAX_KEYS: usize = 100_000;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("column '{column_name}' does not exist in table '{table_name}'")]
+    ColumnDoesNotExist {
+        table_name: String,
+        column_name: String,
+    },
+
+    #[error(
+        "column '{column_name}' cannot be used as a key column, only tag, string, \
+        integer, unsigned integer, or boolean columns are supported"
+    )]
+    InvalidKeyColumn { column_name: String },
+
+    #[error("a last cache named '{cache_name}' already exists on table '{table_name}'")]
+    CacheAlreadyExists {
+        table_name: String,
+        cache_name: String,
+    },
+
+    #[error("no last cache found for table '{table_name}' in database '{db_name}'")]
+    CacheNotFound { db_name: String, table_name: String },
+
+    #[error(
+        "table '{table_name}' has more than one last cache, the name of the \
+        cache to query must be provided"
+    )]
+    CacheNameRequired { table_name: String },
+
+    #[error("error producing record batch from last cache: {0}")]
+    Arrow(#[from] ArrowError),
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+type CacheMap = RwLock<HashMap<String, HashMap<String, HashMap<String, LastCache>>>>;
+
+/// Holds all of the last caches for the server, organized by database, table, and cache name
+#[derive(Debug)]
+pub struct LastCacheProvider {
+    cache_map: CacheMap,
+    /// The number of unique keys held by each cache
+    max_keys: AtomicUsize,
+}
+
+impl Default for LastCacheProvider {
+    fn default() -> Self {
+        Self {
+            cache_map: Default::default(),
+            max_keys: AtomicUsize::new(DEFAULT_LAST_CACHE_MAX_KEYS),
+        }
+    }
+}
+
+impl LastCacheProvider {
+    /// Create a new, empty [`LastCacheProvider`]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the number of unique keys held by each cache, including the caches already in the
+    /// provider, the keys whose most recent row is the oldest are evicted once a cache holds more
+    /// than this
+    pub fn set_max_keys(&self, max_keys: usize) {
+        let max_keys = max_keys.max(1);
+        let mut cache_map = self.cache_map.write();
+        self.max_keys.store(max_keys, Ordering::Relaxed);
+        for cache in cache_map
+            .values_mut()
+            .flat_map(|db| db.values_mut())
+            .flat_map(|table| table.values_mut())
+        {
+            cache.set_max_keys(max_keys);
+        }
+    }
+
+    /// Create a [`LastCacheProvider`] with a cache for each [`LastCacheDefinition`] in the
+    /// [`Catalog`]
+    pub(crate) fn new_from_catalog(catalog: &Catalog) -> Result<Self> {
+        let provider = Self::new();
+        for db_name in catalog.list_databases() {
+            let Some(db_schema) = catalog.db_schema(&db_name) else {
+                continue;
+            };
+            for table_def in db_schema.tables.values() {
+                for cache_def in &table_def.last_caches {
+                    provider.create_cache(&db_name, table_def, cache_def)?;
+                }
+            }
+        }
+        Ok(provider)
+    }
+
+    /// Create a new cache in the provider for the given table
+    ///
+    /// This validates the columns in the `definition` against the table's schema, and will fail
+    /// if a cache with the same name already exists on the table.
+    pub fn create_cache(
+        &self,
+        db_name: &str,
+        table_def: &TableDefinition,
+        definition: &LastCacheDefinition,
+    ) -> Result<()> {
+        let mut cache_map = self.cache_map.write();
+        let cache = LastCache::new(table_def, definition, self.max_keys.load(Ordering::Relaxed))?;
+        let table_caches = cache_map
+            .entry(db_name.to_string())
+            .or_default()
+            .entry(table_def.name.clone())
+            .or_default();
+        if table_caches.contains_key(&definition.name) {
+            return Err(Error::CacheAlreadyExists {
+                table_name: table_def.name.clone(),
+                cache_name: definition.name.clone(),
+            });
+        }
+        table_caches.insert(definition.name.clone(), cache);
+        Ok(())
+    }
+
+    /// Remove a cache from the provider, returning `true` if the cache existed
+    pub fn delete_cache(&self, db_name: &str, table_name: &str, cache_name: &str) -> bool {
+        self.cache_map
+            .write()
+            .get_mut(db_name)
+            .and_then(|db| db.get_mut(table_name))
+            .and_then(|table| table.remove(cache_name))
+            .is_some()
+    }
+
+    /// Delete all caches for the given database
+    pub(crate) fn delete_caches_for_db(&self, db_name: &str) {
+        self.cache_map.write().remove(db_name);
+    }
+
+    /// Delete all caches for the given table
+    pub(crate) fn delete_caches_for_table(&self, db_name: &str, table_name: &str) {
+        if let Some(db) = self.cache_map.write().get_mut(db_name) {
+            db.remove(table_name);
+        }
+    }
+
+    /// Drop the rows that match a delete from the caches on a table
+    ///
+    /// Rows match if their time is in the range of the `predicate` and they have each of its tag
+    /// values. A tag that isn't one of a cache's columns can't be checked, so all of the rows in
+    /// the time range are dropped from that cache.
+    pub(crate) fn delete_rows(&self, db_name: &str, table_name: &str, predicate: &DeletePredicate) {
+        let mut cache_map = self.cache_map.write();
+        let Some(table_caches) = cache_map
+            .get_mut(db_name)
+            .and_then(|db| db.get_mut(table_name))
+        else {
+            return;
+        };
+        for cache in table_caches.values_mut() {
+            cache.delete_rows(predicate);
+        }
+    }
+
+    /// Drop the rows older than the `cutoff`, in nanoseconds since the epoch, from the caches of a
+    /// database, as they have expired
+    pub(crate) fn remove_expired_rows(&self, db_name: &str, cutoff: i64) {
+        let predicate = DeletePredicate {
+            min_time: i64::MIN,
+            max_time: cutoff.saturating_sub(1),
+            tags: vec![],
+        };
+        let mut cache_map = self.cache_map.write();
+        let Some(db_caches) = cache_map.get_mut(db_name) else {
+            return;
+        };
+        for cache in db_caches
+            .values_mut()
+            .flat_map(|tables| tables.values_mut())
+        {
+            cache.delete_rows(&predicate);
+        }
+    }
+
+    /// Update the caches with rows from a validated write
+    pub(crate) fn write_segmented_data_to_cache(&self, segmented_data: &[ValidSegmentedData]) {
+        let writes = self.cached_rows(segmented_data);
+        self.write_rows_to_cache(writes);
+    }
+
+    /// Copy the rows of a validated write that belong to tables with a cache
+    ///
+    /// The validated write is consumed when it is buffered, so this is used to hold on to the
+    /// rows for the caches until the write has succeeded, when they are passed to
+    /// [`Self::write_rows_to_cache`].
+    pub(crate) fn cached_rows(&self, segmented_data: &[ValidSegmentedData]) -> LastCacheWrites {
+        let cache_map = self.cache_map.read();
+        let mut writes = LastCacheWrites::default();
+        for data in segmented_data {
+            let Some(db_caches) = cache_map.get(data.database_name.as_str()) else {
+                continue;
+            };
+            for (table_name, table_batch) in &data.table_batches {
+                if !db_caches.contains_key(table_name) || table_batch.rows.is_empty() {
+                    continue;
+                }
+                writes.tables.push((
+                    data.database_name.to_string(),
+                    table_name.clone(),
+                    table_batch.rows.clone(),
+                ));
+            }
+        }
+        writes
+    }
+
+    /// Update the caches with the rows of a write that has been buffered
+    pub(crate) fn write_rows_to_cache(&self, writes: LastCacheWrites) {
+        if writes.tables.is_empty() {
+            return;
+        }
+        let mut cache_map = self.cache_map.write();
+        for (db_name, table_name, rows) in writes.tables {
+            let Some(table_caches) = cache_map
+                .get_mut(&db_name)
+                .and_then(|db| db.get_mut(&table_name))
+            else {
+                continue;
+            };
+            for cache in table_caches.values_mut() {
+                for row in &rows {
+                    cache.push(row);
+                }
+            }
+        }
+    }
+
+    /// Returns the database and table names of every table that has a cache
+    pub(crate) fn cached_tables(&self) -> Vec<(String, String)> {
+        self.cache_map
+            .read()
+            .iter()
+            .flat_map(|(db_name, tables)| {
+                tables
+                    .keys()
+                    .map(|table_name| (db_name.clone(), table_name.clone()))
+            })
+            .collect()
+    }
+
+    /// Update the caches on a table with the rows of record batches from the buffer
+    ///
+    /// This is used to rebuild the caches from the data replayed from the WAL when the server
+    /// starts.
+    pub(crate) fn write_batches_to_cache(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        batches: &[RecordBatch],
+    ) -> Result<()> {
+        let mut cache_map = self.cache_map.write();
+        let Some(table_caches) = cache_map
+            .get_mut(db_name)
+            .and_then(|db| db.get_mut(table_name))
+        else {
+            return Ok(());
+        };
+        for batch in batches {
+            let rows = rows_from_batch(batch)?;
+            for cache in table_caches.values_mut() {
+                for row in &rows {
+                    cache.push(row);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Get the schema of a cache
+    ///
+    /// The `cache_name` can be omitted if the table only has a single cache.
+    pub fn get_cache_schema(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        cache_name: Option<&str>,
+    ) -> Result<ArrowSchemaRef> {
+        self.with_cache(db_name, table_name, cache_name, |cache| {
+            Ok(Arc::clone(&cache.schema))
+        })
+    }
+
+    /// Produce the contents of a cache as a [`RecordBatch`]
+    ///
+    /// The `cache_name` can be omitted if the table only has a single cache.
+    pub fn get_cache_record_batch(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        cache_name: Option<&str>,
+    ) -> Result<RecordBatch> {
+        self.with_cache(db_name, table_name, cache_name, LastCache::to_record_batch)
+    }
+
+    fn with_cache<F, R>(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        cache_name: Option<&str>,
+        f: F,
+    ) -> Result<R>
+    where
+        F: FnOnce(&LastCache) -> Result<R>,
+    {
+        let cache_map = self.cache_map.read();
+        let not_found = || Error::CacheNotFound {
+            db_name: db_name.to_string(),
+            table_name: table_name.to_string(),
+        };
+        let table_caches = cache_map
+            .get(db_name)
+            .and_then(|db| db.get(table_name))
+            .ok_or_else(not_found)?;
+        let cache = match cache_name {
+            Some(name) => table_caches.get(name).ok_or_else(not_found)?,
+            None => {
+                if table_caches.len() > 1 {
+                    return Err(Error::CacheNameRequired {
+                        table_name: table_name.to_string(),
+                    });
+                }
+                table_caches.values().next().ok_or_else(not_found)?
+            }
+        };
+        f(cache)
+    }
+}
+
+/// A single last cache on a table
+#[derive(Debug)]
+struct LastCache {
+    /// The number of rows to hold for each unique key
+    count: usize,
+    /// Columns used as the key into the cache, along with their type
+    key_columns: Vec<(String, InfluxColumnType)>,
+    /// Columns whose values are stored in the cache, along with their type
+    ///
+    /// This does not include the `time` column, which is always stored.
+    value_columns: Vec<(String, InfluxColumnType)>,
+    /// The arrow schema of the record batches produced by the cache
+    schema: ArrowSchemaRef,
+    /// The cached rows for each unique key, ordered from most to least recent
+    state: HashMap<Vec<KeyValue>, VecDeque<CachedRow>>,
+    /// The time of the most recent row for each key in `state`, ordered so that the key to
+    /// evict, once there are more than `max_keys`, is first
+    key_times: BTreeSet<(i64, Vec<KeyValue>)>,
+    /// The number of unique keys held by the cache
+    max_keys: usize,
+}
+
+impl LastCache {
+    fn new(
+        table_def: &TableDefinition,
+        definition: &LastCacheDefinition,
+        max_keys: usize,
+    ) -> Result<Self> {
+        let column = |column_name: &str| -> Result<(String, InfluxColumnType, ArrowField)> {
+            let index = table_def.schema.find_index_of(column_name).ok_or_else(|| {
+                Error::ColumnDoesNotExist {
+                    table_name: table_def.name.clone(),
+                    column_name: column_name.to_string(),
+                }
+            })?;
+            let (col_type, field) = table_def.schema.field(index);
+            Ok((
+                column_name.to_string(),
+                col_type,
+                field.clone().with_nullable(true),
+            ))
+        };
+
+        let mut fields = vec![];
+        let mut key_columns = vec![];
+        for column_name in &definition.key_columns {
+            let (name, col_type, field) = column(column_name.as_str())?;
+            if !is_valid_key_column_type(col_type) {
+                return Err(Error::InvalidKeyColumn { column_name: name });
+            }
+            key_columns.push((name, col_type));
+            fields.push(field);
+        }
+        let mut value_columns = vec![];
+        for column_name in &definition.value_columns {
+            if column_name == TIME_COLUMN_NAME
+                || definition.key_columns.contains(column_name)
+                || value_columns.iter().any(|(n, _)| n == column_name)
+            {
+                continue;
+            }
+            let (name, col_type, field) = column(column_name.as_str())?;
+            value_columns.push((name, col_type));
+            fields.push(field);
+        }
+        let (_, _, time_field) = column(TIME_COLUMN_NAME)?;
+        fields.push(time_field);
+
+        Ok(Self {
+            count: definition.count(),
+            key_columns,
+            value_columns,
+            schema: Arc::new(ArrowSchema::new(fields)),
+            state: HashMap::new(),
+            key_times: BTreeSet::new(),
+            max_keys,
+        })
+    }
+
+    /// Push a row into the cache, if it is recent enough to be kept
+    fn push(&mut self, row: &Row) {
+        self.widen_value_columns(row);
+
+        let mut key = Vec::with_capacity(self.key_columns.len());
+        for (name, _) in &self.key_columns {
+            // rows that do not have a value for every key column are not cached
+            let Some(value) = row
+                .fields
+                .iter()
+                .find(|f| &f.name == name)
+                .and_then(|f| KeyValue::from_field_data(&f.value))
+            else {
+                return;
+            };
+            key.push(value);
+        }
+
+        let rows = self.state.entry(key.clone()).or_default();
+        let position = rows
+            .iter()
+            .position(|r| r.time < row.time)
+            .unwrap_or(rows.len());
+        if position >= self.count {
+            return;
+        }
+        if position == 0 {
+            if let Some(newest) = rows.front() {
+                self.key_times.remove(&(newest.time, key.clone()));
+            }
+            self.key_times.insert((row.time, key));
+        }
+        let values = self
+            .value_columns
+            .iter()
+            .map(|(name, _)| {
+                row.fields
+                    .iter()
+                    .find(|f| &f.name == name)
+                    .map(|f| f.value.clone())
+            })
+            .collect();
+        rows.insert(
+            position,
+            CachedRow {
+                time: row.time,
+                values,
+            },
+        );
+        rows.truncate(self.count);
+
+        self.evict_keys();
+    }
+
+    /// Change the number of unique keys held by the cache, evicting keys if it holds more
+    fn set_max_keys(&mut self, max_keys: usize) {
+        self.max_keys = max_keys;
+        self.evict_keys();
+    }
+
+    /// Evict the keys whose most recent row is the oldest until the cache holds at most
+    /// `max_keys` keys
+    fn evict_keys(&mut self) {
+        while self.state.len() > self.max_keys {
+            let Some((_, evicted)) = self.key_times.pop_first() else {
+                break;
+            };
+            self.state.remove(&evicted);
+        }
+    }
+
+    /// Change the type of the integer value columns that the row has a float value for to a
+    /// float, as the field has been widened to a float by the write. The values already in the
+    /// cache are converted to floats when the cache is queried.
+    fn widen_value_columns(&mut self, row: &Row) {
+        for (index, (name, col_type)) in self.value_columns.iter_mut().enumerate() {
+            if !matches!(
+                col_type,
+                InfluxColumnType::Field(InfluxFieldType::Integer | InfluxFieldType::UInteger)
+            ) || !row
+                .fields
+                .iter()
+                .any(|f| &f.name == name && matches!(f.value, FieldData::Float(_)))
+            {
+                continue;
+            }
+            *col_type = InfluxColumnType::Field(InfluxFieldType::Float);
+            let mut fields: Vec<ArrowField> = self
+                .schema
+                .fields()
+                .iter()
+                .map(|field| field.as_ref().clone())
+                .collect();
+            fields[self.key_columns.len() + index] = column_field(name, *col_type);
+            self.schema = Arc::new(ArrowSchema::new(fields));
+        }
+    }
+
+    /// Drop the rows that match a delete, see [`LastCacheProvider::delete_rows`]
+    fn delete_rows(&mut self, predicate: &DeletePredicate) {
+        let Self {
+            key_columns,
+            value_columns,
+            state,
+            key_times,
+            ..
+        } = self;
+        let has_tag_value = |key: &[KeyValue], row: &CachedRow, tag: &str, value: &str| {
+            if let Some(i) = key_columns.iter().position(|(name, _)| name == tag) {
+                return matches!(&key[i], KeyValue::String(v) if v == value);
+            }
+            match value_columns.iter().position(|(name, _)| name == tag) {
+                Some(i) => matches!(
+                    &row.values[i],
+                    Some(FieldData::Tag(v) | FieldData::Key(v)) if v == value
+                ),
+                None => true,
+            }
+        };
+
+        state.retain(|key, rows| {
+            let newest = rows.front().map(|row| row.time);
+            rows.retain(|row| {
+                !(predicate.min_time..=predicate.max_time).contains(&row.time)
+                    || predicate
+                        .tags
+                        .iter()
+                        .any(|(tag, value)| !has_tag_value(key, row, tag, value))
+            });
+            let remaining = rows.front().map(|row| row.time);
+            if remaining != newest {
+                if let Some(time) = newest {
+                    key_times.remove(&(time, key.clone()));
+                }
+                if let Some(time) = remaining {
+                    key_times.insert((time, key.clone()));
+                }
+            }
+            !rows.is_empty()
+        });
+    }
+
+    fn to_record_batch(&self) -> Result<RecordBatch> {
+        let mut key_builders: Vec<ColumnBuilder> = self
+            .key_columns
+            .iter()
+            .map(|(_, t)| ColumnBuilder::new(*t))
+            .collect();
+        let mut value_builders: Vec<ColumnBuilder> = self
+            .value_columns
+            .iter()
+            .map(|(_, t)| ColumnBuilder::new(*t))
+            .collect();
+        let mut time_builder = TimestampNanosecondBuilder::new();
+
+        for (key, rows) in &self.state {
+            for row in rows {
+                for (builder, value) in key_builders.iter_mut().zip(key) {
+                    builder.append(Some(&FieldData::from(value.clone())));
+                }
+                for (builder, value) in value_builders.iter_mut().zip(&row.values) {
+                    builder.append(value.as_ref());
+                }
+                time_builder.append_value(row.time);
+            }
+        }
+
+        let mut columns: Vec<ArrayRef> = key_builders
+            .into_iter()
+            .chain(value_builders)
+            .map(ColumnBuilder::finish)
+            .collect();
+        columns.push(Arc::new(time_builder.finish()));
+
+        Ok(RecordBatch::try_new(Arc::clone(&self.schema), columns)?)
+    }
+}
+
+/// Returns the arrow field for a column of the given type in the record batches produced by a
+/// [`LastCache`]
+fn column_field(name: &str, col_type: InfluxColumnType) -> ArrowField {
+    let mut builder = SchemaBuilder::new();
+    builder.influx_column(name, col_type);
+    let schema = builder
+        .build()
+        .expect("a schema with a single column is valid");
+    schema.field(0).1.clone().with_nullable(true)
+}
+
+fn is_valid_key_column_type(col_type: InfluxColumnType) -> bool {
+    matches!(
+        col_type,
+        InfluxColumnType::Tag
+            | InfluxColumnType::Field(
+                InfluxFieldType::String
+                    | InfluxFieldType::Integer
+                    | InfluxFieldType::UInteger
+                    | InfluxFieldType::Boolean
+            )
+    )
+}
+
+/// A row held in a [`LastCache`]
+#[derive(Debug)]
+struct CachedRow {
+    time: i64,
+    /// Values for the cache's value columns, in the order they are defined in the cache
+    values: Vec<Option<FieldData>>,
+}
+
+/// A hashable value from a key column
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum KeyValue {
+    String(String),
+    Int(i64),
+    UInt(u64),
+    Bool(bool),
+}
+
+impl KeyValue {
+    fn from_field_data(value: &FieldData) -> Option<Self> {
+        match value {
+            FieldData::Key(s) | FieldData::Tag(s) | FieldData::String(s) => {
+                Some(Self::String(s.clone()))
+            }
+            FieldData::Integer(i) => Some(Self::Int(*i)),
+            FieldData::UInteger(u) => Some(Self::UInt(*u)),
+            FieldData::Boolean(b) => Some(Self::Bool(*b)),
+            FieldData::Timestamp(_) | FieldData::Float(_) => None,
+        }
+    }
+}
+
+impl From<KeyValue> for FieldData {
+    fn from(value: KeyValue) -> Self {
+        match value {
+            KeyValue::String(s) => Self::String(s),
+            KeyValue::Int(i) => Self::Integer(i),
+            KeyValue::UInt(u) => Self::UInteger(u),
+            KeyValue::Bool(b) => Self::Boolean(b),
+        }
+    }
+}
+
+/// Rows copied from a validated write, to be pushed into the caches once the write is buffered
+#[derive(Debug, Default)]
+pub(crate) struct LastCacheWrites {
+    /// The rows for each table with a cache, along with the database and table names
+    tables: Vec<(String, String, Vec<Row>)>,
+}
+
+/// Convert the rows of a [`RecordBatch`] from the buffer into [`Row`]s that can be pushed into a
+/// cache, leaving out null values
+fn rows_from_batch(batch: &RecordBatch) -> Result<Vec<Row>> {
+    let schema = batch.schema();
+    let Ok(time_index) = schema.index_of(TIME_COLUMN_NAME) else {
+        return Ok(vec![]);
+    };
+    let times = batch
+        .column(time_index)
+        .as_primitive::<TimestampNanosecondType>();
+
+    let mut rows: Vec<Row> = times
+        .values()
+        .iter()
+        .map(|time| Row {
+            time: *time,
+            fields: vec![],
+        })
+        .collect();
+    for (field, column) in schema.fields().iter().zip(batch.columns()) {
+        if field.name() == TIME_COLUMN_NAME {
+            continue;
+        }
+        let value: Box<dyn Fn(usize) -> FieldData> = match field.data_type() {
+            DataType::Dictionary(_, _) => {
+                let column = cast(column, &DataType::Utf8)?;
+                Box::new(move |i| FieldData::Tag(column.as_string::<i32>().value(i).to_string()))
+            }
+            DataType::Utf8 => {
+                let column = column.as_string::<i32>().clone();
+                Box::new(move |i| FieldData::String(column.value(i).to_string()))
+            }
+            DataType::Int64 => {
+                let column = column.as_primitive::<Int64Type>().clone();
+                Box::new(move |i| FieldData::Integer(column.value(i)))
+            }
+            DataType::UInt64 => {
+                let column = column.as_primitive::<UInt64Type>().clone();
+                Box::new(move |i| FieldData::UInteger(column.value(i)))
+            }
+            DataType::Float64 => {
+                let column = column.as_primitive::<Float64Type>().clone();
+                Box::new(move |i| FieldData::Float(column.value(i)))
+            }
+            DataType::Boolean => {
+                let column = column.as_boolean().clone();
+                Box::new(move |i| FieldData::Boolean(column.value(i)))
+            }
+            _ => continue,
+        };
+        for (i, row) in rows.iter_mut().enumerate() {
+            if column.is_valid(i) {
+                row.fields.push(Field {
+                    name: field.name().clone(),
+                    value: value(i),
+                });
+            }
+        }
+    }
+    Ok(rows)
+}
+
+/// Builds an arrow array for a column in a [`LastCache`]
+#[derive(Debug)]
+enum ColumnBuilder {
+    Tag(StringDictionaryBuilder<Int32Type>),
+    String(StringBuilder),
+    I64(Int64Builder),
+    U64(UInt64Builder),
+    F64(Float64Builder),
+    Bool(BooleanBuilder),
+    Time(TimestampNanosecondBuilder),
+}
+
+impl ColumnBuilder {
+    fn new(col_type: InfluxColumnType) -> Self {
+        match col_type {
+            InfluxColumnType::Tag => Self::Tag(StringDictionaryBuilder::new()),
+            InfluxColumnType::Timestamp => Self::Time(TimestampNanosecondBuilder::new()),
+            InfluxColumnType::Field(InfluxFieldType::String) => Self::String(StringBuilder::new()),
+            InfluxColumnType::Field(InfluxFieldType::Integer) => Self::I64(Int64Builder::new()),
+            InfluxColumnType::Field(InfluxFieldType::UInteger) => Self::U64(UInt64Builder::new()),
+            InfluxColumnType::Field(InfluxFieldType::Float) => Self::F64(Float64Builder::new()),
+            InfluxColumnType::Field(InfluxFieldType::Boolean) => Self::Bool(BooleanBuilder::new()),
+        }
+    }
+
+    /// Append a value to the builder, values that do not match the type of the column are
+    /// appended as null
+    fn append(&mut self, value: Option<&FieldData>) {
+        match (self, value) {
+            (Self::Tag(b), Some(FieldData::Tag(v) | FieldData::Key(v) | FieldData::String(v))) => {
+                b.append_value(v);
+            }
+            (Self::String(b), Some(FieldData::String(v))) => b.append_value(v),
+            (Self::I64(b), Some(FieldData::Integer(v))) => b.append_value(*v),
+            (Self::U64(b), Some(FieldData::UInteger(v))) => b.append_value(*v),
+            (Self::F64(b), Some(FieldData::Float(v))) => b.append_value(*v),
+            (Self::F64(b), Some(FieldData::Integer(v))) => b.append_value(*v as f64),
+            (Self::F64(b), Some(FieldData::UInteger(v))) => b.append_value(*v as f64),
+            (Self::Bool(b), Some(FieldData::Boolean(v))) => b.append_value(*v),
+            (Self::Time(b), Some(FieldData::Timestamp(v))) => b.append_value(*v),
+            (Self::Tag(b), _) => b.append_null(),
+            (Self::String(b), _) => b.append_null(),
+            (Self::I64(b), _) => b.append_null(),
+            (Self::U64(b), _) => b.append_null(),
+            (Self::F64(b), _) => b.append_null(),
+            (Self::Bool(b), _) => b.append_null(),
+            (Self::Time(b), _) => b.append_null(),
+        }
+    }
+
+    fn finish(mut self) -> ArrayRef {
+        match &mut self {
+            Self::Tag(b) => Arc::new(b.finish()),
+            Self::String(b) => Arc::new(b.finish()),
+            Self::I64(b) => Arc::new(b.finish()),
+            Self::U64(b) => Arc::new(b.finish()),
+            Self::F64(b) => Arc::new(b.finish()),
+            Self::Bool(b) => Arc::new(b.finish()),
+            Self::Time(b) => Arc::new(b.finish()),
+        }
+    }
+}
+
+/// The `last_cache` table function, for querying a last cache from SQL
+///
+/// Takes the table name and, optionally, the cache name as arguments. The cache name is only
+/// required if the table has more than one cache.
+#[derive(Debug)]
+pub struct LastCacheFunction {
+    db_name: String,
+    provider: Arc<LastCacheProvider>,
+}
+
+impl LastCacheFunction {
+    pub fn new(db_name: impl Into<String>, provider: Arc<LastCacheProvider>) -> Self {
+        Self {
+            db_name: db_name.into(),
+            provider,
+        }
+    }
+}
+
+impl TableFunctionImpl for LastCacheFunction {
+    fn call(&self, args: &[Expr]) -> DataFusionResult<Arc<dyn TableProvider>> {
+        let (table_name, cache_name) = match args {
+            [Expr::Literal(ScalarValue::Utf8(Some(table_name)))] => (table_name, None),
+            [Expr::Literal(ScalarValue::Utf8(Some(table_name))), Expr::Literal(ScalarValue::Utf8(Some(cache_name)))] => {
+                (table_name, Some(cache_name.as_str()))
+            }
+            _ => {
+                return plan_err!(
+                    "{LAST_CACHE_UDTF_NAME} expects a table name and an optional cache name \
+                    as string arguments"
+                )
+            }
+        };
+        Ok(Arc::new(LastCacheTable {
+            db_name: self.db_name.clone(),
+            table_name: table_name.clone(),
+            cache_name: cache_name.map(ToString::to_string),
+            schema: self
+                .provider
+                .get_cache_schema(&self.db_name, table_name, cache_name)
+                .map_err(|e| DataFusionError::External(Box::new(e)))?,
+            provider: Arc::clone(&self.provider),
+        }))
+    }
+}
+
+/// The [`TableProvider`] produced by the [`LastCacheFunction`]
+#[derive(Debug)]
+struct LastCacheTable {
+    db_name: String,
+    table_name: String,
+    cache_name: Option<String>,
+    schema: ArrowSchemaRef,
+    provider: Arc<LastCacheProvider>,
+}
+
+#[async_trait]
+impl TableProvider for LastCacheTable {
+    fn as_any(&self) -> &dyn Any {
+        self as &dyn Any
+    }
+
+    fn schema(&self) -> ArrowSchemaRef {
+        Arc::clone(&self.schema)
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::Temporary
+    }
+
+    fn supports_filters_pushdown(
+        &self,
+        filters: &[&Expr],
+    ) -> DataFusionResult<Vec<TableProviderFilterPushDown>> {
+        Ok(vec![
+            TableProviderFilterPushDown::Unsupported;
+            filters.len()
+        ])
+    }
+
+    async fn scan(
+        &self,
+        ctx: &SessionState,
+        projection: Option<&Vec<usize>>,
+        filters: &[Expr],
+        limit: Option<usize>,
+    ) -> DataFusionResult<Arc<dyn ExecutionPlan>> {
+        let batch = self
+            .provider
+            .get_cache_record_batch(&self.db_name, &self.table_name, self.cache_name.as_deref())
+            .map_err(|e| DataFusionError::External(Box::new(e)))?;
+        let table = MemTable::try_new(batch.schema(), vec![vec![batch]])?;
+        table.scan(ctx, projection, filters, limit).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_util::assert_batches_sorted_eq;
+    use data_types::NamespaceName;
+    use iox_time::Time;
+
+    use crate::{
+        catalog::{Catalog, LastCacheDefinition},
+        write_buffer::validator::WriteValidator,
+        DeletePredicate, Precision, SegmentDuration,
+    };
+
+    use super::{Error, LastCacheProvider};
+
+    /// Write line protocol through the validator, and push the result into the cache
+    fn write_lp(catalog: &Arc<Catalog>, provider: &LastCacheProvider, lp: &str) {
+        let result =
+            WriteValidator::initialize(NamespaceName::new("foo").unwrap(), Arc::clone(catalog))
+                .unwrap()
+                .v1_parse_lines_and_update_schema(lp, false)
+                .unwrap()
+                .convert_lines_to_buffer(
+                    Time::from_timestamp_nanos(0),
+                    SegmentDuration::new_5m(),
+                    Precision::Nanosecond,
+                );
+        provider.write_segmented_data_to_cache(&result.valid_segmented_data);
+    }
+
+    fn create_cache(
+        catalog: &Catalog,
+        provider: &LastCacheProvider,
+        table_name: &str,
+        definition: LastCacheDefinition,
+    ) -> Result<(), Error> {
+        let db_schema = catalog.db_schema("foo").unwrap();
+        let table_def = db_schema.get_table(table_name).unwrap();
+        provider.create_cache("foo", table_def, &definition)
+    }
+
+    #[test]
+    fn keeps_last_value_per_key() {
+        let catalog = Arc::new(Catalog::new());
+        let provider = LastCacheProvider::new();
+        write_lp(&catalog, &provider, "cpu,host=a,region=us usage=1 1");
+        create_cache(
+            &catalog,
+            &provider,
+            "cpu",
+            LastCacheDefinition::new("cache", ["host"], ["usage"], 1).unwrap(),
+        )
+        .unwrap();
+
+        write_lp(
+            &catalog,
+            &provider,
+            "\
+            cpu,host=a,region=us usage=2 2\n\
+            cpu,host=b,region=us usage=3 3\n\
+            cpu,host=a,region=us usage=4 4\n\
+            cpu,host=b,region=eu usage=5 1",
+        );
+
+        let batch = provider.get_cache_record_batch("foo", "cpu", None).unwrap();
+        assert_batches_sorted_eq!(
+            [
+                "+------+-------+--------------------------------+",
+                "| host | usage | time                           |",
+                "+------+-------+--------------------------------+",
+                "| a    | 4.0   | 1970-01-01T00:00:00.000000004Z |",
+                "| b    | 3.0   | 1970-01-01T00:00:00.000000003Z |",
+                "+------+-------+--------------------------------+",
+            ],
+            &[batch]
+        );
+    }
+
+    #[test]
+    fn keeps_last_n_values_across_multiple_keys() {
+        let catalog = Arc::new(Catalog::new());
+        let provider = LastCacheProvider::new();
+        write_lp(
+            &catalog,
+            &provider,
+            "cpu,host=a,region=us usage=1,up=true 1",
+        );
+        create_cache(
+            &catalog,
+            &provider,
+            "cpu",
+            LastCacheDefinition::new("cache", ["region", "host"], ["usage"], 2).unwrap(),
+        )
+        .unwrap();
+
+        write_lp(
+            &catalog,
+            &provider,
+            "\
+            cpu,host=a,region=us usage=1 1\n\
+            cpu,host=a,region=us usage=3 3\n\
+            cpu,host=a,region=us usage=2 2\n\
+            cpu,host=a,region=eu usage=4 4\n\
+            cpu,region=eu usage=5 5",
+        );
+
+        let batch = provider.get_cache_record_batch("foo", "cpu", None).unwrap();
+        assert_batches_sorted_eq!(
+            [
+                "+--------+------+-------+--------------------------------+",
+                "| region | host | usage | time                           |",
+                "+--------+------+-------+--------------------------------+",
+                "| eu     | a    | 4.0   | 1970-01-01T00:00:00.000000004Z |",
+                "| us     | a    | 2.0   | 1970-01-01T00:00:00.000000002Z |",
+                "| us     | a    | 3.0   | 1970-01-01T00:00:00.000000003Z |",
+                "+--------+------+-------+--------------------------------+",
+            ],
+            &[batch]
+        );
+    }
+
+    #[test]
+    fn evicts_keys_with_least_recent_rows() {
+        let catalog = Arc::new(Catalog::new());
+        let provider = LastCacheProvider::new();
+        provider.set_max_keys(2);
+        write_lp(&catalog, &provider, "cpu,host=a usage=1 1");
+        create_cache(
+            &catalog,
+            &provider,
+            "cpu",
+            LastCacheDefinition::new("cache", ["host"], ["usage"], 1).unwrap(),
+        )
+        .unwrap();
+
+        write_lp(
+            &catalog,
+            &provider,
+            "\
+            cpu,host=a usage=1 1\n\
+            cpu,host=b usage=2 2\n\
+            cpu,host=a usage=3 3\n\
+            cpu,host=c usage=4 4",
+        );
+
+        let batch = provider.get_cache_record_batch("foo", "cpu", None).unwrap();
+        assert_batches_sorted_eq!(
+            [
+                "+------+-------+--------------------------------+",
+                "| host | usage | time                           |",
+                "+------+-------+--------------------------------+",
+                "| a    | 3.0   | 1970-01-01T00:00:00.000000003Z |",
+                "| c    | 4.0   | 1970-01-01T00:00:00.000000004Z |",
+                "+------+-------+--------------------------------+",
+            ],
+            &[batch]
+        );
+
+        // lowering the limit evicts keys from the existing caches:
+        provider.set_max_keys(1);
+        let batch = provider.get_cache_record_batch("foo", "cpu", None).unwrap();
+        assert_batches_sorted_eq!(
+            [
+                "+------+-------+--------------------------------+",
+                "| host | usage | time                           |",
+                "+------+-------+--------------------------------+",
+                "| c    | 4.0   | 1970-01-01T00:00:00.000000004Z |",
+                "+------+-------+--------------------------------+",
+            ],
+            &[batch]
+        );
+    }
+
+    #[test]
+    fn widens_integer_value_columns() {
+        let catalog = Arc::new(Catalog::new());
+        let provider = LastCacheProvider::new();
+        write_lp(&catalog, &provider, "cpu,host=a usage=1i 1");
+        let (sequence, db_schema) = catalog.db_schema_and_sequence("foo").unwrap();
+        let mut db_schema = db_schema.as_ref().clone();
+        db_schema
+            .tables
+            .get_mut("cpu")
+            .unwrap()
+            .widen_numeric_fields = true;
+        catalog
+            .replace_database(sequence, Arc::new(db_schema))
+            .unwrap();
+        create_cache(
+            &catalog,
+            &provider,
+            "cpu",
+            LastCacheDefinition::new("cache", ["host"], ["usage"], 3).unwrap(),
+        )
+        .unwrap();
+
+        write_lp(
+            &catalog,
+            &provider,
+            "\
+            cpu,host=a usage=1i 1\n\
+            cpu,host=a usage=1.5 2\n\
+            cpu,host=a usage=2i 3",
+        );
+
+        let batch = provider.get_cache_record_batch("foo", "cpu", None).unwrap();
+        assert_eq!(
+            &arrow::datatypes::DataType::Float64,
+            batch.schema().field_with_name("usage").unwrap().data_type()
+        );
+        assert_batches_sorted_eq!(
+            [
+                "+------+-------+--------------------------------+",
+                "| host | usage | time                           |",
+                "+------+-------+--------------------------------+",
+                "| a    | 1.0   | 1970-01-01T00:00:00.000000001Z |",
+                "| a    | 1.5   | 1970-01-01T00:00:00.000000002Z |",
+                "| a    | 2.0   | 1970-01-01T00:00:00.000000003Z |",
+                "+------+-------+--------------------------------+",
+            ],
+            &[batch]
+        );
+    }
+
+    #[test]
+    fn deletes_matching_rows() {
+        let catalog = Arc::new(Catalog::new());
+        let provider = LastCacheProvider::new();
+        write_lp(&catalog, &provider, "cpu,host=a,region=us usage=1 1");
+        create_cache(
+            &catalog,
+            &provider,
+            "cpu",
+            LastCacheDefinition::new("cache", ["host"], ["region", "usage"], 2).unwrap(),
+        )
+        .unwrap();
+
+        write_lp(
+            &catalog,
+            &provider,
+            "\
+            cpu,host=a,region=us usage=1 1\n\
+            cpu,host=a,region=eu usage=2 2\n\
+            cpu,host=b,region=us usage=3 3\n\
+            cpu,host=c,region=us usage=4 4",
+        );
+        let delete = |min_time, max_time, tags: &[(&str, &str)]| {
+            provider.delete_rows(
+                "foo",
+                "cpu",
+                &DeletePredicate {
+                    min_time,
+                    max_time,
+                    tags: tags
+                        .iter()
+                        .map(|(t, v)| (t.to_string(), v.to_string()))
+                        .collect(),
+                },
+            )
+        };
+
+        // tags can be matched on key and value columns
+        delete(i64::MIN, i64::MAX, &[("host", "a"), ("region", "eu")]);
+        // rows outside of the time range are kept
+        delete(i64::MIN, 2, &[("host", "b")]);
+        // the rows in the time range are dropped if the cache doesn't hold a tag
+        delete(4, 4, &[("zone", "z")]);
+
+        let batch = provider.get_cache_record_batch("foo", "cpu", None).unwrap();
+        assert_batches_sorted_eq!(
+            [
+                "+------+--------+-------+--------------------------------+",
+                "| host | region | usage | time                           |",
+                "+------+--------+-------+--------------------------------+",
+                "| a    | us     | 1.0   | 1970-01-01T00:00:00.000000001Z |",
+                "| b    | us     | 3.0   | 1970-01-01T00:00:00.000000003Z |",
+                "+------+--------+-------+--------------------------------+",
+            ],
+            &[batch]
+        );
+    }
+
+    #[test]
+    fn removes_expired_rows() {
+        let catalog = Arc::new(Catalog::new());
+        let provider = LastCacheProvider::new();
+        write_lp(&catalog, &provider, "cpu,host=a usage=1 1");
+        create_cache(
+            &catalog,
+            &provider,
+            "cpu",
+            LastCacheDefinition::new("cache", ["host"], ["usage"], 2).unwrap(),
+        )
+        .unwrap();
+
+        write_lp(
+            &catalog,
+            &provider,
+            "\
+            cpu,host=a usage=1 10\n\
+            cpu,host=a usage=2 20\n\
+            cpu,host=b usage=3 15",
+        );
+        provider.remove_expired_rows("bar", 100);
+        provider.remove_expired_rows("foo", 20);
+
+        let batch = provider.get_cache_record_batch("foo", "cpu", None).unwrap();
+        assert_batches_sorted_eq!(
+            [
+                "+------+-------+--------------------------------+",
+                "| host | usage | time                           |",
+                "+------+-------+--------------------------------+",
+                "| a    | 2.0   | 1970-01-01T00:00:00.000000020Z |",
+                "+------+-------+--------------------------------+",
+            ],
+            &[batch]
+        );
+    }
+
+    #[test]
+    fn cache_lookup_errors() {
+        let catalog = Arc::new(Catalog::new());
+        let provider = LastCacheProvider::new();
+        write_lp(&catalog, &provider, "cpu,host=a usage=1 1");
+
+        assert!(matches!(
+            create_cache(
+                &catalog,
+                &provider,
+                "cpu",
+                LastCacheDefinition::new("cache", ["nope"], ["usage"], 1).unwrap(),
+            ),
+            Err(Error::ColumnDoesNotExist { .. })
+        ));
+        assert!(matches!(
+            create_cache(
+                &catalog,
+                &provider,
+                "cpu",
+                LastCacheDefinition::new("cache", ["usage"], ["host"], 1).unwrap(),
+            ),
+            Err(Error::InvalidKeyColumn { .. })
+        ));
+        assert!(matches!(
+            provider.get_cache_record_batch("foo", "cpu", None),
+            Err(Error::CacheNotFound { .. })
+        ));
+
+        for name in ["one", "two"] {
+            create_cache(
+                &catalog,
+                &provider,
+                "cpu",
+                LastCacheDefinition::new(name, ["host"], ["usage"], 1).unwrap(),
+            )
+            .unwrap();
+        }
+        assert!(matches!(
+            provider.get_cache_record_batch("foo", "cpu", None),
+            Err(Error::CacheNameRequired { .. })
+        ));
+        assert!(matches!(
+            create_cache(
+                &catalog,
+                &provider,
+                "cpu",
+                LastCacheDefinition::new("one", ["host"], ["usage"], 1).unwrap(),
+            ),
+            Err(Error::CacheAlreadyExists { .. })
+        ));
+        assert!(provider
+            .get_cache_record_batch("foo", "cpu", Some("two"))
+            .is_ok());
+        assert!(provider.delete_cache("foo", "cpu", "two"));
+        assert!(!provider.delete_cache("foo", "cpu", "two"));
+        assert!(provider.get_cache_record_batch("foo", "cpu", None).is_ok());
+    }
+}
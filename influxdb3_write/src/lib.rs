@@ -9,12 +9,17 @@
 pub mod cache;
 pub mod catalog;
 mod chunk;
+pub mod last_cache;
 pub mod paths;
 pub mod persister;
 pub mod wal;
 pub mod write_buffer;
 
 use crate::paths::{ParquetFilePath, SegmentWalFilePath};
+use arrow::error::ArrowError;
+use arrow::ipc::reader::StreamReader;
+use arrow::ipc::writer::StreamWriter;
+use arrow::record_batch::RecordBatch;
 use async_trait::async_trait;
 use bytes::Bytes;
 use data_types::{NamespaceName, TimestampMinMax};
@@ -26,6 +31,7 @@ use datafusion::prelude::Expr;
 use iox_query::QueryChunk;
 use iox_time::Time;
 use parquet::format::FileMetaData;
+use schema::{InfluxColumnType, InfluxFieldType};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::any::Any;
 use std::collections::HashMap;
@@ -60,7 +66,7 @@ pub enum Error {
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
 
-pub trait WriteBuffer: Bufferer + ChunkContainer {}
+pub trait WriteBuffer: Bufferer + ChunkContainer + DatabaseManager + LastCacheManager {}
 
 /// The buffer is for buffering data in memory before it is persisted to object storage. The buffer is queryable and
 /// aims to use as little memory as possible, converting data into in-memory Parquet data periodically as it arrives
@@ -94,11 +100,48 @@ pub trait Bufferer: Debug + Send + Sync + 'static {
         precision: Precision,
     ) -> write_buffer::Result<BufferedWriteRequest>;
 
+    /// Write rows of JSON, which are converted to line protocol and then validated and written
+    /// like `write_lp`. Errors for invalid rows refer to the rows by their number in the write.
+    async fn write_json(
+        &self,
+        database: NamespaceName<'static>,
+        json: &str,
+        ingest_time: Time,
+        accept_partial: bool,
+        precision: Precision,
+    ) -> write_buffer::Result<BufferedWriteRequest>;
+
+    /// Write Arrow record batches to a table. The schema of the batches is validated against,
+    /// or merged into, the table's definition in the catalog, and the batches are written to
+    /// the WAL in their columnar form and buffered without being converted to line protocol.
+    /// The whole write is rejected if any of its rows are invalid.
+    async fn write_arrow(
+        &self,
+        database: NamespaceName<'static>,
+        table_name: &str,
+        batches: Vec<RecordBatch>,
+        ingest_time: Time,
+    ) -> write_buffer::Result<BufferedWriteRequest>;
+
+    /// Import the rows of a parquet file into a table. The schema of the file is validated
+    /// against, or merged into, the table's definition in the catalog like an Arrow write. The
+    /// rows are sorted, deduplicated and persisted directly to parquet files in a segment of
+    /// their own, so they're queryable once the import is done without going through the buffer.
+    async fn import_parquet(
+        &self,
+        database: NamespaceName<'static>,
+        table_name: &str,
+        parquet: Bytes,
+    ) -> write_buffer::Result<PersistedSegment>;
+
     /// Returns the configured WAL, if there is one.
     fn wal(&self) -> Option<Arc<impl Wal>>;
 
     /// Returns the catalog
     fn catalog(&self) -> Arc<catalog::Catalog>;
+
+    /// Returns the last cache provider
+    fn last_cache_provider(&self) -> Arc<last_cache::LastCacheProvider>;
 }
 
 /// A segment in the buffer that corresponds to a single WAL segment file. It contains a catalog with any updates
@@ -130,6 +173,117 @@ pub trait ChunkContainer: Debug + Send + Sync + 'static {
     ) -> Result<Vec<Arc<dyn QueryChunk>>, DataFusionError>;
 }
 
+/// [`DatabaseManager`] is used to create, delete, and change the settings of databases, and to
+/// delete their tables. Changes are applied to the catalog, which is persisted.
+#[async_trait]
+pub trait DatabaseManager: Debug + Send + Sync + 'static {
+    /// Create a new database, with an optional retention period and overrides of the server's
+    /// catalog limits
+    ///
+    /// If `strict_schema` is set, writes to the database are only accepted for tables and columns
+    /// that have been declared with [`DatabaseManager::create_table`].
+    async fn create_database(
+        &self,
+        db_name: &str,
+        retention_period: Option<Duration>,
+        limits: catalog::DatabaseLimits,
+        strict_schema: bool,
+    ) -> write_buffer::Result<()>;
+
+    /// Set the retention period of an existing database, or remove it if `None` is given
+    async fn set_retention_period(
+        &self,
+        db_name: &str,
+        retention_period: Option<Duration>,
+    ) -> write_buffer::Result<()>;
+
+    /// Set the overrides of the server's catalog limits for an existing database, the default
+    /// [`catalog::DatabaseLimits`] remove any overrides
+    async fn set_database_limits(
+        &self,
+        db_name: &str,
+        limits: catalog::DatabaseLimits,
+    ) -> write_buffer::Result<()>;
+
+    /// Enable or disable the strict schema mode of an existing database
+    async fn set_strict_schema(
+        &self,
+        db_name: &str,
+        strict_schema: bool,
+    ) -> write_buffer::Result<()>;
+
+    /// Declare a new table in an existing database, before any data has been written to it
+    ///
+    /// The table is given the `tags` and typed `fields`, along with a time column. If a
+    /// `series_key` is provided, the table uses the v3 data model. If `widen_numeric_fields` is
+    /// set, see [`DatabaseManager::set_table_field_widening`].
+    async fn create_table(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        tags: Vec<String>,
+        fields: Vec<(String, InfluxFieldType)>,
+        series_key: Option<Vec<String>>,
+        widen_numeric_fields: bool,
+    ) -> write_buffer::Result<()>;
+
+    /// Set whether writes to a table with numeric field values that conflict with the type of the
+    /// field widen the field to a float, instead of being rejected
+    async fn set_table_field_widening(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        widen_numeric_fields: bool,
+    ) -> write_buffer::Result<()>;
+
+    /// Delete a database, dropping its buffered data and removing its persisted files
+    async fn delete_database(&self, db_name: &str) -> write_buffer::Result<()>;
+
+    /// Delete a table from a database, dropping its buffered data and removing its persisted
+    /// files
+    async fn delete_table(&self, db_name: &str, table_name: &str) -> write_buffer::Result<()>;
+
+    /// Delete the rows of a table that match the predicate
+    ///
+    /// Matching rows are dropped from the buffer right away. Persisted files that may contain
+    /// matching rows are given a tombstone, so the rows are filtered out when the files are
+    /// queried, until the files are rewritten without them.
+    async fn delete_rows(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        predicate: DeletePredicate,
+    ) -> write_buffer::Result<()>;
+}
+
+/// [`LastCacheManager`] is used to create and delete last caches on tables. Changes are applied to
+/// the catalog, which is persisted, as well as to the in-memory caches.
+#[async_trait]
+pub trait LastCacheManager: Debug + Send + Sync + 'static {
+    /// Create a new last cache on the given table
+    ///
+    /// Any of the optional arguments that are not provided will be given defaults: the name is
+    /// generated from the table and key column names; the key columns are the table's tags; the
+    /// value columns are all non-key columns in the table; and the count is 1.
+    async fn create_last_cache(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        cache_name: Option<&str>,
+        count: Option<usize>,
+        key_columns: Option<Vec<String>>,
+        value_columns: Option<Vec<String>>,
+    ) -> write_buffer::Result<catalog::LastCacheDefinition>;
+
+    /// Delete the named last cache from the given table
+    async fn delete_last_cache(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        cache_name: &str,
+    ) -> write_buffer::Result<()>;
+}
+
 /// The segment identifier, which will be monotonically increasing.
 #[derive(
     Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
@@ -295,6 +449,10 @@ impl SegmentId {
     pub fn next(&self) -> Self {
         Self(self.0 + 1)
     }
+
+    pub fn as_u32(&self) -> u32 {
+        self.0
+    }
 }
 
 /// The sequence number of a batch of WAL operations.
@@ -311,6 +469,10 @@ impl SequenceNumber {
     pub fn next(&self) -> Self {
         Self(self.0 + 1)
     }
+
+    pub fn as_u32(&self) -> u32 {
+        self.0
+    }
 }
 
 pub const DEFAULT_OBJECT_STORE_URL: &str = "iox://influxdb3/";
@@ -328,6 +490,11 @@ pub trait Persister: Debug + Send + Sync + 'static {
         most_recent_n: usize,
     ) -> Result<Vec<PersistedSegment>, Self::Error>;
 
+    /// Loads every persisted segment parquet file list from object storage, most recent first.
+    async fn load_all_segments(&self) -> Result<Vec<PersistedSegment>, Self::Error> {
+        self.load_segments(usize::MAX).await
+    }
+
     // Loads a Parquet file from ObjectStore
     async fn load_parquet_file(&self, path: ParquetFilePath) -> Result<Bytes, Self::Error>;
 
@@ -391,9 +558,56 @@ pub trait Wal: Debug + Send + Sync + 'static {
     /// Deletes the WAL segment file from disk.
     fn delete_wal_segment(&self, segment_id: SegmentId) -> wal::Result<()>;
 
+    /// Checks that every block in a segment file can be read before it is loaded, recovering
+    /// from a block that was only partially written when the process crashed according to the
+    /// WAL's recovery mode. Returns `false` if the segment should not be loaded.
+    fn recover_segment(&self, segment_id: SegmentId) -> wal::Result<bool>;
+
+    /// How writes are batched into the WAL and when they are fsync'd.
+    fn flush_config(&self) -> wal::WalFlushConfig;
+
     fn as_any(&self) -> &dyn Any;
 }
 
+/// Lets the WAL implementation be chosen at runtime, e.g. from the server's configuration.
+impl Wal for Box<dyn Wal> {
+    fn new_segment_writer(
+        &self,
+        segment_id: SegmentId,
+        range: SegmentRange,
+    ) -> wal::Result<Box<dyn WalSegmentWriter>> {
+        self.as_ref().new_segment_writer(segment_id, range)
+    }
+
+    fn open_segment_writer(&self, segment_id: SegmentId) -> wal::Result<Box<dyn WalSegmentWriter>> {
+        self.as_ref().open_segment_writer(segment_id)
+    }
+
+    fn open_segment_reader(&self, segment_id: SegmentId) -> wal::Result<Box<dyn WalSegmentReader>> {
+        self.as_ref().open_segment_reader(segment_id)
+    }
+
+    fn segment_files(&self) -> wal::Result<Vec<SegmentFile>> {
+        self.as_ref().segment_files()
+    }
+
+    fn delete_wal_segment(&self, segment_id: SegmentId) -> wal::Result<()> {
+        self.as_ref().delete_wal_segment(segment_id)
+    }
+
+    fn recover_segment(&self, segment_id: SegmentId) -> wal::Result<bool> {
+        self.as_ref().recover_segment(segment_id)
+    }
+
+    fn flush_config(&self) -> wal::WalFlushConfig {
+        self.as_ref().flush_config()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self.as_ref().as_any()
+    }
+}
+
 #[derive(Debug)]
 pub struct SegmentFile {
     /// The path to the segment file
@@ -407,8 +621,12 @@ pub trait WalSegmentWriter: Debug + Send + Sync + 'static {
 
     fn bytes_written(&self) -> u64;
 
+    /// Writes the batch to the segment, without waiting for it to be durable.
     fn write_batch(&mut self, ops: Vec<WalOp>) -> wal::Result<()>;
 
+    /// Makes everything written to the segment durable.
+    fn sync(&mut self) -> wal::Result<()>;
+
     fn last_sequence_number(&self) -> SequenceNumber;
 }
 
@@ -435,6 +653,9 @@ pub struct WalOpBatch {
 pub enum WalOp {
     LpWrite(LpWriteOp),
     ParquetWrite(ParquetWriteOp),
+    Catalog(CatalogOp),
+    Delete(DeleteOp),
+    ArrowWrite(ArrowWriteOp),
 }
 
 /// A write of 1 or more lines of line protocol to a single database. The default time is set by the server at the
@@ -447,6 +668,42 @@ pub struct LpWriteOp {
     pub precision: Precision,
 }
 
+/// A write of Arrow record batches to a single table. The rows are kept in their columnar form,
+/// as an Arrow IPC stream, rather than being converted to line protocol.
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct ArrowWriteOp {
+    pub db_name: String,
+    pub table_name: String,
+    /// The time the write was made, which is compared to the time the database or table was
+    /// deleted at when the WAL is replayed
+    pub ingest_time: i64,
+    pub ipc: Vec<u8>,
+}
+
+impl ArrowWriteOp {
+    pub(crate) fn new(
+        db_name: impl Into<String>,
+        table_name: impl Into<String>,
+        ingest_time: i64,
+        batch: &RecordBatch,
+    ) -> Result<Self, ArrowError> {
+        let mut writer = StreamWriter::try_new(vec![], &batch.schema())?;
+        writer.write(batch)?;
+        writer.finish()?;
+        Ok(Self {
+            db_name: db_name.into(),
+            table_name: table_name.into(),
+            ingest_time,
+            ipc: writer.into_inner()?,
+        })
+    }
+
+    /// Decodes the record batches of the write
+    pub fn record_batches(&self) -> Result<Vec<RecordBatch>, ArrowError> {
+        StreamReader::try_new(self.ipc.as_slice(), None)?.collect()
+    }
+}
+
 /// A Parquet file that has been persisted to object storage ahead of a segment being closed.
 #[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
 pub struct ParquetWriteOp {
@@ -457,6 +714,171 @@ pub struct ParquetWriteOp {
     pub row_count: u64,
     pub min_time: i64,
     pub max_time: i64,
+    /// Deletes made while the file was being persisted, whose matching rows it may contain
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tombstones: Vec<DeletePredicate>,
+}
+
+/// A delete of the rows of a table that match a predicate. Rows buffered before the op in the
+/// WAL are dropped when it's replayed.
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct DeleteOp {
+    pub db_name: String,
+    pub table_name: String,
+    pub predicate: DeletePredicate,
+    pub deleted_at_ns: i64,
+}
+
+impl DeleteOp {
+    /// Adds a tombstone for the delete to the files of its table in the `databases` that may
+    /// contain rows that match it, returning true if any were given one
+    pub(crate) fn add_tombstones(&self, databases: &mut HashMap<String, DatabaseTables>) -> bool {
+        databases
+            .get_mut(&self.db_name)
+            .and_then(|db_tables| db_tables.tables.get_mut(&self.table_name))
+            .map(|table| {
+                table.parquet_files.iter_mut().fold(false, |added, file| {
+                    file.add_tombstone(&self.predicate) || added
+                })
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// The rows of a table to delete: those with a time in `min_time..=max_time` that have all of
+/// the given tag values
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct DeletePredicate {
+    pub min_time: i64,
+    pub max_time: i64,
+    /// Pairs of tag name and value, all of which must match for a row to be deleted
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<(String, String)>,
+}
+
+impl DeletePredicate {
+    /// Returns true if rows in the time range from `min_time` to `max_time`, inclusive, may be
+    /// deleted
+    pub fn overlaps(&self, min_time: i64, max_time: i64) -> bool {
+        self.min_time <= max_time && min_time <= self.max_time
+    }
+}
+
+/// A change to the catalog. Catalog ops are written to the WAL ahead of any writes that depend on
+/// them, and are replayed in order when the WAL is loaded, so that the schema is restored without
+/// re-deriving it from the line protocol, and changes that aren't made through writes, like
+/// deletes and last cache definitions, survive a restart.
+///
+/// Replaying an op that is already reflected in the catalog leaves the catalog unchanged.
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub enum CatalogOp {
+    CreateDatabase {
+        db_name: String,
+        settings: catalog::DatabaseSettings,
+    },
+    UpdateDatabase {
+        db_name: String,
+        settings: catalog::DatabaseSettings,
+    },
+    DeleteDatabase {
+        db_name: String,
+        deleted_at_ns: i64,
+    },
+    /// A new table, given with its full definition
+    CreateTable {
+        db_name: String,
+        table: catalog::TableDefinition,
+    },
+    AddColumns {
+        db_name: String,
+        table_name: String,
+        columns: Vec<(String, ColumnDataType)>,
+    },
+    /// Numeric fields that were widened to floats
+    WidenFields {
+        db_name: String,
+        table_name: String,
+        field_names: Vec<String>,
+    },
+    UpdateTable {
+        db_name: String,
+        table_name: String,
+        widen_numeric_fields: bool,
+    },
+    DeleteTable {
+        db_name: String,
+        table_name: String,
+        deleted_at_ns: i64,
+    },
+    DefineLastCache {
+        db_name: String,
+        table_name: String,
+        definition: catalog::LastCacheDefinition,
+    },
+    DeleteLastCache {
+        db_name: String,
+        table_name: String,
+        cache_name: String,
+    },
+}
+
+impl CatalogOp {
+    /// The name of the database the op applies to
+    pub fn db_name(&self) -> &str {
+        match self {
+            Self::CreateDatabase { db_name, .. }
+            | Self::UpdateDatabase { db_name, .. }
+            | Self::DeleteDatabase { db_name, .. }
+            | Self::CreateTable { db_name, .. }
+            | Self::AddColumns { db_name, .. }
+            | Self::WidenFields { db_name, .. }
+            | Self::UpdateTable { db_name, .. }
+            | Self::DeleteTable { db_name, .. }
+            | Self::DefineLastCache { db_name, .. }
+            | Self::DeleteLastCache { db_name, .. } => db_name,
+        }
+    }
+}
+
+/// The type of a column added to a table in a [`CatalogOp::AddColumns`]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ColumnDataType {
+    Tag,
+    Time,
+    String,
+    Integer,
+    UInteger,
+    Float,
+    Boolean,
+}
+
+impl From<InfluxColumnType> for ColumnDataType {
+    fn from(col_type: InfluxColumnType) -> Self {
+        match col_type {
+            InfluxColumnType::Tag => Self::Tag,
+            InfluxColumnType::Timestamp => Self::Time,
+            InfluxColumnType::Field(InfluxFieldType::String) => Self::String,
+            InfluxColumnType::Field(InfluxFieldType::Integer) => Self::Integer,
+            InfluxColumnType::Field(InfluxFieldType::UInteger) => Self::UInteger,
+            InfluxColumnType::Field(InfluxFieldType::Float) => Self::Float,
+            InfluxColumnType::Field(InfluxFieldType::Boolean) => Self::Boolean,
+        }
+    }
+}
+
+impl From<ColumnDataType> for InfluxColumnType {
+    fn from(data_type: ColumnDataType) -> Self {
+        match data_type {
+            ColumnDataType::Tag => Self::Tag,
+            ColumnDataType::Time => Self::Timestamp,
+            ColumnDataType::String => Self::Field(InfluxFieldType::String),
+            ColumnDataType::Integer => Self::Field(InfluxFieldType::Integer),
+            ColumnDataType::UInteger => Self::Field(InfluxFieldType::UInteger),
+            ColumnDataType::Float => Self::Field(InfluxFieldType::Float),
+            ColumnDataType::Boolean => Self::Field(InfluxFieldType::Boolean),
+        }
+    }
 }
 
 /// A single write request can have many lines in it. A writer can request to accept all lines that are valid, while
@@ -529,9 +951,14 @@ pub struct TableParquetFiles {
 pub struct ParquetFile {
     pub path: String,
     pub size_bytes: u64,
+    /// The number of rows written to the file, including any that have since been deleted
     pub row_count: u64,
     pub min_time: i64,
     pub max_time: i64,
+    /// Deletes whose matching rows may still be in the file. They're filtered out when the file
+    /// is queried, until it is rewritten without them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tombstones: Vec<DeletePredicate>,
 }
 
 impl ParquetFile {
@@ -541,6 +968,17 @@ impl ParquetFile {
             max: self.max_time,
         }
     }
+
+    /// Adds a tombstone for the delete if the file may contain rows that match it, returning
+    /// true if it was added
+    pub fn add_tombstone(&mut self, predicate: &DeletePredicate) -> bool {
+        if !predicate.overlaps(self.min_time, self.max_time) || self.tombstones.contains(predicate)
+        {
+            return false;
+        }
+        self.tombstones.push(predicate.clone());
+        true
+    }
 }
 
 /// The precision of the timestamp
@@ -602,6 +1040,21 @@ pub(crate) fn guess_precision(timestamp: i64) -> Precision {
     }
 }
 
+/// Escapes a table name, tag key or value, field key, or string field value for writing in a
+/// line of line protocol. Backslashes are always escaped, along with the `special` characters
+/// for the part of the line: `,` and ` ` in table names, `,`, `=` and ` ` in tag keys and values,
+/// and field keys, and `"` in string field values.
+pub fn escape_lp(s: &str, special: &[char]) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if c == '\\' || special.contains(&c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
 #[cfg(test)]
 mod test_helpers {
     use crate::catalog::Catalog;
@@ -727,6 +1180,32 @@ mod tests {
 
         assert_eq!(expected, actual);
     }
+
+    #[test]
+    fn escaped_lp_parses() {
+        let lp = format!(
+            "{},{}={} {}=\"{}\"",
+            escape_lp(r"c\p u,", &[',', ' ']),
+            escape_lp(r"host\", &[',', '=', ' ']),
+            escape_lp(r"a=b\", &[',', '=', ' ']),
+            escape_lp(r"va lue\", &[',', '=', ' ']),
+            escape_lp(r#"say "hi"\"#, &['"']),
+        );
+        let line = influxdb_line_protocol::parse_lines(&lp)
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(line.series.measurement.to_string(), r"c\p u,");
+        let (key, value) = &line.series.tag_set.as_ref().unwrap()[0];
+        assert_eq!(key.to_string(), r"host\");
+        assert_eq!(value.to_string(), r"a=b\");
+        let (key, value) = &line.field_set[0];
+        assert_eq!(key.to_string(), r"va lue\");
+        assert_eq!(
+            *value,
+            influxdb_line_protocol::FieldValue::String(r#"say "hi"\"#.into())
+        );
+    }
 }
 
 #[cfg(test)]
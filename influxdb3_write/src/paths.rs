@@ -91,6 +91,16 @@ impl ParquetFilePath {
         ));
         Self(path)
     }
+
+    /// The path of the file that replaces the file at `path` when it's rewritten at the given
+    /// time, e.g. to drop deleted rows from it. The new file sits alongside the old one.
+    pub fn new_rewritten(path: &str, rewrite_time_ns: i64) -> Self {
+        let (dir, file_name) = path.rsplit_once('/').unwrap_or(("", path));
+        let stem = file_name.split('.').next().unwrap_or(file_name);
+        Self(ObjPath::from(format!(
+            "{dir}/{stem}.{rewrite_time_ns}.{PARQUET_FILE_EXTENSION}"
+        )))
+    }
 }
 
 impl Deref for ParquetFilePath {
@@ -209,6 +219,21 @@ fn parquet_file_percent_encoded() {
     );
 }
 
+#[test]
+fn parquet_file_path_new_rewritten() {
+    let path = "dbs/my_db/my_table/2038-01-19/4294967295/1.parquet";
+    let rewritten = ParquetFilePath::new_rewritten(path, 10);
+    assert_eq!(
+        *rewritten,
+        ObjPath::from("dbs/my_db/my_table/2038-01-19/4294967295/1.10.parquet")
+    );
+    // rewriting a rewritten file replaces the time rather than appending to it
+    assert_eq!(
+        *ParquetFilePath::new_rewritten(&rewritten.to_string(), 20),
+        ObjPath::from("dbs/my_db/my_table/2038-01-19/4294967295/1.20.parquet")
+    );
+}
+
 #[test]
 fn segment_info_file_path_new() {
     assert_eq!(
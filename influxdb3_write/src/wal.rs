@@ -12,21 +12,32 @@ use crc32fast::Hasher;
 use datafusion::parquet::file::reader::Length;
 use iox_time::Time;
 use observability_deps::tracing::{info, warn};
+use prost::Message;
 use serde::{Deserialize, Serialize};
 use snap::read::FrameDecoder;
 use std::any::Any;
 use std::fmt::Debug;
 use std::{
     fs::{File, OpenOptions},
-    io::{self, BufReader, Cursor, Read, Write},
+    io::{self, BufReader, Cursor, Read, Seek, Write},
     mem,
-    path::PathBuf,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
 };
 use thiserror::Error;
 
+mod object_store_wal;
+mod proto;
+
+pub use object_store_wal::{
+    ObjectStoreWal, ObjectStoreWalSegmentReader, ObjectStoreWalSegmentWriter, WAL_PREFIX,
+};
+
 /// The first bytes written into a segment file to identify it and its version.
 type FileTypeIdentifier = [u8; 8];
-const FILE_TYPE_IDENTIFIER: &[u8] = b"idb3.001";
+const FILE_TYPE_IDENTIFIER_V1: &[u8] = b"idb3.001";
+const FILE_TYPE_IDENTIFIER_V2: &[u8] = b"idb3.002";
 
 #[derive(Debug, Error)]
 pub enum Error {
@@ -42,6 +53,21 @@ pub enum Error {
         source: serde_json::Error,
     },
 
+    #[error("error decoding wal batch from protobuf: {source}")]
+    Protobuf {
+        #[from]
+        source: prost::DecodeError,
+    },
+
+    #[error("invalid wal op: {0}")]
+    InvalidWalOp(String),
+
+    #[error("object store error: {source}")]
+    ObjectStore {
+        #[from]
+        source: object_store::Error,
+    },
+
     #[error("converting u64 to u32: {source}")]
     TryFromU64 {
         #[from]
@@ -90,13 +116,103 @@ pub enum Error {
 
     #[error("open segment limit reached: {0}")]
     OpenSegmentLimitReached(usize),
+
+    #[error("segment {segment_id:?} has {bytes} bytes after its last complete block")]
+    TrailingBytes { segment_id: SegmentId, bytes: u64 },
+
+    #[error("invalid wal recovery mode {0:?}, expected strict, truncate or skip-segment")]
+    InvalidRecoveryMode(String),
+
+    #[error("invalid wal sync mode {0:?}, expected always, interval=<duration> or none")]
+    InvalidSyncMode(String),
 }
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
 
+/// How a segment file with a block that can't be read is handled when the WAL is loaded. This
+/// typically happens to the last block in a segment when the process crashed part way through
+/// writing it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum WalRecoveryMode {
+    /// Fail to load the WAL.
+    Strict,
+    /// Truncate the segment file at the end of its last valid block, dropping everything after
+    /// it, and load the rest of the segment.
+    #[default]
+    Truncate,
+    /// Leave the segment file as is, for inspection, and don't load any of it.
+    SkipSegment,
+}
+
+impl FromStr for WalRecoveryMode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "strict" => Ok(Self::Strict),
+            "truncate" => Ok(Self::Truncate),
+            "skip-segment" => Ok(Self::SkipSegment),
+            _ => Err(Error::InvalidRecoveryMode(s.to_string())),
+        }
+    }
+}
+
+/// When the WAL segment files are fsync'd. Writes are only acknowledged once they are durable
+/// under the mode.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum WalSyncMode {
+    /// Fsync every batch of writes flushed to the WAL before acknowledging it.
+    #[default]
+    Always,
+    /// Fsync at most once per interval. Writes flushed during the interval are acknowledged
+    /// together once the fsync at the end of it completes.
+    Interval(Duration),
+    /// Never fsync, leaving it to the OS. Writes are acknowledged once they are written to the
+    /// segment file.
+    None,
+}
+
+impl FromStr for WalSyncMode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "always" => Ok(Self::Always),
+            "none" => Ok(Self::None),
+            _ => s
+                .strip_prefix("interval=")
+                .and_then(|interval| humantime::parse_duration(interval).ok())
+                .map(Self::Interval)
+                .ok_or_else(|| Error::InvalidSyncMode(s.to_string())),
+        }
+    }
+}
+
+/// How buffered writes are batched and flushed into the WAL.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WalFlushConfig {
+    /// How long writes are buffered before they are flushed to the WAL as a batch
+    pub flush_interval: Duration,
+    /// The number of buffered writes at which they are flushed without waiting for the interval
+    pub max_batch_size: usize,
+    pub sync_mode: WalSyncMode,
+}
+
+impl Default for WalFlushConfig {
+    fn default() -> Self {
+        Self {
+            flush_interval: Duration::from_millis(10),
+            max_batch_size: 10_000,
+            sync_mode: WalSyncMode::default(),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct WalImpl {
     root: PathBuf,
+    recovery_mode: WalRecoveryMode,
+    flush_config: WalFlushConfig,
 }
 
 impl WalImpl {
@@ -112,7 +228,23 @@ impl WalImpl {
             .sync_all()
             .expect("fsync failure");
 
-        Ok(Self { root })
+        Ok(Self {
+            root,
+            recovery_mode: WalRecoveryMode::default(),
+            flush_config: WalFlushConfig::default(),
+        })
+    }
+
+    /// Set how writes are batched into the WAL and when they are fsync'd
+    pub fn with_flush_config(mut self, flush_config: WalFlushConfig) -> Self {
+        self.flush_config = flush_config;
+        self
+    }
+
+    /// Set how segment files with unreadable blocks are handled when they are recovered
+    pub fn with_recovery_mode(mut self, recovery_mode: WalRecoveryMode) -> Self {
+        self.recovery_mode = recovery_mode;
+        self
     }
 
     fn open_segment_reader(&self, segment_id: SegmentId) -> Result<Box<dyn WalSegmentReader>> {
@@ -169,6 +301,63 @@ impl WalImpl {
         std::fs::remove_file(path)?;
         Ok(())
     }
+
+    fn recover_segment(&self, segment_id: SegmentId) -> Result<bool> {
+        let path = SegmentWalFilePath::new(self.root.clone(), segment_id);
+        let file_len = std::fs::metadata(&path)?.len();
+
+        // find the end of the last block that can be read
+        let (valid_len, error) = match WalSegmentReaderImpl::new(self.root.clone(), segment_id) {
+            Ok(mut reader) => {
+                let mut valid_len = reader.offset()?;
+                let error = loop {
+                    match reader.next_batch() {
+                        Ok(Some(_)) => valid_len = reader.offset()?,
+                        Ok(None) if valid_len < file_len => {
+                            // a partial block header reads as the end of the file
+                            break Some(Error::TrailingBytes {
+                                segment_id,
+                                bytes: file_len - valid_len,
+                            });
+                        }
+                        Ok(None) => break None,
+                        Err(e) => break Some(e),
+                    }
+                };
+                (Some(valid_len), error)
+            }
+            Err(e) => (None, Some(e)),
+        };
+
+        let Some(error) = error else {
+            return Ok(true);
+        };
+
+        match (self.recovery_mode, valid_len) {
+            (WalRecoveryMode::Truncate, Some(valid_len)) => {
+                warn!(
+                    ?segment_id,
+                    %error,
+                    bytes_dropped = file_len - valid_len,
+                    "Truncating WAL segment after its last valid block"
+                );
+                let f = OpenOptions::new().write(true).open(&path)?;
+                f.set_len(valid_len)?;
+                f.sync_all().expect("fsync failure");
+                Ok(true)
+            }
+            (WalRecoveryMode::SkipSegment, _) => {
+                warn!(
+                    ?segment_id,
+                    %error,
+                    "Skipping WAL segment with a block that can't be read"
+                );
+                Ok(false)
+            }
+            // there is nothing to truncate to if the header can't be read
+            (WalRecoveryMode::Strict, _) | (WalRecoveryMode::Truncate, None) => Err(error),
+        }
+    }
 }
 
 impl Wal for WalImpl {
@@ -198,11 +387,70 @@ impl Wal for WalImpl {
         self.delete_wal_segment(_segment_id)
     }
 
+    fn recover_segment(&self, segment_id: SegmentId) -> Result<bool> {
+        self.recover_segment(segment_id)
+    }
+
+    fn flush_config(&self) -> WalFlushConfig {
+        self.flush_config
+    }
+
     fn as_any(&self) -> &dyn Any {
         self as &dyn Any
     }
 }
 
+/// The version of a segment file, identified by the first bytes written into it. The version
+/// determines how the `WalOpBatch`es in the file are encoded; the header is JSON in all versions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SegmentFileVersion {
+    /// Batches are encoded as JSON.
+    V1,
+    /// Batches are encoded as protobuf.
+    V2,
+}
+
+impl SegmentFileVersion {
+    /// The version new segment files are written with.
+    pub const CURRENT: Self = Self::V2;
+
+    fn identifier(&self) -> &'static [u8] {
+        match self {
+            Self::V1 => FILE_TYPE_IDENTIFIER_V1,
+            Self::V2 => FILE_TYPE_IDENTIFIER_V2,
+        }
+    }
+
+    fn from_identifier(identifier: &FileTypeIdentifier) -> Option<Self> {
+        [Self::V1, Self::V2]
+            .into_iter()
+            .find(|version| version.identifier() == identifier)
+    }
+
+    /// Encodes a batch into the bytes that are compressed and written as a block in a segment
+    /// file of this version.
+    pub fn encode_batch(&self, batch: &WalOpBatch) -> Result<Vec<u8>> {
+        match self {
+            Self::V1 => Ok(serde_json::to_vec(batch)?),
+            Self::V2 => Ok(proto::WalOpBatch::try_from_batch(batch)?.encode_to_vec()),
+        }
+    }
+
+    /// Decodes a batch from the uncompressed bytes of a block in a segment file of this version.
+    pub fn decode_batch(&self, data: &[u8]) -> Result<WalOpBatch> {
+        match self {
+            Self::V1 => Ok(serde_json::from_slice(data)?),
+            Self::V2 => proto::WalOpBatch::decode(data)?.try_into_batch(),
+        }
+    }
+}
+
+impl std::fmt::Display for SegmentFileVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", String::from_utf8_lossy(self.identifier()))
+    }
+}
+
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct SegmentHeader {
     pub id: SegmentId,
@@ -212,6 +460,7 @@ pub struct SegmentHeader {
 #[derive(Debug)]
 pub struct WalSegmentWriterImpl {
     segment_id: SegmentId,
+    version: SegmentFileVersion,
     f: File,
     bytes_written: usize,
     sequence_number: SequenceNumber,
@@ -221,6 +470,15 @@ pub struct WalSegmentWriterImpl {
 
 impl WalSegmentWriterImpl {
     pub fn new(root: PathBuf, segment_id: SegmentId, range: SegmentRange) -> Result<Self> {
+        Self::new_with_version(root, segment_id, range, SegmentFileVersion::CURRENT)
+    }
+
+    fn new_with_version(
+        root: PathBuf,
+        segment_id: SegmentId,
+        range: SegmentRange,
+        version: SegmentFileVersion,
+    ) -> Result<Self> {
         let path = SegmentWalFilePath::new(root, segment_id);
 
         // if there's already a file there, error out
@@ -235,29 +493,20 @@ impl WalSegmentWriterImpl {
             .truncate(true)
             .open(&path)?;
 
-        f.write_all(FILE_TYPE_IDENTIFIER)?;
-        let file_type_bytes_written = FILE_TYPE_IDENTIFIER.len();
-
         let header = SegmentHeader {
             id: segment_id,
             range,
         };
-
-        let header_bytes = serde_json::to_vec(&header)?;
-        f.write_u16::<BigEndian>(
-            header_bytes
-                .len()
-                .try_into()
-                .expect("header byes longer than u16"),
-        )?;
+        let header_bytes = encode_header(version, &header)?;
         f.write_all(&header_bytes)?;
 
         f.sync_all().expect("fsync failure");
 
-        let bytes_written = file_type_bytes_written + header_bytes.len();
+        let bytes_written = header_bytes.len();
 
         Ok(Self {
             segment_id,
+            version,
             f,
             bytes_written,
             sequence_number: SequenceNumber::new(0),
@@ -272,8 +521,10 @@ impl WalSegmentWriterImpl {
         {
             let f = OpenOptions::new().append(true).open(&path)?;
 
+            // keep appending in the encoding of the existing file so it remains readable
             Ok(Self {
                 segment_id,
+                version: file_info.version,
                 f,
                 bytes_written: file_info
                     .bytes_written
@@ -298,7 +549,7 @@ impl WalSegmentWriterImpl {
             ops,
         };
 
-        let data = serde_json::to_vec(&batch)?;
+        let data = self.version.encode_batch(&batch)?;
 
         let bytes_written = self.write_bytes(data)?;
 
@@ -309,46 +560,17 @@ impl WalSegmentWriterImpl {
     }
 
     fn write_bytes(&mut self, data: Vec<u8>) -> Result<usize> {
-        // Only designed to support chunks up to `u32::max` bytes long.
-        let uncompressed_len = data.len();
-        u32::try_from(uncompressed_len)?;
-
-        // The chunk header is two u32 values, so write a dummy u64 value and
-        // come back to fill them in later.
-        self.buffer
-            .write_u64::<BigEndian>(0)
-            .expect("cannot fail to write to buffer");
-
-        // Compress the payload into the reused buffer, recording the crc hash
-        // as it is written.
-        let mut encoder = snap::write::FrameEncoder::new(HasherWrapper::new(&mut self.buffer));
-        encoder.write_all(&data)?;
-        let (checksum, buf) = encoder
-            .into_inner()
-            .expect("cannot fail to flush to a Vec")
-            .finalize();
-
-        // Adjust the compressed length to take into account the u64 padding
-        // above.
-        let compressed_len = buf.len() - mem::size_of::<u64>();
-        let compressed_len = u32::try_from(compressed_len)?;
-
-        // Go back and write the chunk header values
-        let mut buf = Cursor::new(buf);
-        buf.set_position(0);
-
-        buf.write_u32::<BigEndian>(checksum)?;
-        buf.write_u32::<BigEndian>(compressed_len)?;
+        encode_block(&mut self.buffer, &data)?;
 
         // Write the entire buffer to the file
-        let buf = buf.into_inner();
-        let bytes_written = buf.len();
-        self.f.write_all(buf)?;
+        self.f.write_all(&self.buffer)?;
 
-        // fsync the fd
-        self.f.sync_all().expect("fsync failure");
+        Ok(self.buffer.len())
+    }
 
-        Ok(bytes_written)
+    fn sync(&mut self) -> Result<()> {
+        self.f.sync_all().expect("fsync failure");
+        Ok(())
     }
 }
 
@@ -366,6 +588,10 @@ impl WalSegmentWriter for WalSegmentWriterImpl {
         self.write_batch(ops)
     }
 
+    fn sync(&mut self) -> Result<()> {
+        self.sync()
+    }
+
     fn last_sequence_number(&self) -> SequenceNumber {
         self.sequence_number
     }
@@ -404,6 +630,10 @@ impl WalSegmentWriter for WalSegmentWriterNoopImpl {
         Ok(())
     }
 
+    fn sync(&mut self) -> Result<()> {
+        Ok(())
+    }
+
     fn last_sequence_number(&self) -> SequenceNumber {
         self.sequence_number
     }
@@ -413,6 +643,7 @@ impl WalSegmentWriter for WalSegmentWriterNoopImpl {
 pub struct WalSegmentReaderImpl {
     f: BufReader<File>,
     path: SegmentWalFilePath,
+    version: SegmentFileVersion,
     segment_header: SegmentHeader,
 }
 
@@ -421,7 +652,7 @@ impl WalSegmentReaderImpl {
         let path = SegmentWalFilePath::new(root, segment_id);
         let mut f = BufReader::new(File::open(path.clone())?);
 
-        let segment_header = read_header(&path, &mut f)?;
+        let (version, segment_header) = read_header(&path, &mut f)?;
 
         if segment_id != segment_header.id {
             return Err(Error::InvalidSegmentFile {
@@ -436,6 +667,7 @@ impl WalSegmentReaderImpl {
         let reader = Self {
             f,
             path,
+            version,
             segment_header,
         };
 
@@ -454,11 +686,12 @@ impl WalSegmentReaderImpl {
         let bytes_written = f.len().try_into()?;
 
         let mut f = BufReader::new(f);
-        let segment_header = read_header(&path, &mut f)?;
+        let (version, segment_header) = read_header(&path, &mut f)?;
 
         let mut reader = Self {
             f,
             path,
+            version,
             segment_header,
         };
 
@@ -469,23 +702,35 @@ impl WalSegmentReaderImpl {
         }
 
         if let Some(block) = last_block {
-            let batch: WalOpBatch = serde_json::from_slice(&block)?;
+            let batch = version.decode_batch(&block)?;
 
             Ok(Some(ExistingSegmentFileInfo {
+                version,
                 last_sequence_number: batch.sequence_number,
                 bytes_written,
             }))
         } else {
             Ok(Some(ExistingSegmentFileInfo {
+                version,
                 last_sequence_number: SequenceNumber::new(0),
                 bytes_written,
             }))
         }
     }
 
+    /// The version of the segment file being read.
+    pub fn version(&self) -> SegmentFileVersion {
+        self.version
+    }
+
+    /// The byte offset in the segment file of the next block to be read.
+    pub fn offset(&mut self) -> Result<u64> {
+        Ok(self.f.stream_position()?)
+    }
+
     pub fn next_batch(&mut self) -> Result<Option<WalOpBatch>> {
         if let Some(data) = self.next_segment_block()? {
-            let batch: WalOpBatch = serde_json::from_slice(&data)?;
+            let batch = self.version.decode_batch(&data)?;
 
             Ok(Some(batch))
         } else {
@@ -494,72 +739,134 @@ impl WalSegmentReaderImpl {
     }
 
     fn next_segment_block(&mut self) -> Result<Option<Vec<u8>>> {
-        let expected_checksum = match self.f.read_u32::<BigEndian>() {
-            Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
-            other => other?,
-        };
+        read_block(&mut self.f, self.segment_header.id)
+    }
+}
 
-        let expected_len: u32 = self.f.read_u32::<BigEndian>()?;
+/// Encodes the bytes written at the start of a segment: the file type identifier, followed by
+/// the length of the JSON encoded header and the header itself.
+fn encode_header(version: SegmentFileVersion, header: &SegmentHeader) -> Result<Vec<u8>> {
+    let header_bytes = serde_json::to_vec(header)?;
+    let mut buf = Vec::with_capacity(version.identifier().len() + 2 + header_bytes.len());
+    buf.extend_from_slice(version.identifier());
+    buf.write_u16::<BigEndian>(
+        header_bytes
+            .len()
+            .try_into()
+            .expect("header byes longer than u16"),
+    )?;
+    buf.extend_from_slice(&header_bytes);
+    Ok(buf)
+}
 
-        let compressed_read = self.f.by_ref().take(expected_len.into());
-        let hashing_read = CrcReader::new(compressed_read);
-        let mut decompressing_read = FrameDecoder::new(hashing_read);
+/// Encodes a block of segment data into the (cleared) buffer: its crc checksum and compressed
+/// length, followed by the snappy compressed data.
+fn encode_block(buffer: &mut Vec<u8>, data: &[u8]) -> Result<()> {
+    // Only designed to support chunks up to `u32::max` bytes long.
+    let uncompressed_len = data.len();
+    u32::try_from(uncompressed_len)?;
+
+    // Ensure the buffer is always empty before using it.
+    buffer.clear();
+
+    // The chunk header is two u32 values, so write a dummy u64 value and
+    // come back to fill them in later.
+    buffer
+        .write_u64::<BigEndian>(0)
+        .expect("cannot fail to write to buffer");
+
+    // Compress the payload into the reused buffer, recording the crc hash
+    // as it is written.
+    let mut encoder = snap::write::FrameEncoder::new(HasherWrapper::new(buffer));
+    encoder.write_all(data)?;
+    let (checksum, buf) = encoder
+        .into_inner()
+        .expect("cannot fail to flush to a Vec")
+        .finalize();
+
+    // Adjust the compressed length to take into account the u64 padding
+    // above.
+    let compressed_len = buf.len() - mem::size_of::<u64>();
+    let compressed_len = u32::try_from(compressed_len)?;
+
+    // Go back and write the chunk header values
+    let mut buf = Cursor::new(buf);
+    buf.set_position(0);
+
+    buf.write_u32::<BigEndian>(checksum)?;
+    buf.write_u32::<BigEndian>(compressed_len)?;
+
+    Ok(())
+}
 
-        let mut data = Vec::with_capacity(100);
-        decompressing_read.read_to_end(&mut data)?;
+/// Reads the next block of segment data, returning `None` at the end of the segment.
+fn read_block<R: Read>(f: &mut R, segment_id: SegmentId) -> Result<Option<Vec<u8>>> {
+    let expected_checksum = match f.read_u32::<BigEndian>() {
+        Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        other => other?,
+    };
 
-        let (actual_compressed_len, actual_checksum) = decompressing_read.into_inner().checksum();
-        let actual_compressed_len = u32::try_from(actual_compressed_len)
-            .expect("segment blocks are only designed to support chunks up to u32::max bytes long");
+    let expected_len: u32 = f.read_u32::<BigEndian>()?;
 
-        if expected_len != actual_compressed_len {
-            return Err(Error::LengthMismatch {
-                segment_id: self.segment_header.id,
-                expected: expected_len,
-                actual: actual_compressed_len,
-            });
-        }
+    let compressed_read = f.by_ref().take(expected_len.into());
+    let hashing_read = CrcReader::new(compressed_read);
+    let mut decompressing_read = FrameDecoder::new(hashing_read);
 
-        if expected_checksum != actual_checksum {
-            return Err(Error::ChecksumMismatch {
-                segment_id: self.segment_header.id,
-                expected: expected_checksum,
-                actual: actual_checksum,
-            });
-        }
+    let mut data = Vec::with_capacity(100);
+    decompressing_read.read_to_end(&mut data)?;
+
+    let (actual_compressed_len, actual_checksum) = decompressing_read.into_inner().checksum();
+    let actual_compressed_len = u32::try_from(actual_compressed_len)
+        .expect("segment blocks are only designed to support chunks up to u32::max bytes long");
 
-        Ok(Some(data))
+    if expected_len != actual_compressed_len {
+        return Err(Error::LengthMismatch {
+            segment_id,
+            expected: expected_len,
+            actual: actual_compressed_len,
+        });
     }
+
+    if expected_checksum != actual_checksum {
+        return Err(Error::ChecksumMismatch {
+            segment_id,
+            expected: expected_checksum,
+            actual: actual_checksum,
+        });
+    }
+
+    Ok(Some(data))
 }
 
-fn read_header(path: &SegmentWalFilePath, f: &mut BufReader<File>) -> Result<SegmentHeader> {
+fn read_header<R: Read>(path: &Path, f: &mut R) -> Result<(SegmentFileVersion, SegmentHeader)> {
     let file_type: FileTypeIdentifier = read_array(f)?;
 
-    if file_type != FILE_TYPE_IDENTIFIER {
+    let Some(version) = SegmentFileVersion::from_identifier(&file_type) else {
         return Err(Error::InvalidSegmentFile {
             path: path.to_path_buf(),
             reason: format!(
-                "expected file type identifier {:?}, got {:?}",
-                FILE_TYPE_IDENTIFIER, file_type
+                "expected file type identifier {:?} or {:?}, got {:?}",
+                FILE_TYPE_IDENTIFIER_V1, FILE_TYPE_IDENTIFIER_V2, file_type
             ),
         });
-    }
+    };
 
     let len = f.read_u16::<BigEndian>()?;
     let mut data = vec![0u8; len.into()];
     f.read_exact(&mut data)?;
     let header: SegmentHeader = serde_json::from_slice(&data)?;
 
-    Ok(header)
+    Ok((version, header))
 }
 
-fn read_array<const N: usize>(f: &mut BufReader<File>) -> Result<[u8; N]> {
+fn read_array<const N: usize, R: Read>(f: &mut R) -> Result<[u8; N]> {
     let mut data = [0u8; N];
     f.read_exact(&mut data)?;
     Ok(data)
 }
 
 struct ExistingSegmentFileInfo {
+    version: SegmentFileVersion,
     last_sequence_number: SequenceNumber,
     bytes_written: u32,
 }
@@ -661,9 +968,10 @@ fn segment_id_from_file_name(name: &str) -> Result<SegmentId> {
 mod tests {
     use super::*;
     use crate::catalog::Catalog;
-    use crate::LpWriteOp;
     use crate::Precision;
+    use crate::{ArrowWriteOp, CatalogOp, DeleteOp, DeletePredicate, LpWriteOp, ParquetWriteOp};
     use arrow::record_batch::RecordBatch;
+    use std::path::Path;
     use std::sync::Arc;
 
     #[test]
@@ -728,6 +1036,264 @@ mod tests {
         assert_eq!(batch.sequence_number, SequenceNumber::new(2));
     }
 
+    #[test]
+    fn segment_file_versions_round_trip_all_ops() {
+        let wal_ops = vec![
+            WalOp::Catalog(CatalogOp::DeleteTable {
+                db_name: "foo".to_string(),
+                table_name: "mem".to_string(),
+                deleted_at_ns: 5,
+            }),
+            WalOp::LpWrite(LpWriteOp {
+                db_name: "foo".to_string(),
+                lp: "cpu host=a val=10i 10".to_string(),
+                default_time: 1,
+                precision: Precision::Millisecond,
+            }),
+            WalOp::ParquetWrite(ParquetWriteOp {
+                db_name: "foo".to_string(),
+                table_name: "cpu".to_string(),
+                path: "dbs/foo/cpu/1.parquet".to_string(),
+                size_bytes: 100,
+                row_count: 3,
+                min_time: -10,
+                max_time: 10,
+                tombstones: vec![DeletePredicate {
+                    min_time: 0,
+                    max_time: 5,
+                    tags: vec![],
+                }],
+            }),
+            WalOp::Delete(DeleteOp {
+                db_name: "foo".to_string(),
+                table_name: "cpu".to_string(),
+                predicate: DeletePredicate {
+                    min_time: -5,
+                    max_time: 5,
+                    tags: vec![("host".to_string(), "a".to_string())],
+                },
+                deleted_at_ns: 6,
+            }),
+            WalOp::ArrowWrite(ArrowWriteOp {
+                db_name: "foo".to_string(),
+                table_name: "cpu".to_string(),
+                ingest_time: 7,
+                ipc: vec![0, 1, 2, 255],
+            }),
+        ];
+
+        for version in [SegmentFileVersion::V1, SegmentFileVersion::V2] {
+            let dir = test_helpers::tmp_dir().unwrap().into_path();
+            let mut writer = WalSegmentWriterImpl::new_with_version(
+                dir.clone(),
+                SegmentId::new(0),
+                SegmentRange::test_range(),
+                version,
+            )
+            .unwrap();
+            writer.write_batch(wal_ops.clone()).unwrap();
+
+            let mut reader = WalSegmentReaderImpl::new(dir, SegmentId::new(0)).unwrap();
+            assert_eq!(reader.version, version);
+            let batch = reader.next_batch().unwrap().unwrap();
+            assert_eq!(batch.ops, wal_ops);
+            assert_eq!(batch.sequence_number, SequenceNumber::new(1));
+        }
+    }
+
+    #[test]
+    fn segment_writer_appends_to_v1_segment_in_v1_format() {
+        let dir = test_helpers::tmp_dir().unwrap().into_path();
+        let wal_op = WalOp::LpWrite(LpWriteOp {
+            db_name: "foo".to_string(),
+            lp: "cpu host=a val=10i 10".to_string(),
+            default_time: 1,
+            precision: Precision::Nanosecond,
+        });
+
+        // write a segment file the way earlier versions did
+        {
+            let mut writer = WalSegmentWriterImpl::new_with_version(
+                dir.clone(),
+                SegmentId::new(0),
+                SegmentRange::test_range(),
+                SegmentFileVersion::V1,
+            )
+            .unwrap();
+            writer.write_batch(vec![wal_op.clone()]).unwrap();
+        }
+        let path = SegmentWalFilePath::new(dir.clone(), SegmentId::new(0));
+        assert!(std::fs::read(&path).unwrap().starts_with(b"idb3.001"));
+
+        // reopening it keeps the v1 encoding so the whole file can still be read
+        {
+            let mut writer = WalSegmentWriterImpl::open(dir.clone(), SegmentId::new(0)).unwrap();
+            assert_eq!(writer.version, SegmentFileVersion::V1);
+            writer.write_batch(vec![wal_op.clone()]).unwrap();
+        }
+
+        let mut reader = WalSegmentReaderImpl::new(dir.clone(), SegmentId::new(0)).unwrap();
+        for sequence_number in [1, 2] {
+            let batch = reader.next_batch().unwrap().unwrap();
+            assert_eq!(batch.ops, vec![wal_op.clone()]);
+            assert_eq!(batch.sequence_number, SequenceNumber::new(sequence_number));
+        }
+        assert!(reader.next_batch().unwrap().is_none());
+
+        // new segments are written with the current version
+        WalSegmentWriterImpl::new(dir.clone(), SegmentId::new(1), SegmentRange::test_range())
+            .unwrap();
+        let path = SegmentWalFilePath::new(dir, SegmentId::new(1));
+        assert!(std::fs::read(&path).unwrap().starts_with(b"idb3.002"));
+    }
+
+    fn lp_write_op(lp: &str) -> WalOp {
+        WalOp::LpWrite(LpWriteOp {
+            db_name: "foo".to_string(),
+            lp: lp.to_string(),
+            default_time: 1,
+            precision: Precision::Nanosecond,
+        })
+    }
+
+    /// Writes a segment with three batches, returning the contents of the file and the offset
+    /// at which each block starts and ends.
+    fn write_segment_for_recovery(dir: &Path) -> (Vec<u8>, Vec<u64>) {
+        let mut writer = WalSegmentWriterImpl::new(
+            dir.to_path_buf(),
+            SegmentId::new(0),
+            SegmentRange::test_range(),
+        )
+        .unwrap();
+        for i in 0..3 {
+            writer
+                .write_batch(vec![lp_write_op(&format!("cpu host=a val={i}i {i}"))])
+                .unwrap();
+        }
+
+        let mut reader = WalSegmentReaderImpl::new(dir, SegmentId::new(0)).unwrap();
+        let mut block_boundaries = vec![reader.offset().unwrap()];
+        while reader.next_batch().unwrap().is_some() {
+            block_boundaries.push(reader.offset().unwrap());
+        }
+        let path = SegmentWalFilePath::new(dir, SegmentId::new(0));
+        (std::fs::read(path).unwrap(), block_boundaries)
+    }
+
+    fn read_all_batches(dir: &Path) -> Vec<WalOpBatch> {
+        let mut reader = WalSegmentReaderImpl::new(dir, SegmentId::new(0)).unwrap();
+        let mut batches = vec![];
+        while let Some(batch) = reader.next_batch().unwrap() {
+            batches.push(batch);
+        }
+        batches
+    }
+
+    #[test]
+    fn recover_segment_truncates_at_last_valid_block() {
+        let dir = test_helpers::tmp_dir().unwrap().into_path();
+        let (contents, block_boundaries) = write_segment_for_recovery(&dir);
+        let path = SegmentWalFilePath::new(dir.clone(), SegmentId::new(0));
+        let wal = WalImpl::new(dir.clone()).unwrap();
+        let header_len = block_boundaries[0] as usize;
+
+        // an intact segment is left alone
+        assert!(wal.recover_segment(SegmentId::new(0)).unwrap());
+        assert_eq!(std::fs::read(&path).unwrap(), contents);
+
+        // tear off the tail, or corrupt a byte, at every offset within the blocks
+        for offset in header_len..contents.len() {
+            let torn = contents[..offset].to_vec();
+            let mut flipped = contents.clone();
+            flipped[offset] ^= 0xff;
+
+            for corrupted in [torn, flipped] {
+                std::fs::write(&path, corrupted).unwrap();
+                assert!(wal.recover_segment(SegmentId::new(0)).unwrap());
+
+                // the segment is truncated at the start of the block containing the offset
+                let valid_blocks = block_boundaries
+                    .iter()
+                    .filter(|b| **b <= offset as u64)
+                    .count()
+                    - 1;
+                assert_eq!(
+                    std::fs::read(&path).unwrap(),
+                    contents[..block_boundaries[valid_blocks] as usize],
+                    "offset {offset}"
+                );
+                assert_eq!(read_all_batches(&dir).len(), valid_blocks);
+
+                // and new writes can be appended and read back
+                let mut writer =
+                    WalSegmentWriterImpl::open(dir.clone(), SegmentId::new(0)).unwrap();
+                writer
+                    .write_batch(vec![lp_write_op("cpu host=b val=1i 1")])
+                    .unwrap();
+                let batches = read_all_batches(&dir);
+                assert_eq!(batches.len(), valid_blocks + 1, "offset {offset}");
+                assert_eq!(
+                    batches.last().unwrap().ops,
+                    vec![lp_write_op("cpu host=b val=1i 1")]
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn recover_segment_strict_and_skip_segment() {
+        let dir = test_helpers::tmp_dir().unwrap().into_path();
+        let (contents, block_boundaries) = write_segment_for_recovery(&dir);
+        let path = SegmentWalFilePath::new(dir.clone(), SegmentId::new(0));
+        let torn = contents[..block_boundaries[2] as usize + 5].to_vec();
+        std::fs::write(&path, &torn).unwrap();
+
+        let strict = WalImpl::new(dir.clone())
+            .unwrap()
+            .with_recovery_mode(WalRecoveryMode::Strict);
+        assert!(matches!(
+            strict.recover_segment(SegmentId::new(0)),
+            Err(Error::Io { .. })
+        ));
+        assert_eq!(std::fs::read(&path).unwrap(), torn);
+
+        let skip = WalImpl::new(dir.clone())
+            .unwrap()
+            .with_recovery_mode(WalRecoveryMode::SkipSegment);
+        assert!(!skip.recover_segment(SegmentId::new(0)).unwrap());
+        assert_eq!(std::fs::read(&path).unwrap(), torn);
+
+        // a partial block header reads as the end of the file, but is still detected
+        let torn = contents[..block_boundaries[2] as usize + 2].to_vec();
+        std::fs::write(&path, &torn).unwrap();
+        assert!(matches!(
+            strict.recover_segment(SegmentId::new(0)),
+            Err(Error::TrailingBytes { bytes: 2, .. })
+        ));
+
+        assert_eq!(
+            "skip-segment".parse::<WalRecoveryMode>().unwrap(),
+            WalRecoveryMode::SkipSegment
+        );
+        assert!("lenient".parse::<WalRecoveryMode>().is_err());
+    }
+
+    #[test]
+    fn wal_sync_mode_from_str() {
+        assert_eq!(
+            "always".parse::<WalSyncMode>().unwrap(),
+            WalSyncMode::Always
+        );
+        assert_eq!("none".parse::<WalSyncMode>().unwrap(), WalSyncMode::None);
+        assert_eq!(
+            "interval=250ms".parse::<WalSyncMode>().unwrap(),
+            WalSyncMode::Interval(Duration::from_millis(250))
+        );
+        for invalid in ["interval", "interval=", "interval=soon", "sometimes"] {
+            assert!(invalid.parse::<WalSyncMode>().is_err(), "{invalid}");
+        }
+    }
+
     #[test]
     fn wal_can_open_write_and_read_segments() {
         let dir = test_helpers::tmp_dir().unwrap().into_path();
@@ -0,0 +1,603 @@
+//! A `Wal` that writes its segments as objects through an `ObjectStore`, so that the WAL can be
+//! replayed from the object store alone, without a local disk.
+//!
+//! Each segment is a prefix under `wal/` named like the segment file written by `WalImpl`, e.g.
+//! `wal/0000000001.wal/`. The prefix holds a `header` object, with the same contents as the start
+//! of a segment file, and one `{sequence_number}.chunk` object per batch, each holding a single
+//! block encoded exactly as in a segment file.
+
+use super::{
+    encode_block, encode_header, read_block, read_header, segment_id_from_file_name, Error, Result,
+    SegmentFileVersion, SegmentHeader, WalFlushConfig,
+};
+use crate::paths::{SegmentWalFilePath, SEGMENT_WAL_FILE_EXTENSION};
+use crate::{
+    SegmentFile, SegmentId, SegmentRange, SequenceNumber, Wal, WalOp, WalOpBatch, WalSegmentReader,
+    WalSegmentWriter,
+};
+use bytes::Bytes;
+use futures_util::TryStreamExt;
+use object_store::path::Path as ObjPath;
+use object_store::{ObjectMeta, ObjectStore};
+use observability_deps::tracing::{info, warn};
+use std::any::Any;
+use std::collections::BTreeSet;
+use std::future::Future;
+use std::sync::Arc;
+
+/// The prefix in the object store under which the segments are written
+pub const WAL_PREFIX: &str = "wal";
+
+const HEADER_OBJECT_NAME: &str = "header";
+const CHUNK_EXTENSION: &str = "chunk";
+
+#[derive(Debug)]
+pub struct ObjectStoreWal {
+    io: Arc<ObjectStoreIo>,
+    flush_config: WalFlushConfig,
+}
+
+impl ObjectStoreWal {
+    pub fn new(object_store: Arc<dyn ObjectStore>) -> Result<Self> {
+        info!(prefix = WAL_PREFIX, "Writing WAL to object store");
+        Ok(Self {
+            io: Arc::new(ObjectStoreIo::new(object_store)?),
+            flush_config: WalFlushConfig::default(),
+        })
+    }
+
+    /// Set how writes are batched into the WAL. Every batch is durable once its chunk has been
+    /// put, so the sync mode has no effect.
+    pub fn with_flush_config(mut self, flush_config: WalFlushConfig) -> Self {
+        self.flush_config = flush_config;
+        self
+    }
+
+    fn segment_files(&self) -> Result<Vec<SegmentFile>> {
+        let objects = self.io.list(ObjPath::from(WAL_PREFIX))?;
+
+        // a segment only exists once its header has been written, and stops existing as soon as
+        // its header is deleted
+        let mut segment_ids = BTreeSet::new();
+        for object in objects {
+            let mut parts = object.location.parts().skip(1);
+            let (Some(segment), Some(name), None) = (parts.next(), parts.next(), parts.next())
+            else {
+                continue;
+            };
+            if name.as_ref() != HEADER_OBJECT_NAME {
+                continue;
+            }
+
+            let segment_id = segment
+                .as_ref()
+                .strip_suffix(SEGMENT_WAL_FILE_EXTENSION)
+                .and_then(|stem| stem.strip_suffix('.'))
+                .map(segment_id_from_file_name);
+            match segment_id {
+                Some(Ok(segment_id)) => {
+                    segment_ids.insert(segment_id);
+                }
+                _ => warn!(
+                    location = %object.location,
+                    "Object in wal prefix doesn't belong to a segment, ignoring"
+                ),
+            }
+        }
+
+        Ok(segment_ids
+            .into_iter()
+            .map(|segment_id| SegmentFile {
+                segment_id,
+                path: SegmentWalFilePath::new(WAL_PREFIX, segment_id).to_path_buf(),
+            })
+            .collect())
+    }
+
+    fn delete_wal_segment(&self, segment_id: SegmentId) -> Result<()> {
+        // delete the header first so that a partially deleted segment is never replayed
+        self.io.delete(header_path(segment_id))?;
+        for object in self.io.list(segment_prefix(segment_id))? {
+            self.io.delete(object.location)?;
+        }
+        Ok(())
+    }
+}
+
+impl Wal for ObjectStoreWal {
+    fn new_segment_writer(
+        &self,
+        segment_id: SegmentId,
+        range: SegmentRange,
+    ) -> Result<Box<dyn WalSegmentWriter>> {
+        let writer = ObjectStoreWalSegmentWriter::new(Arc::clone(&self.io), segment_id, range)?;
+        Ok(Box::new(writer))
+    }
+
+    fn open_segment_writer(&self, segment_id: SegmentId) -> Result<Box<dyn WalSegmentWriter>> {
+        let writer = ObjectStoreWalSegmentWriter::open(Arc::clone(&self.io), segment_id)?;
+        Ok(Box::new(writer))
+    }
+
+    fn open_segment_reader(&self, segment_id: SegmentId) -> Result<Box<dyn WalSegmentReader>> {
+        let reader = ObjectStoreWalSegmentReader::new(Arc::clone(&self.io), segment_id)?;
+        Ok(Box::new(reader))
+    }
+
+    fn segment_files(&self) -> Result<Vec<SegmentFile>> {
+        self.segment_files()
+    }
+
+    fn delete_wal_segment(&self, segment_id: SegmentId) -> Result<()> {
+        self.delete_wal_segment(segment_id)
+    }
+
+    fn recover_segment(&self, _segment_id: SegmentId) -> Result<bool> {
+        // puts are atomic, so there is never a partially written chunk to recover from
+        Ok(true)
+    }
+
+    fn flush_config(&self) -> WalFlushConfig {
+        self.flush_config
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self as &dyn Any
+    }
+}
+
+#[derive(Debug)]
+pub struct ObjectStoreWalSegmentWriter {
+    io: Arc<ObjectStoreIo>,
+    segment_id: SegmentId,
+    version: SegmentFileVersion,
+    bytes_written: usize,
+    sequence_number: SequenceNumber,
+
+    buffer: Vec<u8>,
+}
+
+impl ObjectStoreWalSegmentWriter {
+    fn new(io: Arc<ObjectStoreIo>, segment_id: SegmentId, range: SegmentRange) -> Result<Self> {
+        let path = header_path(segment_id);
+
+        // if the segment already exists, error out
+        if io.head(path.clone())?.is_some() {
+            return Err(Error::FileExists(
+                SegmentWalFilePath::new(WAL_PREFIX, segment_id).to_path_buf(),
+            ));
+        }
+
+        let version = SegmentFileVersion::CURRENT;
+        let header = SegmentHeader {
+            id: segment_id,
+            range,
+        };
+        let header_bytes = encode_header(version, &header)?;
+        let bytes_written = header_bytes.len();
+        io.put(path, header_bytes.into())?;
+
+        Ok(Self {
+            io,
+            segment_id,
+            version,
+            bytes_written,
+            sequence_number: SequenceNumber::new(0),
+            buffer: Vec::with_capacity(8 * 1024), // 8kiB initial size
+        })
+    }
+
+    fn open(io: Arc<ObjectStoreIo>, segment_id: SegmentId) -> Result<Self> {
+        let path = SegmentWalFilePath::new(WAL_PREFIX, segment_id);
+        let Some(header_bytes) = io.get_if_exists(header_path(segment_id))? else {
+            return Err(Error::FileDoesntExist(path.to_path_buf()));
+        };
+        let (version, _) = read_header(&path, &mut header_bytes.as_ref())?;
+
+        let chunks = list_chunks(&io, segment_id)?;
+        let sequence_number = chunks
+            .last()
+            .map(|(sequence_number, _)| *sequence_number)
+            .unwrap_or(SequenceNumber::new(0));
+        let bytes_written =
+            header_bytes.len() + chunks.iter().map(|(_, meta)| meta.size).sum::<usize>();
+
+        // keep appending in the encoding of the existing segment so it remains readable
+        Ok(Self {
+            io,
+            segment_id,
+            version,
+            bytes_written,
+            sequence_number,
+            buffer: Vec::with_capacity(8 * 1024), // 8kiB initial size
+        })
+    }
+
+    fn write_batch(&mut self, ops: Vec<WalOp>) -> Result<()> {
+        let sequence_number = self.sequence_number.next();
+
+        let batch = WalOpBatch {
+            sequence_number,
+            ops,
+        };
+        let data = self.version.encode_batch(&batch)?;
+        encode_block(&mut self.buffer, &data)?;
+
+        let bytes_written = self.buffer.len();
+        self.io.put(
+            chunk_path(self.segment_id, sequence_number),
+            Bytes::copy_from_slice(&self.buffer),
+        )?;
+
+        self.bytes_written += bytes_written;
+        self.sequence_number = sequence_number;
+
+        Ok(())
+    }
+}
+
+impl WalSegmentWriter for ObjectStoreWalSegmentWriter {
+    fn id(&self) -> SegmentId {
+        self.segment_id
+    }
+
+    fn bytes_written(&self) -> u64 {
+        self.bytes_written as u64
+    }
+
+    fn write_batch(&mut self, ops: Vec<WalOp>) -> Result<()> {
+        self.write_batch(ops)
+    }
+
+    fn sync(&mut self) -> Result<()> {
+        // every chunk is durable once the put that wrote it returns
+        Ok(())
+    }
+
+    fn last_sequence_number(&self) -> SequenceNumber {
+        self.sequence_number
+    }
+}
+
+#[derive(Debug)]
+pub struct ObjectStoreWalSegmentReader {
+    io: Arc<ObjectStoreIo>,
+    path: SegmentWalFilePath,
+    version: SegmentFileVersion,
+    segment_header: SegmentHeader,
+    /// The chunks that haven't been read yet, in reverse order
+    chunks: Vec<ObjPath>,
+}
+
+impl ObjectStoreWalSegmentReader {
+    fn new(io: Arc<ObjectStoreIo>, segment_id: SegmentId) -> Result<Self> {
+        let path = SegmentWalFilePath::new(WAL_PREFIX, segment_id);
+        let Some(header_bytes) = io.get_if_exists(header_path(segment_id))? else {
+            return Err(Error::FileDoesntExist(path.to_path_buf()));
+        };
+        let (version, segment_header) = read_header(&path, &mut header_bytes.as_ref())?;
+
+        if segment_id != segment_header.id {
+            return Err(Error::InvalidSegmentFile {
+                path: path.to_path_buf(),
+                reason: format!(
+                    "expected segment id {:?} in header, got {:?}",
+                    segment_id, segment_header.id,
+                ),
+            });
+        }
+
+        let chunks = list_chunks(&io, segment_id)?
+            .into_iter()
+            .rev()
+            .map(|(_, meta)| meta.location)
+            .collect();
+
+        Ok(Self {
+            io,
+            path,
+            version,
+            segment_header,
+            chunks,
+        })
+    }
+
+    /// The version of the segment being read.
+    pub fn version(&self) -> SegmentFileVersion {
+        self.version
+    }
+
+    fn next_batch(&mut self) -> Result<Option<WalOpBatch>> {
+        let Some(location) = self.chunks.pop() else {
+            return Ok(None);
+        };
+
+        let Some(data) = self.io.get_if_exists(location.clone())? else {
+            return Err(Error::InvalidSegmentFile {
+                path: self.path.to_path_buf(),
+                reason: format!("chunk {location} was deleted while reading the segment"),
+            });
+        };
+        match read_block(&mut data.as_ref(), self.segment_header.id)? {
+            Some(block) => Ok(Some(self.version.decode_batch(&block)?)),
+            None => Err(Error::InvalidSegmentFile {
+                path: self.path.to_path_buf(),
+                reason: format!("chunk {location} is empty"),
+            }),
+        }
+    }
+}
+
+impl WalSegmentReader for ObjectStoreWalSegmentReader {
+    fn next_batch(&mut self) -> Result<Option<WalOpBatch>> {
+        self.next_batch()
+    }
+
+    fn header(&self) -> &SegmentHeader {
+        &self.segment_header
+    }
+
+    fn path(&self) -> &SegmentWalFilePath {
+        &self.path
+    }
+}
+
+fn segment_prefix(segment_id: SegmentId) -> ObjPath {
+    ObjPath::from(format!(
+        "{WAL_PREFIX}/{:010}.{SEGMENT_WAL_FILE_EXTENSION}",
+        segment_id.as_u32()
+    ))
+}
+
+fn header_path(segment_id: SegmentId) -> ObjPath {
+    segment_prefix(segment_id).child(HEADER_OBJECT_NAME)
+}
+
+fn chunk_path(segment_id: SegmentId, sequence_number: SequenceNumber) -> ObjPath {
+    segment_prefix(segment_id).child(format!(
+        "{:010}.{CHUNK_EXTENSION}",
+        sequence_number.as_u32()
+    ))
+}
+
+/// Lists the chunks of a segment, sorted by sequence number.
+fn list_chunks(
+    io: &ObjectStoreIo,
+    segment_id: SegmentId,
+) -> Result<Vec<(SequenceNumber, ObjectMeta)>> {
+    let mut chunks = io
+        .list(segment_prefix(segment_id))?
+        .into_iter()
+        .filter_map(|meta| {
+            let sequence_number = meta
+                .location
+                .filename()?
+                .strip_suffix(CHUNK_EXTENSION)?
+                .strip_suffix('.')?
+                .parse::<u32>()
+                .ok()?;
+            Some((SequenceNumber::new(sequence_number), meta))
+        })
+        .collect::<Vec<_>>();
+    chunks.sort_by_key(|(sequence_number, _)| *sequence_number);
+    Ok(chunks)
+}
+
+/// Runs object store requests to completion on a runtime owned by the WAL. The `Wal` traits are
+/// synchronous, so each call blocks its thread until the requests complete: they're made from the
+/// WAL io thread, and from blocking tasks, never from the workers of the server's runtime.
+#[derive(Debug)]
+struct ObjectStoreIo {
+    object_store: Arc<dyn ObjectStore>,
+    runtime: Option<tokio::runtime::Runtime>,
+}
+
+impl ObjectStoreIo {
+    fn new(object_store: Arc<dyn ObjectStore>) -> Result<Self> {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("wal-object-store")
+            .enable_all()
+            .build()?;
+        Ok(Self {
+            object_store,
+            runtime: Some(runtime),
+        })
+    }
+
+    fn run<T, F>(&self, f: impl FnOnce(Arc<dyn ObjectStore>) -> F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: Future<Output = object_store::Result<T>> + Send + 'static,
+    {
+        let fut = f(Arc::clone(&self.object_store));
+        let (tx, rx) = std::sync::mpsc::sync_channel(1);
+        self.runtime
+            .as_ref()
+            .expect("runtime is only taken on drop")
+            .spawn(async move {
+                // the receiver only goes away if the calling thread panicked
+                let _ = tx.send(fut.await);
+            });
+        Ok(rx.recv().expect("object store request task panicked")?)
+    }
+
+    fn put(&self, location: ObjPath, bytes: Bytes) -> Result<()> {
+        self.run(|store| async move { store.put(&location, bytes).await.map(|_| ()) })
+    }
+
+    fn head(&self, location: ObjPath) -> Result<Option<ObjectMeta>> {
+        self.run(|store| async move {
+            match store.head(&location).await {
+                Ok(meta) => Ok(Some(meta)),
+                Err(object_store::Error::NotFound { .. }) => Ok(None),
+                Err(e) => Err(e),
+            }
+        })
+    }
+
+    fn get_if_exists(&self, location: ObjPath) -> Result<Option<Bytes>> {
+        self.run(|store| async move {
+            match store.get(&location).await {
+                Ok(result) => result.bytes().await.map(Some),
+                Err(object_store::Error::NotFound { .. }) => Ok(None),
+                Err(e) => Err(e),
+            }
+        })
+    }
+
+    fn list(&self, prefix: ObjPath) -> Result<Vec<ObjectMeta>> {
+        self.run(|store| async move { store.list(Some(&prefix)).try_collect().await })
+    }
+
+    fn delete(&self, location: ObjPath) -> Result<()> {
+        self.run(|store| async move {
+            match store.delete(&location).await {
+                Ok(()) | Err(object_store::Error::NotFound { .. }) => Ok(()),
+                Err(e) => Err(e),
+            }
+        })
+    }
+}
+
+impl Drop for ObjectStoreIo {
+    fn drop(&mut self) {
+        // the WAL can be dropped from within an async context, where dropping a runtime panics
+        if let Some(runtime) = self.runtime.take() {
+            runtime.shutdown_background();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{LpWriteOp, Precision, SegmentDuration};
+    use iox_time::Time;
+    use object_store::local::LocalFileSystem;
+    use object_store::memory::InMemory;
+
+    fn object_stores() -> Vec<Arc<dyn ObjectStore>> {
+        let dir = test_helpers::tmp_dir().unwrap().into_path();
+        vec![
+            Arc::new(InMemory::new()),
+            Arc::new(LocalFileSystem::new_with_prefix(dir).unwrap()),
+        ]
+    }
+
+    fn range() -> SegmentRange {
+        SegmentRange::from_time_and_duration(
+            Time::from_timestamp_nanos(0),
+            SegmentDuration::new_5m(),
+            false,
+        )
+    }
+
+    fn lp_write(lp: &str) -> WalOp {
+        WalOp::LpWrite(LpWriteOp {
+            db_name: "foo".to_string(),
+            lp: lp.to_string(),
+            default_time: 0,
+            precision: Precision::Nanosecond,
+        })
+    }
+
+    #[test]
+    fn write_reopen_and_read_segment() {
+        for object_store in object_stores() {
+            let wal = ObjectStoreWal::new(object_store).unwrap();
+            let segment_id = SegmentId::new(1);
+
+            let mut writer = wal.new_segment_writer(segment_id, range()).unwrap();
+            writer
+                .write_batch(vec![lp_write("cpu,host=a val=1i 1")])
+                .unwrap();
+            writer
+                .write_batch(vec![lp_write("cpu,host=a val=2i 2")])
+                .unwrap();
+            writer.sync().unwrap();
+            let bytes_written = writer.bytes_written();
+            drop(writer);
+
+            assert!(matches!(
+                wal.new_segment_writer(segment_id, range()),
+                Err(Error::FileExists(_))
+            ));
+
+            let mut writer = wal.open_segment_writer(segment_id).unwrap();
+            assert_eq!(writer.last_sequence_number(), SequenceNumber::new(2));
+            assert_eq!(writer.bytes_written(), bytes_written);
+            writer
+                .write_batch(vec![lp_write("cpu,host=a val=3i 3")])
+                .unwrap();
+
+            let mut reader = wal.open_segment_reader(segment_id).unwrap();
+            assert_eq!(reader.header().id, segment_id);
+            assert_eq!(reader.header().range, range());
+            assert_eq!(
+                reader.path().to_path_buf(),
+                SegmentWalFilePath::new(WAL_PREFIX, segment_id).to_path_buf()
+            );
+            for i in 1..=3 {
+                let batch = reader.next_batch().unwrap().unwrap();
+                assert_eq!(batch.sequence_number, SequenceNumber::new(i));
+                assert_eq!(
+                    batch.ops,
+                    vec![lp_write(&format!("cpu,host=a val={i}i {i}"))]
+                );
+            }
+            assert!(reader.next_batch().unwrap().is_none());
+
+            assert!(matches!(
+                wal.open_segment_reader(SegmentId::new(2)),
+                Err(Error::FileDoesntExist(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn segment_files_and_delete() {
+        for object_store in object_stores() {
+            let wal = ObjectStoreWal::new(Arc::clone(&object_store)).unwrap();
+            for id in [3, 1, 2] {
+                let mut writer = wal.new_segment_writer(SegmentId::new(id), range()).unwrap();
+                writer.write_batch(vec![lp_write("cpu val=1i 1")]).unwrap();
+            }
+
+            let segment_ids = |wal: &ObjectStoreWal| {
+                wal.segment_files()
+                    .unwrap()
+                    .into_iter()
+                    .map(|f| f.segment_id.as_u32())
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(segment_ids(&wal), vec![1, 2, 3]);
+            assert_eq!(
+                wal.segment_files().unwrap()[0].path,
+                std::path::PathBuf::from("wal/0000000001.wal")
+            );
+
+            wal.delete_wal_segment(SegmentId::new(2)).unwrap();
+            assert_eq!(segment_ids(&wal), vec![1, 3]);
+            assert!(ObjectStoreIo::new(object_store)
+                .unwrap()
+                .list(segment_prefix(SegmentId::new(2)))
+                .unwrap()
+                .is_empty());
+
+            // a new wal over the same store sees the same segments
+            let wal = ObjectStoreWal::new(Arc::clone(&wal.io.object_store)).unwrap();
+            assert_eq!(segment_ids(&wal), vec![1, 3]);
+        }
+    }
+
+    #[tokio::test]
+    async fn can_be_used_and_dropped_in_async_context() {
+        let wal = ObjectStoreWal::new(Arc::new(InMemory::new())).unwrap();
+        let mut writer = wal.new_segment_writer(SegmentId::new(1), range()).unwrap();
+        writer.write_batch(vec![lp_write("cpu val=1i 1")]).unwrap();
+        assert_eq!(wal.segment_files().unwrap().len(), 1);
+        drop(writer);
+        drop(wal);
+    }
+}
@@ -0,0 +1,291 @@
+//! The protobuf messages used to encode `WalOpBatch`es in version 2 WAL segment files. The
+//! messages are declared with `prost` derives rather than generated from `.proto` files so that
+//! building the crate doesn't require `protoc`. Tags must never be reused or renumbered, as that
+//! would break reading segment files written by earlier versions.
+
+use crate::wal::{Error, Result};
+use crate::{CatalogOp, SequenceNumber};
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub(super) struct WalOpBatch {
+    #[prost(uint32, tag = "1")]
+    sequence_number: u32,
+    #[prost(message, repeated, tag = "2")]
+    ops: Vec<WalOp>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct WalOp {
+    #[prost(oneof = "wal_op::Op", tags = "1, 2, 3, 4, 5")]
+    op: Option<wal_op::Op>,
+}
+
+mod wal_op {
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub(super) enum Op {
+        #[prost(message, tag = "1")]
+        LpWrite(super::LpWriteOp),
+        #[prost(message, tag = "2")]
+        ParquetWrite(super::ParquetWriteOp),
+        /// Catalog ops are rare compared to writes, so they keep the serde representation used
+        /// for the persisted catalog instead of duplicating table definitions in protobuf.
+        #[prost(bytes, tag = "3")]
+        CatalogJson(Vec<u8>),
+        #[prost(message, tag = "4")]
+        Delete(super::DeleteOp),
+        #[prost(message, tag = "5")]
+        ArrowWrite(super::ArrowWriteOp),
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct LpWriteOp {
+    #[prost(string, tag = "1")]
+    db_name: String,
+    #[prost(string, tag = "2")]
+    lp: String,
+    #[prost(int64, tag = "3")]
+    default_time: i64,
+    #[prost(enumeration = "Precision", tag = "4")]
+    precision: i32,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct ParquetWriteOp {
+    #[prost(string, tag = "1")]
+    db_name: String,
+    #[prost(string, tag = "2")]
+    table_name: String,
+    #[prost(string, tag = "3")]
+    path: String,
+    #[prost(uint64, tag = "4")]
+    size_bytes: u64,
+    #[prost(uint64, tag = "5")]
+    row_count: u64,
+    #[prost(int64, tag = "6")]
+    min_time: i64,
+    #[prost(int64, tag = "7")]
+    max_time: i64,
+    #[prost(message, repeated, tag = "8")]
+    tombstones: Vec<DeletePredicate>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct DeleteOp {
+    #[prost(string, tag = "1")]
+    db_name: String,
+    #[prost(string, tag = "2")]
+    table_name: String,
+    #[prost(message, optional, tag = "3")]
+    predicate: Option<DeletePredicate>,
+    #[prost(int64, tag = "4")]
+    deleted_at_ns: i64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct ArrowWriteOp {
+    #[prost(string, tag = "1")]
+    db_name: String,
+    #[prost(string, tag = "2")]
+    table_name: String,
+    #[prost(int64, tag = "3")]
+    ingest_time: i64,
+    /// The rows of the write as an Arrow IPC stream
+    #[prost(bytes, tag = "4")]
+    ipc: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct DeletePredicate {
+    #[prost(int64, tag = "1")]
+    min_time: i64,
+    #[prost(int64, tag = "2")]
+    max_time: i64,
+    #[prost(message, repeated, tag = "3")]
+    tags: Vec<TagValue>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct TagValue {
+    #[prost(string, tag = "1")]
+    name: String,
+    #[prost(string, tag = "2")]
+    value: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+enum Precision {
+    Auto = 0,
+    Second = 1,
+    Millisecond = 2,
+    Microsecond = 3,
+    Nanosecond = 4,
+}
+
+impl WalOpBatch {
+    pub(super) fn try_from_batch(batch: &crate::WalOpBatch) -> Result<Self> {
+        let ops = batch
+            .ops
+            .iter()
+            .map(|op| {
+                let op = match op {
+                    crate::WalOp::LpWrite(write) => wal_op::Op::LpWrite(LpWriteOp {
+                        db_name: write.db_name.clone(),
+                        lp: write.lp.clone(),
+                        default_time: write.default_time,
+                        precision: Precision::from(write.precision).into(),
+                    }),
+                    crate::WalOp::ParquetWrite(write) => wal_op::Op::ParquetWrite(ParquetWriteOp {
+                        db_name: write.db_name.clone(),
+                        table_name: write.table_name.clone(),
+                        path: write.path.clone(),
+                        size_bytes: write.size_bytes,
+                        row_count: write.row_count,
+                        min_time: write.min_time,
+                        max_time: write.max_time,
+                        tombstones: write.tombstones.iter().map(Into::into).collect(),
+                    }),
+                    crate::WalOp::Catalog(op) => wal_op::Op::CatalogJson(serde_json::to_vec(op)?),
+                    crate::WalOp::Delete(delete) => wal_op::Op::Delete(DeleteOp {
+                        db_name: delete.db_name.clone(),
+                        table_name: delete.table_name.clone(),
+                        predicate: Some((&delete.predicate).into()),
+                        deleted_at_ns: delete.deleted_at_ns,
+                    }),
+                    crate::WalOp::ArrowWrite(write) => wal_op::Op::ArrowWrite(ArrowWriteOp {
+                        db_name: write.db_name.clone(),
+                        table_name: write.table_name.clone(),
+                        ingest_time: write.ingest_time,
+                        ipc: write.ipc.clone(),
+                    }),
+                };
+                Ok(WalOp { op: Some(op) })
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            sequence_number: batch.sequence_number.as_u32(),
+            ops,
+        })
+    }
+
+    pub(super) fn try_into_batch(self) -> Result<crate::WalOpBatch> {
+        let ops = self
+            .ops
+            .into_iter()
+            .map(|op| {
+                let op = match op.op {
+                    Some(wal_op::Op::LpWrite(write)) => {
+                        let precision = Precision::try_from(write.precision).map_err(|_| {
+                            Error::InvalidWalOp(format!("unknown precision {}", write.precision))
+                        })?;
+                        crate::WalOp::LpWrite(crate::LpWriteOp {
+                            db_name: write.db_name,
+                            lp: write.lp,
+                            default_time: write.default_time,
+                            precision: precision.into(),
+                        })
+                    }
+                    Some(wal_op::Op::ParquetWrite(write)) => {
+                        crate::WalOp::ParquetWrite(crate::ParquetWriteOp {
+                            db_name: write.db_name,
+                            table_name: write.table_name,
+                            path: write.path,
+                            size_bytes: write.size_bytes,
+                            row_count: write.row_count,
+                            min_time: write.min_time,
+                            max_time: write.max_time,
+                            tombstones: write.tombstones.into_iter().map(Into::into).collect(),
+                        })
+                    }
+                    Some(wal_op::Op::CatalogJson(json)) => {
+                        let op: CatalogOp = serde_json::from_slice(&json)?;
+                        crate::WalOp::Catalog(op)
+                    }
+                    Some(wal_op::Op::Delete(delete)) => {
+                        let predicate = delete.predicate.ok_or_else(|| {
+                            Error::InvalidWalOp("delete op missing predicate".to_string())
+                        })?;
+                        crate::WalOp::Delete(crate::DeleteOp {
+                            db_name: delete.db_name,
+                            table_name: delete.table_name,
+                            predicate: predicate.into(),
+                            deleted_at_ns: delete.deleted_at_ns,
+                        })
+                    }
+                    Some(wal_op::Op::ArrowWrite(write)) => {
+                        crate::WalOp::ArrowWrite(crate::ArrowWriteOp {
+                            db_name: write.db_name,
+                            table_name: write.table_name,
+                            ingest_time: write.ingest_time,
+                            ipc: write.ipc,
+                        })
+                    }
+                    None => return Err(Error::InvalidWalOp("missing op".to_string())),
+                };
+                Ok(op)
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(crate::WalOpBatch {
+            sequence_number: SequenceNumber::new(self.sequence_number),
+            ops,
+        })
+    }
+}
+
+impl From<&crate::DeletePredicate> for DeletePredicate {
+    fn from(predicate: &crate::DeletePredicate) -> Self {
+        Self {
+            min_time: predicate.min_time,
+            max_time: predicate.max_time,
+            tags: predicate
+                .tags
+                .iter()
+                .map(|(name, value)| TagValue {
+                    name: name.clone(),
+                    value: value.clone(),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<DeletePredicate> for crate::DeletePredicate {
+    fn from(predicate: DeletePredicate) -> Self {
+        Self {
+            min_time: predicate.min_time,
+            max_time: predicate.max_time,
+            tags: predicate
+                .tags
+                .into_iter()
+                .map(|tag| (tag.name, tag.value))
+                .collect(),
+        }
+    }
+}
+
+impl From<crate::Precision> for Precision {
+    fn from(precision: crate::Precision) -> Self {
+        match precision {
+            crate::Precision::Auto => Self::Auto,
+            crate::Precision::Second => Self::Second,
+            crate::Precision::Millisecond => Self::Millisecond,
+            crate::Precision::Microsecond => Self::Microsecond,
+            crate::Precision::Nanosecond => Self::Nanosecond,
+        }
+    }
+}
+
+impl From<Precision> for crate::Precision {
+    fn from(precision: Precision) -> Self {
+        match precision {
+            Precision::Auto => Self::Auto,
+            Precision::Second => Self::Second,
+            Precision::Millisecond => Self::Millisecond,
+            Precision::Microsecond => Self::Microsecond,
+            Precision::Nanosecond => Self::Nanosecond,
+        }
+    }
+}
@@ -0,0 +1,158 @@
+//! Rejects writes while the write buffer is saturated, so that clients get a clear signal to back
+//! off and retry, rather than stalling on a full queue or growing the buffer past its limit.
+
+use crate::write_buffer::flusher::BUFFER_CHANNEL_LIMIT;
+use crate::write_buffer::persister::BUFFER_SIZE_CHECK_INTERVAL;
+use crate::write_buffer::{Error, Result};
+use metric::U64Counter;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How long clients are asked to wait before retrying a write rejected because the queue of
+/// writes waiting to be flushed to the WAL is full. The queue is flushed every few milliseconds,
+/// but `Retry-After` has a resolution of seconds.
+const QUEUE_FULL_RETRY_AFTER: Duration = Duration::from_secs(1);
+
+/// The thresholds at which the write buffer rejects writes
+#[derive(Debug, Clone, Copy)]
+pub struct BackpressureConfig {
+    /// Writes are rejected while this many writes are queued waiting to be flushed to the WAL. This
+    /// is capped at the size of the queue, 10,000.
+    pub max_queued_writes: usize,
+    /// Writes are rejected while the open segments of the buffer use more than this many MB. If
+    /// not set, writes are never rejected because of the buffer size.
+    pub max_buffer_mem_mb: Option<usize>,
+}
+
+impl Default for BackpressureConfig {
+    fn default() -> Self {
+        Self {
+            max_queued_writes: BUFFER_CHANNEL_LIMIT,
+            max_buffer_mem_mb: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Backpressure {
+    config: BackpressureConfig,
+    /// The size in bytes of the open segments, as of the last buffer size check
+    buffer_size: Arc<AtomicUsize>,
+    rejected_queue_full: U64Counter,
+    rejected_buffer_mem: U64Counter,
+}
+
+impl Backpressure {
+    pub(crate) fn new(metric_registry: &metric::Registry) -> Self {
+        let rejected_writes = metric_registry.register_metric::<U64Counter>(
+            "write_buffer_rejected_writes",
+            "Number of writes rejected because the write buffer was saturated",
+        );
+        Self {
+            config: BackpressureConfig::default(),
+            buffer_size: Arc::new(AtomicUsize::new(0)),
+            rejected_queue_full: rejected_writes.recorder(&[("reason", "queue_full")]),
+            rejected_buffer_mem: rejected_writes.recorder(&[("reason", "buffer_mem")]),
+        }
+    }
+
+    pub(crate) fn set_config(&mut self, config: BackpressureConfig) {
+        self.config = config;
+    }
+
+    /// The buffer size, shared with the task that periodically measures it
+    pub(crate) fn buffer_size(&self) -> Arc<AtomicUsize> {
+        Arc::clone(&self.buffer_size)
+    }
+
+    /// Returns an error if a write should be rejected, given the number of writes currently
+    /// queued to be flushed to the WAL.
+    pub(crate) fn check(&self, queued_writes: usize) -> Result<()> {
+        let max_queued_writes = self.config.max_queued_writes.min(BUFFER_CHANNEL_LIMIT);
+        if queued_writes >= max_queued_writes {
+            self.rejected_queue_full.inc(1);
+            return Err(Error::WriteQueueFull {
+                queued_writes,
+                retry_after: QUEUE_FULL_RETRY_AFTER,
+            });
+        }
+
+        if let Some(limit_mb) = self.config.max_buffer_mem_mb {
+            let buffer_mb = self.buffer_size.load(Ordering::Relaxed) / 1024 / 1024;
+            if buffer_mb > limit_mb {
+                self.rejected_buffer_mem.inc(1);
+                // the buffer size is only measured, and the buffer shed, once per interval:
+                return Err(Error::BufferMemoryLimitExceeded {
+                    buffer_mb,
+                    limit_mb,
+                    retry_after: BUFFER_SIZE_CHECK_INTERVAL,
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use metric::{Attributes, Metric};
+
+    fn rejected(registry: &metric::Registry, reason: &'static str) -> u64 {
+        registry
+            .get_instrument::<Metric<U64Counter>>("write_buffer_rejected_writes")
+            .unwrap()
+            .get_observer(&Attributes::from(&[("reason", reason)]))
+            .unwrap()
+            .fetch()
+    }
+
+    #[test]
+    fn rejects_writes_over_thresholds() {
+        let registry = metric::Registry::default();
+        let mut backpressure = Backpressure::new(&registry);
+
+        // by default, writes are only rejected once the queue is full:
+        backpressure
+            .buffer_size()
+            .store(usize::MAX, Ordering::Relaxed);
+        backpressure.check(BUFFER_CHANNEL_LIMIT - 1).unwrap();
+        assert!(matches!(
+            backpressure.check(BUFFER_CHANNEL_LIMIT),
+            Err(Error::WriteQueueFull {
+                queued_writes: BUFFER_CHANNEL_LIMIT,
+                ..
+            })
+        ));
+
+        backpressure.set_config(BackpressureConfig {
+            max_queued_writes: 10,
+            max_buffer_mem_mb: Some(2),
+        });
+        backpressure
+            .buffer_size()
+            .store(2 * 1024 * 1024, Ordering::Relaxed);
+        backpressure.check(9).unwrap();
+        assert!(matches!(
+            backpressure.check(10),
+            Err(Error::WriteQueueFull { .. })
+        ));
+
+        backpressure
+            .buffer_size()
+            .store(3 * 1024 * 1024, Ordering::Relaxed);
+        assert!(matches!(
+            backpressure.check(0),
+            Err(Error::BufferMemoryLimitExceeded {
+                buffer_mb: 3,
+                limit_mb: 2,
+                ..
+            })
+        ));
+
+        assert_eq!(rejected(&registry, "queue_full"), 2);
+        assert_eq!(rejected(&registry, "buffer_mem"), 1);
+    }
+}
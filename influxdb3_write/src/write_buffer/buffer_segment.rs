@@ -4,15 +4,16 @@
 
 use crate::catalog::Catalog;
 use crate::chunk::BufferChunk;
-use crate::paths::ParquetFilePath;
+use crate::paths::{ParquetFilePath, SegmentWalFilePath};
 use crate::write_buffer::flusher::BufferedWriteResult;
 use crate::write_buffer::table_buffer::{Result as TableBufferResult, TableBuffer};
 use crate::write_buffer::DatabaseSchema;
 use crate::write_buffer::{Error, TableBatch, ValidSegmentedData};
 use crate::{
-    wal, write_buffer, write_buffer::Result, DatabaseTables, ParquetFile, ParquetWriteOp,
-    PersistedSegment, Persister, SegmentDuration, SegmentId, SegmentRange, SequenceNumber,
-    TableParquetFiles, WalOp, WalSegmentReader, WalSegmentWriter,
+    wal, write_buffer, write_buffer::Result, CatalogOp, DatabaseTables, DeleteOp, DeletePredicate,
+    LpWriteOp, ParquetFile, ParquetWriteOp, PersistedSegment, Persister, SegmentDuration,
+    SegmentId, SegmentRange, SequenceNumber, TableParquetFiles, WalOp, WalSegmentReader,
+    WalSegmentWriter,
 };
 use arrow::datatypes::SchemaRef;
 use arrow::record_batch::RecordBatch;
@@ -23,23 +24,30 @@ use data_types::TransitionPartitionId;
 use data_types::{NamespaceName, PartitionKey};
 use datafusion::logical_expr::Expr;
 use datafusion_util::stream_from_batches;
+use influxdb_line_protocol::parse_lines;
 use iox_query::chunk_statistics::{create_chunk_statistics, NoColumnRanges};
 use iox_query::frontend::reorg::ReorgPlanner;
 use iox_query::QueryChunk;
 use iox_time::Time;
 use observability_deps::tracing::error;
+use parking_lot::Mutex;
 use schema::sort::SortKey;
 use schema::Schema;
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::oneshot;
 
-use super::validator::WriteValidator;
+use super::validator::{ValidatedLines, WriteValidator};
+
+/// The wal writer of an open segment, shared so that ops can be written to the wal without
+/// holding the lock on the segment state
+pub(crate) type SegmentWriter = Arc<Mutex<Box<dyn WalSegmentWriter>>>;
 
 #[derive(Debug)]
 pub struct OpenBufferSegment {
-    segment_writer: Box<dyn WalSegmentWriter>,
+    segment_writer: SegmentWriter,
     segment_id: SegmentId,
     segment_range: SegmentRange,
     segment_duration: SegmentDuration,
@@ -74,7 +82,7 @@ impl OpenBufferSegment {
 
         Self {
             catalog,
-            segment_writer,
+            segment_writer: Arc::new(Mutex::new(segment_writer)),
             segment_id,
             segment_range,
             segment_duration,
@@ -101,7 +109,11 @@ impl OpenBufferSegment {
     }
 
     pub fn write_wal_ops(&mut self, write_batch: Vec<WalOp>) -> wal::Result<()> {
-        self.segment_writer.write_batch(write_batch)
+        self.segment_writer.lock().write_batch(write_batch)
+    }
+
+    pub(crate) fn segment_writer(&self) -> SegmentWriter {
+        Arc::clone(&self.segment_writer)
     }
 
     pub fn sizes(&self) -> SegmentSizes {
@@ -152,25 +164,29 @@ impl OpenBufferSegment {
         Some((parquet_file_path, persist_batch.record_batch))
     }
 
-    /// Writes a record of the persisted parquet file to the segment writer, clears the buffer,
-    /// and adds the parquet file to the persisted parquet files.
+    /// Clears the buffer, and adds the parquet file to the persisted parquet files, returning the
+    /// op that records the file, which must be written to the segment's wal.
     pub fn clear_persisting_table_buffer(
         &mut self,
-        parquet_file: ParquetFile,
+        mut parquet_file: ParquetFile,
         db_name: &str,
         table_name: &str,
-    ) -> Result<()> {
+    ) -> WalOp {
         let db_buffer = self
             .buffered_data
             .database_buffers
             .get_mut(db_name)
             .expect("database should exist in buffer");
 
-        db_buffer
+        // rows deleted while the data was being persisted may still be in the file
+        let tombstones = db_buffer
             .table_buffers
             .get_mut(table_name)
             .expect("table should exist in buffer")
             .clear_persisting_data();
+        for predicate in &tombstones {
+            parquet_file.add_tombstone(predicate);
+        }
 
         let parquet_write_op = ParquetWriteOp {
             db_name: db_name.to_string(),
@@ -180,6 +196,7 @@ impl OpenBufferSegment {
             row_count: parquet_file.row_count,
             min_time: parquet_file.min_time,
             max_time: parquet_file.max_time,
+            tombstones: parquet_file.tombstones.clone(),
         };
 
         self.persisted_parquet_files
@@ -195,9 +212,33 @@ impl OpenBufferSegment {
             .parquet_files
             .push(parquet_file);
 
-        self.segment_writer
-            .write_batch(vec![WalOp::ParquetWrite(parquet_write_op)])?;
+        WalOp::ParquetWrite(parquet_write_op)
+    }
+
+    /// Drops all buffered data and persisted parquet files for the database from the segment
+    pub(crate) fn remove_database(&mut self, db_name: &str) {
+        self.buffered_data.database_buffers.remove(db_name);
+        self.persisted_parquet_files.remove(db_name);
+    }
 
+    /// Drops all buffered data and persisted parquet files for the table from the segment
+    pub(crate) fn remove_table(&mut self, db_name: &str, table_name: &str) {
+        if let Some(db_buffer) = self.buffered_data.database_buffers.get_mut(db_name) {
+            db_buffer.table_buffers.remove(table_name);
+        }
+        if let Some(db_tables) = self.persisted_parquet_files.get_mut(db_name) {
+            db_tables.tables.remove(table_name);
+        }
+    }
+
+    /// Drops the buffered rows that match the delete, and adds a tombstone for it to the
+    /// persisted parquet files of the table
+    pub(crate) fn delete_rows(&mut self, op: &DeleteOp) -> Result<()> {
+        let deleted = self
+            .buffered_data
+            .delete_rows(&op.db_name, &op.table_name, &op.predicate)?;
+        self.segment_size = self.segment_size.saturating_sub(deleted);
+        op.add_tombstones(&mut self.persisted_parquet_files);
         Ok(())
     }
 
@@ -209,6 +250,11 @@ impl OpenBufferSegment {
     /// Adds the batch into the in memory buffer.
     pub(crate) fn buffer_writes(&mut self, write_batch: WriteBatch) -> Result<()> {
         for (db_name, db_batch) in write_batch.database_batches {
+            // the database may have been deleted after the write was validated
+            let Some(schema) = self.catalog.db_schema(&db_name) else {
+                continue;
+            };
+
             let db_buffer = self
                 .buffered_data
                 .database_buffers
@@ -217,10 +263,6 @@ impl OpenBufferSegment {
                     table_buffers: hashbrown::HashMap::new(),
                 });
 
-            let schema = self
-                .catalog
-                .db_schema(&db_name)
-                .expect("database should exist in schema");
             for (table_name, table_batch) in db_batch.table_batches {
                 // TODO: for now we'll just have the number of rows represent the segment size. The entire
                 //       buffer is going to get refactored to use different structures, so this will change.
@@ -286,7 +328,7 @@ impl OpenBufferSegment {
             self.starting_catalog_sequence_number,
             catalog.sequence_number(),
             self.buffered_data,
-            self.segment_writer.bytes_written(),
+            self.segment_writer.lock().bytes_written(),
             catalog,
             self.persisted_parquet_files,
         )
@@ -316,6 +358,71 @@ pub(crate) struct LoadedBufferSegment {
     pub(crate) persisted_parquet_files: HashMap<String, DatabaseTables>,
 }
 
+impl LoadedBufferSegment {
+    /// Drops the data loaded for a database that was deleted later in the segment
+    fn remove_database(&mut self, db_name: &str) {
+        self.buffered_data.database_buffers.remove(db_name);
+        self.persisted_parquet_files.remove(db_name);
+    }
+
+    /// Drops the data loaded for a table that was deleted later in the segment
+    fn remove_table(&mut self, db_name: &str, table_name: &str) {
+        if let Some(db_buffer) = self.buffered_data.database_buffers.get_mut(db_name) {
+            db_buffer.table_buffers.remove(table_name);
+        }
+        if let Some(db_tables) = self.persisted_parquet_files.get_mut(db_name) {
+            db_tables.tables.remove(table_name);
+        }
+    }
+
+    /// Drops the rows loaded before a delete later in the segment that match it
+    fn delete_rows(&mut self, op: &DeleteOp) -> Result<()> {
+        let deleted = self
+            .buffered_data
+            .delete_rows(&op.db_name, &op.table_name, &op.predicate)?;
+        self.segment_size = self.segment_size.saturating_sub(deleted);
+        op.add_tombstones(&mut self.persisted_parquet_files);
+        Ok(())
+    }
+
+    /// Buffers a write replayed from the segment's WAL file at `path`
+    fn buffer_replayed_write(
+        &mut self,
+        catalog: &Catalog,
+        segment_key: &PartitionKey,
+        path: &SegmentWalFilePath,
+        mut validated_write: ValidatedLines,
+    ) -> Result<()> {
+        // there should only ever be data for a single segment as this is all read
+        // from one segment file
+        if validated_write.valid_segmented_data.len() != 1 {
+            return Err(Error::WalOpForMultipleSegments(path.to_string()));
+        }
+        let segment_data = validated_write.valid_segmented_data.pop().unwrap();
+
+        let db_name = segment_data.database_name.as_str();
+        let db_buffer = self
+            .buffered_data
+            .database_buffers
+            .entry_ref(db_name)
+            .or_insert(DatabaseBuffer {
+                table_buffers: hashbrown::HashMap::new(),
+            });
+
+        let schema = catalog
+            .db_schema(db_name)
+            .expect("database exists in schema");
+        for (table_name, table_batch) in segment_data.table_batches {
+            // TODO: for now we'll just have the number of rows represent the segment size. The entire
+            //       buffer is going to get refactored to use different structures, so this will change.
+            self.segment_size += table_batch.rows.len();
+
+            db_buffer.buffer_table_batch(table_name, segment_key, table_batch, &schema);
+        }
+        Ok(())
+    }
+}
+
 pub(crate) fn load_buffer_from_segment(
     catalog: &Arc<Catalog>,
     mut segment_reader: Box<dyn WalSegmentReader>,
@@ -327,60 +434,102 @@ pub(crate) fn load_buffer_from_segment(
     };
     let segment_key = PartitionKey::from(segment_reader.header().range.key());
     let segment_duration = SegmentDuration::from_range(segment_reader.header().range);
+    // databases and tables whose most recent write in the segment was made before they were
+    // deleted
+    let mut deleted_dbs = HashSet::new();
+    let mut deleted_tables = HashSet::new();
 
     while let Some(batch) = segment_reader.next_batch()? {
         for wal_op in batch.ops {
             match wal_op {
                 WalOp::LpWrite(write) => {
+                    if catalog.db_deleted_after(&write.db_name, write.default_time) {
+                        deleted_dbs.insert(write.db_name);
+                        continue;
+                    }
+                    deleted_dbs.remove(&write.db_name);
+
+                    let lp = remove_deleted_table_lines(catalog, &write, &mut deleted_tables);
+                    if lp.is_empty() {
+                        continue;
+                    }
+
                     let ns = NamespaceName::new(write.db_name.clone())?;
-                    let mut validated_write = WriteValidator::initialize(ns, Arc::clone(catalog))?
-                        .v1_parse_lines_and_update_schema(&write.lp, false)?
+                    let validated_write = WriteValidator::initialize(ns, Arc::clone(catalog))?
+                        .v1_parse_lines_and_update_schema(&lp, false)?
                         .convert_lines_to_buffer(
                             Time::from_timestamp_nanos(write.default_time),
                             segment_duration,
                             write.precision,
                         );
 
-                    let db_name = &write.db_name;
-                    loaded_buffer
-                        .buffered_data
-                        .database_buffers
-                        .entry_ref(db_name)
-                        .or_insert(DatabaseBuffer {
-                            table_buffers: hashbrown::HashMap::new(),
-                        });
-                    let db_buffer = loaded_buffer
-                        .buffered_data
-                        .database_buffers
-                        .get_mut(db_name)
-                        .unwrap();
-
-                    // there should only ever be data for a single segment as this is all read
-                    // from one segment file
-                    if validated_write.valid_segmented_data.len() != 1 {
-                        return Err(Error::WalOpForMultipleSegments(
-                            segment_reader.path().to_string(),
-                        ));
+                    loaded_buffer.buffer_replayed_write(
+                        catalog,
+                        &segment_key,
+                        segment_reader.path(),
+                        validated_write,
+                    )?;
+                }
+                WalOp::ArrowWrite(write) => {
+                    if catalog.db_deleted_after(&write.db_name, write.ingest_time) {
+                        deleted_dbs.insert(write.db_name);
+                        continue;
                     }
-                    let segment_data = validated_write.valid_segmented_data.pop().unwrap();
-
-                    let schema = catalog
-                        .db_schema(db_name)
-                        .expect("database exists in schema");
-                    for (table_name, table_batch) in segment_data.table_batches {
-                        // TODO: for now we'll just have the number of rows represent the segment size. The entire
-                        //       buffer is going to get refactored to use different structures, so this will change.
-                        loaded_buffer.segment_size += table_batch.rows.len();
+                    deleted_dbs.remove(&write.db_name);
+
+                    let table_key = (write.db_name.clone(), write.table_name.clone());
+                    if catalog.db_schema(&write.db_name).is_some_and(|db_schema| {
+                        db_schema.table_deleted_after(&write.table_name, write.ingest_time)
+                    }) {
+                        deleted_tables.insert(table_key);
+                        continue;
+                    }
+                    deleted_tables.remove(&table_key);
 
-                        db_buffer.buffer_table_batch(
+                    let ns = NamespaceName::new(write.db_name.clone())?;
+                    let validated_write = WriteValidator::initialize(ns, Arc::clone(catalog))?
+                        .validate_batches_and_update_schema(
+                            &write.table_name,
+                            write.record_batches()?,
+                        )?
+                        .convert_batches_to_buffer(
+                            Time::from_timestamp_nanos(write.ingest_time),
+                            segment_duration,
+                        )?;
+
+                    loaded_buffer.buffer_replayed_write(
+                        catalog,
+                        &segment_key,
+                        segment_reader.path(),
+                        validated_write,
+                    )?;
+                }
+                WalOp::Catalog(op) => {
+                    match &op {
+                        CatalogOp::DeleteDatabase { db_name, .. } => {
+                            loaded_buffer.remove_database(db_name);
+                        }
+                        CatalogOp::DeleteTable {
+                            db_name,
                             table_name,
-                            &segment_key,
-                            table_batch,
-                            &schema,
-                        );
+                            ..
+                        } => {
+                            loaded_buffer.remove_table(db_name, table_name);
+                        }
+                        _ => (),
                     }
+                    catalog.apply_op(&op);
                 }
                 WalOp::ParquetWrite(parquet_write) => {
+                    if deleted_dbs.contains(&parquet_write.db_name)
+                        || deleted_tables.contains(&(
+                            parquet_write.db_name.clone(),
+                            parquet_write.table_name.clone(),
+                        ))
+                    {
+                        continue;
+                    }
+
                     let db = loaded_buffer
                         .persisted_parquet_files
                         .entry(parquet_write.db_name)
@@ -396,10 +545,14 @@ pub(crate) fn load_buffer_from_segment(
                                 row_count: parquet_write.row_count,
                                 min_time: parquet_write.min_time,
                                 max_time: parquet_write.max_time,
+                                tombstones: parquet_write.tombstones,
                             }],
                             sort_key: vec![],
                         });
                 }
+                WalOp::Delete(op) => {
+                    loaded_buffer.delete_rows(&op)?;
+                }
             }
         }
     }
@@ -407,6 +560,43 @@ pub(crate) fn load_buffer_from_segment(
     Ok(loaded_buffer)
 }
 
+/// Removes the lines of line protocol from the write that are for tables that were deleted after
+/// the write was made. The `deleted_tables` are updated with the tables whose lines were removed,
+/// and cleared of the tables whose lines were kept.
+fn remove_deleted_table_lines<'a>(
+    catalog: &Catalog,
+    write: &'a LpWriteOp,
+    deleted_tables: &mut HashSet<(String, String)>,
+) -> Cow<'a, str> {
+    let Some(db_schema) = catalog
+        .db_schema(&write.db_name)
+        .filter(|db_schema| db_schema.has_deleted_tables())
+    else {
+        return Cow::Borrowed(&write.lp);
+    };
+
+    let mut kept_lines = vec![];
+    for line in write.lp.lines() {
+        // lines that fail to parse are kept, so that the error is reported by the validator
+        let Some(Ok(parsed_line)) = parse_lines(line).next() else {
+            kept_lines.push(line);
+            continue;
+        };
+        let table_key = (
+            write.db_name.clone(),
+            parsed_line.series.measurement.to_string(),
+        );
+        if db_schema.table_deleted_after(&table_key.1, write.default_time) {
+            deleted_tables.insert(table_key);
+        } else {
+            deleted_tables.remove(&table_key);
+            kept_lines.push(line);
+        }
+    }
+
+    Cow::Owned(kept_lines.join("\n"))
+}
+
 #[derive(Debug, Default)]
 pub(crate) struct WriteBatch {
     database_batches: HashMap<NamespaceName<'static>, DatabaseBatch>,
@@ -462,6 +652,23 @@ impl BufferedData {
             .map(|table_buffer| table_buffer.record_batches(schema, filter))
     }
 
+    /// Drops the rows of the table that match the predicate, returning the number dropped
+    pub(crate) fn delete_rows(
+        &mut self,
+        db_name: &str,
+        table_name: &str,
+        predicate: &DeletePredicate,
+    ) -> TableBufferResult<usize> {
+        match self
+            .database_buffers
+            .get_mut(db_name)
+            .and_then(|db_buffer| db_buffer.table_buffers.get_mut(table_name))
+        {
+            Some(table_buffer) => table_buffer.delete_rows(predicate),
+            None => Ok(0),
+        }
+    }
+
     /// Verifies that the passed in buffer has the same data as this buffer
     #[cfg(test)]
     pub(crate) fn verify_matches(&self, other: &BufferedData, catalog: &Catalog) {
@@ -543,6 +750,10 @@ pub struct ClosedBufferSegment {
     pub segment_wal_bytes: u64,
     catalog: Arc<Catalog>,
     persisted_parquet_files: HashMap<String, DatabaseTables>,
+    // deletes made after the segment was closed. The buffered data is left as is while it's
+    // persisted, so the deleted rows are filtered out when it's queried, and the files persisted
+    // from it are given tombstones once the segment is persisted.
+    deletes: Mutex<Vec<DeleteOp>>,
 }
 
 impl ClosedBufferSegment {
@@ -569,10 +780,53 @@ impl ClosedBufferSegment {
             segment_wal_bytes,
             catalog,
             persisted_parquet_files,
+            deletes: Mutex::new(vec![]),
         }
     }
 
+    /// Records the delete, to be applied to the segment's data when it's queried, and to the
+    /// files persisted from it once the segment is persisted
+    pub(crate) fn delete_rows(&self, op: &DeleteOp) {
+        self.deletes.lock().push(op.clone());
+    }
+
+    /// Returns the predicates of the deletes made to the table since the segment was closed
+    pub(crate) fn table_deletes(&self, db_name: &str, table_name: &str) -> Vec<DeletePredicate> {
+        self.deletes
+            .lock()
+            .iter()
+            .filter(|op| op.db_name == db_name && op.table_name == table_name)
+            .map(|op| op.predicate.clone())
+            .collect()
+    }
+
+    /// Returns the deletes made since the segment was closed, leaving none. This should be called
+    /// when the segment has been persisted, to add tombstones to the persisted files.
+    pub(crate) fn take_deletes(&self) -> Vec<DeleteOp> {
+        std::mem::take(&mut *self.deletes.lock())
+    }
+
     pub(crate) async fn persist<P>(
+        &self,
+        persister: Arc<P>,
+        executor: Arc<iox_query::exec::Executor>,
+        sort_key: Option<SortKey>,
+    ) -> Result<PersistedSegment>
+    where
+        P: Persister,
+        write_buffer::Error: From<<P as Persister>::Error>,
+    {
+        let persisted_segment = self
+            .persist_parquet_files(Arc::clone(&persister), executor, sort_key)
+            .await?;
+        persister.persist_segment(&persisted_segment).await?;
+
+        Ok(persisted_segment)
+    }
+
+    /// Persists the catalog, if it changed in the segment, and the segment's data as parquet
+    /// files, returning the [`PersistedSegment`] for them without writing its segment info file
+    pub(crate) async fn persist_parquet_files<P>(
         &self,
         persister: Arc<P>,
         executor: Arc<iox_query::exec::Executor>,
@@ -598,12 +852,21 @@ impl ClosedBufferSegment {
 
         // persist every partition buffer
         for (db_name, db_buffer) in &self.buffered_data.database_buffers {
+            let Some(db_schema) = self.catalog.db_schema(db_name) else {
+                // the database was deleted after the segment was closed
+                continue;
+            };
+
             // start off with whatever was persisted while the segment was still open
             let mut database_tables = self
                 .persisted_parquet_files
                 .get(db_name)
                 .cloned()
                 .unwrap_or_default();
+            // skipping any tables that were deleted after the segment was closed
+            database_tables
+                .tables
+                .retain(|table_name, _| db_schema.table_exists(table_name));
             for t in database_tables.tables.values() {
                 segment_parquet_size_bytes +=
                     t.parquet_files.iter().map(|f| f.size_bytes).sum::<u64>();
@@ -624,117 +887,115 @@ impl ClosedBufferSegment {
                 );
             }
 
-            if let Some(db_schema) = self.catalog.db_schema(db_name) {
-                for (table_name, table_buffer) in &db_buffer.table_buffers {
-                    if let Some(table) = db_schema.get_table(table_name) {
-                        let table_parquet_files = database_tables
-                            .tables
-                            .entry(table_name.clone())
-                            .or_insert_with(|| TableParquetFiles {
-                                table_name: table_name.clone(),
-                                parquet_files: vec![],
-                                sort_key: vec![],
-                            });
-
-                        // All of the record batches for this table that we will
-                        // want to dedupe
-                        let batches =
-                            table_buffer.record_batches(table.schema().as_arrow(), &[])?;
-                        let row_count = batches.iter().map(|b| b.num_rows()).sum::<usize>();
-
-                        // Dedupe and sort using the COMPACT query built into
-                        // iox_query
-                        let mut chunks: Vec<Arc<dyn QueryChunk>> = vec![];
-                        let time_min_max = table_buffer.timestamp_min_max();
-                        let schema = table.schema();
-
-                        let chunk_stats = create_chunk_statistics(
-                            Some(row_count),
-                            schema,
-                            Some(time_min_max),
-                            &NoColumnRanges,
-                        );
+            for (table_name, table_buffer) in &db_buffer.table_buffers {
+                if let Some(table) = db_schema.get_table(table_name) {
+                    let table_parquet_files = database_tables
+                        .tables
+                        .entry(table_name.clone())
+                        .or_insert_with(|| TableParquetFiles {
+                            table_name: table_name.clone(),
+                            parquet_files: vec![],
+                            sort_key: vec![],
+                        });
 
-                        chunks.push(Arc::new(BufferChunk {
-                            batches,
-                            schema: schema.clone(),
-                            stats: Arc::new(chunk_stats),
-                            partition_id: TransitionPartitionId::new(
-                                TableId::new(0),
-                                &self.segment_key,
-                            ),
-                            sort_key: None,
-                            id: ChunkId::new(),
-                            chunk_order: ChunkOrder::new(
-                                chunks
-                                    .len()
-                                    .try_into()
-                                    .expect("should never have this many chunks"),
-                            ),
-                        }));
-
-                        let ctx = executor.new_context();
-
-                        let sort_key = if let Some(key) = sort_key.take() {
-                            key
-                        } else {
-                            SortKey::from(
-                                schema
-                                    .primary_key()
-                                    .into_iter()
-                                    .map(|s| s.to_string())
-                                    .collect::<Vec<String>>(),
-                            )
-                        };
-
-                        let logical_plan = ReorgPlanner::new()
-                            .compact_plan(
-                                Arc::from(table_name.clone()),
-                                table.schema(),
-                                chunks,
-                                sort_key,
-                            )
-                            .unwrap();
-
-                        // Build physical plan
-                        let physical_plan = ctx.create_physical_plan(&logical_plan).await.unwrap();
-
-                        // Execute the plan and return compacted record batches
-                        let data = ctx.collect(physical_plan).await.unwrap();
-
-                        // Get the new row count before turning it into a
-                        // stream. We couldn't turn the data directly into a
-                        // stream since we needed the row count for
-                        // `ParquetFile` below
-                        let row_count = data.iter().map(|b| b.num_rows()).sum::<usize>();
-
-                        let batch_stream = stream_from_batches(table.schema().as_arrow(), data);
-                        let parquet_file_path = ParquetFilePath::new_with_partition_key(
-                            db_name,
-                            &table.name,
-                            &table_buffer.segment_key.to_string(),
-                            self.segment_id,
-                            table_parquet_files.parquet_files.len() as u32 + 1,
-                        );
-                        let path = parquet_file_path.to_string();
-                        let (size_bytes, meta) = persister
-                            .persist_parquet_file(parquet_file_path, batch_stream)
-                            .await?;
-
-                        let parquet_file = ParquetFile {
-                            path,
-                            size_bytes,
-                            row_count: row_count as u64,
-                            min_time: time_min_max.min,
-                            max_time: time_min_max.max,
-                        };
-                        table_parquet_files.parquet_files.push(parquet_file);
-
-                        segment_parquet_size_bytes += size_bytes;
-                        segment_row_count += meta.num_rows as u64;
-                        segment_max_time = segment_max_time.max(time_min_max.max);
-                        segment_min_time = segment_min_time.min(time_min_max.min);
-                    }
+                    // All of the record batches for this table that we will
+                    // want to dedupe
+                    let batches = table_buffer.record_batches(table.schema().as_arrow(), &[])?;
+                    let row_count = batches.iter().map(|b| b.num_rows()).sum::<usize>();
+
+                    // Dedupe and sort using the COMPACT query built into
+                    // iox_query
+                    let mut chunks: Vec<Arc<dyn QueryChunk>> = vec![];
+                    let time_min_max = table_buffer.timestamp_min_max();
+                    let schema = table.schema();
+
+                    let chunk_stats = create_chunk_statistics(
+                        Some(row_count),
+                        schema,
+                        Some(time_min_max),
+                        &NoColumnRanges,
+                    );
+
+                    chunks.push(Arc::new(BufferChunk {
+                        batches,
+                        schema: schema.clone(),
+                        stats: Arc::new(chunk_stats),
+                        partition_id: TransitionPartitionId::new(
+                            TableId::new(0),
+                            &self.segment_key,
+                        ),
+                        sort_key: None,
+                        id: ChunkId::new(),
+                        chunk_order: ChunkOrder::new(
+                            chunks
+                                .len()
+                                .try_into()
+                                .expect("should never have this many chunks"),
+                        ),
+                    }));
+
+                    let ctx = executor.new_context();
+
+                    let sort_key = if let Some(key) = sort_key.take() {
+                        key
+                    } else {
+                        SortKey::from(
+                            schema
+                                .primary_key()
+                                .into_iter()
+                                .map(|s| s.to_string())
+                                .collect::<Vec<String>>(),
+                        )
+                    };
+
+                    let logical_plan = ReorgPlanner::new()
+                        .compact_plan(
+                            Arc::from(table_name.clone()),
+                            table.schema(),
+                            chunks,
+                            sort_key,
+                        )
+                        .unwrap();
+
+                    // Build physical plan
+                    let physical_plan = ctx.create_physical_plan(&logical_plan).await.unwrap();
+
+                    // Execute the plan and return compacted record batches
+                    let data = ctx.collect(physical_plan).await.unwrap();
+
+                    // Get the new row count before turning it into a
+                    // stream. We couldn't turn the data directly into a
+                    // stream since we needed the row count for
+                    // `ParquetFile` below
+                    let row_count = data.iter().map(|b| b.num_rows()).sum::<usize>();
+
+                    let batch_stream = stream_from_batches(table.schema().as_arrow(), data);
+                    let parquet_file_path = ParquetFilePath::new_with_partition_key(
+                        db_name,
+                        &table.name,
+                        &table_buffer.segment_key.to_string(),
+                        self.segment_id,
+                        table_parquet_files.parquet_files.len() as u32 + 1,
+                    );
+                    let path = parquet_file_path.to_string();
+                    let (size_bytes, meta) = persister
+                        .persist_parquet_file(parquet_file_path, batch_stream)
+                        .await?;
+
+                    let parquet_file = ParquetFile {
+                        path,
+                        size_bytes,
+                        row_count: row_count as u64,
+                        min_time: time_min_max.min,
+                        max_time: time_min_max.max,
+                        tombstones: vec![],
+                    };
+                    table_parquet_files.parquet_files.push(parquet_file);
+
+                    segment_parquet_size_bytes += size_bytes;
+                    segment_row_count += meta.num_rows as u64;
+                    segment_max_time = segment_max_time.max(time_min_max.max);
+                    segment_min_time = segment_min_time.min(time_min_max.min);
                 }
             }
 
@@ -743,7 +1004,7 @@ impl ClosedBufferSegment {
             }
         }
 
-        let persisted_segment = PersistedSegment {
+        Ok(PersistedSegment {
             segment_id: self.segment_id,
             segment_wal_size_bytes: self.segment_wal_bytes,
             segment_parquet_size_bytes,
@@ -751,11 +1012,7 @@ impl ClosedBufferSegment {
             segment_min_time,
             segment_max_time,
             databases: persisted_database_files,
-        };
-
-        persister.persist_segment(&persisted_segment).await?;
-
-        Ok(persisted_segment)
+        })
     }
 }
 
@@ -0,0 +1,544 @@
+//! This module contains the logic to filter deleted rows out of record batches, and the
+//! background task that rewrites persisted parquet files that have tombstones without the rows
+//! they delete.
+
+use crate::catalog::TIME_COLUMN_NAME;
+use crate::paths::ParquetFilePath;
+use crate::write_buffer::persisted_files::PersistedFiles;
+use crate::write_buffer::persister::min_max_time_from_batch;
+use crate::write_buffer::Error;
+use crate::{write_buffer, DeletePredicate, ParquetFile, Persister};
+use arrow::array::{Array, BooleanArray, StringArray, TimestampNanosecondArray};
+use arrow::compute::kernels::boolean::{and, not, or};
+use arrow::compute::kernels::cmp::{eq, gt_eq, lt_eq};
+use arrow::compute::{cast, filter_record_batch, prep_null_mask_filter};
+use arrow::datatypes::DataType;
+use arrow::error::ArrowError;
+use arrow::record_batch::RecordBatch;
+use datafusion_util::stream_from_batches;
+use iox_time::{Time, TimeProvider};
+use object_store::path::Path as ObjPath;
+use observability_deps::tracing::{error, info};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::time::MissedTickBehavior;
+
+#[cfg(test)]
+const REWRITE_CHECK_INTERVAL: Duration = Duration::from_millis(10);
+
+#[cfg(not(test))]
+const REWRITE_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Returns a mask of the rows in the batch that match any of the predicates
+pub(crate) fn deleted_rows(
+    batch: &RecordBatch,
+    predicates: &[DeletePredicate],
+) -> Result<BooleanArray, ArrowError> {
+    let mut deleted = BooleanArray::from(vec![false; batch.num_rows()]);
+    let Some(time) = batch.column_by_name(TIME_COLUMN_NAME) else {
+        return Ok(deleted);
+    };
+
+    'predicates: for predicate in predicates {
+        let mut matches = and(
+            &gt_eq(
+                time,
+                &TimestampNanosecondArray::new_scalar(predicate.min_time),
+            )?,
+            &lt_eq(
+                time,
+                &TimestampNanosecondArray::new_scalar(predicate.max_time),
+            )?,
+        )?;
+        for (tag, value) in &predicate.tags {
+            let Some(tag_column) = batch.column_by_name(tag) else {
+                // no rows have a value for the tag
+                continue 'predicates;
+            };
+            let tag_column = cast(tag_column, &DataType::Utf8)?;
+            matches = and(
+                &matches,
+                &eq(&tag_column, &StringArray::new_scalar(value.as_str()))?,
+            )?;
+        }
+        deleted = or(&deleted, &matches)?;
+    }
+
+    // rows without a value for a tag in the predicate don't match it
+    Ok(if deleted.null_count() > 0 {
+        prep_null_mask_filter(&deleted)
+    } else {
+        deleted
+    })
+}
+
+/// Returns the batch without the rows that match any of the predicates
+pub(crate) fn remove_deleted_rows(
+    batch: RecordBatch,
+    predicates: &[DeletePredicate],
+) -> Result<RecordBatch, ArrowError> {
+    if predicates.is_empty() {
+        return Ok(batch);
+    }
+    filter_record_batch(&batch, &not(&deleted_rows(&batch, predicates)?)?)
+}
+
+/// Periodically rewrites persisted parquet files that have tombstones without the deleted rows
+pub(crate) async fn run_delete_rewrite<P, T>(
+    persister: Arc<P>,
+    persisted_files: Arc<PersistedFiles>,
+    segment_info_lock: Arc<tokio::sync::Mutex<()>>,
+    time_provider: Arc<T>,
+    mut shutdown_rx: watch::Receiver<()>,
+) where
+    P: Persister,
+    T: TimeProvider,
+    write_buffer::Error: From<<P as Persister>::Error>,
+{
+    let mut interval = tokio::time::interval(REWRITE_CHECK_INTERVAL);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+    loop {
+        tokio::select! {
+            _ = shutdown_rx.changed() => {
+                break;
+            }
+            _ = interval.tick() => {
+                if let Err(e) = rewrite_files_with_tombstones(Arc::clone(&persister), &persisted_files, &segment_info_lock, time_provider.now()).await {
+                    error!("Error rewriting files with tombstones: {}", e);
+                }
+            }
+        }
+    }
+}
+
+// Performs the following:
+// 1. If any persisted files have tombstones, load every persisted segment while holding the
+//    segment info lock, so no tombstones are added to a file while it's rewritten, and for each
+//    file in them that has tombstones:
+//    a. read the file and write a new one without the deleted rows, or none if no rows are left
+//    b. write the updated segment info file so the new file is loaded on restart
+//    c. replace the file in the persisted files so the new file is queried
+//    d. delete the old file from the object store
+pub(crate) async fn rewrite_files_with_tombstones<P>(
+    persister: Arc<P>,
+    persisted_files: &PersistedFiles,
+    segment_info_lock: &tokio::sync::Mutex<()>,
+    now: Time,
+) -> write_buffer::Result<()>
+where
+    P: Persister,
+    write_buffer::Error: From<<P as Persister>::Error>,
+{
+    if !persisted_files.has_tombstones() {
+        return Ok(());
+    }
+
+    let object_store = persister.object_store();
+    let _segment_info_guard = segment_info_lock.lock().await;
+    for mut segment in persister.load_all_segments().await? {
+        let mut rewritten: Vec<(String, String, ParquetFile, Option<ParquetFile>)> = vec![];
+        for (db_name, db_tables) in segment.databases.iter_mut() {
+            for (table_name, table) in db_tables.tables.iter_mut() {
+                let mut parquet_files = Vec::with_capacity(table.parquet_files.len());
+                for file in std::mem::take(&mut table.parquet_files) {
+                    if file.tombstones.is_empty() {
+                        parquet_files.push(file);
+                        continue;
+                    }
+                    let new_file = rewrite_file(Arc::clone(&persister), &file, now).await?;
+                    parquet_files.extend(new_file.clone());
+                    rewritten.push((db_name.clone(), table_name.clone(), file, new_file));
+                }
+                table.parquet_files = parquet_files;
+            }
+            db_tables
+                .tables
+                .retain(|_, table| !table.parquet_files.is_empty());
+        }
+        if rewritten.is_empty() {
+            continue;
+        }
+        segment
+            .databases
+            .retain(|_, db_tables| !db_tables.tables.is_empty());
+
+        for (_, _, old_file, new_file) in &rewritten {
+            segment.segment_parquet_size_bytes = segment
+                .segment_parquet_size_bytes
+                .saturating_sub(old_file.size_bytes)
+                + new_file.as_ref().map_or(0, |f| f.size_bytes);
+            segment.segment_row_count =
+                segment.segment_row_count.saturating_sub(old_file.row_count)
+                    + new_file.as_ref().map_or(0, |f| f.row_count);
+        }
+
+        persister.persist_segment(&segment).await?;
+
+        for (db_name, table_name, old_file, new_file) in rewritten {
+            let kept = new_file
+                .as_ref()
+                .is_some_and(|new_file| new_file.path == old_file.path);
+            persisted_files.replace_file(
+                &db_name,
+                &table_name,
+                &old_file.path,
+                new_file,
+                &old_file.tombstones,
+            );
+            if kept {
+                continue;
+            }
+            info!(%db_name, %table_name, path = %old_file.path, "removing parquet file rewritten without deleted rows");
+            if let Err(e) = object_store.delete(&ObjPath::from(old_file.path)).await {
+                error!("Error deleting rewritten parquet file: {}", e);
+            }
+        }
+    }
+    persisted_files.clear_older_segment_tombstones();
+
+    Ok(())
+}
+
+/// Writes a copy of the file without the rows deleted by its tombstones, returning the new file,
+/// or `None` if all of its rows were deleted. If none of its rows were deleted, the file is kept
+/// and returned without its tombstones.
+async fn rewrite_file<P>(
+    persister: Arc<P>,
+    file: &ParquetFile,
+    now: Time,
+) -> write_buffer::Result<Option<ParquetFile>>
+where
+    P: Persister,
+    write_buffer::Error: From<<P as Persister>::Error>,
+{
+    let rewrite_error = |e: &dyn std::fmt::Display| Error::ParquetRewriteError {
+        path: file.path.clone(),
+        message: e.to_string(),
+    };
+
+    let bytes = persister
+        .object_store()
+        .get(&ObjPath::from(file.path.as_str()))
+        .await
+        .map_err(|e| rewrite_error(&e))?
+        .bytes()
+        .await
+        .map_err(|e| rewrite_error(&e))?;
+    let builder = ParquetRecordBatchReaderBuilder::try_new(bytes).map_err(|e| rewrite_error(&e))?;
+    let schema = Arc::clone(builder.schema());
+    let batches = builder
+        .build()
+        .map_err(|e| rewrite_error(&e))?
+        .map(|batch| remove_deleted_rows(batch?, &file.tombstones))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| rewrite_error(&e))?;
+    let batch = arrow::compute::concat_batches(&schema, &batches).map_err(|e| rewrite_error(&e))?;
+    if batch.num_rows() == 0 {
+        return Ok(None);
+    }
+    if batch.num_rows() as u64 == file.row_count {
+        return Ok(Some(ParquetFile {
+            tombstones: vec![],
+            ..file.clone()
+        }));
+    }
+
+    let (min_time, max_time) = min_max_time_from_batch(&batch);
+    let row_count = batch.num_rows() as u64;
+    let path = ParquetFilePath::new_rewritten(&file.path, now.timestamp_nanos());
+    let path_string = path.to_string();
+    let (size_bytes, _) = persister
+        .persist_parquet_file(path, stream_from_batches(schema, vec![batch]))
+        .await?;
+
+    Ok(Some(ParquetFile {
+        path: path_string,
+        size_bytes,
+        row_count,
+        min_time,
+        max_time,
+        tombstones: vec![],
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::persister::PersisterImpl;
+    use crate::{DatabaseTables, PersistedSegment, SegmentId, TableParquetFiles};
+    use arrow::array::{ArrayRef, DictionaryArray, Int64Array};
+    use arrow::datatypes::Int32Type;
+    use arrow_util::assert_batches_sorted_eq;
+    use object_store::memory::InMemory;
+    use object_store::ObjectStore;
+
+    fn predicate(min_time: i64, max_time: i64, tags: &[(&str, &str)]) -> DeletePredicate {
+        DeletePredicate {
+            min_time,
+            max_time,
+            tags: tags
+                .iter()
+                .map(|(t, v)| (t.to_string(), v.to_string()))
+                .collect(),
+        }
+    }
+
+    fn cpu_batch() -> RecordBatch {
+        let host: DictionaryArray<Int32Type> = vec![Some("a"), Some("b"), None, Some("a")]
+            .into_iter()
+            .collect();
+        RecordBatch::try_from_iter(vec![
+            ("host", Arc::new(host) as ArrayRef),
+            (
+                "usage",
+                Arc::new(Int64Array::from(vec![1, 2, 3, 4])) as ArrayRef,
+            ),
+            (
+                TIME_COLUMN_NAME,
+                Arc::new(TimestampNanosecondArray::from(vec![10, 20, 30, 40])) as ArrayRef,
+            ),
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn removes_rows_matching_any_predicate() {
+        let batch = cpu_batch();
+
+        // time range only
+        let kept = remove_deleted_rows(batch.clone(), &[predicate(20, 30, &[])]).unwrap();
+        assert_eq!(kept.num_rows(), 2);
+
+        // time range and tag, where rows without the tag don't match
+        let kept =
+            remove_deleted_rows(batch.clone(), &[predicate(0, 100, &[("host", "a")])]).unwrap();
+        assert_batches_sorted_eq!(
+            [
+                "+------+-------+--------------------------------+",
+                "| host | usage | time                           |",
+                "+------+-------+--------------------------------+",
+                "|      | 3     | 1970-01-01T00:00:00.000000030Z |",
+                "| b    | 2     | 1970-01-01T00:00:00.000000020Z |",
+                "+------+-------+--------------------------------+",
+            ],
+            &[kept]
+        );
+
+        // any of several predicates
+        let kept = remove_deleted_rows(
+            batch.clone(),
+            &[predicate(0, 15, &[]), predicate(0, 100, &[("host", "b")])],
+        )
+        .unwrap();
+        assert_eq!(kept.num_rows(), 2);
+
+        // a tag the batch doesn't have matches no rows
+        let kept = remove_deleted_rows(batch, &[predicate(0, 100, &[("region", "us")])]).unwrap();
+        assert_eq!(kept.num_rows(), 4);
+    }
+
+    #[tokio::test]
+    async fn rewrites_files_with_tombstones() {
+        let object_store: Arc<dyn ObjectStore> = Arc::new(InMemory::new());
+        let persister = Arc::new(PersisterImpl::new(Arc::clone(&object_store)));
+
+        let mut files = vec![];
+        for file_number in 1..=2 {
+            let path = ParquetFilePath::new_with_partition_key(
+                "foo",
+                "cpu",
+                "1970-01-01",
+                SegmentId::new(1),
+                file_number,
+            );
+            let path_string = path.to_string();
+            let (size_bytes, _) = persister
+                .persist_parquet_file(
+                    path,
+                    stream_from_batches(cpu_batch().schema(), vec![cpu_batch()]),
+                )
+                .await
+                .unwrap();
+            files.push(ParquetFile {
+                path: path_string,
+                size_bytes,
+                row_count: 4,
+                min_time: 10,
+                max_time: 40,
+                tombstones: vec![],
+            });
+        }
+        // deletes some rows from the first file and all of them from the second
+        files[0].add_tombstone(&predicate(0, 100, &[("host", "a")]));
+        files[1].add_tombstone(&predicate(0, 100, &[]));
+
+        let segment = PersistedSegment {
+            segment_id: SegmentId::new(1),
+            segment_wal_size_bytes: 0,
+            segment_parquet_size_bytes: files.iter().map(|f| f.size_bytes).sum(),
+            segment_row_count: 8,
+            segment_min_time: 10,
+            segment_max_time: 40,
+            databases: [(
+                "foo".to_string(),
+                DatabaseTables {
+                    tables: [(
+                        "cpu".to_string(),
+                        TableParquetFiles {
+                            table_name: "cpu".to_string(),
+                            parquet_files: files.clone(),
+                            sort_key: vec![],
+                        },
+                    )]
+                    .into_iter()
+                    .collect(),
+                },
+            )]
+            .into_iter()
+            .collect(),
+        };
+        persister.persist_segment(&segment).await.unwrap();
+        let persisted_files = PersistedFiles::new_from_persisted_segments(vec![segment]);
+
+        rewrite_files_with_tombstones(
+            Arc::clone(&persister),
+            &persisted_files,
+            &tokio::sync::Mutex::new(()),
+            Time::from_timestamp_nanos(1_000),
+        )
+        .await
+        .unwrap();
+        assert!(!persisted_files.has_tombstones());
+
+        let rewritten = persisted_files.get_files("foo", "cpu");
+        assert_eq!(rewritten.len(), 1);
+        assert_eq!(
+            rewritten[0].path,
+            "dbs/foo/cpu/1970-01-01/4294967294/1.1000.parquet"
+        );
+        assert_eq!(rewritten[0].row_count, 2);
+        assert_eq!((rewritten[0].min_time, rewritten[0].max_time), (20, 30));
+
+        // the old files are gone and the segment info file has the new one
+        for file in &files {
+            assert!(object_store
+                .head(&ObjPath::from(file.path.as_str()))
+                .await
+                .is_err());
+        }
+        let segments = persister.load_segments(10).await.unwrap();
+        assert_eq!(segments[0].segment_row_count, 2);
+        assert_eq!(
+            segments[0].databases["foo"].tables["cpu"].parquet_files,
+            rewritten
+        );
+
+        let bytes = object_store
+            .get(&ObjPath::from(rewritten[0].path.as_str()))
+            .await
+            .unwrap()
+            .bytes()
+            .await
+            .unwrap();
+        let batches = ParquetRecordBatchReaderBuilder::try_new(bytes)
+            .unwrap()
+            .build()
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_batches_sorted_eq!(
+            [
+                "+------+-------+--------------------------------+",
+                "| host | usage | time                           |",
+                "+------+-------+--------------------------------+",
+                "|      | 3     | 1970-01-01T00:00:00.000000030Z |",
+                "| b    | 2     | 1970-01-01T00:00:00.000000020Z |",
+                "+------+-------+--------------------------------+",
+            ],
+            &batches
+        );
+    }
+
+    #[tokio::test]
+    async fn rewrites_files_in_segments_that_are_not_loaded() {
+        let object_store: Arc<dyn ObjectStore> = Arc::new(InMemory::new());
+        let persister = Arc::new(PersisterImpl::new(Arc::clone(&object_store)));
+
+        let path = ParquetFilePath::new_with_partition_key(
+            "foo",
+            "cpu",
+            "1970-01-01",
+            SegmentId::new(1),
+            1,
+        );
+        let path_string = path.to_string();
+        let (size_bytes, _) = persister
+            .persist_parquet_file(
+                path,
+                stream_from_batches(cpu_batch().schema(), vec![cpu_batch()]),
+            )
+            .await
+            .unwrap();
+        let mut file = ParquetFile {
+            path: path_string,
+            size_bytes,
+            row_count: 4,
+            min_time: 10,
+            max_time: 40,
+            tombstones: vec![],
+        };
+        file.add_tombstone(&predicate(0, 100, &[("host", "a")]));
+        let segment = PersistedSegment {
+            segment_id: SegmentId::new(1),
+            segment_wal_size_bytes: 0,
+            segment_parquet_size_bytes: size_bytes,
+            segment_row_count: 4,
+            segment_min_time: 10,
+            segment_max_time: 40,
+            databases: [(
+                "foo".to_string(),
+                DatabaseTables {
+                    tables: [(
+                        "cpu".to_string(),
+                        TableParquetFiles {
+                            table_name: "cpu".to_string(),
+                            parquet_files: vec![file],
+                            sort_key: vec![],
+                        },
+                    )]
+                    .into_iter()
+                    .collect(),
+                },
+            )]
+            .into_iter()
+            .collect(),
+        };
+        persister.persist_segment(&segment).await.unwrap();
+
+        // none of the segment's files are loaded, as if it was older than the loaded segments
+        let persisted_files = PersistedFiles::default();
+        assert!(!persisted_files.has_tombstones());
+        persisted_files.add_older_segment_tombstones();
+        assert!(persisted_files.has_tombstones());
+
+        rewrite_files_with_tombstones(
+            Arc::clone(&persister),
+            &persisted_files,
+            &tokio::sync::Mutex::new(()),
+            Time::from_timestamp_nanos(1_000),
+        )
+        .await
+        .unwrap();
+        assert!(!persisted_files.has_tombstones());
+
+        let segments = persister.load_segments(10).await.unwrap();
+        let files = &segments[0].databases["foo"].tables["cpu"].parquet_files;
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].row_count, 2);
+        assert!(files[0].tombstones.is_empty());
+        assert_eq!(segments[0].segment_row_count, 2);
+    }
+}
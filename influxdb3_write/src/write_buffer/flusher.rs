@@ -1,5 +1,6 @@
 //! Buffers writes and flushes them to the configured wal
 
+use crate::wal::{WalFlushConfig, WalSyncMode};
 use crate::write_buffer::buffer_segment::{BufferedWrite, WriteBatch};
 use crate::write_buffer::{Error, SegmentState, ValidSegmentedData};
 use crate::{wal, SequenceNumber, Wal, WalOp};
@@ -9,15 +10,13 @@ use observability_deps::tracing::debug;
 use parking_lot::{Mutex, RwLock};
 use std::collections::HashMap;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::select;
 use tokio::sync::{mpsc, oneshot, watch};
 use tokio::time::MissedTickBehavior;
 
-// Duration to buffer writes before flushing them to the wal
-const BUFFER_FLUSH_INTERVAL: Duration = Duration::from_millis(10);
 // The maximum number of buffered writes that can be queued up before backpressure is applied
-const BUFFER_CHANNEL_LIMIT: usize = 10_000;
+pub(crate) const BUFFER_CHANNEL_LIMIT: usize = 10_000;
 
 // buffered writes should only fail if the underlying WAL throws an error. They are validated before they
 // are buffered. If there is an error, it'll be here
@@ -29,6 +28,8 @@ pub enum BufferedWriteResult {
 
 type SegmentedWalOps = HashMap<Time, (SequenceNumber, Vec<WalOp>)>;
 type SegmentedWriteBatch = HashMap<Time, (SequenceNumber, WriteBatch)>;
+/// Ops sent to the wal io thread, with the channel its result is sent back on once they're written
+type IoFlushRequest = (SegmentedWalOps, oneshot::Sender<wal::Result<()>>);
 
 /// The WriteBufferFlusher buffers writes and flushes them to the configured wal. The wal IO is done in a native
 /// thread rather than a tokio task to avoid blocking the tokio runtime. As referenced in this post, continuous
@@ -43,11 +44,13 @@ pub struct WriteBufferFlusher {
 }
 
 impl WriteBufferFlusher {
-    pub fn new<T: TimeProvider, W: Wal>(segment_state: Arc<RwLock<SegmentState<T, W>>>) -> Self {
+    pub fn new<T: TimeProvider, W: Wal>(
+        segment_state: Arc<RwLock<SegmentState<T, W>>>,
+        flush_config: WalFlushConfig,
+    ) -> Self {
         let (shutdown_tx, shutdown_rx) = watch::channel(());
         let (buffer_tx, buffer_rx) = mpsc::channel(BUFFER_CHANNEL_LIMIT);
         let (io_flush_tx, io_flush_rx) = bounded(1);
-        let (io_flush_notify_tx, io_flush_notify_rx) = bounded(1);
 
         let flusher = Self {
             join_handle: Default::default(),
@@ -62,7 +65,7 @@ impl WriteBufferFlusher {
             std::thread::Builder::new()
                 .name("write buffer io flusher".to_string())
                 .spawn(move || {
-                    run_io_flush(segment_state, io_flush_rx, io_flush_notify_tx);
+                    run_io_flush(segment_state, io_flush_rx, flush_config.sync_mode);
                 })
                 .expect("failed to spawn write buffer io flusher thread"),
         );
@@ -72,8 +75,8 @@ impl WriteBufferFlusher {
                 wal_op_buffer_segment_state,
                 buffer_rx,
                 io_flush_tx,
-                io_flush_notify_rx,
                 shutdown_rx,
+                flush_config,
             )
             .await;
         }));
@@ -81,6 +84,11 @@ impl WriteBufferFlusher {
         flusher
     }
 
+    /// The number of writes queued waiting to be flushed to the wal
+    pub(crate) fn queued_writes(&self) -> usize {
+        self.buffer_tx.max_capacity() - self.buffer_tx.capacity()
+    }
+
     pub async fn write_to_open_segment(
         &self,
         segmented_data: Vec<ValidSegmentedData>,
@@ -113,68 +121,33 @@ impl WriteBufferFlusher {
 async fn run_wal_op_buffer<T: TimeProvider, W: Wal>(
     segment_state: Arc<RwLock<SegmentState<T, W>>>,
     mut buffer_rx: mpsc::Receiver<BufferedWrite>,
-    io_flush_tx: CrossbeamSender<SegmentedWalOps>,
-    io_flush_notify_rx: CrossbeamReceiver<wal::Result<()>>,
+    io_flush_tx: CrossbeamSender<IoFlushRequest>,
     mut shutdown: watch::Receiver<()>,
+    flush_config: WalFlushConfig,
 ) {
-    let mut ops = SegmentedWalOps::new();
-    let mut write_batch = SegmentedWriteBatch::new();
-    let mut notifies = Vec::new();
-    let mut interval = tokio::time::interval(BUFFER_FLUSH_INTERVAL);
+    let mut buffer = WalOpBuffer::default();
+    // tokio intervals can't be zero, so a zero flush interval flushes as often as possible
+    let mut interval =
+        tokio::time::interval(flush_config.flush_interval.max(Duration::from_millis(1)));
     interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
 
     loop {
         // select on either buffering an op, ticking the flush interval, or shutting down
         select! {
             Some(buffered_write) = buffer_rx.recv() => {
-                for segmented_data in buffered_write.segmented_data {
-                    let segment_ops = ops.entry(segmented_data.segment_start).or_insert_with(|| {
-                        (segmented_data.starting_catalog_sequence_number, Vec::new())
-                    });
-                    segment_ops.1.push(segmented_data.wal_op);
-
-                    let segment_write_batch = write_batch.entry(segmented_data.segment_start).or_insert_with(|| {
-                        (segmented_data.starting_catalog_sequence_number, WriteBatch::default())
-                    });
-                    segment_write_batch.1.add_db_write(segmented_data.database_name, segmented_data.table_batches);
+                buffer.add(buffered_write);
+                // flush a full batch right away rather than waiting for the interval
+                if buffer.notifies.len() >= flush_config.max_batch_size {
+                    std::mem::take(&mut buffer).flush(&segment_state, &io_flush_tx).await;
+                    interval.reset();
                 }
-                notifies.push(buffered_write.response_tx);
             },
             _ = interval.tick() => {
-                if ops.is_empty() {
+                if buffer.ops.is_empty() {
                     continue;
                 }
 
-                // send ops into IO flush channel and wait for response
-                io_flush_tx.send(ops).expect("wal io thread is dead");
-
-                let res = match io_flush_notify_rx.recv().expect("wal io thread is dead") {
-                  Ok(()) => {
-                       let mut err = BufferedWriteResult::Success(());
-
-                        let mut segment_state = segment_state.write();
-
-                        for (time, (sequence_number, write_batch)) in write_batch {
-                            if let Err(e) = segment_state.write_batch_to_segment(time, write_batch, sequence_number) {
-                                err = BufferedWriteResult::Error(e.to_string());
-                                break;
-                            }
-                        }
-
-                        err
-                    },
-                    Err(e) => BufferedWriteResult::Error(e.to_string()),
-                };
-
-                // notify the watchers of the write response
-                for response_tx in notifies {
-                    let _ = response_tx.send(res.clone());
-                }
-
-                // reset the buffers
-                ops = SegmentedWalOps::new();
-                write_batch = SegmentedWriteBatch::new();
-                notifies = Vec::new();
+                std::mem::take(&mut buffer).flush(&segment_state, &io_flush_tx).await;
             },
             _ = shutdown.changed() => {
                 // shutdown has been requested
@@ -185,14 +158,94 @@ async fn run_wal_op_buffer<T: TimeProvider, W: Wal>(
     }
 }
 
+/// The writes buffered since the last flush to the wal
+#[derive(Debug, Default)]
+struct WalOpBuffer {
+    ops: SegmentedWalOps,
+    write_batch: SegmentedWriteBatch,
+    notifies: Vec<oneshot::Sender<BufferedWriteResult>>,
+}
+
+impl WalOpBuffer {
+    fn add(&mut self, buffered_write: BufferedWrite) {
+        for segmented_data in buffered_write.segmented_data {
+            let segment_ops = self
+                .ops
+                .entry(segmented_data.segment_start)
+                .or_insert_with(|| (segmented_data.starting_catalog_sequence_number, Vec::new()));
+            segment_ops.1.extend(segmented_data.wal_ops);
+
+            let segment_write_batch = self
+                .write_batch
+                .entry(segmented_data.segment_start)
+                .or_insert_with(|| {
+                    (
+                        segmented_data.starting_catalog_sequence_number,
+                        WriteBatch::default(),
+                    )
+                });
+            // writes of catalog ops alone have no data to buffer:
+            if !segmented_data.table_batches.is_empty() {
+                segment_write_batch
+                    .1
+                    .add_db_write(segmented_data.database_name, segmented_data.table_batches);
+            }
+        }
+        self.notifies.push(buffered_write.response_tx);
+    }
+
+    /// Sends the ops to the wal io thread, and once they are durable, buffers the writes in their
+    /// segments and acknowledges them.
+    async fn flush<T: TimeProvider, W: Wal>(
+        self,
+        segment_state: &RwLock<SegmentState<T, W>>,
+        io_flush_tx: &CrossbeamSender<IoFlushRequest>,
+    ) {
+        // send ops into IO flush channel and wait for response. Only one flush is in flight at a
+        // time, so the send doesn't block, and the response is awaited rather than blocking the
+        // runtime while the io thread waits to sync the wal.
+        let (notify_tx, notify_rx) = oneshot::channel();
+        io_flush_tx
+            .send((self.ops, notify_tx))
+            .expect("wal io thread is dead");
+
+        let res = match notify_rx.await.expect("wal io thread is dead") {
+            Ok(()) => {
+                let mut err = BufferedWriteResult::Success(());
+
+                let mut segment_state = segment_state.write();
+
+                for (time, (sequence_number, write_batch)) in self.write_batch {
+                    if let Err(e) =
+                        segment_state.write_batch_to_segment(time, write_batch, sequence_number)
+                    {
+                        err = BufferedWriteResult::Error(e.to_string());
+                        break;
+                    }
+                }
+
+                err
+            }
+            Err(e) => BufferedWriteResult::Error(e.to_string()),
+        };
+
+        // notify the watchers of the write response
+        for response_tx in self.notifies {
+            let _ = response_tx.send(res.clone());
+        }
+    }
+}
+
 fn run_io_flush<T: TimeProvider, W: Wal>(
     segment_state: Arc<RwLock<SegmentState<T, W>>>,
-    buffer_rx: CrossbeamReceiver<SegmentedWalOps>,
-    buffer_notify: CrossbeamSender<wal::Result<()>>,
+    buffer_rx: CrossbeamReceiver<IoFlushRequest>,
+    sync_mode: WalSyncMode,
 ) {
+    let mut last_sync = Instant::now();
+
     loop {
-        let segmented_wal_ops = match buffer_rx.recv() {
-            Ok(batch) => batch,
+        let (segmented_wal_ops, buffer_notify) = match buffer_rx.recv() {
+            Ok(request) => request,
             Err(_) => {
                 // the buffer channel has closed, it's shutdown
                 debug!("stopping wal io thread");
@@ -200,19 +253,47 @@ fn run_io_flush<T: TimeProvider, W: Wal>(
             }
         };
 
-        let mut state = segment_state.write();
-
-        // write the ops to the segment files, or return on first error
-        for (time, (sequence_number, wal_ops)) in segmented_wal_ops {
-            let res = state.write_ops_to_segment(time, wal_ops, sequence_number);
-            if res.is_err() {
-                buffer_notify.send(res).expect("buffer flusher is dead");
-                continue;
+        // hold the ops until the interval since the last fsync has passed. Writes that come in
+        // meanwhile are buffered, so they are flushed and fsync'd together in the next batch
+        if let WalSyncMode::Interval(interval) = sync_mode {
+            if let Some(wait) = (last_sync + interval).checked_duration_since(Instant::now()) {
+                std::thread::sleep(wait);
             }
         }
 
-        buffer_notify.send(Ok(())).expect("buffer flusher is dead");
+        let res = write_ops_to_segments(
+            &segment_state,
+            segmented_wal_ops,
+            sync_mode != WalSyncMode::None,
+        );
+        last_sync = Instant::now();
+
+        // the flusher waiting on the result may have been stopped by a shutdown
+        let _ = buffer_notify.send(res);
+    }
+}
+
+// write the ops to the segment files, or return on first error. The segment state is only locked
+// to get the segments' wal writers, so queries and persistence aren't held up by the io.
+fn write_ops_to_segments<T: TimeProvider, W: Wal>(
+    state: &RwLock<SegmentState<T, W>>,
+    segmented_wal_ops: SegmentedWalOps,
+    sync: bool,
+) -> wal::Result<()> {
+    let mut written = Vec::with_capacity(segmented_wal_ops.len());
+    for (time, (sequence_number, wal_ops)) in segmented_wal_ops {
+        let segment_writer = SegmentState::segment_writer_for_time(state, time, sequence_number)?;
+        segment_writer.lock().write_batch(wal_ops)?;
+        written.push(segment_writer);
     }
+
+    if sync {
+        for segment_writer in written {
+            segment_writer.lock().sync()?;
+        }
+    }
+
+    Ok(())
 }
 
 #[cfg(test)]
@@ -260,7 +341,8 @@ mod tests {
             vec![],
             None,
         )));
-        let flusher = WriteBufferFlusher::new(Arc::clone(&segment_state));
+        let flusher =
+            WriteBufferFlusher::new(Arc::clone(&segment_state), WalFlushConfig::default());
 
         let db_name = NamespaceName::new("db1").unwrap();
         let ingest_time = Time::from_timestamp_nanos(0);
@@ -315,4 +397,88 @@ mod tests {
             .unwrap();
         assert_eq!(data[0].num_rows(), 2);
     }
+
+    fn single_segment_state(
+        catalog: &Arc<Catalog>,
+    ) -> Arc<RwLock<SegmentState<MockProvider, WalImpl>>> {
+        let segment_id = SegmentId::new(1);
+        let open_segment = OpenBufferSegment::new(
+            Arc::clone(catalog),
+            segment_id,
+            SegmentRange::test_range(),
+            Time::from_timestamp_nanos(0),
+            SequenceNumber::new(0),
+            Box::new(WalSegmentWriterNoopImpl::new(segment_id)),
+            None,
+        );
+        Arc::new(RwLock::new(SegmentState::new(
+            SegmentDuration::new_5m(),
+            segment_id,
+            Arc::clone(catalog),
+            Arc::new(MockProvider::new(Time::from_timestamp_nanos(0))),
+            vec![open_segment],
+            vec![],
+            None,
+        )))
+    }
+
+    fn validated_write(catalog: &Arc<Catalog>, lp: &str) -> Vec<ValidSegmentedData> {
+        WriteValidator::initialize(NamespaceName::new("db1").unwrap(), Arc::clone(catalog))
+            .unwrap()
+            .v1_parse_lines_and_update_schema(lp, false)
+            .unwrap()
+            .convert_lines_to_buffer(
+                Time::from_timestamp_nanos(0),
+                SegmentDuration::new_5m(),
+                Precision::Nanosecond,
+            )
+            .valid_segmented_data
+    }
+
+    #[tokio::test]
+    async fn flushes_full_batch_without_waiting_for_interval() {
+        let catalog = Arc::new(Catalog::new());
+        let flusher = WriteBufferFlusher::new(
+            single_segment_state(&catalog),
+            WalFlushConfig {
+                flush_interval: Duration::from_secs(3600),
+                max_batch_size: 1,
+                sync_mode: WalSyncMode::Always,
+            },
+        );
+
+        tokio::time::timeout(
+            Duration::from_secs(10),
+            flusher.write_to_open_segment(validated_write(&catalog, "cpu bar=1 10")),
+        )
+        .await
+        .expect("write should be flushed once the batch is full")
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn interval_sync_mode_acknowledges_after_interval() {
+        let catalog = Arc::new(Catalog::new());
+        let sync_interval = Duration::from_millis(100);
+        let flusher = WriteBufferFlusher::new(
+            single_segment_state(&catalog),
+            WalFlushConfig {
+                sync_mode: WalSyncMode::Interval(sync_interval),
+                ..Default::default()
+            },
+        );
+
+        flusher
+            .write_to_open_segment(validated_write(&catalog, "cpu bar=1 10"))
+            .await
+            .unwrap();
+
+        // the next write isn't acknowledged until the interval since the last sync has passed
+        let start = std::time::Instant::now();
+        flusher
+            .write_to_open_segment(validated_write(&catalog, "cpu bar=2 20"))
+            .await
+            .unwrap();
+        assert!(start.elapsed() >= sync_interval - Duration::from_millis(10));
+    }
 }
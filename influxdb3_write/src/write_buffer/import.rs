@@ -0,0 +1,137 @@
+//! Imports of parquet files of historical data into a table.
+//!
+//! The record batches of an import are validated against, or merged into, the table's schema
+//! the same way as an Arrow write. Rather than being buffered and written to the WAL, the rows
+//! are sorted and deduplicated with the same plan used to persist the buffer, and persisted
+//! directly to parquet files, a file for each segment their times fall in. The files are
+//! recorded in a segment info file of their own, so they're queryable as soon as the import is
+//! done, and are loaded like any other persisted segment on restart.
+
+use crate::catalog::TableDefinition;
+use crate::paths::ParquetFilePath;
+use crate::persister::PersisterImpl;
+use crate::write_buffer::persister::sort_dedupe;
+use crate::write_buffer::table_buffer::TableBuffer;
+use crate::write_buffer::{Error, Result, Row};
+use crate::{
+    DatabaseTables, ParquetFile, PersistedSegment, Persister, SegmentDuration, SegmentId,
+    SegmentRange, TableParquetFiles,
+};
+use arrow::compute::concat_batches;
+use arrow::record_batch::RecordBatch;
+use bytes::Bytes;
+use data_types::PartitionKey;
+use datafusion_util::stream_from_batches;
+use iox_time::Time;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use schema::sort::SortKey;
+use std::collections::{BTreeMap, HashMap};
+
+/// Reads the record batches of a parquet file that's being imported
+pub(crate) fn parquet_record_batches(parquet: Bytes) -> Result<Vec<RecordBatch>> {
+    ParquetRecordBatchReaderBuilder::try_new(parquet)
+        .and_then(|builder| builder.build())
+        .map_err(|e| Error::InvalidParquetImport(e.to_string()))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| Error::InvalidParquetImport(e.to_string()))
+}
+
+/// Sorts, dedupes and persists the rows imported into a table, which are keyed by the start
+/// time of the segment they fall in, to parquet files in the given segment. Returns the
+/// segment with the files, which is yet to be persisted itself.
+pub(crate) async fn persist_imported_rows(
+    db_name: &str,
+    table: &TableDefinition,
+    segment_id: SegmentId,
+    segment_duration: SegmentDuration,
+    segment_rows: BTreeMap<Time, Vec<Row>>,
+    persister: &PersisterImpl,
+    executor: &iox_query::exec::Executor,
+) -> Result<PersistedSegment> {
+    let schema = table.schema();
+    let sort_key = SortKey::from(
+        schema
+            .primary_key()
+            .into_iter()
+            .map(|s| s.to_string())
+            .collect::<Vec<String>>(),
+    );
+
+    let mut table_parquet_files = TableParquetFiles {
+        table_name: table.name.clone(),
+        parquet_files: vec![],
+        sort_key: vec![],
+    };
+    let mut segment_parquet_size_bytes = 0;
+    let mut segment_row_count = 0;
+    let mut segment_min_time = i64::MAX;
+    let mut segment_max_time = i64::MIN;
+
+    for (segment_start, rows) in segment_rows {
+        let segment_key = PartitionKey::from(
+            SegmentRange::from_time_and_duration(segment_start, segment_duration, false).key(),
+        );
+        let mut table_buffer = TableBuffer::new(segment_key.clone(), &table.index_columns());
+        table_buffer.add_rows(rows);
+        let time_min_max = table_buffer.timestamp_min_max();
+        let batches = table_buffer.record_batches(schema.as_arrow(), &[])?;
+        let batch = concat_batches(&schema.as_arrow(), &batches)?;
+
+        let data = sort_dedupe(
+            &table.name,
+            batch,
+            schema,
+            time_min_max,
+            &segment_key,
+            sort_key.clone(),
+            executor,
+        )
+        .await
+        .map_err(Error::SortDedupeError)?;
+        let row_count = data.iter().map(|b| b.num_rows()).sum::<usize>();
+
+        let path = ParquetFilePath::new_with_partition_key(
+            db_name,
+            &table.name,
+            &segment_key.to_string(),
+            segment_id,
+            table_parquet_files.parquet_files.len() as u32 + 1,
+        );
+        let path_string = path.to_string();
+        let (size_bytes, _) = persister
+            .persist_parquet_file(path, stream_from_batches(schema.as_arrow(), data))
+            .await?;
+
+        table_parquet_files.parquet_files.push(ParquetFile {
+            path: path_string,
+            size_bytes,
+            row_count: row_count as u64,
+            min_time: time_min_max.min,
+            max_time: time_min_max.max,
+            tombstones: vec![],
+        });
+        segment_parquet_size_bytes += size_bytes;
+        segment_row_count += row_count as u64;
+        segment_min_time = segment_min_time.min(time_min_max.min);
+        segment_max_time = segment_max_time.max(time_min_max.max);
+    }
+
+    let mut databases = HashMap::new();
+    if !table_parquet_files.parquet_files.is_empty() {
+        let mut database_tables = DatabaseTables::default();
+        database_tables
+            .tables
+            .insert(table.name.clone(), table_parquet_files);
+        databases.insert(db_name.to_string(), database_tables);
+    }
+
+    Ok(PersistedSegment {
+        segment_id,
+        segment_wal_size_bytes: 0,
+        segment_parquet_size_bytes,
+        segment_row_count,
+        segment_min_time,
+        segment_max_time,
+        databases,
+    })
+}
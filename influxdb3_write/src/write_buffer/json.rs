@@ -0,0 +1,260 @@
+//! Conversion of writes of rows of JSON to line protocol, so that they're validated, logged to the
+//! WAL and buffered the same way as writes of line protocol.
+//!
+//! Each row is an object with the name of its `table`, an object of its `tags`, which is
+//! optional, an object of its `fields`, and optionally a `timestamp` in the precision of the
+//! write. Numbers with a fraction or exponent are written as floats, and other numbers as
+//! integers, or unsigned integers if they're too large for a signed integer. Fields with a
+//! `null` value are left out of the row.
+
+use crate::{escape_lp, WriteLineError};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct JsonRow {
+    table: String,
+    #[serde(default)]
+    tags: BTreeMap<String, String>,
+    fields: Map<String, Value>,
+    timestamp: Option<i64>,
+}
+
+/// The rows of a JSON write, converted to line protocol
+#[derive(Debug, Default)]
+pub(crate) struct JsonLines {
+    /// The rows that could be converted, a line each
+    pub(crate) lp: String,
+    /// The number in the write, and JSON, of the row each line of `lp` was converted from
+    rows: Vec<(usize, String)>,
+    /// The errors for the rows that couldn't be converted
+    pub(crate) errors: Vec<WriteLineError>,
+}
+
+impl JsonLines {
+    /// Converts the rows of a write, which is either a JSON array of rows, or newline delimited
+    /// JSON with a row on each line. Rows are numbered from 1, by their position in the array or
+    /// the line they are on.
+    pub(crate) fn parse(json: &str) -> Result<Self, serde_json::Error> {
+        let mut lines = Self::default();
+        if json.trim_start().starts_with('[') {
+            let rows: Vec<Value> = serde_json::from_str(json)?;
+            for (i, row) in rows.into_iter().enumerate() {
+                let raw_row = row.to_string();
+                lines.push(i + 1, raw_row, serde_json::from_value(row));
+            }
+        } else {
+            for (i, line) in json.lines().enumerate() {
+                if !line.trim().is_empty() {
+                    lines.push(i + 1, line.to_string(), serde_json::from_str(line));
+                }
+            }
+        }
+        Ok(lines)
+    }
+
+    fn push(
+        &mut self,
+        row_number: usize,
+        raw_row: String,
+        row: Result<JsonRow, serde_json::Error>,
+    ) {
+        match row
+            .map_err(|e| e.to_string())
+            .and_then(|row| to_lp_line(&row))
+        {
+            Ok(line) => {
+                self.lp.push_str(&line);
+                self.lp.push('\n');
+                self.rows.push((row_number, raw_row));
+            }
+            Err(error_message) => self.errors.push(WriteLineError {
+                original_line: raw_row,
+                line_number: row_number,
+                error_message,
+            }),
+        }
+    }
+
+    /// Maps an error for a line of the converted line protocol to the row it was converted from
+    pub(crate) fn row_error(&self, error: WriteLineError) -> WriteLineError {
+        match error
+            .line_number
+            .checked_sub(1)
+            .and_then(|i| self.rows.get(i))
+        {
+            Some((row_number, raw_row)) => WriteLineError {
+                original_line: raw_row.clone(),
+                line_number: *row_number,
+                error_message: error.error_message,
+            },
+            None => error,
+        }
+    }
+}
+
+/// Converts a row to a line of line protocol, without the trailing newline
+fn to_lp_line(row: &JsonRow) -> Result<String, String> {
+    check_no_newlines("table name", &row.table)?;
+    if row.table.is_empty() {
+        return Err("the table name can't be empty".to_string());
+    }
+    // a line starting with '#' is a comment:
+    if row.table.starts_with('#') {
+        return Err(format!("the table name {} can't start with '#'", row.table));
+    }
+
+    let mut line = escape_lp(&row.table, &[',', ' ']);
+    for (key, value) in &row.tags {
+        check_no_newlines("tag", key)?;
+        check_no_newlines("tag value", value)?;
+        write!(
+            line,
+            ",{}={}",
+            escape_lp(key, &[',', '=', ' ']),
+            escape_lp(value, &[',', '=', ' '])
+        )
+        .unwrap();
+    }
+
+    let mut separator = ' ';
+    for (key, value) in &row.fields {
+        check_no_newlines("field", key)?;
+        let value = match value {
+            Value::Null => continue,
+            Value::Bool(b) => b.to_string(),
+            Value::Number(n) => match (n.as_i64(), n.as_u64()) {
+                _ if n.is_f64() => n.to_string(),
+                (Some(i), _) => format!("{i}i"),
+                (None, Some(u)) => format!("{u}u"),
+                (None, None) => n.to_string(),
+            },
+            Value::String(s) => {
+                check_no_newlines("field value", s)?;
+                format!("\"{}\"", escape_lp(s, &['"']))
+            }
+            Value::Array(_) | Value::Object(_) => {
+                return Err(format!(
+                    "the value of field {key} must be a number, string or boolean"
+                ))
+            }
+        };
+        write!(
+            line,
+            "{separator}{}={value}",
+            escape_lp(key, &[',', '=', ' '])
+        )
+        .unwrap();
+        separator = ',';
+    }
+    if separator == ' ' {
+        return Err("the row must have at least one field with a value".to_string());
+    }
+
+    if let Some(timestamp) = row.timestamp {
+        write!(line, " {timestamp}").unwrap();
+    }
+    Ok(line)
+}
+
+fn check_no_newlines(what: &str, s: &str) -> Result<(), String> {
+    if s.contains(['\n', '\r']) {
+        Err(format!("the {what} {s:?} can't contain newlines"))
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_rows_to_lp() {
+        let json = r#"[
+            {"table": "cpu", "tags": {"host": "a", "region": "us west"}, "fields": {"usage": 0.5, "count": 3, "big": 18446744073709551615, "up": true, "note": "say \"hi\"", "gone": null}, "timestamp": 123},
+            {"table": "cpu", "fields": {"usage": 1.0}},
+            {"table": "cpu", "tags": {"host": 1}, "fields": {"usage": 1.0}},
+            {"table": "mem", "fields": {"used": [1]}},
+            {"table": "mem", "fields": {"used": null}},
+            {"table": "mem", "fields": {"used": 1}, "extra": true}
+        ]"#;
+        let lines = JsonLines::parse(json).unwrap();
+        assert_eq!(
+            lines.lp,
+            "cpu,host=a,region=us\\ west big=18446744073709551615u,count=3i,note=\"say \\\"hi\\\"\",up=true,usage=0.5 123\n\
+            cpu usage=1.0\n"
+        );
+        let errors: Vec<_> = lines
+            .errors
+            .iter()
+            .map(|e| (e.line_number, e.error_message.as_str()))
+            .collect();
+        assert_eq!(errors.len(), 4);
+        assert_eq!(errors[0].0, 3);
+        assert!(errors[0].1.contains("invalid type"), "{}", errors[0].1);
+        assert_eq!(
+            errors[1],
+            (
+                4,
+                "the value of field used must be a number, string or boolean"
+            )
+        );
+        assert_eq!(
+            errors[2],
+            (5, "the row must have at least one field with a value")
+        );
+        assert_eq!(errors[3].0, 6);
+        assert!(
+            errors[3].1.contains("unknown field `extra`"),
+            "{}",
+            errors[3].1
+        );
+
+        // errors for the line protocol are mapped back to the rows it was converted from
+        let error = lines.row_error(WriteLineError {
+            original_line: "cpu usage=1.0".to_string(),
+            line_number: 2,
+            error_message: "bad".to_string(),
+        });
+        assert_eq!(error.line_number, 2);
+        assert_eq!(
+            error.original_line,
+            r#"{"fields":{"usage":1.0},"table":"cpu"}"#
+        );
+    }
+
+    #[test]
+    fn converts_ndjson_rows() {
+        let json = "{\"table\": \"cpu\", \"fields\": {\"usage\": 2}}\n\n\
+            not json\n\
+            {\"table\": \"cpu\", \"fields\": {\"usage\": 3}, \"timestamp\": 5}\n";
+        let lines = JsonLines::parse(json).unwrap();
+        assert_eq!(lines.lp, "cpu usage=2i\ncpu usage=3i 5\n");
+        assert_eq!(lines.errors.len(), 1);
+        assert_eq!(lines.errors[0].line_number, 3);
+        assert_eq!(lines.errors[0].original_line, "not json");
+
+        let error = lines.row_error(WriteLineError {
+            original_line: "cpu usage=3i 5".to_string(),
+            line_number: 2,
+            error_message: "bad".to_string(),
+        });
+        assert_eq!(error.line_number, 4);
+
+        assert!(JsonLines::parse("[{\"table\": ").is_err());
+    }
+
+    #[test]
+    fn escapes_backslashes() {
+        let json = r#"{"table": "c\\pu", "tags": {"path": "C:\\", "host": "a"}, "fields": {"dir\\": "C:\\"}}"#;
+        let lines = JsonLines::parse(json).unwrap();
+        assert_eq!(
+            lines.lp,
+            concat!(r#"c\\pu,host=a,path=C:\\ dir\\="C:\\""#, "\n")
+        );
+    }
+}
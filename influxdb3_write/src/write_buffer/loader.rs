@@ -1,18 +1,31 @@
 //! This module contains logic to load the initial server state from the persister and wal
 //! if configured.
 
-use crate::catalog::Catalog;
-use crate::wal::WalSegmentWriterNoopImpl;
+use crate::catalog::{Catalog, CatalogLimits, InnerCatalog};
+use crate::paths::SegmentWalFilePath;
+use crate::wal::{self, SegmentHeader, WalSegmentWriterNoopImpl};
 use crate::write_buffer::{
-    buffer_segment::{load_buffer_from_segment, ClosedBufferSegment, OpenBufferSegment},
-    Result,
+    buffer_segment::{
+        load_buffer_from_segment, ClosedBufferSegment, LoadedBufferSegment, OpenBufferSegment,
+    },
+    Error, Result,
 };
-use crate::{persister, write_buffer, PersistedCatalog, PersistedSegment, Persister, SegmentId};
-use crate::{SegmentDuration, SegmentRange, Wal};
+use crate::{
+    persister, write_buffer, DeleteOp, PersistedCatalog, PersistedSegment, Persister, SegmentId,
+    WalOp,
+};
+use crate::{SegmentDuration, SegmentRange, Wal, WalOpBatch, WalSegmentReader};
 use iox_time::Time;
-use std::sync::Arc;
+use metric::{U64Counter, U64Gauge};
+use observability_deps::tracing::info;
+use parking_lot::{Condvar, Mutex};
+use std::collections::{BTreeMap, BTreeSet};
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::Instant;
 
-const SEGMENTS_TO_LOAD: usize = 1000;
+pub(crate) const SEGMENTS_TO_LOAD: usize = 1000;
 
 /// The state loaded and initialized from the persister and wal.
 #[derive(Debug)]
@@ -21,6 +34,9 @@ pub struct LoadedState {
     pub open_segments: Vec<OpenBufferSegment>,
     pub persisting_buffer_segments: Vec<ClosedBufferSegment>,
     pub persisted_segments: Vec<PersistedSegment>,
+    /// The deletes replayed from the wal, with the id of the segment each was read from, in the
+    /// order they were replayed
+    pub replayed_deletes: Vec<(SegmentId, DeleteOp)>,
     pub last_segment_id: SegmentId,
 }
 
@@ -29,6 +45,8 @@ pub async fn load_starting_state<P, W>(
     wal: Option<Arc<W>>,
     server_load_time: Time,
     segment_duration: SegmentDuration,
+    catalog_limits: CatalogLimits,
+    metric_registry: &metric::Registry,
 ) -> Result<LoadedState>
 where
     P: Persister,
@@ -37,14 +55,21 @@ where
     write_buffer::Error: From<<P as Persister>::Error>,
 {
     let PersistedCatalog { catalog, .. } = persister.load_catalog().await?.unwrap_or_default();
-    let catalog = Arc::new(Catalog::from_inner(catalog));
+    let catalog = Arc::new(Catalog::from_inner(catalog).with_limits(catalog_limits));
 
-    let persisted_segments = persister.load_segments(SEGMENTS_TO_LOAD).await?;
+    let mut persisted_segments = persister.load_segments(SEGMENTS_TO_LOAD).await?;
 
+    // the segments are loaded most recent first
     let last_persisted_segment_id = persisted_segments
-        .last()
+        .first()
         .map(|s| s.segment_id)
         .unwrap_or(SegmentId::new(0));
+    // segments older than the oldest loaded one were persisted long ago, if there are more
+    // persisted segments than were loaded
+    let oldest_loaded_segment_id = persisted_segments
+        .last()
+        .filter(|_| persisted_segments.len() >= SEGMENTS_TO_LOAD)
+        .map(|s| s.segment_id);
     let mut persisting_buffer_segments = Vec::new();
 
     let current_segment_range =
@@ -52,65 +77,138 @@ where
     let next_segment_range = current_segment_range.next();
 
     let mut open_segments = Vec::new();
+    let mut replayed_deletes = Vec::new();
     let mut max_segment_id = last_persisted_segment_id;
 
     if let Some(wal) = wal {
-        // read any segments that don't show up in the list of persisted segments
-        let wal_segments = wal.segment_files()?;
-
-        for segment_file in wal_segments {
-            max_segment_id = max_segment_id.max(segment_file.segment_id);
-
-            // if persisted segments is empty, load all segments from the wal, otherwise
-            // only load segments that haven't been persisted yet
-            if segment_file.segment_id <= last_persisted_segment_id
-                && !persisted_segments.is_empty()
-            {
-                continue;
+        let metrics = ReplayMetrics::new(metric_registry);
+        let replay_catalog = Arc::clone(&catalog);
+        // the wal is read, and its segments decoded and validated, on the blocking pool, as the
+        // wal's io and the work of replaying would otherwise hold up the runtime
+        let tombstoned_segments;
+        (
+            open_segments,
+            persisting_buffer_segments,
+            persisted_segments,
+            replayed_deletes,
+            tombstoned_segments,
+            max_segment_id,
+        ) = tokio::task::spawn_blocking(move || {
+            let catalog = replay_catalog;
+            let mut open_segments = Vec::new();
+            let mut replayed_deletes = Vec::new();
+            // the persisted segments that were given tombstones by deletes in the wal
+            let mut tombstoned_segments = BTreeSet::new();
+
+            // read any segments that don't show up in the list of persisted segments
+            let wal_segments = wal.segment_files()?;
+
+            let mut segments_to_replay = Vec::with_capacity(wal_segments.len());
+            for segment_file in wal_segments {
+                max_segment_id = max_segment_id.max(segment_file.segment_id);
+
+                // only load segments that haven't been persisted yet. Imports are persisted in
+                // segments of their own, so a segment older than the last persisted one may not
+                // have been persisted.
+                if persisted_segments
+                    .iter()
+                    .any(|s| s.segment_id == segment_file.segment_id)
+                    || oldest_loaded_segment_id.is_some_and(|id| segment_file.segment_id < id)
+                {
+                    continue;
+                }
+                segments_to_replay.push(segment_file.segment_id);
             }
 
-            let starting_sequence_number = catalog.sequence_number();
-            let segment_reader = wal.open_segment_reader(segment_file.segment_id)?;
-            let segment_header = *segment_reader.header();
-            let buffer = load_buffer_from_segment(&catalog, segment_reader)?;
-
-            let segment = OpenBufferSegment::new(
-                Arc::clone(&catalog),
-                segment_header.id,
-                segment_header.range,
-                server_load_time,
-                starting_sequence_number,
-                wal.open_segment_writer(segment_file.segment_id)?,
-                Some(buffer),
-            );
+            replay_segments(
+                wal.as_ref(),
+                &catalog,
+                &segments_to_replay,
+                &metrics,
+                |segment| {
+                    // deletes apply to the data in the segments before this one, as well as the
+                    // data before them in this one. Writes to an older segment made after a
+                    // delete are rare enough that they're deleted too, rather than tracking the
+                    // order of writes across segments.
+                    for op in &segment.delete_ops {
+                        for open_segment in &mut open_segments {
+                            open_segment.delete_rows(op)?;
+                        }
+                        for persisting_segment in &persisting_buffer_segments {
+                            persisting_segment.delete_rows(op);
+                        }
+                        for persisted_segment in &mut persisted_segments {
+                            if op.add_tombstones(&mut persisted_segment.databases) {
+                                tombstoned_segments.insert(persisted_segment.segment_id);
+                            }
+                        }
+                        // the deletes are applied to the last caches when they're rebuilt from
+                        // the replayed segments
+                        replayed_deletes.push((segment.header.id, op.clone()));
+                    }
 
-            // if it's the current or next segment, we want to keep it open rather than move it to
-            // a persisting state
-            if segment_header.range == current_segment_range
-                || segment_header.range == next_segment_range
-            {
-                open_segments.push(segment);
-            } else {
-                persisting_buffer_segments.push(segment.into_closed_segment(Arc::clone(&catalog)));
+                    let starting_sequence_number = catalog.sequence_number();
+                    let segment_header = segment.header;
+                    let buffer = segment.apply(wal.as_ref(), &catalog)?;
+
+                    let segment = OpenBufferSegment::new(
+                        Arc::clone(&catalog),
+                        segment_header.id,
+                        segment_header.range,
+                        server_load_time,
+                        starting_sequence_number,
+                        wal.open_segment_writer(segment_header.id)?,
+                        Some(buffer),
+                    );
+
+                    // if it's the current or next segment, we want to keep it open rather than
+                    // move it to a persisting state
+                    if segment_header.range == current_segment_range
+                        || segment_header.range == next_segment_range
+                    {
+                        open_segments.push(segment);
+                    } else {
+                        persisting_buffer_segments
+                            .push(segment.into_closed_segment(Arc::clone(&catalog)));
+                    }
+                    Ok(())
+                },
+            )?;
+
+            if open_segments.is_empty() {
+                // ensure that we open up a segment for the "now" period of time
+                let current_segment_id = max_segment_id.next();
+                max_segment_id = current_segment_id;
+
+                let current_segment = OpenBufferSegment::new(
+                    Arc::clone(&catalog),
+                    current_segment_id,
+                    current_segment_range,
+                    server_load_time,
+                    catalog.sequence_number(),
+                    wal.new_segment_writer(current_segment_id, current_segment_range)?,
+                    None,
+                );
+
+                open_segments.push(current_segment);
             }
-        }
 
-        if open_segments.is_empty() {
-            // ensure that we open up a segment for the "now" period of time
-            let current_segment_id = max_segment_id.next();
-            max_segment_id = current_segment_id;
-
-            let current_segment = OpenBufferSegment::new(
-                Arc::clone(&catalog),
-                current_segment_id,
-                current_segment_range,
-                server_load_time,
-                catalog.sequence_number(),
-                wal.new_segment_writer(current_segment_id, current_segment_range)?,
-                None,
-            );
+            Ok::<_, Error>((
+                open_segments,
+                persisting_buffer_segments,
+                persisted_segments,
+                replayed_deletes,
+                tombstoned_segments,
+                max_segment_id,
+            ))
+        })
+        .await
+        .expect("wal replay task panicked")?;
 
-            open_segments.push(current_segment);
+        for persisted_segment in &persisted_segments {
+            if tombstoned_segments.contains(&persisted_segment.segment_id) {
+                persister.persist_segment(persisted_segment).await?;
+            }
         }
     } else {
         // ensure that we open up a segment for the "now" period of time
@@ -136,26 +234,330 @@ where
         open_segments,
         persisting_buffer_segments,
         persisted_segments,
+        replayed_deletes,
     })
 }
 
+/// A WAL segment whose batches have all been read and decoded ahead of being applied.
+#[derive(Debug)]
+struct DecodedSegment {
+    header: SegmentHeader,
+    path: SegmentWalFilePath,
+    batches: std::vec::IntoIter<WalOpBatch>,
+}
+
+impl DecodedSegment {
+    /// Recovers, reads and decodes every batch in the segment, returning `None` if the WAL's
+    /// recovery mode skips it.
+    fn read<W: Wal>(wal: &W, segment_id: SegmentId) -> Result<Option<Self>> {
+        if !wal.recover_segment(segment_id)? {
+            return Ok(None);
+        }
+
+        let mut reader = wal.open_segment_reader(segment_id)?;
+        let mut batches = Vec::new();
+        while let Some(batch) = reader.next_batch()? {
+            batches.push(batch);
+        }
+
+        Ok(Some(Self {
+            header: *reader.header(),
+            path: reader.path().clone(),
+            batches: batches.into_iter(),
+        }))
+    }
+
+    /// The number of ops in the segment
+    fn ops(&self) -> usize {
+        self.batches
+            .as_slice()
+            .iter()
+            .map(|batch| batch.ops.len())
+            .sum()
+    }
+
+    /// The deletes in the segment
+    fn delete_ops(&self) -> Vec<DeleteOp> {
+        self.batches
+            .as_slice()
+            .iter()
+            .flat_map(|batch| &batch.ops)
+            .filter_map(|op| match op {
+                WalOp::Delete(op) => Some(op.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+impl WalSegmentReader for DecodedSegment {
+    fn next_batch(&mut self) -> wal::Result<Option<WalOpBatch>> {
+        Ok(self.batches.next())
+    }
+
+    fn header(&self) -> &SegmentHeader {
+        &self.header
+    }
+
+    fn path(&self) -> &SegmentWalFilePath {
+        &self.path
+    }
+}
+
+/// A WAL segment whose writes have been parsed, validated and buffered against a snapshot of the
+/// catalog, ahead of being applied to the catalog being replayed into.
+#[derive(Debug)]
+struct PreparedSegment {
+    header: SegmentHeader,
+    ops: usize,
+    delete_ops: Vec<DeleteOp>,
+    /// The state of the catalog when its snapshot was taken
+    catalog_start: InnerCatalog,
+    /// The snapshot of the catalog, with the segment's changes applied to it
+    catalog: Arc<Catalog>,
+    buffer: Result<LoadedBufferSegment>,
+}
+
+impl PreparedSegment {
+    /// Reads the segment and loads its buffer against a snapshot of the `catalog`, returning
+    /// `None` if the WAL's recovery mode skips it.
+    fn read<W: Wal>(wal: &W, segment_id: SegmentId, catalog: &Catalog) -> Result<Option<Self>> {
+        let Some(segment) = DecodedSegment::read(wal, segment_id)? else {
+            return Ok(None);
+        };
+
+        let catalog_start = catalog.clone_inner();
+        let snapshot =
+            Arc::new(Catalog::from_inner(catalog_start.clone()).with_limits(catalog.limits()));
+        let header = segment.header;
+        let ops = segment.ops();
+        let delete_ops = segment.delete_ops();
+        let buffer = load_buffer_from_segment(&snapshot, Box::new(segment));
+
+        Ok(Some(Self {
+            header,
+            ops,
+            delete_ops,
+            catalog_start,
+            catalog: snapshot,
+            buffer,
+        }))
+    }
+
+    /// Applies the segment's changes to the `catalog`, returning its buffer. If the catalog was
+    /// changed by the segments applied since the snapshot was taken, the buffer loaded against
+    /// the snapshot may not be valid, so the segment is loaded again against the catalog.
+    fn apply<W: Wal>(self, wal: &W, catalog: &Arc<Catalog>) -> Result<LoadedBufferSegment> {
+        if catalog.fast_forward(&self.catalog_start, self.catalog.clone_inner()) {
+            return self.buffer;
+        }
+
+        load_buffer_from_segment(catalog, wal.open_segment_reader(self.header.id)?)
+    }
+}
+
+/// The metrics reporting the progress of replaying the WAL on startup
+#[derive(Debug)]
+struct ReplayMetrics {
+    segments_total: U64Gauge,
+    segments_replayed: U64Counter,
+    ops_replayed: U64Counter,
+}
+
+impl ReplayMetrics {
+    fn new(metric_registry: &metric::Registry) -> Self {
+        Self {
+            segments_total: metric_registry
+                .register_metric::<U64Gauge>(
+                    "wal_replay_segments",
+                    "Number of WAL segments to replay on startup",
+                )
+                .recorder(&[]),
+            segments_replayed: metric_registry
+                .register_metric::<U64Counter>(
+                    "wal_replay_segments_replayed",
+                    "Number of WAL segments replayed on startup",
+                )
+                .recorder(&[]),
+            ops_replayed: metric_registry
+                .register_metric::<U64Counter>(
+                    "wal_replay_ops_replayed",
+                    "Number of WAL ops replayed on startup",
+                )
+                .recorder(&[]),
+        }
+    }
+}
+
+/// Reads the WAL segments, and parses, validates and buffers their writes, on a thread per
+/// available CPU, while `apply` is called with each prepared segment in segment id order on the
+/// calling thread. Applying a segment updates the catalog, so it has to happen in order, but
+/// preparing the segments ahead of that doesn't. At most one segment per thread is prepared ahead
+/// of the one being applied, to bound the memory used by prepared segments.
+///
+/// This blocks until the segments are replayed, so it must be called off the async runtime.
+fn replay_segments<W: Wal>(
+    wal: &W,
+    catalog: &Catalog,
+    segment_ids: &[SegmentId],
+    metrics: &ReplayMetrics,
+    mut apply: impl FnMut(PreparedSegment) -> Result<()>,
+) -> Result<()> {
+    if segment_ids.is_empty() {
+        return Ok(());
+    }
+    metrics.segments_total.set(segment_ids.len() as u64);
+
+    let threads = std::thread::available_parallelism()
+        .map(NonZeroUsize::get)
+        .unwrap_or(1)
+        .min(segment_ids.len());
+    let start = Instant::now();
+    info!(
+        segments = segment_ids.len(),
+        threads, "Replaying WAL segments"
+    );
+
+    let window = ReplayWindow::new(threads);
+    let next_to_read = AtomicUsize::new(0);
+    let (tx, rx) = mpsc::channel();
+
+    let total_ops = std::thread::scope(|scope| {
+        // stop the readers on any return, including a panic, so that the scope can join them
+        let _stop = StopOnDrop(&window);
+
+        for _ in 0..threads {
+            let tx = tx.clone();
+            let (window, next_to_read) = (&window, &next_to_read);
+            std::thread::Builder::new()
+                .name("wal-replay".to_string())
+                .spawn_scoped(scope, move || loop {
+                    let index = next_to_read.fetch_add(1, Ordering::Relaxed);
+                    if index >= segment_ids.len() || !window.wait_for(index) {
+                        return;
+                    }
+                    let segment = PreparedSegment::read(wal, segment_ids[index], catalog);
+                    if tx.send((index, segment)).is_err() {
+                        return;
+                    }
+                })
+                .expect("failed to spawn wal replay thread");
+        }
+        drop(tx);
+
+        let mut prepared = BTreeMap::new();
+        let mut total_ops = 0;
+        for (index, segment_id) in segment_ids.iter().enumerate() {
+            let segment = loop {
+                if let Some(segment) = prepared.remove(&index) {
+                    break segment;
+                }
+                let (index, segment) = rx
+                    .recv()
+                    .expect("every segment is sent before the readers exit");
+                prepared.insert(index, segment);
+            };
+
+            if let Some(segment) = segment? {
+                let ops = segment.ops;
+                apply(segment)?;
+                total_ops += ops;
+                metrics.ops_replayed.inc(ops as u64);
+            }
+            metrics.segments_replayed.inc(1);
+            window.advance(index + 1);
+
+            info!(
+                ?segment_id,
+                replayed = index + 1,
+                total = segment_ids.len(),
+                "Replayed WAL segment"
+            );
+        }
+        Ok::<_, Error>(total_ops)
+    })?;
+
+    info!(
+        segments = segment_ids.len(),
+        ops = total_ops,
+        elapsed = ?start.elapsed(),
+        "Finished replaying WAL segments"
+    );
+    Ok(())
+}
+
+/// Limits how far ahead of the segment being applied the segments are read.
+#[derive(Debug)]
+struct ReplayWindow {
+    /// The index of the next segment to apply, or `None` if replay has stopped
+    next_to_apply: Mutex<Option<usize>>,
+    changed: Condvar,
+    size: usize,
+}
+
+impl ReplayWindow {
+    fn new(size: usize) -> Self {
+        Self {
+            next_to_apply: Mutex::new(Some(0)),
+            changed: Condvar::new(),
+            size,
+        }
+    }
+
+    /// Waits until the segment at `index` is within the window, returning `false` if replay
+    /// stopped first.
+    fn wait_for(&self, index: usize) -> bool {
+        let mut next_to_apply = self.next_to_apply.lock();
+        loop {
+            match *next_to_apply {
+                None => return false,
+                Some(next) if index < next + self.size => return true,
+                Some(_) => self.changed.wait(&mut next_to_apply),
+            }
+        }
+    }
+
+    fn advance(&self, next_to_apply: usize) {
+        *self.next_to_apply.lock() = Some(next_to_apply);
+        self.changed.notify_all();
+    }
+
+    fn stop(&self) {
+        *self.next_to_apply.lock() = None;
+        self.changed.notify_all();
+    }
+}
+
+struct StopOnDrop<'a>(&'a ReplayWindow);
+
+impl Drop for StopOnDrop<'_> {
+    fn drop(&mut self) {
+        self.0.stop();
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::catalog::{DatabaseSettings, LastCacheDefinition, TableDefinition};
+    use crate::paths::SegmentWalFilePath;
     use crate::persister::PersisterImpl;
     use crate::test_helpers::lp_to_write_batch;
-    use crate::wal::{WalImpl, WalSegmentWriterNoopImpl};
+    use crate::wal::{ObjectStoreWal, WalImpl, WalRecoveryMode, WalSegmentWriterNoopImpl};
     use crate::Precision;
     use crate::{
-        DatabaseTables, LpWriteOp, ParquetFile, SegmentRange, SequenceNumber, TableParquetFiles,
-        WalOp,
+        CatalogOp, ColumnDataType, DatabaseTables, LpWriteOp, ParquetFile, SegmentRange,
+        SequenceNumber, TableParquetFiles, WalOp,
     };
     use arrow_util::assert_batches_eq;
     use iox_time::Time;
+    use metric::{Attributes, Metric};
     use object_store::memory::InMemory;
     use object_store::ObjectStore;
     use pretty_assertions::assert_eq;
+    use schema::{InfluxColumnType, InfluxFieldType};
     use std::collections::HashMap;
+    use std::io::Write;
 
     #[tokio::test]
     async fn loads_without_wal() {
@@ -201,6 +603,8 @@ mod tests {
             None::<Arc<crate::wal::WalImpl>>,
             Time::from_timestamp_nanos(0),
             SegmentDuration::new_5m(),
+            CatalogLimits::default(),
+            &metric::Registry::default(),
         )
         .await
         .unwrap();
@@ -240,6 +644,168 @@ mod tests {
         assert_eq!(loaded_state.last_segment_id, SegmentId::new(5));
     }
 
+    #[tokio::test]
+    async fn loads_segment_with_torn_tail() {
+        let object_store: Arc<dyn ObjectStore> = Arc::new(InMemory::new());
+        let persister = Arc::new(PersisterImpl::new(Arc::clone(&object_store)));
+        let dir = test_helpers::tmp_dir().unwrap().into_path();
+        let wal = Arc::new(WalImpl::new(dir.clone()).unwrap());
+        let db_name = "db1";
+
+        let LoadedState {
+            catalog,
+            mut open_segments,
+            ..
+        } = load_starting_state(
+            Arc::clone(&persister),
+            Some(Arc::clone(&wal)),
+            Time::from_timestamp_nanos(0),
+            SegmentDuration::new_5m(),
+            CatalogLimits::default(),
+            &metric::Registry::default(),
+        )
+        .await
+        .unwrap();
+
+        let mut current_segment = open_segments.pop().unwrap();
+        let segment_id = current_segment.segment_id();
+        let lp = "cpu,tag1=cupcakes bar=1 10";
+        current_segment
+            .write_wal_ops(vec![WalOp::LpWrite(LpWriteOp {
+                db_name: db_name.to_string(),
+                lp: lp.to_string(),
+                default_time: 0,
+                precision: Precision::Nanosecond,
+            })])
+            .unwrap();
+        current_segment
+            .buffer_writes(lp_to_write_batch(Arc::clone(&catalog), db_name, lp))
+            .unwrap();
+        drop(current_segment);
+
+        // simulate a crash part way through writing the next block
+        let path = SegmentWalFilePath::new(dir.clone(), segment_id);
+        let mut f = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        f.write_all(&[0, 0, 0, 1, 0, 0]).unwrap();
+        drop(f);
+
+        let strict_wal = Arc::new(
+            WalImpl::new(dir.clone())
+                .unwrap()
+                .with_recovery_mode(WalRecoveryMode::Strict),
+        );
+        assert!(load_starting_state(
+            Arc::clone(&persister),
+            Some(strict_wal),
+            Time::from_timestamp_nanos(0),
+            SegmentDuration::new_5m(),
+            CatalogLimits::default(),
+            &metric::Registry::default(),
+        )
+        .await
+        .is_err());
+
+        let loaded_state = load_starting_state(
+            persister,
+            Some(wal),
+            Time::from_timestamp_nanos(0),
+            SegmentDuration::new_5m(),
+            CatalogLimits::default(),
+            &metric::Registry::default(),
+        )
+        .await
+        .unwrap();
+        let current_segment = loaded_state.open_segments.first().unwrap();
+        assert_eq!(current_segment.segment_id(), segment_id);
+
+        let db = loaded_state.catalog.db_schema(db_name).unwrap();
+        let cpu_table = db.get_table("cpu").unwrap();
+        let cpu_data = current_segment
+            .table_record_batches(db_name, "cpu", cpu_table.schema().as_arrow(), &[])
+            .unwrap()
+            .unwrap();
+        let expected = [
+            "+-----+----------+--------------------------------+",
+            "| bar | tag1     | time                           |",
+            "+-----+----------+--------------------------------+",
+            "| 1.0 | cupcakes | 1970-01-01T00:00:00.000000010Z |",
+            "+-----+----------+--------------------------------+",
+        ];
+        assert_batches_eq!(&expected, &cpu_data);
+    }
+
+    #[tokio::test]
+    async fn loads_wal_from_object_store() {
+        let object_store: Arc<dyn ObjectStore> = Arc::new(InMemory::new());
+        let persister = Arc::new(PersisterImpl::new(Arc::clone(&object_store)));
+        let wal = Arc::new(ObjectStoreWal::new(Arc::clone(&object_store)).unwrap());
+        let db_name = "db1";
+
+        let LoadedState {
+            catalog,
+            mut open_segments,
+            ..
+        } = load_starting_state(
+            Arc::clone(&persister),
+            Some(wal),
+            Time::from_timestamp_nanos(0),
+            SegmentDuration::new_5m(),
+            CatalogLimits::default(),
+            &metric::Registry::default(),
+        )
+        .await
+        .unwrap();
+
+        let mut current_segment = open_segments.pop().unwrap();
+        let segment_id = current_segment.segment_id();
+        let lp = "cpu,tag1=cupcakes bar=1 10";
+        current_segment
+            .write_wal_ops(vec![WalOp::LpWrite(LpWriteOp {
+                db_name: db_name.to_string(),
+                lp: lp.to_string(),
+                default_time: 0,
+                precision: Precision::Nanosecond,
+            })])
+            .unwrap();
+        current_segment
+            .buffer_writes(lp_to_write_batch(Arc::clone(&catalog), db_name, lp))
+            .unwrap();
+        drop(current_segment);
+
+        // replay using nothing but the object store
+        let wal = Arc::new(ObjectStoreWal::new(Arc::clone(&object_store)).unwrap());
+        let loaded_state = load_starting_state(
+            persister,
+            Some(wal),
+            Time::from_timestamp_nanos(0),
+            SegmentDuration::new_5m(),
+            CatalogLimits::default(),
+            &metric::Registry::default(),
+        )
+        .await
+        .unwrap();
+        let current_segment = loaded_state.open_segments.first().unwrap();
+        assert_eq!(current_segment.segment_id(), segment_id);
+
+        let db = loaded_state.catalog.db_schema(db_name).unwrap();
+        let cpu_table = db.get_table("cpu").unwrap();
+        let cpu_data = current_segment
+            .table_record_batches(db_name, "cpu", cpu_table.schema().as_arrow(), &[])
+            .unwrap()
+            .unwrap();
+        let expected = [
+            "+-----+----------+--------------------------------+",
+            "| bar | tag1     | time                           |",
+            "+-----+----------+--------------------------------+",
+            "| 1.0 | cupcakes | 1970-01-01T00:00:00.000000010Z |",
+            "+-----+----------+--------------------------------+",
+        ];
+        assert_batches_eq!(&expected, &cpu_data);
+    }
+
     #[tokio::test]
     async fn loads_with_no_persisted_segments_and_wal() {
         let object_store: Arc<dyn ObjectStore> = Arc::new(InMemory::new());
@@ -257,6 +823,8 @@ mod tests {
             Some(Arc::clone(&wal)),
             Time::from_timestamp_nanos(0),
             SegmentDuration::new_5m(),
+            CatalogLimits::default(),
+            &metric::Registry::default(),
         )
         .await
         .unwrap();
@@ -282,6 +850,8 @@ mod tests {
             Some(wal),
             Time::from_timestamp_nanos(0),
             SegmentDuration::new_5m(),
+            CatalogLimits::default(),
+            &metric::Registry::default(),
         )
         .await
         .unwrap();
@@ -326,6 +896,97 @@ mod tests {
         assert_eq!(loaded_state.last_segment_id, SegmentId::new(1));
     }
 
+    #[tokio::test]
+    async fn loads_catalog_ops_from_wal() {
+        let object_store: Arc<dyn ObjectStore> = Arc::new(InMemory::new());
+        let persister = Arc::new(PersisterImpl::new(Arc::clone(&object_store)));
+        let dir = test_helpers::tmp_dir().unwrap().into_path();
+        let wal = Arc::new(WalImpl::new(dir.clone()).unwrap());
+        let db_name = "db1";
+
+        let LoadedState {
+            mut open_segments, ..
+        } = load_starting_state(
+            Arc::clone(&persister),
+            Some(Arc::clone(&wal)),
+            Time::from_timestamp_nanos(0),
+            SegmentDuration::new_5m(),
+            CatalogLimits::default(),
+            &metric::Registry::default(),
+        )
+        .await
+        .unwrap();
+        let mut current_segment = open_segments.pop().unwrap();
+
+        // the catalog is never persisted, so it can only be restored from the ops in the wal,
+        // and the database has a strict schema, so the write is only valid if they are replayed
+        // ahead of it:
+        let mut cpu = TableDefinition::new(
+            "cpu",
+            [
+                ("host", InfluxColumnType::Tag),
+                ("usage", InfluxColumnType::Field(InfluxFieldType::Float)),
+                ("time", InfluxColumnType::Timestamp),
+            ],
+            Option::<Vec<String>>::None,
+        );
+        cpu.add_last_cache(LastCacheDefinition::new("cpu_cache", ["host"], ["usage"], 1).unwrap());
+        let ops = vec![
+            CatalogOp::CreateDatabase {
+                db_name: db_name.to_string(),
+                settings: DatabaseSettings {
+                    strict_schema: true,
+                    ..Default::default()
+                },
+            },
+            CatalogOp::CreateTable {
+                db_name: db_name.to_string(),
+                table: cpu,
+            },
+            CatalogOp::AddColumns {
+                db_name: db_name.to_string(),
+                table_name: "cpu".to_string(),
+                columns: vec![("region".to_string(), ColumnDataType::Tag)],
+            },
+        ];
+        let mut wal_ops: Vec<WalOp> = ops.into_iter().map(WalOp::Catalog).collect();
+        wal_ops.push(WalOp::LpWrite(LpWriteOp {
+            db_name: db_name.to_string(),
+            lp: "cpu,host=a,region=us usage=1 10".to_string(),
+            default_time: 0,
+            precision: Precision::Nanosecond,
+        }));
+        current_segment.write_wal_ops(wal_ops).unwrap();
+
+        let loaded_state = load_starting_state(
+            persister,
+            Some(wal),
+            Time::from_timestamp_nanos(0),
+            SegmentDuration::new_5m(),
+            CatalogLimits::default(),
+            &metric::Registry::default(),
+        )
+        .await
+        .unwrap();
+
+        let db = loaded_state.catalog.db_schema(db_name).unwrap();
+        assert!(db.strict_schema());
+        let cpu_table = db.get_table("cpu").unwrap();
+        assert!(cpu_table.get_last_cache("cpu_cache").is_some());
+        let cpu_data = loaded_state.open_segments[0]
+            .table_record_batches(db_name, "cpu", cpu_table.schema().as_arrow(), &[])
+            .unwrap()
+            .unwrap();
+        let expected = [
+            "+------+--------+--------------------------------+-------+",
+            "| host | region | time                           | usage |",
+            "+------+--------+--------------------------------+-------+",
+            "| a    | us     | 1970-01-01T00:00:00.000000010Z | 1.0   |",
+            "+------+--------+--------------------------------+-------+",
+        ];
+        assert_batches_eq!(&expected, &cpu_data);
+    }
+
     #[tokio::test]
     async fn loads_with_persisted_segments_and_wal() {
         let object_store: Arc<dyn ObjectStore> = Arc::new(InMemory::new());
@@ -343,6 +1004,8 @@ mod tests {
             Some(Arc::clone(&wal)),
             Time::from_timestamp_nanos(0),
             SegmentDuration::new_5m(),
+            CatalogLimits::default(),
+            &metric::Registry::default(),
         )
         .await
         .unwrap();
@@ -410,6 +1073,8 @@ mod tests {
             Some(wal),
             Time::from_timestamp(6 * 60, 0).unwrap(),
             SegmentDuration::new_5m(),
+            CatalogLimits::default(),
+            &metric::Registry::default(),
         )
         .await
         .unwrap();
@@ -442,6 +1107,7 @@ mod tests {
                                         row_count: 1,
                                         min_time: 10,
                                         max_time: 10,
+                                        tombstones: vec![],
                                     }],
                                     sort_key: vec![],
                                 }
@@ -457,6 +1123,7 @@ mod tests {
                                         row_count: 2,
                                         min_time: 15,
                                         max_time: 20,
+                                        tombstones: vec![],
                                     }],
                                     sort_key: vec![],
                                 }
@@ -520,6 +1187,8 @@ mod tests {
             Some(Arc::clone(&wal)),
             Time::from_timestamp_nanos(0),
             SegmentDuration::new_5m(),
+            CatalogLimits::default(),
+            &metric::Registry::default(),
         )
         .await
         .unwrap();
@@ -581,6 +1250,8 @@ mod tests {
             Some(wal),
             Time::from_timestamp(6 * 60, 0).unwrap(),
             SegmentDuration::new_5m(),
+            CatalogLimits::default(),
+            &metric::Registry::default(),
         )
         .await
         .unwrap();
@@ -638,6 +1309,90 @@ mod tests {
         assert_eq!(loaded_state.last_segment_id, SegmentId::new(2));
     }
 
+    #[tokio::test]
+    async fn replays_segments_in_order() {
+        let object_store: Arc<dyn ObjectStore> = Arc::new(InMemory::new());
+        let persister = Arc::new(PersisterImpl::new(Arc::clone(&object_store)));
+        let dir = test_helpers::tmp_dir().unwrap().into_path();
+        let wal = Arc::new(WalImpl::new(dir.clone()).unwrap());
+        let db_name = "db1";
+        let segment_count = 12;
+
+        // each segment creates a new table, so the segments only replay to the same catalog if
+        // they are applied in order
+        let mut range = SegmentRange::test_range();
+        for i in 1..=segment_count {
+            let mut writer = wal.new_segment_writer(SegmentId::new(i), range).unwrap();
+            writer
+                .write_batch(vec![WalOp::LpWrite(LpWriteOp {
+                    db_name: db_name.to_string(),
+                    lp: format!(
+                        "table_{i},tag=a val={i}i {}",
+                        range.start_time.timestamp_nanos()
+                    ),
+                    default_time: 0,
+                    precision: Precision::Nanosecond,
+                })])
+                .unwrap();
+            range = range.next();
+        }
+
+        let metric_registry = metric::Registry::default();
+        let loaded_state = load_starting_state(
+            persister,
+            Some(wal),
+            Time::from_timestamp_nanos(0),
+            SegmentDuration::new_5m(),
+            CatalogLimits::default(),
+            &metric_registry,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(loaded_state.open_segments.len(), 2);
+        assert_eq!(
+            loaded_state.persisting_buffer_segments.len(),
+            segment_count as usize - 2
+        );
+        let db = loaded_state.catalog.db_schema(db_name).unwrap();
+        assert_eq!(db.tables.len(), segment_count as usize);
+
+        let sequence_numbers = loaded_state
+            .persisting_buffer_segments
+            .iter()
+            .map(|segment| (segment.segment_id, segment.catalog_start_sequence_number))
+            .collect::<Vec<_>>();
+        assert!(
+            sequence_numbers
+                .windows(2)
+                .all(|w| w[0].0 < w[1].0 && w[0].1 < w[1].1),
+            "{sequence_numbers:?}"
+        );
+
+        let observe = |name: &'static str| {
+            metric_registry
+                .get_instrument::<Metric<U64Counter>>(name)
+                .unwrap()
+                .get_observer(&Attributes::from(&[]))
+                .unwrap()
+                .fetch()
+        };
+        assert_eq!(
+            observe("wal_replay_segments_replayed"),
+            segment_count as u64
+        );
+        assert_eq!(observe("wal_replay_ops_replayed"), segment_count as u64);
+        assert_eq!(
+            metric_registry
+                .get_instrument::<Metric<U64Gauge>>("wal_replay_segments")
+                .unwrap()
+                .get_observer(&Attributes::from(&[]))
+                .unwrap()
+                .fetch(),
+            segment_count as u64
+        );
+    }
+
     #[tokio::test]
     async fn loads_with_persisting_wal_file_and_no_open_segment() {
         let object_store: Arc<dyn ObjectStore> = Arc::new(InMemory::new());
@@ -652,6 +1407,8 @@ mod tests {
             Some(Arc::clone(&wal)),
             Time::from_timestamp_nanos(0),
             SegmentDuration::new_5m(),
+            CatalogLimits::default(),
+            &metric::Registry::default(),
         )
         .await
         .unwrap();
@@ -665,6 +1422,8 @@ mod tests {
             Some(wal),
             Time::from_timestamp(360, 0).unwrap(),
             SegmentDuration::new_5m(),
+            CatalogLimits::default(),
+            &metric::Registry::default(),
         )
         .await
         .unwrap();
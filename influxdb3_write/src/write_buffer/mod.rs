@@ -1,37 +1,54 @@
 //! Implementation of an in-memory buffer for writes that persists data into a wal if it is configured.
 
+pub mod backpressure;
 pub(crate) mod buffer_segment;
+mod deletes;
 mod flusher;
+mod import;
+mod json;
 mod loader;
 pub mod persisted_files;
 mod persister;
+mod retention;
 mod segment_state;
 mod table_buffer;
 pub(crate) mod validator;
 
 use crate::cache::ParquetCache;
-use crate::catalog::{Catalog, DatabaseSchema};
+use crate::catalog::{
+    Catalog, CatalogLimits, DatabaseLimits, DatabaseSchema, LastCacheDefinition, TIME_COLUMN_NAME,
+};
 use crate::chunk::ParquetChunk;
+use crate::last_cache::LastCacheProvider;
 use crate::persister::PersisterImpl;
+use crate::write_buffer::backpressure::{Backpressure, BackpressureConfig};
+use crate::write_buffer::buffer_segment::{ClosedBufferSegment, OpenBufferSegment};
+use crate::write_buffer::deletes::run_delete_rewrite;
 use crate::write_buffer::flusher::WriteBufferFlusher;
-use crate::write_buffer::loader::load_starting_state;
+use crate::write_buffer::json::JsonLines;
+use crate::write_buffer::loader::{load_starting_state, SEGMENTS_TO_LOAD};
 use crate::write_buffer::persisted_files::PersistedFiles;
 use crate::write_buffer::persister::{
     run_buffer_segment_persist_and_cleanup, run_buffer_size_check_and_persist,
 };
+use crate::write_buffer::retention::run_retention_enforcement;
 use crate::write_buffer::segment_state::SegmentState;
 use crate::write_buffer::validator::WriteValidator;
 use crate::{
-    BufferedWriteRequest, Bufferer, ChunkContainer, ParquetFile, Persister, Precision,
-    SegmentDuration, SequenceNumber, Wal, WalOp, WriteBuffer, WriteLineError,
+    BufferedWriteRequest, Bufferer, CatalogOp, ChunkContainer, DatabaseManager, DeleteOp,
+    DeletePredicate, LastCacheManager, ParquetFile, PersistedSegment, Persister, Precision,
+    SegmentDuration, SegmentId, SequenceNumber, Wal, WalOp, WriteBuffer, WriteLineError,
 };
+use arrow::record_batch::RecordBatch;
 use async_trait::async_trait;
+use bytes::Bytes;
 use data_types::{ChunkId, ChunkOrder, ColumnType, NamespaceName, NamespaceNameError};
 use datafusion::common::DataFusionError;
 use datafusion::datasource::object_store::ObjectStoreUrl;
 use datafusion::execution::context::SessionState;
 use datafusion::logical_expr::Expr;
 use datafusion::physical_plan::SendableRecordBatchStream;
+use futures_util::stream::StreamExt;
 use influxdb_line_protocol::v3::SeriesValue;
 use influxdb_line_protocol::FieldValue;
 use iox_query::chunk_statistics::{create_chunk_statistics, NoColumnRanges};
@@ -42,9 +59,10 @@ use object_store::{ObjectMeta, ObjectStore};
 use observability_deps::tracing::{debug, error};
 use parking_lot::{Mutex, RwLock};
 use parquet_file::storage::ParquetExecInput;
-use schema::Schema;
+use schema::{InfluxFieldType, Schema};
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 use thiserror::Error;
 use tokio::sync::watch;
 
@@ -83,10 +101,89 @@ pub enum Error {
 
     #[error("error from table buffer: {0}")]
     TableBufferError(#[from] table_buffer::Error),
+
+    #[error("error from last cache: {0}")]
+    LastCacheError(#[from] crate::last_cache::Error),
+
+    #[error("database not found: {db_name}")]
+    DatabaseNotFound { db_name: String },
+
+    #[error("table not found: {table_name} in database {db_name}")]
+    TableNotFound { db_name: String, table_name: String },
+
+    #[error("invalid delete predicate: {0}")]
+    InvalidDeletePredicate(String),
+
+    #[error("invalid JSON write: {0}")]
+    InvalidJson(String),
+
+    #[error("invalid arrow write: {0}")]
+    InvalidArrowWrite(String),
+
+    #[error("invalid parquet import: {0}")]
+    InvalidParquetImport(String),
+
+    #[error("error sorting and deduplicating rows: {0}")]
+    SortDedupeError(DataFusionError),
+
+    #[error("error encoding or decoding arrow data: {0}")]
+    ArrowError(#[from] arrow::error::ArrowError),
+
+    #[error("error rewriting parquet file {path} without deleted rows: {message}")]
+    ParquetRewriteError { path: String, message: String },
+
+    #[error(
+        "write queue is full with {queued_writes} writes waiting to be flushed to the wal, \
+        retry after {retry_after:?}"
+    )]
+    WriteQueueFull {
+        queued_writes: usize,
+        retry_after: Duration,
+    },
+
+    #[error(
+        "write buffer is using {buffer_mb}MB, over the limit of {limit_mb}MB, \
+        retry after {retry_after:?}"
+    )]
+    BufferMemoryLimitExceeded {
+        buffer_mb: usize,
+        limit_mb: usize,
+        retry_after: Duration,
+    },
+
+    #[error("last cache not found: {cache_name} on table {table_name} in database {db_name}")]
+    LastCacheNotFound {
+        db_name: String,
+        table_name: String,
+        cache_name: String,
+    },
+}
+
+impl Error {
+    /// How long a client should wait before retrying a write that failed with this error, if it
+    /// was rejected because the buffer is saturated
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::WriteQueueFull { retry_after, .. }
+            | Self::BufferMemoryLimitExceeded { retry_after, .. } => Some(*retry_after),
+            _ => None,
+        }
+    }
 }
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
 
+/// The window of timestamps, relative to the server time, of lines that are accepted by writes.
+/// Lines outside of it are rejected, so that a client with a bad clock can't open segments far
+/// in the past or future.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct WriteTimeWindow {
+    /// Lines with timestamps more than this far before the server time are rejected
+    pub max_write_age: Option<Duration>,
+    /// Lines with timestamps more than this far after the server time are rejected
+    pub max_write_future: Option<Duration>,
+}
+
 #[derive(Debug)]
 pub struct WriteRequest<'a> {
     pub db_name: NamespaceName<'static>,
@@ -101,31 +198,61 @@ pub struct WriteBufferImpl<W, T> {
     parquet_cache: Arc<ParquetCache>,
     segment_state: Arc<RwLock<SegmentState<T, W>>>,
     persisted_files: Arc<PersistedFiles>,
+    /// Held while the segment info files are read, modified, and written back, by deletes,
+    /// retention enforcement, the rewrite of files with tombstones, and segment persistence, so
+    /// that none of their updates are lost
+    segment_info_lock: Arc<tokio::sync::Mutex<()>>,
+    last_cache: Arc<LastCacheProvider>,
     wal: Option<Arc<W>>,
     write_buffer_flusher: WriteBufferFlusher,
+    backpressure: Backpressure,
+    write_time_window: WriteTimeWindow,
     segment_duration: SegmentDuration,
-    #[allow(dead_code)]
     time_provider: Arc<T>,
+    executor: Arc<iox_query::exec::Executor>,
     #[allow(dead_code)]
     segment_persist_handle: Mutex<tokio::task::JoinHandle<()>>,
     #[allow(dead_code)]
     shutdown_segment_persist_tx: watch::Sender<()>,
     #[allow(dead_code)]
     buffer_check_handle: Mutex<tokio::task::JoinHandle<()>>,
+    #[allow(dead_code)]
+    retention_handle: Mutex<tokio::task::JoinHandle<()>>,
+    #[allow(dead_code)]
+    delete_rewrite_handle: Mutex<tokio::task::JoinHandle<()>>,
 }
 
 impl<W: Wal, T: TimeProvider> WriteBufferImpl<W, T> {
+    #[allow(clippy::too_many_arguments)]
     pub async fn new(
         persister: Arc<PersisterImpl>,
         wal: Option<Arc<W>>,
         time_provider: Arc<T>,
         segment_duration: SegmentDuration,
         executor: Arc<iox_query::exec::Executor>,
+        metric_registry: Arc<metric::Registry>,
         buffer_mem_limit_mb: usize,
+        catalog_limits: CatalogLimits,
     ) -> Result<Self> {
         let now = time_provider.now();
-        let loaded_state =
-            load_starting_state(Arc::clone(&persister), wal.clone(), now, segment_duration).await?;
+        let loaded_state = load_starting_state(
+            Arc::clone(&persister),
+            wal.clone(),
+            now,
+            segment_duration,
+            catalog_limits,
+            &metric_registry,
+        )
+        .await?;
+
+        let last_cache = Arc::new(LastCacheProvider::new_from_catalog(&loaded_state.catalog)?);
+        rebuild_last_caches(
+            &last_cache,
+            &loaded_state.catalog,
+            &loaded_state.open_segments,
+            &loaded_state.persisting_buffer_segments,
+            &loaded_state.replayed_deletes,
+        )?;
 
         let segment_state = Arc::new(RwLock::new(SegmentState::new(
             segment_duration,
@@ -137,14 +264,27 @@ impl<W: Wal, T: TimeProvider> WriteBufferImpl<W, T> {
             wal.clone(),
         )));
 
+        let has_older_segments = loaded_state.persisted_segments.len() >= SEGMENTS_TO_LOAD;
         let persisted_files = Arc::new(PersistedFiles::new_from_persisted_segments(
             loaded_state.persisted_segments,
         ));
+        if has_older_segments {
+            // the segments older than those loaded may have been given tombstones by deletes
+            // before the restart, that their files haven't been rewritten for
+            persisted_files.add_older_segment_tombstones();
+        }
+        let segment_info_lock = Arc::new(tokio::sync::Mutex::new(()));
 
-        let write_buffer_flusher = WriteBufferFlusher::new(Arc::clone(&segment_state));
+        let flush_config = wal
+            .as_ref()
+            .map(|wal| wal.flush_config())
+            .unwrap_or_default();
+        let write_buffer_flusher =
+            WriteBufferFlusher::new(Arc::clone(&segment_state), flush_config);
 
         let segment_state_persister = Arc::clone(&segment_state);
         let persisted_files_persister = Arc::clone(&persisted_files);
+        let segment_info_lock_persister = Arc::clone(&segment_info_lock);
         let time_provider_persister = Arc::clone(&time_provider);
         let wal_perister = wal.clone();
         let cloned_persister = Arc::clone(&persister);
@@ -157,6 +297,7 @@ impl<W: Wal, T: TimeProvider> WriteBufferImpl<W, T> {
                 cloned_persister,
                 segment_state_persister,
                 persisted_files_persister,
+                segment_info_lock_persister,
                 shutdown_rx,
                 time_provider_persister,
                 wal_perister,
@@ -168,14 +309,54 @@ impl<W: Wal, T: TimeProvider> WriteBufferImpl<W, T> {
         let segment_state_persister = Arc::clone(&segment_state);
         let cloned_persister = Arc::clone(&persister);
         let cloned_executor = Arc::clone(&executor);
+        let backpressure = Backpressure::new(&metric_registry);
+        let buffer_size = backpressure.buffer_size();
 
         let buffer_check_handle = tokio::task::spawn(async move {
             run_buffer_size_check_and_persist(
                 cloned_persister,
                 segment_state_persister,
-                shutdown,
+                shutdown.clone(),
                 cloned_executor,
                 buffer_mem_limit_mb,
+                buffer_size,
+            )
+            .await;
+        });
+
+        let cloned_persister = Arc::clone(&persister);
+        let cloned_catalog = Arc::clone(&loaded_state.catalog);
+        let persisted_files_retention = Arc::clone(&persisted_files);
+        let segment_info_lock_retention = Arc::clone(&segment_info_lock);
+        let last_cache_retention = Arc::clone(&last_cache);
+        let time_provider_retention = Arc::clone(&time_provider);
+
+        let shutdown_retention = shutdown.clone();
+        let retention_handle = tokio::task::spawn(async move {
+            run_retention_enforcement(
+                cloned_persister,
+                cloned_catalog,
+                persisted_files_retention,
+                segment_info_lock_retention,
+                last_cache_retention,
+                time_provider_retention,
+                shutdown_retention,
+            )
+            .await;
+        });
+
+        let cloned_persister = Arc::clone(&persister);
+        let persisted_files_rewrite = Arc::clone(&persisted_files);
+        let segment_info_lock_rewrite = Arc::clone(&segment_info_lock);
+        let time_provider_rewrite = Arc::clone(&time_provider);
+
+        let delete_rewrite_handle = tokio::task::spawn(async move {
+            run_delete_rewrite(
+                cloned_persister,
+                persisted_files_rewrite,
+                segment_info_lock_rewrite,
+                time_provider_rewrite,
+                shutdown,
             )
             .await;
         });
@@ -187,19 +368,50 @@ impl<W: Wal, T: TimeProvider> WriteBufferImpl<W, T> {
             persister,
             wal,
             write_buffer_flusher,
+            backpressure,
+            write_time_window: WriteTimeWindow::default(),
             time_provider,
             segment_duration,
+            executor,
             segment_persist_handle: Mutex::new(segment_persist_handle),
             shutdown_segment_persist_tx,
             buffer_check_handle: Mutex::new(buffer_check_handle),
+            retention_handle: Mutex::new(retention_handle),
+            delete_rewrite_handle: Mutex::new(delete_rewrite_handle),
             persisted_files,
+            segment_info_lock,
+            last_cache,
         })
     }
 
+    /// Set the thresholds at which writes are rejected, rather than queued, while the buffer is
+    /// saturated
+    pub fn with_backpressure_config(mut self, config: BackpressureConfig) -> Self {
+        self.backpressure.set_config(config);
+        self
+    }
+
+    /// Set the window of timestamps, around the server time, of lines that are accepted by writes
+    pub fn with_write_time_window(mut self, window: WriteTimeWindow) -> Self {
+        self.write_time_window = window;
+        self
+    }
+
+    /// Set the number of unique keys held by each last cache, beyond which the keys with the
+    /// least recent rows are evicted
+    pub fn with_last_cache_max_keys(self, max_keys: usize) -> Self {
+        self.last_cache.set_max_keys(max_keys);
+        self
+    }
+
     pub fn catalog(&self) -> Arc<Catalog> {
         Arc::clone(&self.catalog)
     }
 
+    pub fn last_cache_provider(&self) -> Arc<LastCacheProvider> {
+        Arc::clone(&self.last_cache)
+    }
+
     pub fn persisted_files(&self) -> Arc<PersistedFiles> {
         Arc::clone(&self.persisted_files)
     }
@@ -213,14 +425,96 @@ impl<W: Wal, T: TimeProvider> WriteBufferImpl<W, T> {
         precision: Precision,
     ) -> Result<BufferedWriteRequest> {
         debug!("write_lp to {} in writebuffer", db_name);
+        self.backpressure
+            .check(self.write_buffer_flusher.queued_writes())?;
 
         let result = WriteValidator::initialize(db_name.clone(), self.catalog())?
+            .with_time_window(self.write_time_window, ingest_time, precision)
             .v1_parse_lines_and_update_schema(lp, accept_partial)?
             .convert_lines_to_buffer(ingest_time, self.segment_duration, precision);
 
+        self.buffer_and_cache(result.valid_segmented_data).await?;
+
+        Ok(BufferedWriteRequest {
+            db_name,
+            invalid_lines: result.errors,
+            line_count: result.line_count,
+            field_count: result.field_count,
+            index_count: result.index_count,
+        })
+    }
+
+    /// Writes the data to the open segments, then updates the last caches with its rows
+    async fn buffer_and_cache(&self, valid_segmented_data: Vec<ValidSegmentedData>) -> Result<()> {
+        // the cache is only updated once the write has been buffered, so a write that fails
+        // doesn't leave its rows in the cache:
+        let cache_writes = self.last_cache.cached_rows(&valid_segmented_data);
         self.write_buffer_flusher
-            .write_to_open_segment(result.valid_segmented_data)
+            .write_to_open_segment(valid_segmented_data)
             .await?;
+        self.last_cache.write_rows_to_cache(cache_writes);
+        Ok(())
+    }
+
+    async fn write_json(
+        &self,
+        db_name: NamespaceName<'static>,
+        json: &str,
+        ingest_time: Time,
+        accept_partial: bool,
+        precision: Precision,
+    ) -> Result<BufferedWriteRequest> {
+        debug!("write_json to {} in writebuffer", db_name);
+        let mut json_lines =
+            JsonLines::parse(json).map_err(|e| Error::InvalidJson(e.to_string()))?;
+        if !accept_partial && !json_lines.errors.is_empty() {
+            return Err(Error::ParseError(json_lines.errors.remove(0)));
+        }
+
+        // the rows are written as line protocol, so that the write is logged to the WAL, and
+        // replayed, the same way as other writes:
+        let mut result = self
+            .write_lp(
+                db_name,
+                &json_lines.lp,
+                ingest_time,
+                accept_partial,
+                precision,
+            )
+            .await
+            .map_err(|e| match e {
+                Error::ParseError(e) => Error::ParseError(json_lines.row_error(e)),
+                e => e,
+            })?;
+
+        let invalid_lines = std::mem::take(&mut result.invalid_lines);
+        result.line_count += json_lines.errors.len();
+        result.invalid_lines = invalid_lines
+            .into_iter()
+            .map(|e| json_lines.row_error(e))
+            .chain(json_lines.errors)
+            .collect();
+        result.invalid_lines.sort_by_key(|e| e.line_number);
+        Ok(result)
+    }
+
+    async fn write_arrow(
+        &self,
+        db_name: NamespaceName<'static>,
+        table_name: &str,
+        batches: Vec<RecordBatch>,
+        ingest_time: Time,
+    ) -> Result<BufferedWriteRequest> {
+        debug!("write_arrow to {}.{} in writebuffer", db_name, table_name);
+        self.backpressure
+            .check(self.write_buffer_flusher.queued_writes())?;
+
+        let result = WriteValidator::initialize(db_name.clone(), self.catalog())?
+            .with_time_window(self.write_time_window, ingest_time, Precision::Nanosecond)
+            .validate_batches_and_update_schema(table_name, batches)?
+            .convert_batches_to_buffer(ingest_time, self.segment_duration)?;
+
+        self.buffer_and_cache(result.valid_segmented_data).await?;
 
         Ok(BufferedWriteRequest {
             db_name,
@@ -231,6 +525,68 @@ impl<W: Wal, T: TimeProvider> WriteBufferImpl<W, T> {
         })
     }
 
+    async fn import_parquet(
+        &self,
+        db_name: NamespaceName<'static>,
+        table_name: &str,
+        parquet: Bytes,
+    ) -> Result<PersistedSegment> {
+        debug!(
+            "import_parquet to {}.{} in writebuffer",
+            db_name, table_name
+        );
+        let batches = import::parquet_record_batches(parquet)?;
+
+        // imports are of historical data, so they aren't limited by the write time window
+        let (catalog_ops, segment_rows) =
+            WriteValidator::initialize(db_name.clone(), self.catalog())?
+                .validate_batches_and_update_schema(table_name, batches)
+                .map_err(|e| match e {
+                    Error::InvalidArrowWrite(message) => Error::InvalidParquetImport(message),
+                    e => e,
+                })?
+                .convert_batches_to_segment_rows(self.segment_duration);
+        if segment_rows.is_empty() {
+            return Err(Error::InvalidParquetImport(
+                "the file has no rows".to_string(),
+            ));
+        }
+
+        // the changes to the catalog are logged and persisted like those that aren't made
+        // through writes, before the files that depend on them
+        self.write_catalog_ops(db_name.as_str(), catalog_ops)
+            .await?;
+        let segment_id = self.segment_state.write().next_segment_id();
+        self.persist_catalog().await?;
+
+        let table = self
+            .catalog
+            .db_schema(db_name.as_str())
+            .and_then(|db_schema| db_schema.get_table(table_name).cloned())
+            .ok_or_else(|| Error::TableNotFound {
+                db_name: db_name.to_string(),
+                table_name: table_name.to_string(),
+            })?;
+        let persisted_segment = import::persist_imported_rows(
+            db_name.as_str(),
+            &table,
+            segment_id,
+            self.segment_duration,
+            segment_rows,
+            &self.persister,
+            &self.executor,
+        )
+        .await?;
+        {
+            let _segment_info_guard = self.segment_info_lock.lock().await;
+            self.persister.persist_segment(&persisted_segment).await?;
+            self.persisted_files
+                .add_persisted_segment_files(persisted_segment.clone());
+        }
+
+        Ok(persisted_segment)
+    }
+
     async fn write_lp_v3(
         &self,
         db_name: NamespaceName<'static>,
@@ -239,13 +595,15 @@ impl<W: Wal, T: TimeProvider> WriteBufferImpl<W, T> {
         accept_partial: bool,
         precision: Precision,
     ) -> Result<BufferedWriteRequest> {
+        self.backpressure
+            .check(self.write_buffer_flusher.queued_writes())?;
+
         let result = WriteValidator::initialize(db_name.clone(), self.catalog())?
+            .with_time_window(self.write_time_window, ingest_time, precision)
             .v3_parse_lines_and_update_schema(lp, accept_partial)?
             .convert_lines_to_buffer(ingest_time, self.segment_duration, precision);
 
-        self.write_buffer_flusher
-            .write_to_open_segment(result.valid_segmented_data)
-            .await?;
+        self.buffer_and_cache(result.valid_segmented_data).await?;
 
         Ok(BufferedWriteRequest {
             db_name,
@@ -282,6 +640,11 @@ impl<W: Wal, T: TimeProvider> WriteBufferImpl<W, T> {
 
         let object_store_url = self.persister.object_store_url();
 
+        // files whose data has entirely expired may not have been removed yet, so they are
+        // filtered out of the persisted files below:
+        let retention_cutoff =
+            db_schema.retention_cutoff_ns(self.time_provider.now().timestamp_nanos());
+
         let segment_state = self.segment_state.read();
         let mut chunks = segment_state.get_table_chunks(
             db_schema,
@@ -292,7 +655,11 @@ impl<W: Wal, T: TimeProvider> WriteBufferImpl<W, T> {
             self.persister.object_store(),
             ctx,
         )?;
-        let parquet_files = self.persisted_files.get_files(database_name, table_name);
+        let parquet_files = self
+            .persisted_files
+            .get_files(database_name, table_name)
+            .into_iter()
+            .filter(|file| retention_cutoff.map_or(true, |cutoff| file.max_time >= cutoff));
 
         let mut chunk_order = chunks.len() as i64;
 
@@ -352,6 +719,7 @@ impl<W: Wal, T: TimeProvider> WriteBufferImpl<W, T> {
                 id: ChunkId::new(),
                 chunk_order: ChunkOrder::new(chunk_order),
                 parquet_exec,
+                tombstones: vec![],
             };
 
             chunk_order += 1;
@@ -399,6 +767,502 @@ impl<W: Wal, T: TimeProvider> WriteBufferImpl<W, T> {
         Ok(self.parquet_cache.purge_cache().await?)
     }
 
+    async fn create_database(
+        &self,
+        db_name: &str,
+        retention_period: Option<Duration>,
+        limits: DatabaseLimits,
+        strict_schema: bool,
+    ) -> Result<()> {
+        let mut db_schema = DatabaseSchema::new(db_name);
+        db_schema.retention_period = retention_period;
+        db_schema.limits = limits;
+        db_schema.strict_schema = strict_schema;
+        let ops = self.catalog.create_database(db_schema)?;
+        self.write_catalog_ops(db_name, ops).await?;
+        self.persist_catalog().await
+    }
+
+    async fn set_retention_period(
+        &self,
+        db_name: &str,
+        retention_period: Option<Duration>,
+    ) -> Result<()> {
+        let (sequence, db_schema) =
+            self.catalog
+                .db_schema_and_sequence(db_name)
+                .ok_or_else(|| Error::DatabaseNotFound {
+                    db_name: db_name.to_string(),
+                })?;
+        let mut new_db_schema = db_schema.as_ref().clone();
+        new_db_schema.retention_period = retention_period;
+        let ops = self
+            .catalog
+            .replace_database(sequence, Arc::new(new_db_schema))?;
+        self.write_catalog_ops(db_name, ops).await?;
+        self.persist_catalog().await
+    }
+
+    async fn set_database_limits(&self, db_name: &str, limits: DatabaseLimits) -> Result<()> {
+        let (sequence, db_schema) =
+            self.catalog
+                .db_schema_and_sequence(db_name)
+                .ok_or_else(|| Error::DatabaseNotFound {
+                    db_name: db_name.to_string(),
+                })?;
+        let mut new_db_schema = db_schema.as_ref().clone();
+        new_db_schema.limits = limits;
+        let ops = self
+            .catalog
+            .replace_database(sequence, Arc::new(new_db_schema))?;
+        self.write_catalog_ops(db_name, ops).await?;
+        self.persist_catalog().await
+    }
+
+    async fn set_strict_schema(&self, db_name: &str, strict_schema: bool) -> Result<()> {
+        let (sequence, db_schema) =
+            self.catalog
+                .db_schema_and_sequence(db_name)
+                .ok_or_else(|| Error::DatabaseNotFound {
+                    db_name: db_name.to_string(),
+                })?;
+        let mut new_db_schema = db_schema.as_ref().clone();
+        new_db_schema.strict_schema = strict_schema;
+        let ops = self
+            .catalog
+            .replace_database(sequence, Arc::new(new_db_schema))?;
+        self.write_catalog_ops(db_name, ops).await?;
+        self.persist_catalog().await
+    }
+
+    async fn create_table(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        tags: Vec<String>,
+        fields: Vec<(String, InfluxFieldType)>,
+        series_key: Option<Vec<String>>,
+        widen_numeric_fields: bool,
+    ) -> Result<()> {
+        let (sequence, db_schema) =
+            self.catalog
+                .db_schema_and_sequence(db_name)
+                .ok_or_else(|| Error::DatabaseNotFound {
+                    db_name: db_name.to_string(),
+                })?;
+        let mut new_db_schema = db_schema.as_ref().clone();
+        new_db_schema.create_table(
+            table_name,
+            &tags,
+            &fields,
+            series_key.as_deref(),
+            widen_numeric_fields,
+        )?;
+        let ops = self
+            .catalog
+            .replace_database(sequence, Arc::new(new_db_schema))?;
+        self.write_catalog_ops(db_name, ops).await?;
+        self.persist_catalog().await
+    }
+
+    async fn set_table_field_widening(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        widen_numeric_fields: bool,
+    ) -> Result<()> {
+        let (sequence, db_schema) =
+            self.catalog
+                .db_schema_and_sequence(db_name)
+                .ok_or_else(|| Error::DatabaseNotFound {
+                    db_name: db_name.to_string(),
+                })?;
+        let mut new_db_schema = db_schema.as_ref().clone();
+        new_db_schema
+            .tables
+            .get_mut(table_name)
+            .ok_or_else(|| Error::TableNotFound {
+                db_name: db_name.to_string(),
+                table_name: table_name.to_string(),
+            })?
+            .widen_numeric_fields = widen_numeric_fields;
+        let ops = self
+            .catalog
+            .replace_database(sequence, Arc::new(new_db_schema))?;
+        self.write_catalog_ops(db_name, ops).await?;
+        self.persist_catalog().await
+    }
+
+    async fn delete_database(&self, db_name: &str) -> Result<()> {
+        // the persisted files are removed from memory and the segment info files together, so a
+        // segment persisted in between can't bring them back
+        let _segment_info_guard = self.segment_info_lock.lock().await;
+
+        // remove the database from the catalog while holding the segment state lock, so that no
+        // writes are buffered for it between removing it from the catalog and the open segments
+        let (op, deleted_files) = {
+            let mut segment_state = self.segment_state.write();
+            let op = self
+                .catalog
+                .delete_database(db_name, self.time_provider.now().timestamp_nanos())
+                .map_err(|e| match e {
+                    crate::catalog::Error::DatabaseNotFound { db_name } => {
+                        Error::DatabaseNotFound { db_name }
+                    }
+                    e => e.into(),
+                })?;
+            segment_state.remove_database(db_name);
+            (op, self.persisted_files.remove_database(db_name))
+        };
+        self.last_cache.delete_caches_for_db(db_name);
+        self.write_catalog_ops(db_name, vec![op]).await?;
+        self.persist_catalog().await?;
+        self.remove_persisted_files(db_name, None, deleted_files)
+            .await
+    }
+
+    async fn delete_table(&self, db_name: &str, table_name: &str) -> Result<()> {
+        let (sequence, db_schema) =
+            self.catalog
+                .db_schema_and_sequence(db_name)
+                .ok_or_else(|| Error::DatabaseNotFound {
+                    db_name: db_name.to_string(),
+                })?;
+        let mut new_db_schema = db_schema.as_ref().clone();
+        new_db_schema
+            .delete_table(table_name, self.time_provider.now().timestamp_nanos())
+            .ok_or_else(|| Error::TableNotFound {
+                db_name: db_name.to_string(),
+                table_name: table_name.to_string(),
+            })?;
+
+        // see delete_database for why the segment info and segment state locks are held while
+        // updating the catalog
+        let _segment_info_guard = self.segment_info_lock.lock().await;
+        let (ops, deleted_files) = {
+            let mut segment_state = self.segment_state.write();
+            let ops = self
+                .catalog
+                .replace_database(sequence, Arc::new(new_db_schema))?;
+            segment_state.remove_table(db_name, table_name);
+            (ops, self.persisted_files.remove_table(db_name, table_name))
+        };
+        self.last_cache.delete_caches_for_table(db_name, table_name);
+        self.write_catalog_ops(db_name, ops).await?;
+        self.persist_catalog().await?;
+        self.remove_persisted_files(db_name, Some(table_name), deleted_files)
+            .await
+    }
+
+    async fn delete_rows(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        predicate: DeletePredicate,
+    ) -> Result<()> {
+        let db_schema = self
+            .catalog
+            .db_schema(db_name)
+            .ok_or_else(|| Error::DatabaseNotFound {
+                db_name: db_name.to_string(),
+            })?;
+        let table = db_schema
+            .get_table(table_name)
+            .ok_or_else(|| Error::TableNotFound {
+                db_name: db_name.to_string(),
+                table_name: table_name.to_string(),
+            })?;
+        if predicate.min_time > predicate.max_time {
+            return Err(Error::InvalidDeletePredicate(format!(
+                "start time {} is after stop time {}",
+                predicate.min_time, predicate.max_time
+            )));
+        }
+        let index_columns = table.index_columns();
+        if let Some((tag, _)) = predicate
+            .tags
+            .iter()
+            .find(|(tag, _)| !index_columns.contains(&tag.as_str()))
+        {
+            return Err(Error::InvalidDeletePredicate(format!(
+                "{tag} is not a tag of table {table_name}"
+            )));
+        }
+
+        let op = DeleteOp {
+            db_name: db_name.to_string(),
+            table_name: table_name.to_string(),
+            predicate,
+            deleted_at_ns: self.time_provider.now().timestamp_nanos(),
+        };
+        self.write_wal_ops(db_name, vec![WalOp::Delete(op.clone())])
+            .await?;
+
+        // deletes are recorded for the buffered segments and in the segment info files while
+        // holding the segment info lock, so a segment being persisted gets the tombstones from
+        // one or the other
+        let _segment_info_guard = self.segment_info_lock.lock().await;
+        {
+            let mut segment_state = self.segment_state.write();
+            segment_state.delete_rows(&op)?;
+            self.persisted_files
+                .add_tombstone(db_name, table_name, &op.predicate);
+        }
+        self.last_cache
+            .delete_rows(db_name, table_name, &op.predicate);
+
+        // record the tombstones in the segment info files, so that they're loaded on restart
+        // and the files are rewritten without the deleted rows. This includes the segments older
+        // than those whose files are loaded, as their files would otherwise keep the rows.
+        let mut tombstoned = false;
+        for mut segment in self.persister.load_all_segments().await? {
+            if !op
+                .predicate
+                .overlaps(segment.segment_min_time, segment.segment_max_time)
+            {
+                continue;
+            }
+            if op.add_tombstones(&mut segment.databases) {
+                self.persister.persist_segment(&segment).await?;
+                tombstoned = true;
+            }
+        }
+        if tombstoned {
+            self.persisted_files.add_older_segment_tombstones();
+        }
+
+        Ok(())
+    }
+
+    /// Remove the persisted files of a database, or of a single table in it, from the segment
+    /// info files, so they are not loaded on restart, and delete them from the object store
+    ///
+    /// This must be called while holding the segment info lock.
+    async fn remove_persisted_files(
+        &self,
+        db_name: &str,
+        table_name: Option<&str>,
+        deleted_files: Vec<ParquetFile>,
+    ) -> Result<()> {
+        for mut segment in self.persister.load_segments(SEGMENTS_TO_LOAD).await? {
+            let removed_files = match table_name {
+                None => segment
+                    .databases
+                    .remove(db_name)
+                    .map(|db_tables| db_tables.tables.into_values().collect::<Vec<_>>()),
+                Some(table_name) => {
+                    let table = segment
+                        .databases
+                        .get_mut(db_name)
+                        .and_then(|db_tables| db_tables.tables.remove(table_name));
+                    if segment
+                        .databases
+                        .get(db_name)
+                        .is_some_and(|db_tables| db_tables.tables.is_empty())
+                    {
+                        segment.databases.remove(db_name);
+                    }
+                    table.map(|table| vec![table])
+                }
+            };
+            let Some(removed_files) = removed_files else {
+                continue;
+            };
+            for file in removed_files.iter().flat_map(|t| &t.parquet_files) {
+                segment.segment_parquet_size_bytes = segment
+                    .segment_parquet_size_bytes
+                    .saturating_sub(file.size_bytes);
+                segment.segment_row_count =
+                    segment.segment_row_count.saturating_sub(file.row_count);
+            }
+            self.persister.persist_segment(&segment).await?;
+        }
+
+        // remove all of the objects under the database or table, including any that were
+        // persisted but not yet recorded in a segment info file
+        let object_store = self.persister.object_store();
+        let prefix = match table_name {
+            None => ObjPath::from(format!("dbs/{db_name}")),
+            Some(table_name) => ObjPath::from(format!("dbs/{db_name}/{table_name}")),
+        };
+        let mut paths: Vec<ObjPath> = deleted_files
+            .into_iter()
+            .map(|file| ObjPath::from(file.path))
+            .collect();
+        let mut list = object_store.list(Some(&prefix));
+        while let Some(item) = list.next().await {
+            match item {
+                Ok(meta) => paths.push(meta.location),
+                Err(e) => error!("Error listing deleted parquet files: {}", e),
+            }
+        }
+        paths.sort();
+        paths.dedup();
+        for path in paths {
+            if let Err(e) = object_store.delete(&path).await {
+                error!("Error deleting parquet file: {}", e);
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn create_last_cache(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        cache_name: Option<&str>,
+        count: Option<usize>,
+        key_columns: Option<Vec<String>>,
+        value_columns: Option<Vec<String>>,
+    ) -> Result<LastCacheDefinition> {
+        let (sequence, db_schema) =
+            self.catalog
+                .db_schema_and_sequence(db_name)
+                .ok_or_else(|| Error::DatabaseNotFound {
+                    db_name: db_name.to_string(),
+                })?;
+        let table_def = db_schema
+            .get_table(table_name)
+            .ok_or_else(|| Error::TableNotFound {
+                db_name: db_name.to_string(),
+                table_name: table_name.to_string(),
+            })?;
+
+        let key_columns = key_columns.unwrap_or_else(|| {
+            table_def
+                .index_columns()
+                .into_iter()
+                .map(ToString::to_string)
+                .collect()
+        });
+        let value_columns = value_columns.unwrap_or_else(|| {
+            table_def
+                .schema
+                .iter()
+                .map(|(_, field)| field.name())
+                .filter(|name| *name != TIME_COLUMN_NAME && !key_columns.contains(*name))
+                .cloned()
+                .collect()
+        });
+        let cache_name = cache_name.map(ToString::to_string).unwrap_or_else(|| {
+            format!(
+                "{table_name}_{keys}_last_cache",
+                keys = key_columns.join("_")
+            )
+        });
+        let definition =
+            LastCacheDefinition::new(cache_name, key_columns, value_columns, count.unwrap_or(1))
+                .map_err(Error::CatalogUpdateError)?;
+
+        // creating the cache validates the definition against the table schema, and ensures
+        // that a cache with the same name does not already exist:
+        self.last_cache
+            .create_cache(db_name, table_def, &definition)?;
+
+        let mut new_db_schema = db_schema.as_ref().clone();
+        new_db_schema
+            .tables
+            .get_mut(table_name)
+            .expect("table exists in database schema")
+            .add_last_cache(definition.clone());
+        let ops = match self
+            .catalog
+            .replace_database(sequence, Arc::new(new_db_schema))
+        {
+            Ok(ops) => ops,
+            Err(e) => {
+                self.last_cache
+                    .delete_cache(db_name, table_name, &definition.name);
+                return Err(e.into());
+            }
+        };
+        self.write_catalog_ops(db_name, ops).await?;
+        self.persist_catalog().await?;
+
+        Ok(definition)
+    }
+
+    async fn delete_last_cache(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        cache_name: &str,
+    ) -> Result<()> {
+        let (sequence, db_schema) =
+            self.catalog
+                .db_schema_and_sequence(db_name)
+                .ok_or_else(|| Error::DatabaseNotFound {
+                    db_name: db_name.to_string(),
+                })?;
+        let mut new_db_schema = db_schema.as_ref().clone();
+        let table_def =
+            new_db_schema
+                .tables
+                .get_mut(table_name)
+                .ok_or_else(|| Error::TableNotFound {
+                    db_name: db_name.to_string(),
+                    table_name: table_name.to_string(),
+                })?;
+        if !table_def.remove_last_cache(cache_name) {
+            return Err(Error::LastCacheNotFound {
+                db_name: db_name.to_string(),
+                table_name: table_name.to_string(),
+                cache_name: cache_name.to_string(),
+            });
+        }
+        let ops = self
+            .catalog
+            .replace_database(sequence, Arc::new(new_db_schema))?;
+        self.last_cache
+            .delete_cache(db_name, table_name, cache_name);
+        self.write_catalog_ops(db_name, ops).await?;
+        self.persist_catalog().await?;
+
+        Ok(())
+    }
+
+    /// Write the [`CatalogOp`]s for a change to the catalog that is not made through a write to
+    /// the WAL, in the segment for the current time, so the change is replayed in order with the
+    /// writes around it on restart
+    async fn write_catalog_ops(&self, db_name: &str, ops: Vec<CatalogOp>) -> Result<()> {
+        self.write_wal_ops(db_name, ops.into_iter().map(WalOp::Catalog).collect())
+            .await
+    }
+
+    /// Write ops that aren't made through a write to the WAL, in the segment for the current
+    /// time
+    async fn write_wal_ops(&self, db_name: &str, wal_ops: Vec<WalOp>) -> Result<()> {
+        if wal_ops.is_empty() {
+            return Ok(());
+        }
+        let now_secs = self.time_provider.now().timestamp_nanos() / 1_000_000_000;
+        let segmented_data = ValidSegmentedData {
+            database_name: NamespaceName::new(db_name.to_string())?,
+            segment_start: self.segment_duration.start_time(now_secs),
+            table_batches: HashMap::new(),
+            wal_ops,
+            starting_catalog_sequence_number: self.catalog.sequence_number(),
+        };
+        self.write_buffer_flusher
+            .write_to_open_segment(vec![segmented_data])
+            .await
+    }
+
+    /// Persist the current state of the catalog
+    ///
+    /// This is used for changes to the catalog that are not made through writes, and would
+    /// otherwise not be persisted until the next segment is persisted. The changes are also
+    /// written to the WAL, but persisting the catalog keeps it consistent with the segment info
+    /// files, which are updated immediately for deletes.
+    async fn persist_catalog(&self) -> Result<()> {
+        let segment_id = self.segment_state.read().last_segment_id();
+        self.persister
+            .persist_catalog(segment_id, Catalog::from_inner(self.catalog.clone_inner()))
+            .await?;
+        Ok(())
+    }
+
     #[cfg(test)]
     fn get_table_record_batches(
         &self,
@@ -428,8 +1292,14 @@ pub(crate) fn parquet_chunk_from_file(
         &partition_key,
     );
 
+    // the row count of a file with tombstones includes rows that are filtered out when it's
+    // scanned, so it can't be used to answer queries from the statistics
+    let row_count = parquet_file
+        .tombstones
+        .is_empty()
+        .then_some(parquet_file.row_count as usize);
     let chunk_stats = create_chunk_statistics(
-        Some(parquet_file.row_count as usize),
+        row_count,
         table_schema,
         Some(parquet_file.timestamp_min_max()),
         &NoColumnRanges,
@@ -449,6 +1319,8 @@ pub(crate) fn parquet_chunk_from_file(
         object_store,
     };
 
+    // the chunk uses the current schema of the table, so that files persisted before a field was
+    // widened to a float have their integer columns cast to the widened type when scanned
     ParquetChunk {
         schema: table_schema.clone(),
         stats: Arc::new(chunk_stats),
@@ -457,6 +1329,7 @@ pub(crate) fn parquet_chunk_from_file(
         id: ChunkId::new(),
         chunk_order: ChunkOrder::new(chunk_order),
         parquet_exec,
+        tombstones: parquet_file.tombstones.clone(),
     }
 }
 
@@ -486,6 +1359,38 @@ impl<W: Wal, T: TimeProvider> Bufferer for WriteBufferImpl<W, T> {
             .await
     }
 
+    async fn write_json(
+        &self,
+        database: NamespaceName<'static>,
+        json: &str,
+        ingest_time: Time,
+        accept_partial: bool,
+        precision: Precision,
+    ) -> Result<BufferedWriteRequest> {
+        self.write_json(database, json, ingest_time, accept_partial, precision)
+            .await
+    }
+
+    async fn write_arrow(
+        &self,
+        database: NamespaceName<'static>,
+        table_name: &str,
+        batches: Vec<RecordBatch>,
+        ingest_time: Time,
+    ) -> Result<BufferedWriteRequest> {
+        self.write_arrow(database, table_name, batches, ingest_time)
+            .await
+    }
+
+    async fn import_parquet(
+        &self,
+        database: NamespaceName<'static>,
+        table_name: &str,
+        parquet: Bytes,
+    ) -> Result<PersistedSegment> {
+        self.import_parquet(database, table_name, parquet).await
+    }
+
     fn wal(&self) -> Option<Arc<impl Wal>> {
         self.wal.clone()
     }
@@ -493,6 +1398,10 @@ impl<W: Wal, T: TimeProvider> Bufferer for WriteBufferImpl<W, T> {
     fn catalog(&self) -> Arc<Catalog> {
         self.catalog()
     }
+
+    fn last_cache_provider(&self) -> Arc<LastCacheProvider> {
+        self.last_cache_provider()
+    }
 }
 
 impl<W: Wal, T: TimeProvider> ChunkContainer for WriteBufferImpl<W, T> {
@@ -508,10 +1417,177 @@ impl<W: Wal, T: TimeProvider> ChunkContainer for WriteBufferImpl<W, T> {
     }
 }
 
-impl<W: Wal, T: TimeProvider> WriteBuffer for WriteBufferImpl<W, T> {}
+#[async_trait]
+impl<W: Wal, T: TimeProvider> DatabaseManager for WriteBufferImpl<W, T> {
+    async fn create_database(
+        &self,
+        db_name: &str,
+        retention_period: Option<Duration>,
+        limits: DatabaseLimits,
+        strict_schema: bool,
+    ) -> Result<()> {
+        self.create_database(db_name, retention_period, limits, strict_schema)
+            .await
+    }
 
-#[derive(Debug, Default)]
-pub(crate) struct TableBatch {
+    async fn set_retention_period(
+        &self,
+        db_name: &str,
+        retention_period: Option<Duration>,
+    ) -> Result<()> {
+        self.set_retention_period(db_name, retention_period).await
+    }
+
+    async fn set_database_limits(&self, db_name: &str, limits: DatabaseLimits) -> Result<()> {
+        self.set_database_limits(db_name, limits).await
+    }
+
+    async fn set_strict_schema(&self, db_name: &str, strict_schema: bool) -> Result<()> {
+        self.set_strict_schema(db_name, strict_schema).await
+    }
+
+    async fn create_table(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        tags: Vec<String>,
+        fields: Vec<(String, InfluxFieldType)>,
+        series_key: Option<Vec<String>>,
+        widen_numeric_fields: bool,
+    ) -> Result<()> {
+        self.create_table(
+            db_name,
+            table_name,
+            tags,
+            fields,
+            series_key,
+            widen_numeric_fields,
+        )
+        .await
+    }
+
+    async fn set_table_field_widening(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        widen_numeric_fields: bool,
+    ) -> Result<()> {
+        self.set_table_field_widening(db_name, table_name, widen_numeric_fields)
+            .await
+    }
+
+    async fn delete_database(&self, db_name: &str) -> Result<()> {
+        self.delete_database(db_name).await
+    }
+
+    async fn delete_table(&self, db_name: &str, table_name: &str) -> Result<()> {
+        self.delete_table(db_name, table_name).await
+    }
+
+    async fn delete_rows(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        predicate: DeletePredicate,
+    ) -> Result<()> {
+        self.delete_rows(db_name, table_name, predicate).await
+    }
+}
+
+#[async_trait]
+impl<W: Wal, T: TimeProvider> LastCacheManager for WriteBufferImpl<W, T> {
+    async fn create_last_cache(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        cache_name: Option<&str>,
+        count: Option<usize>,
+        key_columns: Option<Vec<String>>,
+        value_columns: Option<Vec<String>>,
+    ) -> Result<LastCacheDefinition> {
+        self.create_last_cache(
+            db_name,
+            table_name,
+            cache_name,
+            count,
+            key_columns,
+            value_columns,
+        )
+        .await
+    }
+
+    async fn delete_last_cache(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        cache_name: &str,
+    ) -> Result<()> {
+        self.delete_last_cache(db_name, table_name, cache_name)
+            .await
+    }
+}
+
+impl<W: Wal, T: TimeProvider> WriteBuffer for WriteBufferImpl<W, T> {}
+
+/// Fill the last caches with the rows buffered in the segments replayed from the WAL
+///
+/// Rows that had already been persisted before the server restarted are not put back into the
+/// caches. The segments are cached in the order they were replayed, and the deletes replayed
+/// from each segment are applied to the caches before its rows are added, as the rows of the
+/// persisting segments are kept in their buffers until they've been persisted.
+fn rebuild_last_caches(
+    last_cache: &LastCacheProvider,
+    catalog: &Catalog,
+    open_segments: &[OpenBufferSegment],
+    persisting_segments: &[ClosedBufferSegment],
+    replayed_deletes: &[(SegmentId, DeleteOp)],
+) -> Result<()> {
+    let cached_tables = last_cache.cached_tables();
+    let mut segment_ids: Vec<SegmentId> = persisting_segments
+        .iter()
+        .map(|segment| segment.segment_id)
+        .chain(open_segments.iter().map(OpenBufferSegment::segment_id))
+        .collect();
+    segment_ids.sort();
+
+    for segment_id in segment_ids {
+        for (_, op) in replayed_deletes.iter().filter(|(id, _)| *id == segment_id) {
+            last_cache.delete_rows(&op.db_name, &op.table_name, &op.predicate);
+        }
+        for (db_name, table_name) in &cached_tables {
+            let Some(schema) = catalog
+                .db_schema(db_name)
+                .and_then(|db_schema| db_schema.get_table_schema(table_name).cloned())
+            else {
+                continue;
+            };
+            let batches = match persisting_segments
+                .iter()
+                .find(|segment| segment.segment_id == segment_id)
+            {
+                Some(segment) => segment.buffered_data.table_record_batches(
+                    db_name,
+                    table_name,
+                    schema.as_arrow(),
+                    &[],
+                ),
+                None => open_segments
+                    .iter()
+                    .find(|segment| segment.segment_id() == segment_id)
+                    .and_then(|segment| {
+                        segment.table_record_batches(db_name, table_name, schema.as_arrow(), &[])
+                    }),
+            };
+            if let Some(batches) = batches {
+                last_cache.write_batches_to_cache(db_name, table_name, &batches?)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct TableBatch {
     #[allow(dead_code)]
     pub(crate) name: String,
     pub(crate) rows: Vec<Row>,
@@ -584,7 +1660,9 @@ pub(crate) struct ValidSegmentedData {
     pub(crate) database_name: NamespaceName<'static>,
     pub(crate) segment_start: Time,
     pub(crate) table_batches: HashMap<String, TableBatch>,
-    pub(crate) wal_op: WalOp,
+    /// The ops written to the WAL for the data, which are any changes made to the catalog for
+    /// it, followed by the write itself
+    pub(crate) wal_ops: Vec<WalOp>,
     /// The sequence number of the catalog before any updates were applied based on this write.
     pub(crate) starting_catalog_sequence_number: SequenceNumber,
 }
@@ -600,14 +1678,19 @@ mod tests {
     use super::*;
     use crate::persister::PersisterImpl;
     use crate::wal::WalImpl;
-    use crate::{LpWriteOp, SegmentId, SequenceNumber, WalOpBatch};
-    use arrow::record_batch::RecordBatch;
-    use arrow_util::assert_batches_eq;
+    use crate::{LpWriteOp, SequenceNumber, WalOpBatch};
+    use arrow::array::{
+        ArrayRef, DictionaryArray, Float64Array, Int64Array, StringArray, TimestampMillisecondArray,
+    };
+    use arrow::datatypes::Int32Type;
+    use arrow_util::{assert_batches_eq, assert_batches_sorted_eq};
     use datafusion_util::config::register_iox_object_store;
     use iox_query::exec::IOxSessionContext;
     use iox_time::{MockProvider, Time};
     use object_store::memory::InMemory;
     use object_store::ObjectStore;
+    use parquet::arrow::ArrowWriter;
+    use schema::InfluxColumnType;
 
     #[test]
     fn parse_lp_into_buffer() {
@@ -645,7 +1728,9 @@ mod tests {
             Arc::clone(&time_provider),
             segment_duration,
             crate::test_help::make_exec(),
+            Arc::new(metric::Registry::default()),
             1000,
+            CatalogLimits::default(),
         )
         .await
         .unwrap();
@@ -675,18 +1760,31 @@ mod tests {
         ];
         assert_batches_eq!(&expected, &actual);
 
-        // ensure the data is in the wal
+        // ensure the data is in the wal, preceded by the creation of the table it was written to
         let wal = WalImpl::new(dir).unwrap();
         let mut reader = wal.open_segment_reader(SegmentId::new(1)).unwrap();
         let batch = reader.next_batch().unwrap().unwrap();
+        let cpu_table = write_buffer
+            .catalog()
+            .db_schema("foo")
+            .unwrap()
+            .get_table("cpu")
+            .cloned()
+            .unwrap();
         let expected_batch = WalOpBatch {
             sequence_number: SequenceNumber::new(1),
-            ops: vec![WalOp::LpWrite(LpWriteOp {
-                db_name: "foo".to_string(),
-                lp: "cpu bar=1 10".to_string(),
-                default_time: 123,
-                precision: Precision::Nanosecond,
-            })],
+            ops: vec![
+                WalOp::Catalog(CatalogOp::CreateTable {
+                    db_name: "foo".to_string(),
+                    table: cpu_table,
+                }),
+                WalOp::LpWrite(LpWriteOp {
+                    db_name: "foo".to_string(),
+                    lp: "cpu bar=1 10".to_string(),
+                    default_time: 123,
+                    precision: Precision::Nanosecond,
+                }),
+            ],
         };
         assert_eq!(batch, expected_batch);
 
@@ -697,7 +1795,120 @@ mod tests {
             time_provider,
             segment_duration,
             crate::test_help::make_exec(),
+            Arc::new(metric::Registry::default()),
+            1000,
+            CatalogLimits::default(),
+        )
+        .await
+        .unwrap();
+        let actual = write_buffer.get_table_record_batches("foo", "cpu");
+        assert_batches_eq!(&expected, &actual);
+    }
+
+    #[tokio::test]
+    async fn buffers_arrow_writes_and_replays_them_from_wal() {
+        let dir = test_helpers::tmp_dir().unwrap().into_path();
+        let object_store: Arc<dyn ObjectStore> = Arc::new(InMemory::new());
+        let persister = Arc::new(PersisterImpl::new(Arc::clone(&object_store)));
+        let time_provider = Arc::new(MockProvider::new(Time::from_timestamp_nanos(0)));
+        let segment_duration = SegmentDuration::new_5m();
+        let write_buffer = WriteBufferImpl::new(
+            Arc::clone(&persister),
+            Some(Arc::new(WalImpl::new(dir.clone()).unwrap())),
+            Arc::clone(&time_provider),
+            segment_duration,
+            crate::test_help::make_exec(),
+            Arc::new(metric::Registry::default()),
+            1000,
+            CatalogLimits::default(),
+        )
+        .await
+        .unwrap();
+
+        let batch = RecordBatch::try_from_iter([
+            (
+                "host",
+                Arc::new(DictionaryArray::<Int32Type>::from_iter(["a", "b"])) as ArrayRef,
+            ),
+            (
+                "usage",
+                Arc::new(Float64Array::from(vec![Some(0.5), None])) as ArrayRef,
+            ),
+            ("count", Arc::new(Int64Array::from(vec![1, 2])) as ArrayRef),
+            (
+                "time",
+                Arc::new(TimestampMillisecondArray::from(vec![10, 20])) as ArrayRef,
+            ),
+        ])
+        .unwrap();
+        let summary = write_buffer
+            .write_arrow(
+                NamespaceName::new("foo").unwrap(),
+                "cpu",
+                vec![batch.clone()],
+                Time::from_timestamp_nanos(123),
+            )
+            .await
+            .unwrap();
+        assert_eq!(summary.line_count, 2);
+        assert_eq!(summary.field_count, 3);
+        assert_eq!(summary.index_count, 2);
+
+        let actual = write_buffer.get_table_record_batches("foo", "cpu");
+        let expected = [
+            "+-------+------+--------------------------+-------+",
+            "| count | host | time                     | usage |",
+            "+-------+------+--------------------------+-------+",
+            "| 1     | a    | 1970-01-01T00:00:00.010Z | 0.5   |",
+            "| 2     | b    | 1970-01-01T00:00:00.020Z |       |",
+            "+-------+------+--------------------------+-------+",
+        ];
+        assert_batches_eq!(&expected, &actual);
+
+        // the batch is in the wal as it was written, after the creation of its table
+        let wal = WalImpl::new(dir).unwrap();
+        let mut reader = wal.open_segment_reader(SegmentId::new(1)).unwrap();
+        let wal_batch = reader.next_batch().unwrap().unwrap();
+        assert_eq!(wal_batch.ops.len(), 2);
+        assert!(matches!(
+            &wal_batch.ops[0],
+            WalOp::Catalog(CatalogOp::CreateTable { .. })
+        ));
+        let WalOp::ArrowWrite(write) = &wal_batch.ops[1] else {
+            panic!("expected an arrow write, got {:?}", wal_batch.ops[1]);
+        };
+        assert_eq!(write.ingest_time, 123);
+        assert_eq!(write.record_batches().unwrap(), vec![batch]);
+
+        // a write that doesn't match the table's schema is rejected
+        let batch = RecordBatch::try_from_iter([
+            ("count", Arc::new(Float64Array::from(vec![1.5])) as ArrayRef),
+            (
+                "time",
+                Arc::new(TimestampMillisecondArray::from(vec![30])) as ArrayRef,
+            ),
+        ])
+        .unwrap();
+        let result = write_buffer
+            .write_arrow(
+                NamespaceName::new("foo").unwrap(),
+                "cpu",
+                vec![batch],
+                Time::from_timestamp_nanos(123),
+            )
+            .await;
+        assert!(matches!(result, Err(Error::InvalidArrowWrite(_))));
+
+        // the buffer is restored by replaying the wal
+        let write_buffer = WriteBufferImpl::new(
+            persister,
+            Some(Arc::new(wal)),
+            time_provider,
+            segment_duration,
+            crate::test_help::make_exec(),
+            Arc::new(metric::Registry::default()),
             1000,
+            CatalogLimits::default(),
         )
         .await
         .unwrap();
@@ -705,6 +1916,146 @@ mod tests {
         assert_batches_eq!(&expected, &actual);
     }
 
+    fn parquet_bytes(batch: &RecordBatch) -> Bytes {
+        let mut parquet = vec![];
+        let mut writer = ArrowWriter::try_new(&mut parquet, batch.schema(), None).unwrap();
+        writer.write(batch).unwrap();
+        writer.close().unwrap();
+        Bytes::from(parquet)
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn imports_parquet_files_and_queries_them() {
+        let dir = test_helpers::tmp_dir().unwrap().into_path();
+        let wal = Some(Arc::new(WalImpl::new(dir.clone()).unwrap()));
+        let object_store: Arc<dyn ObjectStore> = Arc::new(InMemory::new());
+        let persister = Arc::new(PersisterImpl::new(Arc::clone(&object_store)));
+        let time_provider = Arc::new(MockProvider::new(Time::from_timestamp_nanos(0)));
+        let segment_duration = SegmentDuration::new_5m();
+        let write_buffer = WriteBufferImpl::new(
+            Arc::clone(&persister),
+            wal.clone(),
+            Arc::clone(&time_provider),
+            segment_duration,
+            crate::test_help::make_exec(),
+            Arc::new(metric::Registry::default()),
+            1000,
+            CatalogLimits::default(),
+        )
+        .await
+        .unwrap();
+        let session_context = IOxSessionContext::with_testing();
+        let runtime_env = session_context.inner().runtime_env();
+        register_iox_object_store(runtime_env, "influxdb3", Arc::clone(&object_store));
+
+        // a buffered write, in the open segment
+        write_buffer
+            .write_lp(
+                NamespaceName::new("foo").unwrap(),
+                "cpu,host=c usage=1.0 10",
+                Time::from_timestamp_nanos(123),
+                false,
+                Precision::Nanosecond,
+            )
+            .await
+            .unwrap();
+
+        // the file has a duplicate row, and rows in two segments
+        let batch = RecordBatch::try_from_iter([
+            (
+                "host",
+                Arc::new(DictionaryArray::<Int32Type>::from_iter(["a", "a", "b"])) as ArrayRef,
+            ),
+            (
+                "usage",
+                Arc::new(Float64Array::from(vec![0.5, 0.5, 0.9])) as ArrayRef,
+            ),
+            (
+                "time",
+                Arc::new(TimestampMillisecondArray::from(vec![1_000, 1_000, 400_000])) as ArrayRef,
+            ),
+        ])
+        .unwrap();
+        let segment = write_buffer
+            .import_parquet(
+                NamespaceName::new("foo").unwrap(),
+                "cpu",
+                parquet_bytes(&batch),
+            )
+            .await
+            .unwrap();
+        assert_eq!(segment.segment_id, SegmentId::new(2));
+        assert_eq!(segment.segment_row_count, 2);
+        assert_eq!(segment.segment_min_time, 1_000_000_000);
+        assert_eq!(segment.segment_max_time, 400_000_000_000);
+        let files = &segment.databases["foo"].tables["cpu"].parquet_files;
+        assert_eq!(files.len(), 2);
+        assert_eq!(persister.load_segments(10).await.unwrap(), vec![segment]);
+
+        // the imported rows are queryable along with the buffered ones
+        let expected = [
+            "+------+--------------------------------+-------+",
+            "| host | time                           | usage |",
+            "+------+--------------------------------+-------+",
+            "| a    | 1970-01-01T00:00:01Z           | 0.5   |",
+            "| b    | 1970-01-01T00:06:40Z           | 0.9   |",
+            "| c    | 1970-01-01T00:00:00.000000010Z | 1.0   |",
+            "+------+--------------------------------+-------+",
+        ];
+        let actual = get_table_batches(&write_buffer, "foo", "cpu", &session_context).await;
+        assert_batches_sorted_eq!(&expected, &actual);
+
+        // a file that doesn't match the table's schema is rejected
+        let batch = RecordBatch::try_from_iter([
+            (
+                "usage",
+                Arc::new(StringArray::from(vec!["high"])) as ArrayRef,
+            ),
+            (
+                "time",
+                Arc::new(TimestampMillisecondArray::from(vec![2_000])) as ArrayRef,
+            ),
+        ])
+        .unwrap();
+        let result = write_buffer
+            .import_parquet(
+                NamespaceName::new("foo").unwrap(),
+                "cpu",
+                parquet_bytes(&batch),
+            )
+            .await;
+        assert!(matches!(result, Err(Error::InvalidParquetImport(_))));
+        let result = write_buffer
+            .import_parquet(
+                NamespaceName::new("foo").unwrap(),
+                "cpu",
+                Bytes::from("not parquet"),
+            )
+            .await;
+        assert!(matches!(result, Err(Error::InvalidParquetImport(_))));
+
+        // on restart, the imported files are loaded and the older segment is still replayed
+        // from the wal
+        let write_buffer = WriteBufferImpl::new(
+            Arc::clone(&persister),
+            wal,
+            Arc::clone(&time_provider),
+            segment_duration,
+            crate::test_help::make_exec(),
+            Arc::new(metric::Registry::default()),
+            1000,
+            CatalogLimits::default(),
+        )
+        .await
+        .unwrap();
+        let actual = get_table_batches(&write_buffer, "foo", "cpu", &session_context).await;
+        assert_batches_sorted_eq!(&expected, &actual);
+        assert_eq!(
+            write_buffer.segment_state.read().last_segment_id(),
+            SegmentId::new(2)
+        );
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
     async fn returns_chunks_across_buffered_and_persisted_data() {
         let dir = test_helpers::tmp_dir().unwrap().into_path();
@@ -719,7 +2070,9 @@ mod tests {
             Arc::clone(&time_provider),
             segment_duration,
             crate::test_help::make_exec(),
+            Arc::new(metric::Registry::default()),
             1000,
+            CatalogLimits::default(),
         )
         .await
         .unwrap();
@@ -801,7 +2154,9 @@ mod tests {
             Arc::clone(&time_provider),
             segment_duration,
             crate::test_help::make_exec(),
+            Arc::new(metric::Registry::default()),
             1000,
+            CatalogLimits::default(),
         )
         .await
         .unwrap();
@@ -846,7 +2201,9 @@ mod tests {
             Arc::clone(&time_provider),
             segment_duration,
             crate::test_help::make_exec(),
+            Arc::new(metric::Registry::default()),
             1000,
+            CatalogLimits::default(),
         )
         .await
         .unwrap();
@@ -889,6 +2246,799 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn filters_expired_rows_from_buffer() {
+        let dir = test_helpers::tmp_dir().unwrap().into_path();
+        let object_store: Arc<dyn ObjectStore> = Arc::new(InMemory::new());
+        let persister = Arc::new(PersisterImpl::new(Arc::clone(&object_store)));
+        let time_provider = Arc::new(MockProvider::new(Time::from_timestamp_nanos(0)));
+        let write_buffer = WriteBufferImpl::new(
+            Arc::clone(&persister),
+            Some(Arc::new(WalImpl::new(dir).unwrap())),
+            Arc::clone(&time_provider),
+            SegmentDuration::new_5m(),
+            crate::test_help::make_exec(),
+            Arc::new(metric::Registry::default()),
+            1000,
+            CatalogLimits::default(),
+        )
+        .await
+        .unwrap();
+        let session_context = IOxSessionContext::with_testing();
+        let runtime_env = session_context.inner().runtime_env();
+        register_iox_object_store(runtime_env, "influxdb3", Arc::clone(&object_store));
+
+        write_buffer
+            .create_database(
+                "foo",
+                Some(Duration::from_secs(100)),
+                DatabaseLimits::default(),
+                false,
+            )
+            .await
+            .unwrap();
+        assert!(matches!(
+            write_buffer
+                .create_database("foo", None, DatabaseLimits::default(), false)
+                .await,
+            Err(Error::CatalogUpdateError(
+                crate::catalog::Error::DatabaseAlreadyExists { .. }
+            ))
+        ));
+        write_buffer
+            .write_lp(
+                NamespaceName::new("foo").unwrap(),
+                "cpu bar=1 10\ncpu bar=2 200000000000",
+                Time::from_timestamp_nanos(0),
+                false,
+                Precision::Nanosecond,
+            )
+            .await
+            .unwrap();
+
+        // the retention period has not passed for either row:
+        time_provider.set(Time::from_timestamp(50, 0).unwrap());
+        let actual = get_table_batches(&write_buffer, "foo", "cpu", &session_context).await;
+        assert_batches_eq!(
+            [
+                "+-----+--------------------------------+",
+                "| bar | time                           |",
+                "+-----+--------------------------------+",
+                "| 1.0 | 1970-01-01T00:00:00.000000010Z |",
+                "| 2.0 | 1970-01-01T00:03:20Z           |",
+                "+-----+--------------------------------+",
+            ],
+            &actual
+        );
+
+        // the first row has expired:
+        time_provider.set(Time::from_timestamp(250, 0).unwrap());
+        let actual = get_table_batches(&write_buffer, "foo", "cpu", &session_context).await;
+        assert_batches_eq!(
+            [
+                "+-----+----------------------+",
+                "| bar | time                 |",
+                "+-----+----------------------+",
+                "| 2.0 | 1970-01-01T00:03:20Z |",
+                "+-----+----------------------+",
+            ],
+            &actual
+        );
+
+        // removing the retention period makes all data visible again:
+        write_buffer
+            .set_retention_period("foo", None)
+            .await
+            .unwrap();
+        let actual = get_table_batches(&write_buffer, "foo", "cpu", &session_context).await;
+        assert_eq!(2, actual.iter().map(|b| b.num_rows()).sum::<usize>());
+    }
+
+    #[tokio::test]
+    async fn create_and_delete_last_cache() {
+        let dir = test_helpers::tmp_dir().unwrap().into_path();
+        let object_store: Arc<dyn ObjectStore> = Arc::new(InMemory::new());
+        let persister = Arc::new(PersisterImpl::new(Arc::clone(&object_store)));
+        let time_provider = Arc::new(MockProvider::new(Time::from_timestamp_nanos(0)));
+        let write_buffer = WriteBufferImpl::new(
+            Arc::clone(&persister),
+            Some(Arc::new(WalImpl::new(dir.clone()).unwrap())),
+            Arc::clone(&time_provider),
+            SegmentDuration::new_5m(),
+            crate::test_help::make_exec(),
+            Arc::new(metric::Registry::default()),
+            1000,
+            CatalogLimits::default(),
+        )
+        .await
+        .unwrap();
+        write_buffer
+            .write_lp(
+                NamespaceName::new("foo").unwrap(),
+                "cpu,host=a,region=us usage=1,temp=50i 10",
+                Time::from_timestamp_nanos(123),
+                false,
+                Precision::Nanosecond,
+            )
+            .await
+            .unwrap();
+
+        // unknown database and table:
+        assert!(matches!(
+            write_buffer
+                .create_last_cache("bar", "cpu", None, None, None, None)
+                .await,
+            Err(Error::DatabaseNotFound { .. })
+        ));
+        assert!(matches!(
+            write_buffer
+                .create_last_cache("foo", "mem", None, None, None, None)
+                .await,
+            Err(Error::TableNotFound { .. })
+        ));
+
+        // create with defaults:
+        let definition = write_buffer
+            .create_last_cache("foo", "cpu", None, None, None, None)
+            .await
+            .unwrap();
+        assert_eq!("cpu_host_region_last_cache", definition.name);
+        assert_eq!(vec!["host", "region"], definition.key_columns);
+        assert_eq!(vec!["temp", "usage"], definition.value_columns);
+        assert_eq!(1, definition.count());
+
+        // create with explicit settings:
+        let definition = write_buffer
+            .create_last_cache(
+                "foo",
+                "cpu",
+                Some("usage_by_host"),
+                Some(5),
+                Some(vec!["host".to_string()]),
+                Some(vec!["usage".to_string()]),
+            )
+            .await
+            .unwrap();
+        assert_eq!(vec!["usage"], definition.value_columns);
+        assert_eq!(5, definition.count());
+
+        // duplicate name is rejected:
+        assert!(matches!(
+            write_buffer
+                .create_last_cache("foo", "cpu", Some("usage_by_host"), None, None, None)
+                .await,
+            Err(Error::LastCacheError(
+                crate::last_cache::Error::CacheAlreadyExists { .. }
+            ))
+        ));
+
+        write_buffer
+            .delete_last_cache("foo", "cpu", "cpu_host_region_last_cache")
+            .await
+            .unwrap();
+        assert!(matches!(
+            write_buffer
+                .delete_last_cache("foo", "cpu", "cpu_host_region_last_cache")
+                .await,
+            Err(Error::LastCacheNotFound { .. })
+        ));
+
+        // the catalog was persisted, so the remaining cache is restored on restart:
+        drop(write_buffer);
+        let write_buffer = WriteBufferImpl::new(
+            Arc::clone(&persister),
+            Some(Arc::new(WalImpl::new(dir).unwrap())),
+            Arc::clone(&time_provider),
+            SegmentDuration::new_5m(),
+            crate::test_help::make_exec(),
+            Arc::new(metric::Registry::default()),
+            1000,
+            CatalogLimits::default(),
+        )
+        .await
+        .unwrap();
+        let db_schema = write_buffer.catalog().db_schema("foo").unwrap();
+        let table_def = db_schema.get_table("cpu").unwrap();
+        assert_eq!(Some(&definition), table_def.get_last_cache("usage_by_host"));
+        assert!(table_def
+            .get_last_cache("cpu_host_region_last_cache")
+            .is_none());
+
+        // and is filled with the rows replayed from the WAL:
+        let batch = write_buffer
+            .last_cache_provider()
+            .get_cache_record_batch("foo", "cpu", Some("usage_by_host"))
+            .unwrap();
+        assert_batches_sorted_eq!(
+            [
+                "+------+-------+--------------------------------+",
+                "| host | usage | time                           |",
+                "+------+-------+--------------------------------+",
+                "| a    | 1.0   | 1970-01-01T00:00:00.000000010Z |",
+                "+------+-------+--------------------------------+",
+            ],
+            &[batch]
+        );
+    }
+
+    #[tokio::test]
+    async fn delete_database() {
+        let dir = test_helpers::tmp_dir().unwrap().into_path();
+        let object_store: Arc<dyn ObjectStore> = Arc::new(InMemory::new());
+        let persister = Arc::new(PersisterImpl::new(Arc::clone(&object_store)));
+        let time_provider = Arc::new(MockProvider::new(Time::from_timestamp_nanos(0)));
+        let write_buffer = WriteBufferImpl::new(
+            Arc::clone(&persister),
+            Some(Arc::new(WalImpl::new(dir.clone()).unwrap())),
+            Arc::clone(&time_provider),
+            SegmentDuration::new_5m(),
+            crate::test_help::make_exec(),
+            Arc::new(metric::Registry::default()),
+            1000,
+            CatalogLimits::default(),
+        )
+        .await
+        .unwrap();
+        let session_context = IOxSessionContext::with_testing();
+        let runtime_env = session_context.inner().runtime_env();
+        register_iox_object_store(runtime_env, "influxdb3", Arc::clone(&object_store));
+
+        write_buffer
+            .write_lp(
+                NamespaceName::new("foo").unwrap(),
+                "cpu,host=a usage=1 10",
+                Time::from_timestamp_nanos(0),
+                false,
+                Precision::Nanosecond,
+            )
+            .await
+            .unwrap();
+        write_buffer
+            .create_last_cache("foo", "cpu", None, None, None, None)
+            .await
+            .unwrap();
+        let parquet_path = ObjPath::from("dbs/foo/cpu/1970-01-01/4294967294/1.parquet");
+        object_store
+            .put(&parquet_path, bytes::Bytes::from("data"))
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            write_buffer.delete_database("bar").await,
+            Err(Error::DatabaseNotFound { .. })
+        ));
+
+        time_provider.set(Time::from_timestamp(1, 0).unwrap());
+        write_buffer.delete_database("foo").await.unwrap();
+        assert!(write_buffer.catalog().db_schema("foo").is_none());
+        assert!(write_buffer
+            .last_cache_provider()
+            .get_cache_schema("foo", "cpu", None)
+            .is_err());
+        assert!(object_store.head(&parquet_path).await.is_err());
+
+        // writing to the database again creates it from scratch:
+        time_provider.set(Time::from_timestamp(2, 0).unwrap());
+        write_buffer
+            .write_lp(
+                NamespaceName::new("foo").unwrap(),
+                "cpu,host=b usage=2 20",
+                Time::from_timestamp(2, 0).unwrap(),
+                false,
+                Precision::Nanosecond,
+            )
+            .await
+            .unwrap();
+        let expected = [
+            "+------+--------------------------------+-------+",
+            "| host | time                           | usage |",
+            "+------+--------------------------------+-------+",
+            "| b    | 1970-01-01T00:00:00.000000020Z | 2.0   |",
+            "+------+--------------------------------+-------+",
+        ];
+        let actual = get_table_batches(&write_buffer, "foo", "cpu", &session_context).await;
+        assert_batches_eq!(expected, &actual);
+
+        // writes made before the delete are not replayed from the wal on restart:
+        drop(write_buffer);
+        let write_buffer = WriteBufferImpl::new(
+            Arc::clone(&persister),
+            Some(Arc::new(WalImpl::new(dir).unwrap())),
+            Arc::clone(&time_provider),
+            SegmentDuration::new_5m(),
+            crate::test_help::make_exec(),
+            Arc::new(metric::Registry::default()),
+            1000,
+            CatalogLimits::default(),
+        )
+        .await
+        .unwrap();
+        let actual = get_table_batches(&write_buffer, "foo", "cpu", &session_context).await;
+        assert_batches_eq!(expected, &actual);
+    }
+
+    #[tokio::test]
+    async fn delete_table() {
+        let dir = test_helpers::tmp_dir().unwrap().into_path();
+        let object_store: Arc<dyn ObjectStore> = Arc::new(InMemory::new());
+        let persister = Arc::new(PersisterImpl::new(Arc::clone(&object_store)));
+        let time_provider = Arc::new(MockProvider::new(Time::from_timestamp_nanos(0)));
+        let write_buffer = WriteBufferImpl::new(
+            Arc::clone(&persister),
+            Some(Arc::new(WalImpl::new(dir.clone()).unwrap())),
+            Arc::clone(&time_provider),
+            SegmentDuration::new_5m(),
+            crate::test_help::make_exec(),
+            Arc::new(metric::Registry::default()),
+            1000,
+            CatalogLimits::default(),
+        )
+        .await
+        .unwrap();
+        let session_context = IOxSessionContext::with_testing();
+        let runtime_env = session_context.inner().runtime_env();
+        register_iox_object_store(runtime_env, "influxdb3", Arc::clone(&object_store));
+
+        write_buffer
+            .write_lp(
+                NamespaceName::new("foo").unwrap(),
+                "cpu,host=a usage=1 10\nmem,host=a used=5 10",
+                Time::from_timestamp_nanos(0),
+                false,
+                Precision::Nanosecond,
+            )
+            .await
+            .unwrap();
+        let cpu_path = ObjPath::from("dbs/foo/cpu/1970-01-01/4294967294/1.parquet");
+        let mem_path = ObjPath::from("dbs/foo/mem/1970-01-01/4294967294/1.parquet");
+        for path in [&cpu_path, &mem_path] {
+            object_store
+                .put(path, bytes::Bytes::from("data"))
+                .await
+                .unwrap();
+        }
+
+        assert!(matches!(
+            write_buffer.delete_table("bar", "cpu").await,
+            Err(Error::DatabaseNotFound { .. })
+        ));
+        assert!(matches!(
+            write_buffer.delete_table("foo", "disk").await,
+            Err(Error::TableNotFound { .. })
+        ));
+
+        time_provider.set(Time::from_timestamp(1, 0).unwrap());
+        write_buffer.delete_table("foo", "cpu").await.unwrap();
+        let db_schema = write_buffer.catalog().db_schema("foo").unwrap();
+        assert!(!db_schema.table_exists("cpu"));
+        assert!(db_schema.table_exists("mem"));
+        assert!(object_store.head(&cpu_path).await.is_err());
+        assert!(object_store.head(&mem_path).await.is_ok());
+
+        // writing to the table again creates it from scratch:
+        time_provider.set(Time::from_timestamp(2, 0).unwrap());
+        write_buffer
+            .write_lp(
+                NamespaceName::new("foo").unwrap(),
+                "cpu,host=b usage=2 20",
+                Time::from_timestamp(2, 0).unwrap(),
+                false,
+                Precision::Nanosecond,
+            )
+            .await
+            .unwrap();
+        let expected_cpu = [
+            "+------+--------------------------------+-------+",
+            "| host | time                           | usage |",
+            "+------+--------------------------------+-------+",
+            "| b    | 1970-01-01T00:00:00.000000020Z | 2.0   |",
+            "+------+--------------------------------+-------+",
+        ];
+        let expected_mem = [
+            "+------+--------------------------------+------+",
+            "| host | time                           | used |",
+            "+------+--------------------------------+------+",
+            "| a    | 1970-01-01T00:00:00.000000010Z | 5.0  |",
+            "+------+--------------------------------+------+",
+        ];
+        let actual = get_table_batches(&write_buffer, "foo", "cpu", &session_context).await;
+        assert_batches_eq!(expected_cpu, &actual);
+
+        // writes to the table made before the delete are not replayed from the wal on restart,
+        // while writes to other tables are:
+        drop(write_buffer);
+        let write_buffer = WriteBufferImpl::new(
+            Arc::clone(&persister),
+            Some(Arc::new(WalImpl::new(dir).unwrap())),
+            Arc::clone(&time_provider),
+            SegmentDuration::new_5m(),
+            crate::test_help::make_exec(),
+            Arc::new(metric::Registry::default()),
+            1000,
+            CatalogLimits::default(),
+        )
+        .await
+        .unwrap();
+        let actual = get_table_batches(&write_buffer, "foo", "cpu", &session_context).await;
+        assert_batches_eq!(expected_cpu, &actual);
+        let actual = get_table_batches(&write_buffer, "foo", "mem", &session_context).await;
+        assert_batches_eq!(expected_mem, &actual);
+    }
+
+    #[tokio::test]
+    async fn delete_rows() {
+        let dir = test_helpers::tmp_dir().unwrap().into_path();
+        let object_store: Arc<dyn ObjectStore> = Arc::new(InMemory::new());
+        let persister = Arc::new(PersisterImpl::new(Arc::clone(&object_store)));
+        let time_provider = Arc::new(MockProvider::new(Time::from_timestamp_nanos(0)));
+        let write_buffer = WriteBufferImpl::new(
+            Arc::clone(&persister),
+            Some(Arc::new(WalImpl::new(dir.clone()).unwrap())),
+            Arc::clone(&time_provider),
+            SegmentDuration::new_5m(),
+            crate::test_help::make_exec(),
+            Arc::new(metric::Registry::default()),
+            1000,
+            CatalogLimits::default(),
+        )
+        .await
+        .unwrap();
+        let session_context = IOxSessionContext::with_testing();
+        let runtime_env = session_context.inner().runtime_env();
+        register_iox_object_store(runtime_env, "influxdb3", Arc::clone(&object_store));
+
+        write_buffer
+            .write_lp(
+                NamespaceName::new("foo").unwrap(),
+                "cpu,host=a usage=1 10\ncpu,host=b usage=2 20",
+                Time::from_timestamp_nanos(0),
+                false,
+                Precision::Nanosecond,
+            )
+            .await
+            .unwrap();
+        write_buffer
+            .create_last_cache("foo", "cpu", None, None, None, None)
+            .await
+            .unwrap();
+
+        // advance the time and wait for the first segment to persist
+        time_provider.set(Time::from_timestamp(800, 0).unwrap());
+        loop {
+            tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+            if !write_buffer
+                .persisted_files
+                .get_files("foo", "cpu")
+                .is_empty()
+            {
+                break;
+            }
+        }
+        write_buffer
+            .write_lp(
+                NamespaceName::new("foo").unwrap(),
+                "cpu,host=a usage=3\ncpu,host=b usage=4",
+                Time::from_timestamp(900, 0).unwrap(),
+                false,
+                Precision::Nanosecond,
+            )
+            .await
+            .unwrap();
+
+        let all_time = |tags: &[(&str, &str)]| DeletePredicate {
+            min_time: i64::MIN,
+            max_time: i64::MAX,
+            tags: tags
+                .iter()
+                .map(|(t, v)| (t.to_string(), v.to_string()))
+                .collect(),
+        };
+        assert!(matches!(
+            write_buffer.delete_rows("bar", "cpu", all_time(&[])).await,
+            Err(Error::DatabaseNotFound { .. })
+        ));
+        assert!(matches!(
+            write_buffer.delete_rows("foo", "mem", all_time(&[])).await,
+            Err(Error::TableNotFound { .. })
+        ));
+        assert!(matches!(
+            write_buffer
+                .delete_rows("foo", "cpu", all_time(&[("usage", "1")]))
+                .await,
+            Err(Error::InvalidDeletePredicate(_))
+        ));
+        let backwards = DeletePredicate {
+            min_time: 10,
+            max_time: 0,
+            tags: vec![],
+        };
+        assert!(matches!(
+            write_buffer.delete_rows("foo", "cpu", backwards).await,
+            Err(Error::InvalidDeletePredicate(_))
+        ));
+
+        // the deleted rows are dropped from the buffer, and filtered out of the persisted file
+        // until it's rewritten without them
+        write_buffer
+            .delete_rows("foo", "cpu", all_time(&[("host", "a")]))
+            .await
+            .unwrap();
+        let expected = [
+            "+------+--------------------------------+-------+",
+            "| host | time                           | usage |",
+            "+------+--------------------------------+-------+",
+            "| b    | 1970-01-01T00:15:00Z           | 4.0   |",
+            "| b    | 1970-01-01T00:00:00.000000020Z | 2.0   |",
+            "+------+--------------------------------+-------+",
+        ];
+        let actual = get_table_batches(&write_buffer, "foo", "cpu", &session_context).await;
+        assert_batches_eq!(expected, &actual);
+
+        // and from the last cache
+        let expected_cache = [
+            "+------+-------+----------------------+",
+            "| host | usage | time                 |",
+            "+------+-------+----------------------+",
+            "| b    | 4.0   | 1970-01-01T00:15:00Z |",
+            "+------+-------+----------------------+",
+        ];
+        let cache = write_buffer
+            .last_cache_provider()
+            .get_cache_record_batch("foo", "cpu", None)
+            .unwrap();
+        assert_batches_eq!(expected_cache, &[cache]);
+
+        let rewritten = loop {
+            let files = write_buffer.persisted_files.get_files("foo", "cpu");
+            if files.iter().all(|file| file.tombstones.is_empty()) {
+                break files;
+            }
+            tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+        };
+        assert_eq!(rewritten.len(), 1);
+        assert_eq!(rewritten[0].row_count, 1);
+        assert_eq!((rewritten[0].min_time, rewritten[0].max_time), (20, 20));
+        let actual = get_table_batches(&write_buffer, "foo", "cpu", &session_context).await;
+        assert_batches_eq!(expected, &actual);
+
+        // the delete is replayed from the wal on restart
+        drop(write_buffer);
+        let write_buffer = WriteBufferImpl::new(
+            Arc::clone(&persister),
+            Some(Arc::new(WalImpl::new(dir).unwrap())),
+            Arc::clone(&time_provider),
+            SegmentDuration::new_5m(),
+            crate::test_help::make_exec(),
+            Arc::new(metric::Registry::default()),
+            1000,
+            CatalogLimits::default(),
+        )
+        .await
+        .unwrap();
+        let actual = get_table_batches(&write_buffer, "foo", "cpu", &session_context).await;
+        assert_batches_eq!(expected, &actual);
+        let cache = write_buffer
+            .last_cache_provider()
+            .get_cache_record_batch("foo", "cpu", None)
+            .unwrap();
+        assert_batches_eq!(expected_cache, &[cache]);
+    }
+
+    #[tokio::test]
+    async fn create_table_with_strict_schema() {
+        let dir = test_helpers::tmp_dir().unwrap().into_path();
+        let object_store: Arc<dyn ObjectStore> = Arc::new(InMemory::new());
+        let persister = Arc::new(PersisterImpl::new(Arc::clone(&object_store)));
+        let time_provider = Arc::new(MockProvider::new(Time::from_timestamp_nanos(0)));
+        let write_buffer = WriteBufferImpl::new(
+            Arc::clone(&persister),
+            Some(Arc::new(WalImpl::new(dir.clone()).unwrap())),
+            Arc::clone(&time_provider),
+            SegmentDuration::new_5m(),
+            crate::test_help::make_exec(),
+            Arc::new(metric::Registry::default()),
+            1000,
+            CatalogLimits::default(),
+        )
+        .await
+        .unwrap();
+
+        assert!(matches!(
+            write_buffer
+                .create_table("foo", "cpu", vec![], vec![], None, false)
+                .await,
+            Err(Error::DatabaseNotFound { .. })
+        ));
+        write_buffer
+            .create_database("foo", None, DatabaseLimits::default(), true)
+            .await
+            .unwrap();
+        write_buffer
+            .create_table(
+                "foo",
+                "cpu",
+                vec!["host".to_string()],
+                vec![("usage".to_string(), InfluxFieldType::Float)],
+                None,
+                false,
+            )
+            .await
+            .unwrap();
+        assert!(matches!(
+            write_buffer
+                .create_table("foo", "cpu", vec![], vec![], None, false)
+                .await,
+            Err(Error::CatalogUpdateError(
+                crate::catalog::Error::TableAlreadyExists { .. }
+            ))
+        ));
+
+        // only writes that match the declared schema are accepted:
+        write_buffer
+            .write_lp(
+                NamespaceName::new("foo").unwrap(),
+                "cpu,host=a usage=1 10",
+                Time::from_timestamp_nanos(0),
+                false,
+                Precision::Nanosecond,
+            )
+            .await
+            .unwrap();
+        for lp in ["cpu,host=a,region=b usage=1 10", "mem,host=a used=5 10"] {
+            assert!(matches!(
+                write_buffer
+                    .write_lp(
+                        NamespaceName::new("foo").unwrap(),
+                        lp,
+                        Time::from_timestamp_nanos(0),
+                        false,
+                        Precision::Nanosecond,
+                    )
+                    .await,
+                Err(Error::ParseError(_))
+            ));
+        }
+
+        // once the strict schema is disabled, new tables and columns are added on write:
+        write_buffer.set_strict_schema("foo", false).await.unwrap();
+        write_buffer
+            .write_lp(
+                NamespaceName::new("foo").unwrap(),
+                "mem,host=a used=5 10",
+                Time::from_timestamp_nanos(0),
+                false,
+                Precision::Nanosecond,
+            )
+            .await
+            .unwrap();
+
+        // the declared table is persisted with the catalog:
+        drop(write_buffer);
+        let write_buffer = WriteBufferImpl::new(
+            Arc::clone(&persister),
+            Some(Arc::new(WalImpl::new(dir).unwrap())),
+            Arc::clone(&time_provider),
+            SegmentDuration::new_5m(),
+            crate::test_help::make_exec(),
+            Arc::new(metric::Registry::default()),
+            1000,
+            CatalogLimits::default(),
+        )
+        .await
+        .unwrap();
+        let db_schema = write_buffer.catalog().db_schema("foo").unwrap();
+        assert!(!db_schema.strict_schema());
+        assert_eq!(
+            Some(InfluxColumnType::Field(InfluxFieldType::Float)),
+            db_schema
+                .get_table("cpu")
+                .unwrap()
+                .field_type_by_name("usage")
+        );
+    }
+
+    #[tokio::test]
+    async fn widen_numeric_fields() {
+        let dir = test_helpers::tmp_dir().unwrap().into_path();
+        let object_store: Arc<dyn ObjectStore> = Arc::new(InMemory::new());
+        let persister = Arc::new(PersisterImpl::new(Arc::clone(&object_store)));
+        let time_provider = Arc::new(MockProvider::new(Time::from_timestamp_nanos(0)));
+        let write_buffer = WriteBufferImpl::new(
+            Arc::clone(&persister),
+            Some(Arc::new(WalImpl::new(dir.clone()).unwrap())),
+            Arc::clone(&time_provider),
+            SegmentDuration::new_5m(),
+            crate::test_help::make_exec(),
+            Arc::new(metric::Registry::default()),
+            1000,
+            CatalogLimits::default(),
+        )
+        .await
+        .unwrap();
+        let session_context = IOxSessionContext::with_testing();
+        let runtime_env = session_context.inner().runtime_env();
+        register_iox_object_store(runtime_env, "influxdb3", Arc::clone(&object_store));
+
+        let write = |lp: &'static str| {
+            write_buffer.write_lp(
+                NamespaceName::new("foo").unwrap(),
+                lp,
+                Time::from_timestamp_nanos(0),
+                false,
+                Precision::Nanosecond,
+            )
+        };
+        write("cpu,host=a val=1i 10").await.unwrap();
+
+        // without the policy, a conflicting type is rejected:
+        assert!(matches!(
+            write("cpu,host=a val=1.5 20").await,
+            Err(Error::ParseError(_))
+        ));
+        assert!(matches!(
+            write_buffer
+                .set_table_field_widening("foo", "mem", true)
+                .await,
+            Err(Error::TableNotFound { .. })
+        ));
+
+        // with it, the field is widened to a float, and integers are still accepted:
+        write_buffer
+            .set_table_field_widening("foo", "cpu", true)
+            .await
+            .unwrap();
+        write("cpu,host=a val=1.5 20").await.unwrap();
+        write("cpu,host=a val=2i 30\ncpu,host=a val=3u 40")
+            .await
+            .unwrap();
+        assert!(matches!(
+            write("cpu,host=a val=\"four\" 50").await,
+            Err(Error::ParseError(_))
+        ));
+
+        let table = write_buffer
+            .catalog()
+            .db_schema("foo")
+            .unwrap()
+            .get_table("cpu")
+            .cloned()
+            .unwrap();
+        assert!(table.widens_numeric_fields());
+        assert_eq!(
+            Some(InfluxColumnType::Field(InfluxFieldType::Float)),
+            table.field_type_by_name("val")
+        );
+        let expected = [
+            "+------+--------------------------------+-----+",
+            "| host | time                           | val |",
+            "+------+--------------------------------+-----+",
+            "| a    | 1970-01-01T00:00:00.000000010Z | 1.0 |",
+            "| a    | 1970-01-01T00:00:00.000000020Z | 1.5 |",
+            "| a    | 1970-01-01T00:00:00.000000030Z | 2.0 |",
+            "| a    | 1970-01-01T00:00:00.000000040Z | 3.0 |",
+            "+------+--------------------------------+-----+",
+        ];
+        let actual = get_table_batches(&write_buffer, "foo", "cpu", &session_context).await;
+        assert_batches_eq!(expected, &actual);
+
+        // the writes are widened the same way when they are replayed from the wal:
+        drop(write_buffer);
+        let write_buffer = WriteBufferImpl::new(
+            Arc::clone(&persister),
+            Some(Arc::new(WalImpl::new(dir).unwrap())),
+            Arc::clone(&time_provider),
+            SegmentDuration::new_5m(),
+            crate::test_help::make_exec(),
+            Arc::new(metric::Registry::default()),
+            1000,
+            CatalogLimits::default(),
+        )
+        .await
+        .unwrap();
+        let actual = get_table_batches(&write_buffer, "foo", "cpu", &session_context).await;
+        assert_batches_eq!(expected, &actual);
+    }
+
     async fn get_table_batches(
         write_buffer: &WriteBufferImpl<WalImpl, MockProvider>,
         database_name: &str,
@@ -2,13 +2,17 @@
 //! When queries come in they will combine whatever chunks exist from `SegmentState` with
 //! the persisted files to get the full set of data to query.
 
-use crate::{ParquetFile, PersistedSegment};
+use crate::{DeletePredicate, ParquetFile, PersistedSegment};
 use parking_lot::RwLock;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 #[derive(Debug, Default)]
 pub struct PersistedFiles {
     /// The map of databases to tables to files
     files: RwLock<hashbrown::HashMap<String, hashbrown::HashMap<String, Vec<ParquetFile>>>>,
+    /// Set when files in segments older than the ones held here may have tombstones, until the
+    /// files with tombstones in every segment have been rewritten
+    older_segment_tombstones: AtomicBool,
 }
 
 impl PersistedFiles {
@@ -36,6 +40,7 @@ impl PersistedFiles {
 
         Self {
             files: RwLock::new(files),
+            older_segment_tombstones: AtomicBool::new(false),
         }
     }
 
@@ -63,6 +68,122 @@ impl PersistedFiles {
             });
     }
 
+    /// Remove the file with the given path from the list of persisted files
+    pub fn remove_file(&self, db_name: &str, table_name: &str, path: &str) {
+        let mut files = self.files.write();
+        if let Some(table_files) = files
+            .get_mut(db_name)
+            .and_then(|tables| tables.get_mut(table_name))
+        {
+            table_files.retain(|file| file.path != path);
+        }
+    }
+
+    /// Add a tombstone for the delete to the files of the table that may contain rows that match
+    /// it, returning true if any were given one
+    pub fn add_tombstone(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        predicate: &DeletePredicate,
+    ) -> bool {
+        let mut files = self.files.write();
+        files
+            .get_mut(db_name)
+            .and_then(|tables| tables.get_mut(table_name))
+            .map(|table_files| {
+                table_files
+                    .iter_mut()
+                    .fold(false, |added, file| file.add_tombstone(predicate) || added)
+            })
+            .unwrap_or_default()
+    }
+
+    /// Record that files in segments older than the ones held here may have tombstones, such as
+    /// when a delete added them to the segment info files
+    pub fn add_older_segment_tombstones(&self) {
+        self.older_segment_tombstones.store(true, Ordering::Release);
+    }
+
+    /// Clear the record of tombstones in older segments, once the files with tombstones in every
+    /// segment have been rewritten
+    pub fn clear_older_segment_tombstones(&self) {
+        self.older_segment_tombstones
+            .store(false, Ordering::Release);
+    }
+
+    /// Check if any file, including those in segments older than the ones held here, has
+    /// tombstones for rows that still need to be removed from it
+    pub fn has_tombstones(&self) -> bool {
+        if self.older_segment_tombstones.load(Ordering::Acquire) {
+            return true;
+        }
+        let files = self.files.read();
+        files
+            .values()
+            .flat_map(|tables| tables.values().flatten())
+            .any(|file| !file.tombstones.is_empty())
+    }
+
+    /// Replace the file with the given path by a file rewritten without the rows deleted by the
+    /// `applied` tombstones, or remove it if no rows were left. Tombstones added to the file
+    /// while it was being rewritten are kept on the new file.
+    pub fn replace_file(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        path: &str,
+        new_file: Option<ParquetFile>,
+        applied: &[DeletePredicate],
+    ) {
+        let mut files = self.files.write();
+        let Some(table_files) = files
+            .get_mut(db_name)
+            .and_then(|tables| tables.get_mut(table_name))
+        else {
+            return;
+        };
+        let Some(index) = table_files.iter().position(|file| file.path == path) else {
+            return;
+        };
+        let old_file = table_files.remove(index);
+        if let Some(mut new_file) = new_file {
+            for tombstone in old_file.tombstones {
+                if !applied.contains(&tombstone) {
+                    new_file.add_tombstone(&tombstone);
+                }
+            }
+            table_files.insert(index, new_file);
+        }
+    }
+
+    /// Remove all files for the given database, returning them
+    pub fn remove_database(&self, db_name: &str) -> Vec<ParquetFile> {
+        let mut files = self.files.write();
+        files
+            .remove(db_name)
+            .map(|tables| tables.into_values().flatten().collect())
+            .unwrap_or_default()
+    }
+
+    /// Remove all files for the given table, returning them
+    pub fn remove_table(&self, db_name: &str, table_name: &str) -> Vec<ParquetFile> {
+        let mut files = self.files.write();
+        files
+            .get_mut(db_name)
+            .and_then(|tables| tables.remove(table_name))
+            .unwrap_or_default()
+    }
+
+    /// Check if there are any files in the given database whose data is entirely older than the
+    /// `cutoff`, in nanoseconds since the epoch
+    pub fn has_files_before(&self, db_name: &str, cutoff: i64) -> bool {
+        let files = self.files.read();
+        files
+            .get(db_name)
+            .is_some_and(|tables| tables.values().flatten().any(|file| file.max_time < cutoff))
+    }
+
     /// Get the list of files for a given database and table
     pub fn get_files(&self, db_name: &str, table_name: &str) -> Vec<ParquetFile> {
         let files = self.files.read();
@@ -13,6 +13,7 @@ use arrow::record_batch::RecordBatch;
 use data_types::{
     ChunkId, ChunkOrder, PartitionKey, TableId, TimestampMinMax, TransitionPartitionId,
 };
+use datafusion::error::DataFusionError;
 use datafusion_util::stream_from_batches;
 use iox_query::chunk_statistics::{create_chunk_statistics, NoColumnRanges};
 use iox_query::frontend::reorg::ReorgPlanner;
@@ -24,6 +25,7 @@ use parquet::format::FileMetaData;
 use schema::sort::SortKey;
 use schema::Schema;
 use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::watch;
@@ -35,10 +37,12 @@ const PERSISTER_CHECK_INTERVAL: Duration = Duration::from_millis(10);
 #[cfg(not(test))]
 const PERSISTER_CHECK_INTERVAL: Duration = Duration::from_secs(1);
 
+#[allow(clippy::too_many_arguments)]
 pub(crate) async fn run_buffer_segment_persist_and_cleanup<P, T, W>(
     persister: Arc<P>,
     segment_state: Arc<RwLock<SegmentState<T, W>>>,
     persisted_files: Arc<PersistedFiles>,
+    segment_info_lock: Arc<tokio::sync::Mutex<()>>,
     mut shutdown_rx: watch::Receiver<()>,
     time_provider: Arc<T>,
     wal: Option<Arc<W>>,
@@ -56,7 +60,7 @@ pub(crate) async fn run_buffer_segment_persist_and_cleanup<P, T, W>(
                 break;
             }
             _ = tokio::time::sleep(PERSISTER_CHECK_INTERVAL) => {
-                if let Err(e) = persist_and_cleanup_ready_segments(Arc::clone(&persister), Arc::clone(&segment_state), Arc::clone(&persisted_files), Arc::clone(&time_provider), wal.clone(), Arc::clone(&executor)).await {
+                if let Err(e) = persist_and_cleanup_ready_segments(Arc::clone(&persister), Arc::clone(&segment_state), Arc::clone(&persisted_files), &segment_info_lock, Arc::clone(&time_provider), wal.clone(), Arc::clone(&executor)).await {
                     error!("Error persisting and cleaning up segments: {}", e);
                 }
             }
@@ -68,6 +72,7 @@ async fn persist_and_cleanup_ready_segments<P, T, W>(
     persister: Arc<P>,
     segment_state: Arc<RwLock<SegmentState<T, W>>>,
     persisted_files: Arc<PersistedFiles>,
+    segment_info_lock: &tokio::sync::Mutex<()>,
     time_provider: Arc<T>,
     wal: Option<Arc<W>>,
     executor: Arc<iox_query::exec::Executor>,
@@ -92,6 +97,7 @@ where
             Arc::clone(&persister),
             Arc::clone(&segment_state),
             Arc::clone(&persisted_files),
+            segment_info_lock,
             wal.clone(),
             Arc::clone(&executor),
         )
@@ -118,6 +124,7 @@ where
                 Arc::clone(&persister),
                 Arc::clone(&segment_state),
                 Arc::clone(&persisted_files),
+                segment_info_lock,
                 wal.clone(),
                 Arc::clone(&executor),
             )
@@ -129,14 +136,17 @@ where
 }
 
 // Performs the following:
-// 1. persist the segment to the object store
-// 2. remove the segment from the persisting_segments map and add it to the persisted_segments map
-// 3. remove the wal segment file
+// 1. persist the segment's data to the object store
+// 2. while holding the segment info lock, write the segment info file, with tombstones for any
+//    deletes made while it was persisting
+// 3. remove the segment from the persisting_segments map and add it to the persisted_segments map
+// 4. remove the wal segment file
 async fn persist_closed_segment_and_cleanup<P, T, W>(
     closed_segment: Arc<ClosedBufferSegment>,
     persister: Arc<P>,
     segment_state: Arc<RwLock<SegmentState<T, W>>>,
     persisted_files: Arc<PersistedFiles>,
+    segment_info_lock: &tokio::sync::Mutex<()>,
     wal: Option<Arc<W>>,
     executor: Arc<iox_query::exec::Executor>,
 ) -> Result<(), crate::Error>
@@ -149,35 +159,53 @@ where
 {
     let closed_segment_start_time = closed_segment.segment_range.start_time;
     let closed_segment_id = closed_segment.segment_id;
-    let persisted_segment = closed_segment.persist(persister, executor, None).await?;
-
+    let mut persisted_segment = closed_segment
+        .persist_parquet_files(Arc::clone(&persister), executor, None)
+        .await?;
+
+    // deletes are applied to the segment state and the segment info files while holding the
+    // segment info lock, so none can be missed between taking them and the persisted files being
+    // queried in place of the segment
+    let _segment_info_guard = segment_info_lock.lock().await;
+    for op in closed_segment.take_deletes() {
+        op.add_tombstones(&mut persisted_segment.databases);
+    }
+    persister
+        .persist_segment(&persisted_segment)
+        .await
+        .map_err(persister::Error::from)?;
     {
         let mut segment_state = segment_state.write();
         segment_state.remove_persisting_segment(closed_segment_start_time);
-        persisted_files.add_persisted_segment_files(persisted_segment);
+        persisted_files.add_persisted_segment_files(persisted_segment.clone());
     }
 
+    // the wal may block on io, so it's kept off the runtime's workers
     if let Some(wal) = wal {
-        wal.delete_wal_segment(closed_segment_id)?;
+        tokio::task::spawn_blocking(move || wal.delete_wal_segment(closed_segment_id))
+            .await
+            .expect("wal segment delete task panicked")?;
     }
 
     Ok(())
 }
 
 #[cfg(test)]
-const BUFFER_SIZE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+pub(crate) const BUFFER_SIZE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
 
 #[cfg(not(test))]
-const BUFFER_SIZE_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+pub(crate) const BUFFER_SIZE_CHECK_INTERVAL: Duration = Duration::from_secs(10);
 
 /// Periodically checks the buffer size and persists segments or tables to free up memory if the
-/// buffer size is over the limit.
+/// buffer size is over the limit. The measured size is stored in `current_buffer_size`, which is
+/// used to reject writes while the buffer is too large.
 pub(crate) async fn run_buffer_size_check_and_persist<P, T, W>(
     persister: Arc<P>,
     segment_state: Arc<RwLock<SegmentState<T, W>>>,
     mut shutdown_rx: watch::Receiver<()>,
     executor: Arc<iox_query::exec::Executor>,
     buffer_limit_mb: usize,
+    current_buffer_size: Arc<AtomicUsize>,
 ) where
     P: Persister,
     persister::Error: From<<P as Persister>::Error>,
@@ -196,7 +224,7 @@ pub(crate) async fn run_buffer_size_check_and_persist<P, T, W>(
                 break;
             }
             _ = interval.tick() => {
-                check_buffer_size_and_persist(Arc::clone(&persister), Arc::clone(&segment_state), Arc::clone(&executor), &mut buffer_sizes, buffer_limit_mb).await;
+                check_buffer_size_and_persist(Arc::clone(&persister), Arc::clone(&segment_state), Arc::clone(&executor), &mut buffer_sizes, buffer_limit_mb, &current_buffer_size).await;
             }
         }
     }
@@ -214,6 +242,7 @@ async fn check_buffer_size_and_persist<P, T, W>(
     executor: Arc<iox_query::exec::Executor>,
     buffer_sizes: &mut BufferSizeRingBuffer,
     buffer_limit_mb: usize,
+    current_buffer_size: &AtomicUsize,
 ) where
     P: Persister,
     persister::Error: From<<P as Persister>::Error>,
@@ -226,10 +255,12 @@ async fn check_buffer_size_and_persist<P, T, W>(
         segment_state.open_segments_sizes()
     };
     let buffer_size = segment_sizes.iter().map(|s| s.size()).sum::<usize>();
+    current_buffer_size.store(buffer_size, Ordering::Relaxed);
 
     buffer_sizes.push(buffer_size, Instant::now());
 
     let mut size_to_shed = buffer_sizes.size_to_shed(buffer_limit_mb);
+    let shed_size = size_to_shed;
 
     while let Some(target) = next_to_persist(size_to_shed, &mut segment_sizes) {
         match target {
@@ -298,26 +329,53 @@ async fn check_buffer_size_and_persist<P, T, W>(
                         row_count: meta.num_rows as u64,
                         min_time,
                         max_time,
+                        tombstones: vec![],
                     };
 
                     // grab a lock on segment state and insert the parquet file in the list of files while clearing out the persisting data from the buffer
-                    if let Err(e) = segment_state.write().clear_persisting_table_buffer(
+                    let cleared = segment_state.write().clear_persisting_table_buffer(
                         parquet_file,
                         table.segment_id,
                         &table.database_name,
                         &table.table_name,
-                    ) {
-                        // if there's an error here, it just means there was a problem logging this in the WAL. The data has already been persisted so it's safe.
-                        error!("Error clearing persisted table buffer: {}", e);
+                    );
+
+                    // the buffered data the file replaces has already been dropped, so the op
+                    // recording it must be durable regardless of the wal sync mode. It's written
+                    // without the segment state lock, and off the runtime's workers, as the wal
+                    // may block on io.
+                    if let Some((segment_writer, op)) = cleared {
+                        let res = tokio::task::spawn_blocking(move || {
+                            let mut segment_writer = segment_writer.lock();
+                            segment_writer.write_batch(vec![op])?;
+                            segment_writer.sync()
+                        })
+                        .await
+                        .expect("wal write task panicked");
+                        if let Err(e) = res {
+                            // if there's an error here, it just means there was a problem logging this in the WAL. The data has already been persisted so it's safe.
+                            error!("Error clearing persisted table buffer: {}", e);
+                        }
                     }
                 }
                 size_to_shed -= table.size_bytes;
             }
         }
     }
+
+    // writes shouldn't be rejected until the next check because of what was just shed:
+    if shed_size > 0 {
+        let buffer_size = segment_state
+            .read()
+            .open_segments_sizes()
+            .iter()
+            .map(|s| s.size())
+            .sum::<usize>();
+        current_buffer_size.store(buffer_size, Ordering::Relaxed);
+    }
 }
 
-fn min_max_time_from_batch(batch: &RecordBatch) -> (i64, i64) {
+pub(super) fn min_max_time_from_batch(batch: &RecordBatch) -> (i64, i64) {
     batch
         .column_by_name(TIME_COLUMN_NAME)
         .map(|c| {
@@ -356,8 +414,50 @@ where
     persister::Error: From<<P as Persister>::Error>,
     write_buffer::Error: From<<P as Persister>::Error>,
 {
-    // Dedupe and sort using the COMPACT query built into
-    // iox_query
+    let data = sort_dedupe(
+        table_name,
+        batch,
+        schema,
+        time_min_max,
+        segment_key,
+        sort_key,
+        &executor,
+    )
+    .await
+    .unwrap();
+
+    // keep attempting to persist forever. If we can't reach the object store, we'll stop accepting
+    // writes elsewhere in the system, so we need to keep trying to persist.
+    loop {
+        let batch_stream = stream_from_batches(schema.as_arrow(), data.clone());
+
+        match persister
+            .persist_parquet_file(path.clone(), batch_stream)
+            .await
+        {
+            Ok((size_bytes, meta)) => {
+                info!("Persisted parquet file: {}", path.to_string());
+                return (size_bytes, meta);
+            }
+            Err(_) => {
+                error!("Error persisting parquet file: (TODO: figure out why we can't output the error), sleeping and retrying...");
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+        }
+    }
+}
+
+/// Dedupes and sorts the rows of a table in a segment, using the COMPACT query built into
+/// iox_query, so that they can be persisted to a parquet file
+pub(super) async fn sort_dedupe(
+    table_name: &str,
+    batch: RecordBatch,
+    schema: &Schema,
+    time_min_max: TimestampMinMax,
+    segment_key: &PartitionKey,
+    sort_key: SortKey,
+    executor: &iox_query::exec::Executor,
+) -> Result<Vec<RecordBatch>, DataFusionError> {
     let row_count = batch.num_rows();
 
     let chunk_stats =
@@ -377,33 +477,13 @@ where
 
     let logical_plan = ReorgPlanner::new()
         .compact_plan(Arc::from(table_name), schema, chunks, sort_key)
-        .unwrap();
+        .map_err(|e| DataFusionError::External(Box::new(e)))?;
 
     // Build physical plan
-    let physical_plan = ctx.create_physical_plan(&logical_plan).await.unwrap();
+    let physical_plan = ctx.create_physical_plan(&logical_plan).await?;
 
     // Execute the plan and return compacted record batches
-    let data = ctx.collect(physical_plan).await.unwrap();
-
-    // keep attempting to persist forever. If we can't reach the object store, we'll stop accepting
-    // writes elsewhere in the system, so we need to keep trying to persist.
-    loop {
-        let batch_stream = stream_from_batches(schema.as_arrow(), data.clone());
-
-        match persister
-            .persist_parquet_file(path.clone(), batch_stream)
-            .await
-        {
-            Ok((size_bytes, meta)) => {
-                info!("Persisted parquet file: {}", path.to_string());
-                return (size_bytes, meta);
-            }
-            Err(_) => {
-                error!("Error persisting parquet file: (TODO: figure out why we can't output the error), sleeping and retrying...");
-                tokio::time::sleep(Duration::from_secs(1)).await;
-            }
-        }
-    }
+    ctx.collect(physical_plan).await
 }
 
 // The interval from the last write to a segment at which to close the segment if the buffer size
@@ -703,6 +783,7 @@ mod tests {
             Arc::clone(&persister),
             Arc::clone(&segment_state),
             Arc::clone(&persisted_files),
+            &tokio::sync::Mutex::new(()),
             Arc::clone(&time_provider),
             Some(Arc::clone(&wal)),
             crate::test_help::make_exec(),
@@ -787,6 +868,7 @@ mod tests {
             crate::test_help::make_exec(),
             &mut buffer_sizes,
             1,
+            &AtomicUsize::new(0),
         )
         .await;
 
@@ -942,6 +1024,14 @@ mod tests {
             Ok(())
         }
 
+        fn recover_segment(&self, _segment_id: SegmentId) -> wal::Result<bool> {
+            todo!()
+        }
+
+        fn flush_config(&self) -> wal::WalFlushConfig {
+            wal::WalFlushConfig::default()
+        }
+
         fn as_any(&self) -> &dyn Any {
             self as &dyn Any
         }
@@ -0,0 +1,348 @@
+//! This module contains the background task that enforces the retention periods of databases by
+//! removing persisted parquet files whose data has entirely expired, and the expired rows of the
+//! last caches.
+
+use crate::catalog::Catalog;
+use crate::last_cache::LastCacheProvider;
+use crate::write_buffer::persisted_files::PersistedFiles;
+use crate::{write_buffer, ParquetFile, Persister};
+use iox_time::{Time, TimeProvider};
+use object_store::path::Path as ObjPath;
+use object_store::ObjectStore;
+use observability_deps::tracing::{error, info};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+use tokio::time::MissedTickBehavior;
+
+#[cfg(test)]
+const RETENTION_CHECK_INTERVAL: Duration = Duration::from_millis(10);
+
+#[cfg(not(test))]
+const RETENTION_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How often the segments older than those whose files are loaded are checked for expired files,
+/// as they aren't known to have any until their segment info files are read
+#[cfg(test)]
+const OLDER_SEGMENTS_CHECK_INTERVAL: Duration = Duration::from_millis(10);
+
+#[cfg(not(test))]
+const OLDER_SEGMENTS_CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Periodically removes expired parquet files and last cache rows for databases that have a
+/// retention period
+pub(crate) async fn run_retention_enforcement<P, T>(
+    persister: Arc<P>,
+    catalog: Arc<Catalog>,
+    persisted_files: Arc<PersistedFiles>,
+    segment_info_lock: Arc<tokio::sync::Mutex<()>>,
+    last_cache: Arc<LastCacheProvider>,
+    time_provider: Arc<T>,
+    mut shutdown_rx: watch::Receiver<()>,
+) where
+    P: Persister,
+    T: TimeProvider,
+    write_buffer::Error: From<<P as Persister>::Error>,
+{
+    let mut interval = tokio::time::interval(RETENTION_CHECK_INTERVAL);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    let mut older_segments_checked: Option<Instant> = None;
+
+    loop {
+        tokio::select! {
+            _ = shutdown_rx.changed() => {
+                break;
+            }
+            _ = interval.tick() => {
+                remove_expired_cache_rows(&catalog, &last_cache, time_provider.now());
+                let check_older_segments = older_segments_checked
+                    .map_or(true, |checked| checked.elapsed() >= OLDER_SEGMENTS_CHECK_INTERVAL);
+                match remove_expired_files(Arc::clone(&persister), &catalog, &persisted_files, &segment_info_lock, time_provider.now(), check_older_segments).await {
+                    Ok(()) if check_older_segments => older_segments_checked = Some(Instant::now()),
+                    Ok(()) => {}
+                    Err(e) => error!("Error removing expired files: {}", e),
+                }
+            }
+        }
+    }
+}
+
+/// Drops the rows that have expired from the last caches of the databases that have a retention
+/// period
+pub(crate) fn remove_expired_cache_rows(
+    catalog: &Catalog,
+    last_cache: &LastCacheProvider,
+    now: Time,
+) {
+    let now_ns = now.timestamp_nanos();
+    for db_name in catalog.list_databases() {
+        if let Some(cutoff) = catalog
+            .db_schema(&db_name)
+            .and_then(|db_schema| db_schema.retention_cutoff_ns(now_ns))
+        {
+            last_cache.remove_expired_rows(&db_name, cutoff);
+        }
+    }
+}
+
+// Performs the following:
+// 1. Determine the retention cutoff for each database that has a retention period
+// 2. If any persisted files have expired, or `check_older_segments` is set, as the files of the
+//    segments older than those that are loaded aren't in the persisted files, load every
+//    persisted segment while holding the segment info lock, and for each segment that contains
+//    expired files:
+//    a. remove the files from the persisted files so they are no longer queried
+//    b. write the updated segment info file so the files are not loaded on restart
+//    c. delete the files from the object store
+pub(crate) async fn remove_expired_files<P>(
+    persister: Arc<P>,
+    catalog: &Catalog,
+    persisted_files: &PersistedFiles,
+    segment_info_lock: &tokio::sync::Mutex<()>,
+    now: Time,
+    check_older_segments: bool,
+) -> write_buffer::Result<()>
+where
+    P: Persister,
+    write_buffer::Error: From<<P as Persister>::Error>,
+{
+    let now_ns = now.timestamp_nanos();
+    let cutoffs: HashMap<String, i64> = catalog
+        .list_databases()
+        .into_iter()
+        .filter_map(|db_name| {
+            let cutoff = catalog.db_schema(&db_name)?.retention_cutoff_ns(now_ns)?;
+            (check_older_segments || persisted_files.has_files_before(&db_name, cutoff))
+                .then_some((db_name, cutoff))
+        })
+        .collect();
+    if cutoffs.is_empty() {
+        return Ok(());
+    }
+
+    let object_store = persister.object_store();
+    let _segment_info_guard = segment_info_lock.lock().await;
+    for mut segment in persister.load_all_segments().await? {
+        let mut expired: Vec<(String, String, ParquetFile)> = vec![];
+        for (db_name, db_tables) in segment.databases.iter_mut() {
+            let Some(cutoff) = cutoffs.get(db_name) else {
+                continue;
+            };
+            for (table_name, table) in db_tables.tables.iter_mut() {
+                let (expired_files, retained_files) = std::mem::take(&mut table.parquet_files)
+                    .into_iter()
+                    .partition(|file| file.max_time < *cutoff);
+                table.parquet_files = retained_files;
+                expired.extend(
+                    expired_files
+                        .into_iter()
+                        .map(|file: ParquetFile| (db_name.clone(), table_name.clone(), file)),
+                );
+            }
+            db_tables
+                .tables
+                .retain(|_, table| !table.parquet_files.is_empty());
+        }
+        if expired.is_empty() {
+            continue;
+        }
+        segment
+            .databases
+            .retain(|_, db_tables| !db_tables.tables.is_empty());
+
+        for (db_name, table_name, file) in &expired {
+            segment.segment_parquet_size_bytes = segment
+                .segment_parquet_size_bytes
+                .saturating_sub(file.size_bytes);
+            segment.segment_row_count = segment.segment_row_count.saturating_sub(file.row_count);
+            persisted_files.remove_file(db_name, table_name, &file.path);
+        }
+
+        persister.persist_segment(&segment).await?;
+
+        for (db_name, table_name, file) in expired {
+            info!(%db_name, %table_name, path = %file.path, "removing expired parquet file");
+            if let Err(e) = object_store.delete(&ObjPath::from(file.path)).await {
+                error!("Error deleting expired parquet file: {}", e);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog::DatabaseSchema;
+    use crate::persister::PersisterImpl;
+    use crate::{DatabaseTables, PersistedSegment, SegmentId, TableParquetFiles};
+    use bytes::Bytes;
+    use object_store::memory::InMemory;
+
+    fn parquet_file(path: &str, min_time: i64, max_time: i64) -> ParquetFile {
+        ParquetFile {
+            path: path.to_string(),
+            size_bytes: 10,
+            row_count: 1,
+            min_time,
+            max_time,
+            tombstones: vec![],
+        }
+    }
+
+    #[tokio::test]
+    async fn removes_expired_files() {
+        let object_store: Arc<dyn ObjectStore> = Arc::new(InMemory::new());
+        let persister = Arc::new(PersisterImpl::new(Arc::clone(&object_store)));
+
+        let catalog = Catalog::new();
+        let mut db = DatabaseSchema::new("expiring");
+        db.retention_period = Some(Duration::from_secs(10));
+        catalog.create_database(db).unwrap();
+        catalog
+            .create_database(DatabaseSchema::new("forever"))
+            .unwrap();
+
+        let old_file = parquet_file("expiring/cpu/1.parquet", 0, 1_000_000_000);
+        let new_file = parquet_file("expiring/cpu/2.parquet", 0, 20_000_000_000);
+        let forever_file = parquet_file("forever/cpu/1.parquet", 0, 1_000_000_000);
+        let tables = |files: Vec<ParquetFile>| DatabaseTables {
+            tables: [(
+                "cpu".to_string(),
+                TableParquetFiles {
+                    table_name: "cpu".to_string(),
+                    parquet_files: files,
+                    sort_key: vec![],
+                },
+            )]
+            .into_iter()
+            .collect(),
+        };
+        let segment = PersistedSegment {
+            segment_id: SegmentId::new(1),
+            segment_wal_size_bytes: 0,
+            segment_parquet_size_bytes: 30,
+            segment_row_count: 3,
+            segment_min_time: 0,
+            segment_max_time: 20_000_000_000,
+            databases: [
+                (
+                    "expiring".to_string(),
+                    tables(vec![old_file.clone(), new_file.clone()]),
+                ),
+                ("forever".to_string(), tables(vec![forever_file.clone()])),
+            ]
+            .into_iter()
+            .collect(),
+        };
+        persister.persist_segment(&segment).await.unwrap();
+        for file in [&old_file, &new_file, &forever_file] {
+            object_store
+                .put(&ObjPath::from(file.path.as_str()), Bytes::from("data"))
+                .await
+                .unwrap();
+        }
+        let persisted_files = PersistedFiles::new_from_persisted_segments(vec![segment]);
+
+        // nothing has expired yet:
+        remove_expired_files(
+            Arc::clone(&persister),
+            &catalog,
+            &persisted_files,
+            &tokio::sync::Mutex::new(()),
+            Time::from_timestamp(5, 0).unwrap(),
+            false,
+        )
+        .await
+        .unwrap();
+        assert_eq!(2, persisted_files.get_files("expiring", "cpu").len());
+
+        // only the old file in the database with a retention period has expired:
+        remove_expired_files(
+            Arc::clone(&persister),
+            &catalog,
+            &persisted_files,
+            &tokio::sync::Mutex::new(()),
+            Time::from_timestamp(15, 0).unwrap(),
+            false,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            vec![new_file.clone()],
+            persisted_files.get_files("expiring", "cpu")
+        );
+        assert_eq!(
+            vec![forever_file.clone()],
+            persisted_files.get_files("forever", "cpu")
+        );
+        assert!(object_store
+            .head(&ObjPath::from(old_file.path.as_str()))
+            .await
+            .is_err());
+        assert!(object_store
+            .head(&ObjPath::from(new_file.path.as_str()))
+            .await
+            .is_ok());
+
+        // the updated segment info file was persisted:
+        let segments = persister.load_segments(10).await.unwrap();
+        assert_eq!(1, segments.len());
+        assert_eq!(2, segments[0].segment_row_count);
+        assert_eq!(
+            vec![new_file.clone()],
+            segments[0].databases["expiring"].tables["cpu"].parquet_files
+        );
+
+        // the files of a segment older than those loaded are only removed once the older
+        // segments are checked
+        let older_file = parquet_file("expiring/cpu/0.parquet", 0, 1_000_000_000);
+        let older_segment = PersistedSegment {
+            segment_id: SegmentId::new(0),
+            segment_wal_size_bytes: 0,
+            segment_parquet_size_bytes: 10,
+            segment_row_count: 1,
+            segment_min_time: 0,
+            segment_max_time: 1_000_000_000,
+            databases: [("expiring".to_string(), tables(vec![older_file.clone()]))]
+                .into_iter()
+                .collect(),
+        };
+        persister.persist_segment(&older_segment).await.unwrap();
+        object_store
+            .put(
+                &ObjPath::from(older_file.path.as_str()),
+                Bytes::from("data"),
+            )
+            .await
+            .unwrap();
+        for check_older_segments in [false, true] {
+            remove_expired_files(
+                Arc::clone(&persister),
+                &catalog,
+                &persisted_files,
+                &tokio::sync::Mutex::new(()),
+                Time::from_timestamp(15, 0).unwrap(),
+                check_older_segments,
+            )
+            .await
+            .unwrap();
+            assert_eq!(
+                check_older_segments,
+                object_store
+                    .head(&ObjPath::from(older_file.path.as_str()))
+                    .await
+                    .is_err()
+            );
+        }
+        let segments = persister.load_segments(10).await.unwrap();
+        assert_eq!(2, segments.len());
+        assert!(segments[1].databases.is_empty());
+        assert_eq!(
+            vec![new_file],
+            segments[0].databases["expiring"].tables["cpu"].parquet_files
+        );
+    }
+}
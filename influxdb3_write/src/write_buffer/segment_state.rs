@@ -1,18 +1,23 @@
 //! State for the write buffer segments.
 
-use crate::catalog::{Catalog, DatabaseSchema};
+use crate::catalog::{Catalog, DatabaseSchema, TIME_COLUMN_NAME};
 use crate::chunk::BufferChunk;
 use crate::paths::ParquetFilePath;
 use crate::wal::WalSegmentWriterNoopImpl;
 use crate::write_buffer::buffer_segment::{
-    ClosedBufferSegment, OpenBufferSegment, SegmentSizes, WriteBatch,
+    ClosedBufferSegment, OpenBufferSegment, SegmentSizes, SegmentWriter, WriteBatch,
 };
+use crate::write_buffer::deletes::remove_deleted_rows;
 use crate::write_buffer::parquet_chunk_from_file;
 use crate::{
-    wal, write_buffer, ParquetFile, SegmentDuration, SegmentId, SegmentRange, SequenceNumber, Wal,
-    WalOp,
+    wal, write_buffer, DeleteOp, DeletePredicate, ParquetFile, SegmentDuration, SegmentId,
+    SegmentRange, SequenceNumber, Wal, WalOp, WalSegmentWriter,
 };
+use arrow::array::TimestampNanosecondArray;
+use arrow::compute::filter_record_batch;
+use arrow::compute::kernels::cmp::gt_eq;
 use arrow::datatypes::SchemaRef;
+use arrow::error::ArrowError;
 use arrow::record_batch::RecordBatch;
 use data_types::{ChunkId, ChunkOrder, TableId, TransitionPartitionId};
 use datafusion::common::DataFusionError;
@@ -24,6 +29,8 @@ use iox_query::QueryChunk;
 use iox_time::{Time, TimeProvider};
 use object_store::ObjectStore;
 use observability_deps::tracing::error;
+use parking_lot::RwLock;
+use schema::sort::SortKey;
 #[cfg(test)]
 use schema::Schema;
 use std::collections::BTreeMap;
@@ -77,15 +84,45 @@ impl<T: TimeProvider, W: Wal> SegmentState<T, W> {
         }
     }
 
-    pub(crate) fn write_ops_to_segment(
-        &mut self,
+    /// Returns the wal writer of the segment with this start time, opening a new segment if it
+    /// isn't currently open.
+    ///
+    /// The wal can block on IO, so the lock on the state is only held to look up or add the
+    /// segment, and not while the new segment's wal file is created.
+    pub(crate) fn segment_writer_for_time(
+        state: &RwLock<Self>,
         segment_start: Time,
-        ops: Vec<WalOp>,
         starting_catalog_sequence_number: SequenceNumber,
-    ) -> wal::Result<()> {
-        let segment =
-            self.get_or_create_segment_for_time(segment_start, starting_catalog_sequence_number)?;
-        segment.write_wal_ops(ops)
+    ) -> wal::Result<SegmentWriter> {
+        if let Some(segment) = state.read().segments.get(&segment_start) {
+            return Ok(segment.segment_writer());
+        }
+
+        let (segment_id, segment_range, wal) = {
+            let mut state = state.write();
+            if state.segments.len() >= OPEN_SEGMENT_LIMIT {
+                return Err(wal::Error::OpenSegmentLimitReached(OPEN_SEGMENT_LIMIT));
+            }
+            let segment_range =
+                SegmentRange::from_time_and_duration(segment_start, state.segment_duration, false);
+            (state.next_segment_id(), segment_range, state.wal.clone())
+        };
+        let segment_writer = new_segment_writer(wal.as_deref(), segment_id, segment_range)?;
+
+        let mut state = state.write();
+        let state = &mut *state;
+        let segment = state.segments.entry(segment_start).or_insert_with(|| {
+            OpenBufferSegment::new(
+                Arc::clone(&state.catalog),
+                segment_id,
+                segment_range,
+                state.time_provider.now(),
+                starting_catalog_sequence_number,
+                segment_writer,
+                None,
+            )
+        });
+        Ok(segment.segment_writer())
     }
 
     pub(crate) fn write_batch_to_segment(
@@ -123,9 +160,27 @@ impl<T: TimeProvider, W: Wal> SegmentState<T, W> {
         let schema = schema::Schema::try_from(Arc::clone(&arrow_schema))
             .map_err(|e| DataFusionError::Execution(format!("schema error {}", e)))?;
 
+        // if the database has a retention period, rows older than the cutoff are filtered out
+        // of the buffered data, which requires the time column, so the batches are produced
+        // with the full table schema and the projection is applied after filtering:
+        let retention_cutoff =
+            db_schema.retention_cutoff_ns(self.time_provider.now().timestamp_nanos());
+        let batch_schema = match retention_cutoff {
+            Some(_) => table.schema.as_arrow(),
+            None => Arc::clone(&arrow_schema),
+        };
+        let is_expired = |segment_range: &SegmentRange| {
+            retention_cutoff
+                .is_some_and(|cutoff| segment_range.end_time.timestamp_nanos() <= cutoff)
+        };
+
         let mut chunks: Vec<Arc<dyn QueryChunk>> = vec![];
 
         for segment in self.segments.values() {
+            if is_expired(segment.segment_range()) {
+                continue;
+            }
+
             // output the older persisted stuff first
             if let Some(table_paruqet_files) =
                 segment.table_persisted_parquet_files(&db_schema.name, table_name)
@@ -150,12 +205,16 @@ impl<T: TimeProvider, W: Wal> SegmentState<T, W> {
             if let Some(batches) = segment.table_record_batches(
                 &db_schema.name,
                 table_name,
-                Arc::clone(&arrow_schema),
+                Arc::clone(&batch_schema),
                 filters,
             ) {
                 let batches = batches.map_err(|e| {
                     DataFusionError::Execution(format!("error getting batches {}", e))
                 })?;
+                let batches = retain_live_rows(batches, retention_cutoff, &[], projection)
+                    .map_err(|e| {
+                        DataFusionError::Execution(format!("error removing expired rows {}", e))
+                    })?;
                 let row_count = batches.iter().map(|batch| batch.num_rows()).sum::<usize>();
 
                 let chunk_stats = create_chunk_statistics(
@@ -173,7 +232,7 @@ impl<T: TimeProvider, W: Wal> SegmentState<T, W> {
                         TableId::new(0),
                         segment.segment_key(),
                     ),
-                    sort_key: None,
+                    sort_key: buffer_sort_key(&schema),
                     id: ChunkId::new(),
                     chunk_order: ChunkOrder::new(
                         chunks
@@ -186,15 +245,35 @@ impl<T: TimeProvider, W: Wal> SegmentState<T, W> {
         }
 
         for persisting_segment in self.persisting_segments.values() {
+            if is_expired(&persisting_segment.segment_range) {
+                continue;
+            }
+
+            // rows deleted since the segment was closed are still in its buffered data, so like
+            // expired rows they're filtered out before the projection is applied
+            let deletes = persisting_segment.table_deletes(&db_schema.name, table_name);
+            let batch_schema = if deletes.is_empty() {
+                Arc::clone(&batch_schema)
+            } else {
+                table.schema.as_arrow()
+            };
+
             if let Some(batches) = persisting_segment.buffered_data.table_record_batches(
                 &db_schema.name,
                 table_name,
-                Arc::clone(&arrow_schema),
+                batch_schema,
                 filters,
             ) {
                 let batches = batches.map_err(|e| {
                     DataFusionError::Execution(format!("error getting batches {}", e))
                 })?;
+                let batches = retain_live_rows(batches, retention_cutoff, &deletes, projection)
+                    .map_err(|e| {
+                        DataFusionError::Execution(format!(
+                            "error removing expired or deleted rows {}",
+                            e
+                        ))
+                    })?;
                 let row_count = batches.iter().map(|batch| batch.num_rows()).sum::<usize>();
 
                 let chunk_stats = create_chunk_statistics(
@@ -212,7 +291,7 @@ impl<T: TimeProvider, W: Wal> SegmentState<T, W> {
                         TableId::new(0),
                         &persisting_segment.segment_key,
                     ),
-                    sort_key: None,
+                    sort_key: buffer_sort_key(&schema),
                     id: ChunkId::new(),
                     chunk_order: ChunkOrder::new(
                         chunks
@@ -243,27 +322,70 @@ impl<T: TimeProvider, W: Wal> SegmentState<T, W> {
         segment.split_table_for_persistence(database_name, table_name, table_schema)
     }
 
+    /// Clears the persisted data from the segment's buffer, returning the segment's wal writer
+    /// and the op recording the parquet file, which must be written to it
     pub(crate) fn clear_persisting_table_buffer(
         &mut self,
         parquet_file: ParquetFile,
         segment_id: SegmentId,
         database_name: &str,
         table_name: &str,
-    ) -> write_buffer::Result<()> {
+    ) -> Option<(SegmentWriter, WalOp)> {
         if let Some(segment) = self
             .segments
             .values_mut()
             .find(|segment| segment.segment_id() == segment_id)
         {
-            segment.clear_persisting_table_buffer(parquet_file, database_name, table_name)
+            let op = segment.clear_persisting_table_buffer(parquet_file, database_name, table_name);
+            Some((segment.segment_writer(), op))
         } else {
             error!("Failed to find segment with id {:?}", segment_id);
             // caller can't call back in with the same id and get any different result, so log
-            // and say it's ok.
-            Ok(())
+            // and carry on.
+            None
         }
     }
 
+    /// Drops the buffered data for the database from every open segment. Segments that are
+    /// already persisting will skip the database if it no longer exists in the catalog.
+    pub(crate) fn remove_database(&mut self, db_name: &str) {
+        for segment in self.segments.values_mut() {
+            segment.remove_database(db_name);
+        }
+    }
+
+    /// Drops the buffered data for the table from every open segment. Segments that are already
+    /// persisting will skip the table if it no longer exists in the catalog.
+    pub(crate) fn remove_table(&mut self, db_name: &str, table_name: &str) {
+        for segment in self.segments.values_mut() {
+            segment.remove_table(db_name, table_name);
+        }
+    }
+
+    /// Drops the rows that match the delete from the buffered data of every open segment, and
+    /// records it for the segments that are persisting
+    pub(crate) fn delete_rows(&mut self, op: &DeleteOp) -> write_buffer::Result<()> {
+        for segment in self.segments.values_mut() {
+            segment.delete_rows(op)?;
+        }
+        for segment in self.persisting_segments.values() {
+            segment.delete_rows(op);
+        }
+        Ok(())
+    }
+
+    /// The id of the most recently opened segment
+    pub(crate) fn last_segment_id(&self) -> SegmentId {
+        self.last_segment_id
+    }
+
+    /// Takes the next segment id, for a segment that's being opened or for files that are
+    /// persisted in a segment of their own, such as imports
+    pub(crate) fn next_segment_id(&mut self) -> SegmentId {
+        self.last_segment_id = self.last_segment_id.next();
+        self.last_segment_id
+    }
+
     #[cfg(test)]
     pub(crate) fn open_segment_times(&self) -> Vec<Time> {
         self.segments.keys().cloned().collect()
@@ -342,15 +464,12 @@ impl<T: TimeProvider, W: Wal> SegmentState<T, W> {
                 return Err(wal::Error::OpenSegmentLimitReached(OPEN_SEGMENT_LIMIT));
             }
 
-            self.last_segment_id = self.last_segment_id.next();
-            let segment_id = self.last_segment_id;
+            let segment_id = self.next_segment_id();
             let segment_range =
                 SegmentRange::from_time_and_duration(time, self.segment_duration, false);
 
-            let segment_writer = match &self.wal {
-                Some(wal) => wal.new_segment_writer(segment_id, segment_range)?,
-                None => Box::new(WalSegmentWriterNoopImpl::new(segment_id)),
-            };
+            let segment_writer =
+                new_segment_writer(self.wal.as_deref(), segment_id, segment_range)?;
 
             let segment = OpenBufferSegment::new(
                 Arc::clone(&self.catalog),
@@ -377,6 +496,58 @@ impl<T: TimeProvider, W: Wal> SegmentState<T, W> {
     }
 }
 
+fn new_segment_writer<W: Wal>(
+    wal: Option<&W>,
+    segment_id: SegmentId,
+    segment_range: SegmentRange,
+) -> wal::Result<Box<dyn WalSegmentWriter>> {
+    match wal {
+        Some(wal) => wal.new_segment_writer(segment_id, segment_range),
+        None => Ok(Box::new(WalSegmentWriterNoopImpl::new(segment_id))),
+    }
+}
+
+/// Buffered rows are returned in time order, so chunks of them are sorted on time, if it's in the
+/// projection
+fn buffer_sort_key(schema: &schema::Schema) -> Option<SortKey> {
+    schema
+        .find_index_of(TIME_COLUMN_NAME)
+        .map(|_| SortKey::from_columns([TIME_COLUMN_NAME]))
+}
+
+/// Remove the rows from the `batches` that are older than the retention `cutoff`, if there is
+/// one, or that match any of the `deletes`, and then apply the `projection`
+fn retain_live_rows(
+    batches: Vec<RecordBatch>,
+    cutoff: Option<i64>,
+    deletes: &[DeletePredicate],
+    projection: Option<&Vec<usize>>,
+) -> Result<Vec<RecordBatch>, ArrowError> {
+    if cutoff.is_none() && deletes.is_empty() {
+        return Ok(batches);
+    }
+    let cutoff = cutoff.map(TimestampNanosecondArray::new_scalar);
+    batches
+        .into_iter()
+        .map(|batch| {
+            let batch = match &cutoff {
+                Some(cutoff) => {
+                    let time = batch.column_by_name(TIME_COLUMN_NAME).ok_or_else(|| {
+                        ArrowError::SchemaError(format!("missing {TIME_COLUMN_NAME} column"))
+                    })?;
+                    filter_record_batch(&batch, &gt_eq(time, cutoff)?)?
+                }
+                None => batch,
+            };
+            let batch = remove_deleted_rows(batch, deletes)?;
+            match projection {
+                Some(projection) => batch.project(projection),
+                None => Ok(batch),
+            }
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
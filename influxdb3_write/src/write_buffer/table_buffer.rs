@@ -1,17 +1,22 @@
 //! The in memory buffer of a table that can be quickly added to and queried
 
 use crate::catalog::TIME_COLUMN_NAME;
+use crate::write_buffer::deletes::remove_deleted_rows;
 use crate::write_buffer::{FieldData, Row};
+use crate::DeletePredicate;
 use arrow::array::{
     Array, ArrayBuilder, ArrayRef, BooleanArray, BooleanBuilder, Float64Builder,
     GenericByteDictionaryBuilder, Int64Builder, StringArray, StringBuilder,
-    StringDictionaryBuilder, TimestampNanosecondBuilder, UInt64Builder,
+    StringDictionaryBuilder, TimestampNanosecondArray, TimestampNanosecondBuilder, UInt64Builder,
 };
-use arrow::compute::filter_record_batch;
-use arrow::datatypes::{GenericStringType, Int32Type, SchemaRef};
+use arrow::compute::{cast, filter_record_batch, interleave};
+use arrow::datatypes::{DataType, GenericStringType, Int32Type, SchemaRef};
+use arrow::error::ArrowError;
 use arrow::record_batch::RecordBatch;
 use data_types::{PartitionKey, TimestampMinMax};
-use datafusion::logical_expr::{BinaryExpr, Expr};
+use datafusion::logical_expr::expr::{Between, InList};
+use datafusion::logical_expr::{BinaryExpr, Expr, Operator};
+use datafusion::scalar::ScalarValue;
 use observability_deps::tracing::{debug, error};
 use std::collections::{BTreeMap, BinaryHeap, HashMap, HashSet};
 use std::mem::size_of;
@@ -35,6 +40,9 @@ pub struct TableBuffer {
     file_number: u32,
     mutable_table_chunk: MutableTableChunk,
     persisting_record_batch: Option<RecordBatch>,
+    // deletes made while the persisting record batch is being persisted, which the file written
+    // from it needs tombstones for
+    persisting_deletes: Vec<DeletePredicate>,
 }
 
 impl TableBuffer {
@@ -42,6 +50,7 @@ impl TableBuffer {
         Self {
             segment_key,
             persisting_record_batch: None,
+            persisting_deletes: vec![],
             file_number: 1,
             mutable_table_chunk: MutableTableChunk {
                 timestamp_min: i64::MAX,
@@ -49,6 +58,7 @@ impl TableBuffer {
                 data: Default::default(),
                 row_count: 0,
                 index: BufferIndex::new(index_columns),
+                time_order: None,
             },
         }
     }
@@ -64,9 +74,11 @@ impl TableBuffer {
         }
     }
 
+    /// Returns the buffered rows that may match the filter, in time order across the batches
     pub fn record_batches(&self, schema: SchemaRef, filter: &[Expr]) -> Result<Vec<RecordBatch>> {
         match self.persisting_record_batch {
             Some(ref rb) => {
+                let rb = TimeRange::from_filter(filter).slice_sorted_batch(rb);
                 let newest = self
                     .mutable_table_chunk
                     .record_batch(schema.clone(), filter)?;
@@ -76,13 +88,13 @@ impl TableBuffer {
                     .map(|f| {
                         let col = rb
                             .column_by_name(f.name())
-                            .ok_or(Error::FieldNotFound(f.name().to_string()));
-                        col.cloned()
+                            .ok_or(Error::FieldNotFound(f.name().to_string()))?;
+                        cast_to_field_type(Arc::clone(col), f.data_type())
                     })
                     .collect();
                 let cols = cols?;
                 let rb = RecordBatch::try_new(schema, cols)?;
-                Ok(vec![rb, newest])
+                Ok(merge_by_time(rb, newest)?)
             }
             None => {
                 let rb = self.mutable_table_chunk.record_batch(schema, filter)?;
@@ -98,6 +110,9 @@ impl TableBuffer {
             size += k.len() + size_of::<String>() + v.size();
         }
         size += self.mutable_table_chunk.index._size();
+        if let Some(time_order) = &self.mutable_table_chunk.time_order {
+            size += time_order.len() * size_of::<usize>();
+        }
         size
     }
 
@@ -114,9 +129,77 @@ impl TableBuffer {
         })
     }
 
-    pub fn clear_persisting_data(&mut self) {
+    /// Clears the data that has been persisted, returning the deletes made while it was being
+    /// persisted
+    pub fn clear_persisting_data(&mut self) -> Vec<DeletePredicate> {
         self.persisting_record_batch = None;
+        std::mem::take(&mut self.persisting_deletes)
+    }
+
+    /// Drops the buffered rows that match the predicate, returning the number of rows dropped
+    pub fn delete_rows(&mut self, predicate: &DeletePredicate) -> Result<usize> {
+        let mut deleted = self.mutable_table_chunk.delete_rows(predicate);
+        if let Some(rb) = self.persisting_record_batch.take() {
+            let rows = rb.num_rows();
+            let rb = remove_deleted_rows(rb, std::slice::from_ref(predicate))?;
+            deleted += rows - rb.num_rows();
+            self.persisting_record_batch = Some(rb);
+            self.persisting_deletes.push(predicate.clone());
+        }
+        Ok(deleted)
+    }
+}
+
+/// Cast a column to the type of its field in the table schema, which differs from the type of
+/// the buffered data if the field was widened after the data was buffered
+fn cast_to_field_type(col: ArrayRef, data_type: &DataType) -> Result<ArrayRef> {
+    if col.data_type() == data_type {
+        Ok(col)
+    } else {
+        Ok(cast(&col, data_type)?)
+    }
+}
+
+/// Merges two batches with the same schema, each sorted by time, returning batches sorted by
+/// time. If the batches don't have a time column they're returned as they are.
+fn merge_by_time(a: RecordBatch, b: RecordBatch) -> Result<Vec<RecordBatch>, ArrowError> {
+    let (Some(a_times), Some(b_times)) = (batch_times(&a), batch_times(&b)) else {
+        return Ok(vec![a, b]);
+    };
+    let (a_times, b_times) = (a_times.values(), b_times.values());
+    match (a_times.last(), b_times.first()) {
+        (Some(a_last), Some(b_first)) if a_last > b_first => (),
+        // the batches don't overlap, so they're in order as they are
+        _ => return Ok(vec![a, b]),
     }
+
+    let mut indices = Vec::with_capacity(a.num_rows() + b.num_rows());
+    let (mut i, mut j) = (0, 0);
+    while i < a_times.len() && j < b_times.len() {
+        if a_times[i] <= b_times[j] {
+            indices.push((0, i));
+            i += 1;
+        } else {
+            indices.push((1, j));
+            j += 1;
+        }
+    }
+    indices.extend((i..a_times.len()).map(|i| (0, i)));
+    indices.extend((j..b_times.len()).map(|j| (1, j)));
+
+    let columns = a
+        .columns()
+        .iter()
+        .zip(b.columns())
+        .map(|(a, b)| interleave(&[a.as_ref(), b.as_ref()], &indices))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(vec![RecordBatch::try_new(a.schema(), columns)?])
+}
+
+fn batch_times(batch: &RecordBatch) -> Option<&TimestampNanosecondArray> {
+    batch
+        .column_by_name(TIME_COLUMN_NAME)
+        .and_then(|c| c.as_any().downcast_ref::<TimestampNanosecondArray>())
 }
 
 /// A batch of data to be persisted to object storage ahead of a segment getting closed
@@ -144,11 +227,15 @@ struct MutableTableChunk {
     data: BTreeMap<String, Builder>,
     row_count: usize,
     index: BufferIndex,
+    /// The ids of the rows ordered by time, then id, or `None` if the rows were added in that
+    /// order. Writes mostly arrive in time order, so this is usually `None`.
+    time_order: Option<Vec<usize>>,
 }
 
 impl MutableTableChunk {
     fn add_rows(&mut self, rows: Vec<Row>) {
         let new_row_count = rows.len();
+        let first_new_row = self.row_count;
 
         for (row_index, r) in rows.into_iter().enumerate() {
             let mut value_added = HashSet::with_capacity(r.fields.len());
@@ -243,7 +330,8 @@ impl MutableTableChunk {
                         if let Builder::I64(b) = b {
                             b.append_value(v);
                         } else {
-                            panic!("unexpected field type");
+                            // the field was widened to a float:
+                            b.widen_to_f64().append_value(v as f64);
                         }
                     }
                     FieldData::UInteger(v) => {
@@ -258,7 +346,8 @@ impl MutableTableChunk {
                         if let Builder::U64(b) = b {
                             b.append_value(v);
                         } else {
-                            panic!("unexpected field type");
+                            // the field was widened to a float:
+                            b.widen_to_f64().append_value(v as f64);
                         }
                     }
                     FieldData::Float(v) => {
@@ -270,11 +359,8 @@ impl MutableTableChunk {
                             }
                             Builder::F64(float_builder)
                         });
-                        if let Builder::F64(b) = b {
-                            b.append_value(v);
-                        } else {
-                            panic!("unexpected field type");
-                        }
+                        // an integer field is widened to a float when a float is written to it:
+                        b.widen_to_f64().append_value(v);
                     }
                     FieldData::Boolean(v) => {
                         let b = self.data.entry(f.name).or_insert_with(|| {
@@ -313,32 +399,117 @@ impl MutableTableChunk {
         }
 
         self.row_count += new_row_count;
+        self.update_time_order(first_new_row);
+    }
+
+    /// The time of each row, by row id
+    fn time_values(&self) -> &[i64] {
+        match self.data.get(TIME_COLUMN_NAME) {
+            Some(Builder::Time(b)) => b.values_slice(),
+            _ => &[],
+        }
+    }
+
+    /// Updates the time order to include the rows added from `first_new_row` on
+    fn update_time_order(&mut self, first_new_row: usize) {
+        let old_time_order = self.time_order.take();
+        let times = self.time_values();
+        let new_times = &times[first_new_row..];
+        // whether the new rows are in time order, and all come at or after the time of `last_row`
+        let in_order_after = |last_row: Option<usize>| {
+            new_times.windows(2).all(|w| w[0] <= w[1])
+                && last_row
+                    .zip(new_times.first())
+                    .map_or(true, |(row, t)| times[row] <= *t)
+        };
+
+        // the rows that come in order after the rows already in the buffer, as is usual, are
+        // added to the end of the time order, without having to merge them in
+        let old_rows = match old_time_order {
+            None if in_order_after(first_new_row.checked_sub(1)) => return,
+            Some(mut time_order) if in_order_after(time_order.last().copied()) => {
+                time_order.extend(first_new_row..times.len());
+                self.time_order = Some(time_order);
+                return;
+            }
+            old_time_order => old_time_order.unwrap_or_else(|| (0..first_new_row).collect()),
+        };
+        let mut new_rows: Vec<usize> = (first_new_row..times.len()).collect();
+        new_rows.sort_by_key(|&row| times[row]);
+
+        // merge the rows, which are both ordered by time then id, and all the old rows have
+        // lower ids than the new rows
+        let mut time_order = Vec::with_capacity(times.len());
+        let (mut old, mut new) = (old_rows.iter().peekable(), new_rows.iter().peekable());
+        while let (Some(&&o), Some(&&n)) = (old.peek(), new.peek()) {
+            if times[o] <= times[n] {
+                time_order.push(o);
+                old.next();
+            } else {
+                time_order.push(n);
+                new.next();
+            }
+        }
+        time_order.extend(old);
+        time_order.extend(new);
+        self.time_order = Some(time_order);
+    }
+
+    /// Returns the ids of all the rows, ordered by time
+    fn rows_in_time_order(&self) -> Vec<usize> {
+        match &self.time_order {
+            Some(time_order) => time_order.clone(),
+            None => (0..self.row_count).collect(),
+        }
+    }
+
+    /// Returns the ids of the rows that may match the filter, ordered by time, or `None` if all
+    /// of the rows may match and are already in time order.
+    fn rows_for_filter(&self, filter: &[Expr]) -> Option<Vec<usize>> {
+        let time_range = TimeRange::from_filter(filter);
+        let index_rows = self.index.get_rows_from_index_for_filter(filter);
+        let times = self.time_values();
+
+        match (&self.time_order, index_rows) {
+            (None, None) => {
+                let rows = time_range.positions_in(times);
+                (rows.len() < times.len()).then(|| rows.collect())
+            }
+            (Some(time_order), None) => {
+                let start = time_order.partition_point(|row| times[*row] < time_range.min);
+                let end = time_order
+                    .partition_point(|row| times[*row] <= time_range.max)
+                    .max(start);
+                Some(time_order[start..end].to_vec())
+            }
+            (time_order, Some(mut rows)) => {
+                rows.retain(|row| time_range.contains(times[*row]));
+                if time_order.is_some() {
+                    rows.sort_by_key(|row| (times[*row], *row));
+                }
+                Some(rows)
+            }
+        }
     }
 
+    /// Returns the rows that may match the filter, ordered by time. Only the rows in the time
+    /// range of the filter, and matching the filter on indexed columns, are materialized, so the
+    /// filter must still be applied to the rows returned.
     fn record_batch(&self, schema: SchemaRef, filter: &[Expr]) -> Result<RecordBatch> {
-        let row_ids = self.index.get_rows_from_index_for_filter(filter);
+        let row_ids = self.rows_for_filter(filter);
 
         let mut cols = Vec::with_capacity(schema.fields().len());
 
         for f in schema.fields() {
-            match row_ids {
-                Some(row_ids) => {
-                    let b = self
-                        .data
-                        .get(f.name())
-                        .ok_or_else(|| Error::FieldNotFound(f.name().to_string()))?
-                        .get_rows(row_ids);
-                    cols.push(b);
-                }
-                None => {
-                    let b = self
-                        .data
-                        .get(f.name())
-                        .ok_or_else(|| Error::FieldNotFound(f.name().to_string()))?
-                        .as_arrow();
-                    cols.push(b);
-                }
-            }
+            let b = self
+                .data
+                .get(f.name())
+                .ok_or_else(|| Error::FieldNotFound(f.name().to_string()))?;
+            let col = match &row_ids {
+                Some(row_ids) => b.get_rows(row_ids),
+                None => b.as_arrow(),
+            };
+            cols.push(cast_to_field_type(col, f.data_type())?);
         }
 
         Ok(RecordBatch::try_new(schema, cols)?)
@@ -372,22 +543,21 @@ impl MutableTableChunk {
 
         let newest_time = heap.peek().copied().unwrap_or_default();
 
-        // create a vec that indicates if the row is old (true) or new (false)
-        let filter_vec: BooleanArray = time_column
+        // the rows are output in time order, so the old rows come first
+        let old_row_count = time_column
             .values_slice()
             .iter()
-            .map(|t| *t < newest_time)
+            .filter(|t| **t < newest_time)
+            .count();
+        let filter_vec: BooleanArray = (0..self.row_count)
+            .map(|i| i < old_row_count)
             .collect::<Vec<bool>>()
             .into();
         let old_data = filter_record_batch(&self.record_batch(schema, &[])?, &filter_vec)?;
 
-        // create a vec with the indexes of the rows to put into a new mutable table chunk
-        let mut new_rows = Vec::with_capacity(max_count);
-        for (i, t) in filter_vec.values().iter().enumerate() {
-            if !t {
-                new_rows.push(i);
-            }
-        }
+        // create a vec with the indexes of the rows to put into a new mutable table chunk, in
+        // time order so that the new chunk is sorted
+        let new_rows = self.rows_in_time_order().split_off(old_row_count);
 
         // construct new data from the new rows
         let data: BTreeMap<String, Builder> = self
@@ -414,10 +584,63 @@ impl MutableTableChunk {
             data,
             row_count: new_rows.len(),
             index,
+            time_order: None,
         };
 
         Ok((old_data, new_data))
     }
+
+    fn delete_rows(&mut self, predicate: &DeletePredicate) -> usize {
+        if self.row_count == 0 || !predicate.overlaps(self.timestamp_min, self.timestamp_max) {
+            return 0;
+        }
+        let Some(Builder::Time(time_column)) = self.data.get(TIME_COLUMN_NAME) else {
+            return 0;
+        };
+        let mut deleted: Vec<bool> = time_column
+            .values_slice()
+            .iter()
+            .map(|t| (predicate.min_time..=predicate.max_time).contains(t))
+            .collect();
+        for (tag, value) in &predicate.tags {
+            let tag_column = match self.data.get(tag) {
+                Some(Builder::Tag(b) | Builder::Key(b)) => b.finish_cloned(),
+                // no rows have a value for the tag
+                _ => return 0,
+            };
+            let tag_values = tag_column.values();
+            let tag_values: &StringArray = tag_values.as_any().downcast_ref().unwrap();
+            for (deleted, key) in deleted.iter_mut().zip(tag_column.keys().iter()) {
+                *deleted &= key.is_some_and(|key| tag_values.value(key as usize) == value.as_str());
+            }
+        }
+
+        // the kept rows are rebuilt in time order, so that the data is sorted afterwards
+        let mut kept_rows = self.rows_in_time_order();
+        kept_rows.retain(|&i| !deleted[i]);
+        let deleted_count = self.row_count - kept_rows.len();
+        if deleted_count == 0 {
+            return 0;
+        }
+
+        self.data = self
+            .data
+            .iter()
+            .map(|(k, v)| (k.clone(), v.new_from_rows(&kept_rows)))
+            .collect();
+        (self.timestamp_min, self.timestamp_max) = match self.data.get(TIME_COLUMN_NAME) {
+            Some(Builder::Time(b)) if !kept_rows.is_empty() => (
+                *b.values_slice().iter().min().unwrap(),
+                *b.values_slice().iter().max().unwrap(),
+            ),
+            _ => (i64::MAX, i64::MIN),
+        };
+        self.index = BufferIndex::new_from_data(&self.data, &self.index);
+        self.row_count = kept_rows.len();
+        self.time_order = None;
+
+        deleted_count
+    }
 }
 
 // Debug implementation for TableBuffer
@@ -493,22 +716,64 @@ impl BufferIndex {
         }
     }
 
-    fn get_rows_from_index_for_filter(&self, filter: &[Expr]) -> Option<&Vec<usize>> {
-        for expr in filter {
-            if let Expr::BinaryExpr(BinaryExpr { left, op, right }) = expr {
-                if *op == datafusion::logical_expr::Operator::Eq {
-                    if let Expr::Column(c) = left.as_ref() {
-                        if let Expr::Literal(datafusion::scalar::ScalarValue::Utf8(Some(v))) =
-                            right.as_ref()
-                        {
-                            return self.columns.get(c.name.as_str()).and_then(|m| m.get(v));
-                        }
+    /// Returns the ids, in ascending order, of the rows that can match all of the `filter`
+    /// expressions, or `None` if none of them can be answered from the index.
+    ///
+    /// Equality predicates on indexed columns, with the literal on either side, are looked up
+    /// directly. `IN` lists and `OR`s of these take the union of the rows, and `AND`s take the
+    /// intersection. The rows returned may still include rows that don't match the parts of the
+    /// filter that can't be answered from the index, so the filter must still be applied to them.
+    fn get_rows_from_index_for_filter(&self, filter: &[Expr]) -> Option<Vec<usize>> {
+        filter
+            .iter()
+            .filter_map(|expr| self.get_rows_for_expr(expr))
+            .reduce(|a, b| intersect_rows(&a, &b))
+    }
+
+    fn get_rows_for_expr(&self, expr: &Expr) -> Option<Vec<usize>> {
+        match expr {
+            Expr::BinaryExpr(BinaryExpr { left, op, right }) => match op {
+                Operator::And => {
+                    match (self.get_rows_for_expr(left), self.get_rows_for_expr(right)) {
+                        (Some(left), Some(right)) => Some(intersect_rows(&left, &right)),
+                        (left, right) => left.or(right),
                     }
                 }
+                Operator::Or => {
+                    let left = self.get_rows_for_expr(left)?;
+                    let right = self.get_rows_for_expr(right)?;
+                    Some(union_rows(&left, &right))
+                }
+                Operator::Eq => match (left.as_ref(), right.as_ref()) {
+                    (Expr::Column(c), value) | (value, Expr::Column(c)) => {
+                        self.get_rows_for_value(&c.name, string_literal(value)?)
+                    }
+                    _ => None,
+                },
+                _ => None,
+            },
+            Expr::InList(InList {
+                expr,
+                list,
+                negated: false,
+            }) => {
+                let Expr::Column(c) = expr.as_ref() else {
+                    return None;
+                };
+                list.iter().try_fold(vec![], |rows, value| {
+                    let value_rows = self.get_rows_for_value(&c.name, string_literal(value)?)?;
+                    Some(union_rows(&rows, &value_rows))
+                })
             }
+            _ => None,
         }
+    }
 
-        None
+    /// Returns the rows with `value` in `column`, or `None` if the column isn't indexed
+    fn get_rows_for_value(&self, column: &str, value: &str) -> Option<Vec<usize>> {
+        self.columns
+            .get(column)
+            .map(|values| values.get(value).cloned().unwrap_or_default())
     }
 
     fn _size(&self) -> usize {
@@ -524,6 +789,184 @@ impl BufferIndex {
     }
 }
 
+/// The range of times, inclusive, of the rows that a filter may match
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct TimeRange {
+    min: i64,
+    max: i64,
+}
+
+impl TimeRange {
+    const ALL: Self = Self {
+        min: i64::MIN,
+        max: i64::MAX,
+    };
+
+    /// Returns the range of times of the rows that may match all of the `filter` expressions
+    fn from_filter(filter: &[Expr]) -> Self {
+        filter.iter().fold(Self::ALL, |range, expr| {
+            range.intersect(Self::from_expr(expr))
+        })
+    }
+
+    fn from_expr(expr: &Expr) -> Self {
+        match expr {
+            Expr::BinaryExpr(BinaryExpr {
+                left,
+                op: Operator::And,
+                right,
+            }) => Self::from_expr(left).intersect(Self::from_expr(right)),
+            Expr::BinaryExpr(BinaryExpr { left, op, right }) => {
+                let (op, value) = match (left.as_ref(), right.as_ref()) {
+                    (time, value) if is_time_column(time) => (Some(*op), value),
+                    (value, time) if is_time_column(time) => (op.swap(), value),
+                    _ => return Self::ALL,
+                };
+                let (Some(op), Some(time)) = (op, timestamp_literal(value)) else {
+                    return Self::ALL;
+                };
+                match op {
+                    Operator::Eq => Self {
+                        min: time,
+                        max: time,
+                    },
+                    Operator::Gt => Self {
+                        min: time.saturating_add(1),
+                        ..Self::ALL
+                    },
+                    Operator::GtEq => Self {
+                        min: time,
+                        ..Self::ALL
+                    },
+                    Operator::Lt => Self {
+                        max: time.saturating_sub(1),
+                        ..Self::ALL
+                    },
+                    Operator::LtEq => Self {
+                        max: time,
+                        ..Self::ALL
+                    },
+                    _ => Self::ALL,
+                }
+            }
+            Expr::Between(Between {
+                expr,
+                negated: false,
+                low,
+                high,
+            }) if is_time_column(expr) => match (timestamp_literal(low), timestamp_literal(high)) {
+                (Some(min), Some(max)) => Self { min, max },
+                _ => Self::ALL,
+            },
+            _ => Self::ALL,
+        }
+    }
+
+    fn intersect(self, other: Self) -> Self {
+        Self {
+            min: self.min.max(other.min),
+            max: self.max.min(other.max),
+        }
+    }
+
+    fn contains(&self, time: i64) -> bool {
+        self.min <= time && time <= self.max
+    }
+
+    /// Returns the range of positions in `times`, which must be sorted, that are in this range
+    fn positions_in(&self, times: &[i64]) -> std::ops::Range<usize> {
+        let start = times.partition_point(|t| *t < self.min);
+        let end = times.partition_point(|t| *t <= self.max).max(start);
+        start..end
+    }
+
+    /// Returns the rows of a batch sorted by time that are in this range
+    fn slice_sorted_batch(&self, batch: &RecordBatch) -> RecordBatch {
+        match batch_times(batch) {
+            Some(times) if *self != Self::ALL => {
+                let rows = self.positions_in(times.values());
+                batch.slice(rows.start, rows.len())
+            }
+            _ => batch.clone(),
+        }
+    }
+}
+
+fn is_time_column(expr: &Expr) -> bool {
+    matches!(expr, Expr::Column(c) if c.name == TIME_COLUMN_NAME)
+}
+
+/// Returns the nanosecond timestamp of a timestamp literal expression, if it is one
+fn timestamp_literal(expr: &Expr) -> Option<i64> {
+    match expr {
+        Expr::Literal(ScalarValue::TimestampNanosecond(Some(v), _)) => Some(*v),
+        Expr::Literal(ScalarValue::TimestampMicrosecond(Some(v), _)) => v.checked_mul(1_000),
+        Expr::Literal(ScalarValue::TimestampMillisecond(Some(v), _)) => v.checked_mul(1_000_000),
+        Expr::Literal(ScalarValue::TimestampSecond(Some(v), _)) => v.checked_mul(1_000_000_000),
+        _ => None,
+    }
+}
+
+/// Returns the string value of a literal expression, if it is one
+fn string_literal(expr: &Expr) -> Option<&str> {
+    fn scalar_str(value: &ScalarValue) -> Option<&str> {
+        match value {
+            ScalarValue::Utf8(Some(v)) | ScalarValue::LargeUtf8(Some(v)) => Some(v.as_str()),
+            ScalarValue::Dictionary(_, v) => scalar_str(v),
+            _ => None,
+        }
+    }
+
+    match expr {
+        Expr::Literal(value) => scalar_str(value),
+        _ => None,
+    }
+}
+
+/// Returns the rows in both of the sorted lists of row ids
+fn intersect_rows(a: &[usize], b: &[usize]) -> Vec<usize> {
+    let mut rows = Vec::with_capacity(a.len().min(b.len()));
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        match a[i].cmp(&b[j]) {
+            std::cmp::Ordering::Less => i += 1,
+            std::cmp::Ordering::Greater => j += 1,
+            std::cmp::Ordering::Equal => {
+                rows.push(a[i]);
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    rows
+}
+
+/// Returns the rows in either of the sorted lists of row ids, sorted and without duplicates
+fn union_rows(a: &[usize], b: &[usize]) -> Vec<usize> {
+    let mut rows = Vec::with_capacity(a.len() + b.len());
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        match a[i].cmp(&b[j]) {
+            std::cmp::Ordering::Less => {
+                rows.push(a[i]);
+                i += 1;
+            }
+            std::cmp::Ordering::Greater => {
+                rows.push(b[j]);
+                j += 1;
+            }
+            std::cmp::Ordering::Equal => {
+                rows.push(a[i]);
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    rows.extend_from_slice(&a[i..]);
+    rows.extend_from_slice(&b[j..]);
+    rows
+}
+
 pub enum Builder {
     Bool(BooleanBuilder),
     I64(Int64Builder),
@@ -551,13 +994,43 @@ impl Builder {
         }
     }
 
+    /// Get the float builder of a numeric field, first converting the builder, and the values it
+    /// holds, to floats if the field was an integer before being widened
+    fn widen_to_f64(&mut self) -> &mut Float64Builder {
+        let values: Option<Vec<Option<f64>>> = match self {
+            Self::I64(b) => Some(
+                b.finish_cloned()
+                    .iter()
+                    .map(|v| v.map(|v| v as f64))
+                    .collect(),
+            ),
+            Self::U64(b) => Some(
+                b.finish_cloned()
+                    .iter()
+                    .map(|v| v.map(|v| v as f64))
+                    .collect(),
+            ),
+            _ => None,
+        };
+        if let Some(values) = values {
+            let mut builder = Float64Builder::with_capacity(values.len());
+            builder.extend(values);
+            *self = Self::F64(builder);
+        }
+        match self {
+            Self::F64(b) => b,
+            _ => panic!("unexpected field type"),
+        }
+    }
+
+    /// Creates a builder holding the values of the given rows, including any nulls
     fn new_from_rows(&self, rows: &[usize]) -> Self {
         match self {
             Self::Bool(b) => {
                 let b = b.finish_cloned();
                 let mut builder = BooleanBuilder::with_capacity(rows.len());
                 for row in rows {
-                    builder.append_value(b.value(*row));
+                    builder.append_option(b.is_valid(*row).then(|| b.value(*row)));
                 }
                 Self::Bool(builder)
             }
@@ -565,7 +1038,7 @@ impl Builder {
                 let b = b.finish_cloned();
                 let mut builder = Int64Builder::with_capacity(rows.len());
                 for row in rows {
-                    builder.append_value(b.value(*row));
+                    builder.append_option(b.is_valid(*row).then(|| b.value(*row)));
                 }
                 Self::I64(builder)
             }
@@ -573,7 +1046,7 @@ impl Builder {
                 let b = b.finish_cloned();
                 let mut builder = Float64Builder::with_capacity(rows.len());
                 for row in rows {
-                    builder.append_value(b.value(*row));
+                    builder.append_option(b.is_valid(*row).then(|| b.value(*row)));
                 }
                 Self::F64(builder)
             }
@@ -581,7 +1054,7 @@ impl Builder {
                 let b = b.finish_cloned();
                 let mut builder = UInt64Builder::with_capacity(rows.len());
                 for row in rows {
-                    builder.append_value(b.value(*row));
+                    builder.append_option(b.is_valid(*row).then(|| b.value(*row)));
                 }
                 Self::U64(builder)
             }
@@ -589,49 +1062,17 @@ impl Builder {
                 let b = b.finish_cloned();
                 let mut builder = StringBuilder::new();
                 for row in rows {
-                    builder.append_value(b.value(*row));
+                    builder.append_option(b.is_valid(*row).then(|| b.value(*row)));
                 }
                 Self::String(builder)
             }
-            Self::Tag(b) => {
-                let b = b.finish_cloned();
-                let bv = b.values();
-                let bva: &StringArray = bv.as_any().downcast_ref::<StringArray>().unwrap();
-
-                let mut builder: GenericByteDictionaryBuilder<Int32Type, GenericStringType<i32>> =
-                    StringDictionaryBuilder::new();
-                for row in rows {
-                    let val = b.key(*row).unwrap();
-                    let tag_val = bva.value(val);
-
-                    builder
-                        .append(tag_val)
-                        .expect("shouldn't be able to overflow 32 bit dictionary");
-                }
-                Self::Tag(builder)
-            }
-            Self::Key(b) => {
-                let b = b.finish_cloned();
-                let bv = b.values();
-                let bva: &StringArray = bv.as_any().downcast_ref::<StringArray>().unwrap();
-
-                let mut builder: GenericByteDictionaryBuilder<Int32Type, GenericStringType<i32>> =
-                    StringDictionaryBuilder::new();
-                for row in rows {
-                    let val = b.key(*row).unwrap();
-                    let tag_val = bva.value(val);
-
-                    builder
-                        .append(tag_val)
-                        .expect("shouldn't be able to overflow 32 bit dictionary");
-                }
-                Self::Key(builder)
-            }
+            Self::Tag(b) => Self::Tag(dictionary_builder_from_rows(b, rows)),
+            Self::Key(b) => Self::Key(dictionary_builder_from_rows(b, rows)),
             Self::Time(b) => {
                 let b = b.finish_cloned();
                 let mut builder = TimestampNanosecondBuilder::with_capacity(rows.len());
                 for row in rows {
-                    builder.append_value(b.value(*row));
+                    builder.append_option(b.is_valid(*row).then(|| b.value(*row)));
                 }
                 Self::Time(builder)
             }
@@ -748,12 +1189,36 @@ impl Builder {
     }
 }
 
+fn dictionary_builder_from_rows(
+    b: &StringDictionaryBuilder<Int32Type>,
+    rows: &[usize],
+) -> StringDictionaryBuilder<Int32Type> {
+    let b = b.finish_cloned();
+    let bv = b.values();
+    let bva: &StringArray = bv.as_any().downcast_ref::<StringArray>().unwrap();
+
+    let mut builder: GenericByteDictionaryBuilder<Int32Type, GenericStringType<i32>> =
+        StringDictionaryBuilder::new();
+    for row in rows {
+        match b.key(*row) {
+            Some(val) => {
+                builder
+                    .append(bva.value(val))
+                    .expect("shouldn't be able to overflow 32 bit dictionary");
+            }
+            None => builder.append_null(),
+        }
+    }
+    builder
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::write_buffer::Field;
     use arrow_util::assert_batches_eq;
     use datafusion::common::Column;
+    use datafusion::prelude::{col, lit};
     use schema::{InfluxFieldType, SchemaBuilder};
 
     #[test]
@@ -944,7 +1409,7 @@ mod tests {
         table_buffer.add_rows(rows);
 
         let size = table_buffer.computed_size();
-        assert_eq!(size, 18198);
+        assert_eq!(size, 18246);
     }
 
     #[test]
@@ -1043,4 +1508,456 @@ mod tests {
         ];
         assert_batches_eq!(expected_a, &a);
     }
+
+    #[test]
+    fn delete_rows() {
+        let mut table_buffer = TableBuffer::new(PartitionKey::from("table"), &["tag"]);
+        let schema = SchemaBuilder::with_capacity(3)
+            .tag("tag")
+            .influx_field("value", InfluxFieldType::Integer)
+            .timestamp()
+            .build()
+            .unwrap();
+        let rows = |times: std::ops::Range<i64>| {
+            times
+                .map(|i| Row {
+                    time: i,
+                    fields: vec![
+                        Field {
+                            name: "tag".to_string(),
+                            value: FieldData::Tag(if i % 2 == 0 { "a" } else { "b" }.to_string()),
+                        },
+                        Field {
+                            name: "value".to_string(),
+                            value: FieldData::Integer(i),
+                        },
+                        Field {
+                            name: "time".to_string(),
+                            value: FieldData::Timestamp(i),
+                        },
+                    ],
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // split off rows 0 to 8 as if they were being persisted, leaving 9 to 13 buffered
+        table_buffer.add_rows(rows(0..10));
+        table_buffer.split(schema.as_arrow()).unwrap();
+        table_buffer.add_rows(rows(10..14));
+
+        let predicate = DeletePredicate {
+            min_time: 2,
+            max_time: 10,
+            tags: vec![("tag".to_string(), "a".to_string())],
+        };
+        assert_eq!(table_buffer.delete_rows(&predicate).unwrap(), 5);
+        // a tag value that isn't buffered matches no rows
+        let no_match = DeletePredicate {
+            min_time: 0,
+            max_time: 20,
+            tags: vec![("tag".to_string(), "c".to_string())],
+        };
+        assert_eq!(table_buffer.delete_rows(&no_match).unwrap(), 0);
+
+        let batches = table_buffer.record_batches(schema.as_arrow(), &[]).unwrap();
+        let expected = vec![
+            "+-----+-------+--------------------------------+",
+            "| tag | value | time                           |",
+            "+-----+-------+--------------------------------+",
+            "| a   | 0     | 1970-01-01T00:00:00Z           |",
+            "| b   | 1     | 1970-01-01T00:00:00.000000001Z |",
+            "| b   | 3     | 1970-01-01T00:00:00.000000003Z |",
+            "| b   | 5     | 1970-01-01T00:00:00.000000005Z |",
+            "| b   | 7     | 1970-01-01T00:00:00.000000007Z |",
+            "| b   | 9     | 1970-01-01T00:00:00.000000009Z |",
+            "| b   | 11    | 1970-01-01T00:00:00.000000011Z |",
+            "| a   | 12    | 1970-01-01T00:00:00.000000012Z |",
+            "| b   | 13    | 1970-01-01T00:00:00.000000013Z |",
+            "+-----+-------+--------------------------------+",
+        ];
+        assert_batches_eq!(&expected, &batches);
+
+        // the index is rebuilt for the remaining rows
+        let filter = &[Expr::BinaryExpr(BinaryExpr {
+            left: Box::new(Expr::Column(Column {
+                relation: None,
+                name: "tag".to_string(),
+            })),
+            op: datafusion::logical_expr::Operator::Eq,
+            right: Box::new(Expr::Literal(datafusion::scalar::ScalarValue::Utf8(Some(
+                "a".to_string(),
+            )))),
+        })];
+        let a_rows = table_buffer
+            .mutable_table_chunk
+            .index
+            .get_rows_from_index_for_filter(filter)
+            .unwrap();
+        assert_eq!(a_rows, &[2]);
+        assert_eq!(
+            table_buffer.timestamp_min_max(),
+            TimestampMinMax { min: 9, max: 13 }
+        );
+
+        // the file persisted from the split rows needs tombstones for the deletes
+        assert_eq!(
+            table_buffer.clear_persisting_data(),
+            vec![predicate, no_match]
+        );
+    }
+
+    /// Rows of a table with `host` and `region` tags, or series key columns if `key` is set
+    fn host_region_rows(key: bool, hosts_and_regions: &[(&str, &str)]) -> Vec<Row> {
+        let value = |v: &str| {
+            if key {
+                FieldData::Key(v.to_string())
+            } else {
+                FieldData::Tag(v.to_string())
+            }
+        };
+        hosts_and_regions
+            .iter()
+            .enumerate()
+            .map(|(i, (host, region))| Row {
+                time: i as i64,
+                fields: vec![
+                    Field {
+                        name: "host".to_string(),
+                        value: value(host),
+                    },
+                    Field {
+                        name: "region".to_string(),
+                        value: value(region),
+                    },
+                    Field {
+                        name: "value".to_string(),
+                        value: FieldData::Integer(i as i64),
+                    },
+                    Field {
+                        name: "time".to_string(),
+                        value: FieldData::Timestamp(i as i64),
+                    },
+                ],
+            })
+            .collect()
+    }
+
+    #[test]
+    fn index_rows_for_filter() {
+        let mut table_buffer = TableBuffer::new(PartitionKey::from("table"), &["host", "region"]);
+        table_buffer.add_rows(host_region_rows(
+            false,
+            &[
+                ("a", "us"),
+                ("b", "eu"),
+                ("c", "us"),
+                ("b", "us"),
+                ("a", "eu"),
+                ("d", "us"),
+            ],
+        ));
+        let index = &table_buffer.mutable_table_chunk.index;
+
+        let cases = [
+            (
+                "IN and equality",
+                vec![col("host")
+                    .in_list(vec![lit("a"), lit("b")], false)
+                    .and(col("region").eq(lit("us")))],
+                Some(vec![0, 3]),
+            ),
+            (
+                "separate filter expressions are intersected",
+                vec![
+                    col("host").in_list(vec![lit("a"), lit("b")], false),
+                    col("region").eq(lit("us")),
+                ],
+                Some(vec![0, 3]),
+            ),
+            (
+                "reversed operands",
+                vec![lit("eu").eq(col("region"))],
+                Some(vec![1, 4]),
+            ),
+            (
+                "OR on the same column",
+                vec![col("host").eq(lit("d")).or(col("host").eq(lit("c")))],
+                Some(vec![2, 5]),
+            ),
+            (
+                "OR across columns",
+                vec![col("host").eq(lit("a")).or(col("region").eq(lit("eu")))],
+                Some(vec![0, 1, 4]),
+            ),
+            (
+                "unindexable side of an AND is ignored",
+                vec![col("host").eq(lit("b")).and(col("value").gt(lit(1i64)))],
+                Some(vec![1, 3]),
+            ),
+            (
+                "unindexable side of an OR can match any row",
+                vec![col("host").eq(lit("b")).or(col("value").gt(lit(1i64)))],
+                None,
+            ),
+            (
+                "negated IN can't use the index",
+                vec![col("host").in_list(vec![lit("a")], true)],
+                None,
+            ),
+            (
+                "unknown value",
+                vec![col("host").eq(lit("z"))],
+                Some(vec![]),
+            ),
+            ("unindexed column", vec![col("other").eq(lit("a"))], None),
+        ];
+        for (desc, filter, expected) in cases {
+            assert_eq!(
+                index.get_rows_from_index_for_filter(&filter),
+                expected,
+                "{desc}"
+            );
+        }
+
+        let schema = SchemaBuilder::with_capacity(4)
+            .tag("host")
+            .tag("region")
+            .influx_field("value", InfluxFieldType::Integer)
+            .timestamp()
+            .build()
+            .unwrap();
+        let batches = table_buffer
+            .record_batches(
+                schema.as_arrow(),
+                &[col("host")
+                    .in_list(vec![lit("a"), lit("b")], false)
+                    .and(col("region").eq(lit("us")))],
+            )
+            .unwrap();
+        let expected = vec![
+            "+------+--------+-------+--------------------------------+",
+            "| host | region | value | time                           |",
+            "+------+--------+-------+--------------------------------+",
+            "| a    | us     | 0     | 1970-01-01T00:00:00Z           |",
+            "| b    | us     | 3     | 1970-01-01T00:00:00.000000003Z |",
+            "+------+--------+-------+--------------------------------+",
+        ];
+        assert_batches_eq!(&expected, &batches);
+    }
+
+    #[test]
+    fn index_rows_for_filter_on_series_key() {
+        let mut table_buffer = TableBuffer::new(PartitionKey::from("table"), &["host", "region"]);
+        table_buffer.add_rows(host_region_rows(
+            true,
+            &[("a", "us"), ("b", "eu"), ("b", "us")],
+        ));
+        let index = &table_buffer.mutable_table_chunk.index;
+
+        let filter = [col("host")
+            .in_list(vec![lit("a"), lit("b")], false)
+            .and(col("region").eq(lit("us")))];
+        assert_eq!(
+            index.get_rows_from_index_for_filter(&filter),
+            Some(vec![0, 2])
+        );
+
+        // the index rebuilt after a delete also covers the series key columns
+        let predicate = DeletePredicate {
+            min_time: 0,
+            max_time: 10,
+            tags: vec![("host".to_string(), "a".to_string())],
+        };
+        assert_eq!(table_buffer.delete_rows(&predicate).unwrap(), 1);
+        let index = &table_buffer.mutable_table_chunk.index;
+        assert_eq!(
+            index.get_rows_from_index_for_filter(&[lit("us").eq(col("region"))]),
+            Some(vec![1])
+        );
+    }
+
+    fn rows_at_times(tag: &str, times: &[i64]) -> Vec<Row> {
+        times
+            .iter()
+            .map(|&time| Row {
+                time,
+                fields: vec![
+                    Field {
+                        name: "tag".to_string(),
+                        value: FieldData::Tag(tag.to_string()),
+                    },
+                    Field {
+                        name: "value".to_string(),
+                        value: FieldData::Integer(time),
+                    },
+                    Field {
+                        name: "time".to_string(),
+                        value: FieldData::Timestamp(time),
+                    },
+                ],
+            })
+            .collect()
+    }
+
+    fn time_lit(time: i64) -> Expr {
+        lit(ScalarValue::TimestampNanosecond(Some(time), None))
+    }
+
+    #[test]
+    fn time_range_from_filter() {
+        let cases = [
+            (
+                vec![col("time")
+                    .gt_eq(time_lit(10))
+                    .and(col("time").lt(time_lit(20)))],
+                TimeRange { min: 10, max: 19 },
+            ),
+            (
+                vec![
+                    time_lit(10).lt(col("time")),
+                    time_lit(20).gt_eq(col("time")),
+                ],
+                TimeRange { min: 11, max: 20 },
+            ),
+            (
+                vec![col("time").between(time_lit(5), time_lit(8))],
+                TimeRange { min: 5, max: 8 },
+            ),
+            (
+                vec![col("time")
+                    .eq(lit(ScalarValue::TimestampMillisecond(Some(2), None)))
+                    .and(col("tag").eq(lit("a")))],
+                TimeRange {
+                    min: 2_000_000,
+                    max: 2_000_000,
+                },
+            ),
+            // an OR can't be pruned on
+            (
+                vec![col("time").gt(time_lit(10)).or(col("tag").eq(lit("a")))],
+                TimeRange::ALL,
+            ),
+            (vec![col("value").gt(lit(10i64))], TimeRange::ALL),
+        ];
+        for (filter, expected) in cases {
+            assert_eq!(TimeRange::from_filter(&filter), expected, "{filter:?}");
+        }
+    }
+
+    #[test]
+    fn rows_in_time_order_and_range() {
+        let mut table_buffer = TableBuffer::new(PartitionKey::from("table"), &["tag"]);
+        let schema = SchemaBuilder::with_capacity(3)
+            .tag("tag")
+            .influx_field("value", InfluxFieldType::Integer)
+            .timestamp()
+            .build()
+            .unwrap();
+
+        // rows written in time order don't need a separate time order
+        table_buffer.add_rows(rows_at_times("a", &[1, 2, 4]));
+        table_buffer.add_rows(rows_at_times("b", &[4, 6]));
+        assert!(table_buffer.mutable_table_chunk.time_order.is_none());
+        let filter = [col("time")
+            .gt_eq(time_lit(2))
+            .and(col("time").lt(time_lit(6)))];
+        assert_eq!(
+            table_buffer.mutable_table_chunk.rows_for_filter(&filter),
+            Some(vec![1, 2, 3])
+        );
+        assert_eq!(table_buffer.mutable_table_chunk.rows_for_filter(&[]), None);
+
+        // late rows are merged into the time order
+        table_buffer.add_rows(rows_at_times("a", &[5, 0, 3]));
+        assert_eq!(
+            table_buffer.mutable_table_chunk.time_order,
+            Some(vec![6, 0, 1, 7, 2, 3, 5, 4])
+        );
+        assert_eq!(
+            table_buffer.mutable_table_chunk.rows_for_filter(&filter),
+            Some(vec![1, 7, 2, 3, 5])
+        );
+        // with an indexed column in the filter too
+        let tag_filter = [filter[0].clone().and(col("tag").eq(lit("a")))];
+        assert_eq!(
+            table_buffer
+                .mutable_table_chunk
+                .rows_for_filter(&tag_filter),
+            Some(vec![1, 7, 2, 5])
+        );
+
+        let batches = table_buffer
+            .record_batches(schema.as_arrow(), &filter)
+            .unwrap();
+        let expected = vec![
+            "+-----+-------+--------------------------------+",
+            "| tag | value | time                           |",
+            "+-----+-------+--------------------------------+",
+            "| a   | 2     | 1970-01-01T00:00:00.000000002Z |",
+            "| a   | 3     | 1970-01-01T00:00:00.000000003Z |",
+            "| a   | 4     | 1970-01-01T00:00:00.000000004Z |",
+            "| b   | 4     | 1970-01-01T00:00:00.000000004Z |",
+            "| a   | 5     | 1970-01-01T00:00:00.000000005Z |",
+            "+-----+-------+--------------------------------+",
+        ];
+        assert_batches_eq!(&expected, &batches);
+
+        // splitting sorts the rows kept in the buffer, and the rows persisting are still merged
+        // in time order with rows written after the split
+        table_buffer.add_rows(rows_at_times("b", &(7..19).rev().collect::<Vec<_>>()));
+        let persist_batch = table_buffer.split(schema.as_arrow()).unwrap();
+        assert_eq!(persist_batch.record_batch.num_rows(), 19);
+        assert_eq!(table_buffer.mutable_table_chunk.time_values(), &[18]);
+        assert!(table_buffer.mutable_table_chunk.time_order.is_none());
+        table_buffer.add_rows(rows_at_times("b", &[3]));
+        let batches = table_buffer
+            .record_batches(schema.as_arrow(), &filter)
+            .unwrap();
+        let expected = vec![
+            "+-----+-------+--------------------------------+",
+            "| tag | value | time                           |",
+            "+-----+-------+--------------------------------+",
+            "| a   | 2     | 1970-01-01T00:00:00.000000002Z |",
+            "| a   | 3     | 1970-01-01T00:00:00.000000003Z |",
+            "| b   | 3     | 1970-01-01T00:00:00.000000003Z |",
+            "| a   | 4     | 1970-01-01T00:00:00.000000004Z |",
+            "| b   | 4     | 1970-01-01T00:00:00.000000004Z |",
+            "| a   | 5     | 1970-01-01T00:00:00.000000005Z |",
+            "+-----+-------+--------------------------------+",
+        ];
+        assert_batches_eq!(&expected, &batches);
+        let all_batches = table_buffer.record_batches(schema.as_arrow(), &[]).unwrap();
+        let times: Vec<i64> = all_batches
+            .iter()
+            .flat_map(|b| batch_times(b).unwrap().values().to_vec())
+            .collect();
+        let mut expected_times = vec![0, 1, 2, 3, 3, 4, 4, 5];
+        expected_times.extend(6..19);
+        assert_eq!(times, expected_times);
+    }
+
+    #[test]
+    fn in_order_rows_are_appended_to_time_order() {
+        let mut table_buffer = TableBuffer::new(PartitionKey::from("table"), &["tag"]);
+        table_buffer.add_rows(rows_at_times("a", &[2, 4]));
+        table_buffer.add_rows(rows_at_times("a", &[1]));
+        assert_eq!(
+            table_buffer.mutable_table_chunk.time_order,
+            Some(vec![2, 0, 1])
+        );
+
+        // rows after the latest one are added to the end of the order
+        table_buffer.add_rows(rows_at_times("b", &[4, 5, 7]));
+        assert_eq!(
+            table_buffer.mutable_table_chunk.time_order,
+            Some(vec![2, 0, 1, 3, 4, 5])
+        );
+
+        // while rows that interleave with those in the buffer are merged in
+        table_buffer.add_rows(rows_at_times("b", &[6, 8]));
+        assert_eq!(
+            table_buffer.mutable_table_chunk.time_order,
+            Some(vec![2, 0, 1, 3, 4, 6, 5, 7])
+        );
+    }
 }
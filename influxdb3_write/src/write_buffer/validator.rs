@@ -1,17 +1,34 @@
-use std::{borrow::Cow, collections::HashMap, sync::Arc};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+    time::Duration,
+};
 
+use arrow::{
+    array::{Array, ArrayRef, AsArray, UInt32Array},
+    compute::{cast, take_record_batch},
+    datatypes::{DataType, Float64Type, Int64Type, TimeUnit, UInt64Type},
+    record_batch::RecordBatch,
+};
 use data_types::NamespaceName;
 use influxdb_line_protocol::{parse_lines, v3, FieldValue, ParsedLine};
 use iox_time::Time;
-use schema::{InfluxColumnType, TIME_COLUMN_NAME};
+use schema::{InfluxColumnType, InfluxFieldType, TIME_COLUMN_NAME};
 
 use crate::{
-    catalog::{influx_column_type_from_field_value, Catalog, DatabaseSchema, TableDefinition},
+    catalog::{
+        influx_column_type_from_field_value, widened_field_type, Catalog, DatabaseSchema,
+        TableDefinition,
+    },
     write_buffer::Result,
-    LpWriteOp, Precision, SegmentDuration, SequenceNumber, WalOp, WriteLineError,
+    ArrowWriteOp, CatalogOp, LpWriteOp, Precision, SegmentDuration, SequenceNumber, WalOp,
+    WriteLineError,
 };
 
-use super::{Error, Field, FieldData, Row, TableBatchMap, ValidSegmentedData};
+use super::{
+    Error, Field, FieldData, Row, TableBatch, TableBatchMap, ValidSegmentedData, WriteTimeWindow,
+};
 
 /// Type state for the [`WriteValidator`] after it has been initialized
 /// with the catalog.
@@ -20,6 +37,51 @@ pub(crate) struct WithCatalog {
     catalog: Arc<Catalog>,
     sequence: SequenceNumber,
     db_schema: Arc<DatabaseSchema>,
+    time_bounds: Option<TimeBounds>,
+}
+
+/// The times of lines that are accepted by a write, derived from a [`WriteTimeWindow`]
+#[derive(Debug, Clone, Copy)]
+struct TimeBounds {
+    window: WriteTimeWindow,
+    ingest_time: Time,
+    precision: Precision,
+}
+
+impl TimeBounds {
+    /// Check that a line with the given timestamp is inside the window, returning the error
+    /// message for the line if it isn't
+    fn check(&self, timestamp: Option<i64>) -> Result<(), String> {
+        // lines without a timestamp are given the ingest time:
+        let Some(timestamp) = timestamp else {
+            return Ok(());
+        };
+        let time = apply_precision_to_timestamp(self.precision, timestamp);
+        let ingest_time = self.ingest_time.timestamp_nanos();
+        if let Some(max_age) = self.window.max_write_age {
+            if time < ingest_time.saturating_sub(duration_nanos(max_age)) {
+                return Err(format!(
+                    "timestamp {} is more than the max write age of {} before the server time",
+                    Time::from_timestamp_nanos(time).date_time().to_rfc3339(),
+                    humantime::format_duration(max_age),
+                ));
+            }
+        }
+        if let Some(max_future) = self.window.max_write_future {
+            if time > ingest_time.saturating_add(duration_nanos(max_future)) {
+                return Err(format!(
+                    "timestamp {} is more than the max write future of {} after the server time",
+                    Time::from_timestamp_nanos(time).date_time().to_rfc3339(),
+                    humantime::format_duration(max_future),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn duration_nanos(duration: Duration) -> i64 {
+    i64::try_from(duration.as_nanos()).unwrap_or(i64::MAX)
 }
 
 /// Type state for the [`WriteValidator`] after it has parsed v1 or v3
@@ -28,6 +90,93 @@ pub(crate) struct LinesParsed<'raw, PL> {
     catalog: WithCatalog,
     lines: Vec<(PL, &'raw str)>,
     errors: Vec<WriteLineError>,
+    /// The changes made to the catalog for the lines, which are written to the WAL ahead of them
+    catalog_ops: Vec<CatalogOp>,
+}
+
+/// Type state for the [`WriteValidator`] after it has validated the record
+/// batches of an Arrow write to a table.
+pub(crate) struct BatchesValidated {
+    catalog: WithCatalog,
+    table_name: String,
+    batches: Vec<ValidBatch>,
+    /// The changes made to the catalog for the batches, which are written to the WAL ahead of them
+    catalog_ops: Vec<CatalogOp>,
+}
+
+/// A record batch of an Arrow write that has been validated against the schema of its table
+struct ValidBatch {
+    batch: RecordBatch,
+    /// The time of each row, in nanoseconds
+    times: Vec<i64>,
+    /// The columns of the batch, other than time
+    columns: Vec<ValidColumn>,
+}
+
+struct ValidColumn {
+    name: String,
+    /// The type of the values written to the column, which is the type of the column in the table
+    /// unless the column is a numeric field that is widened
+    column_type: InfluxColumnType,
+    /// True if the column is a member of the table's series key
+    series_key: bool,
+    /// The values of the column, cast to `Utf8` for tags and strings
+    values: ArrayRef,
+}
+
+impl ValidColumn {
+    /// The value of the column in the given row, or `None` if it's null
+    fn field_data(&self, row: usize) -> Option<FieldData> {
+        if self.values.is_null(row) {
+            return None;
+        }
+        let value = match self.column_type {
+            InfluxColumnType::Tag if self.series_key => {
+                FieldData::Key(self.values.as_string::<i32>().value(row).to_string())
+            }
+            InfluxColumnType::Tag => {
+                FieldData::Tag(self.values.as_string::<i32>().value(row).to_string())
+            }
+            InfluxColumnType::Field(InfluxFieldType::String) => {
+                FieldData::String(self.values.as_string::<i32>().value(row).to_string())
+            }
+            InfluxColumnType::Field(InfluxFieldType::Integer) => {
+                FieldData::Integer(self.values.as_primitive::<Int64Type>().value(row))
+            }
+            InfluxColumnType::Field(InfluxFieldType::UInteger) => {
+                FieldData::UInteger(self.values.as_primitive::<UInt64Type>().value(row))
+            }
+            InfluxColumnType::Field(InfluxFieldType::Float) => {
+                FieldData::Float(self.values.as_primitive::<Float64Type>().value(row))
+            }
+            InfluxColumnType::Field(InfluxFieldType::Boolean) => {
+                FieldData::Boolean(self.values.as_boolean().value(row))
+            }
+            InfluxColumnType::Timestamp => unreachable!("the time column is not a valid column"),
+        };
+        Some(value)
+    }
+}
+
+impl ValidBatch {
+    /// The row of the batch at the given index, without the columns that are null in it
+    fn row(&self, row: usize) -> Row {
+        let mut fields = Vec::with_capacity(self.columns.len() + 1);
+        for column in &self.columns {
+            if let Some(value) = column.field_data(row) {
+                fields.push(Field {
+                    name: column.name.clone(),
+                    value,
+                });
+            }
+        }
+        let time = self.times[row];
+        fields.push(Field {
+            name: TIME_COLUMN_NAME.to_string(),
+            value: FieldData::Timestamp(time),
+        });
+        Row { time, fields }
+    }
 }
 
 /// A state machine for validating v1 or v3 line protocol and updating
@@ -50,10 +199,28 @@ impl WriteValidator<WithCatalog> {
                 catalog,
                 sequence,
                 db_schema,
+                time_bounds: None,
             },
         })
     }
 
+    /// Reject lines with timestamps outside of the `window` around the `ingest_time`, in the same
+    /// way as lines that fail to parse, rather than buffering them in far off segments. The
+    /// timestamps of the lines are in the given `precision`.
+    pub(crate) fn with_time_window(
+        mut self,
+        window: WriteTimeWindow,
+        ingest_time: Time,
+        precision: Precision,
+    ) -> Self {
+        self.state.time_bounds = Some(TimeBounds {
+            window,
+            ingest_time,
+            precision,
+        });
+        self
+    }
+
     /// Parse the incoming lines of line protocol using the v3 parser and update
     /// the [`DatabaseSchema`] if:
     ///
@@ -81,8 +248,15 @@ impl WriteValidator<WithCatalog> {
                     line_number: line_idx + 1,
                     error_message: e.to_string(),
                 })
-                .and_then(|l| validate_v3_line(&mut schema, line_idx, l, lp_lines.peek().unwrap()))
-            {
+                .and_then(|l| {
+                    check_line_time(
+                        self.state.time_bounds.as_ref(),
+                        line_idx + 1,
+                        l.timestamp,
+                        || lp_lines.peek().unwrap().to_string(),
+                    )?;
+                    validate_v3_line(&mut schema, line_idx, l, lp_lines.peek().unwrap())
+                }) {
                 Ok(line) => line,
                 Err(e) => {
                     if !accept_partial {
@@ -97,17 +271,20 @@ impl WriteValidator<WithCatalog> {
             lines.push((line, lp_lines.next().unwrap()));
         }
 
-        if let Cow::Owned(schema) = schema {
-            self.state
+        let catalog_ops = match schema {
+            Cow::Owned(schema) => self
+                .state
                 .catalog
-                .replace_database(self.state.sequence, Arc::new(schema))?;
-        }
+                .replace_database(self.state.sequence, Arc::new(schema))?,
+            Cow::Borrowed(_) => vec![],
+        };
 
         Ok(WriteValidator {
             state: LinesParsed {
                 catalog: self.state,
                 lines,
                 errors,
+                catalog_ops,
             },
         })
     }
@@ -141,8 +318,15 @@ impl WriteValidator<WithCatalog> {
                     line_number: line_idx + 1,
                     error_message: e.to_string(),
                 })
-                .and_then(|l| validate_v1_line(&mut schema, line_idx, l))
-            {
+                .and_then(|l| {
+                    check_line_time(
+                        self.state.time_bounds.as_ref(),
+                        line_idx + 1,
+                        l.timestamp,
+                        || l.to_string(),
+                    )?;
+                    validate_v1_line(&mut schema, line_idx, l)
+                }) {
                 Ok(line) => line,
                 Err(e) => {
                     if !accept_partial {
@@ -161,20 +345,293 @@ impl WriteValidator<WithCatalog> {
         // All lines are parsed and validated, so all steps after this
         // are infallible, therefore, update the catalog if changes were
         // made to the schema:
-        if let Cow::Owned(schema) = schema {
-            self.state
+        let catalog_ops = match schema {
+            Cow::Owned(schema) => self
+                .state
                 .catalog
-                .replace_database(self.state.sequence, Arc::new(schema))?;
-        }
+                .replace_database(self.state.sequence, Arc::new(schema))?,
+            Cow::Borrowed(_) => vec![],
+        };
 
         Ok(WriteValidator {
             state: LinesParsed {
                 catalog: self.state,
                 lines,
                 errors,
+                catalog_ops,
             },
         })
     }
+
+    /// Validate the record batches of an Arrow write to a table and update the
+    /// [`DatabaseSchema`] if:
+    ///
+    /// * The table is new, in which case it's created with a column for each column of the
+    ///   batches. Dictionary encoded string columns are tags, and other columns are fields.
+    /// * New fields, or tags are being added to an existing table
+    ///
+    /// Unlike line protocol, a write of record batches is all or nothing: if any batch is
+    /// invalid, the whole write is rejected.
+    ///
+    /// # Implementation Note
+    ///
+    /// If this function succeeds, then the catalog will receive an update, so
+    /// steps following this should be infallible.
+    pub(crate) fn validate_batches_and_update_schema(
+        self,
+        table_name: &str,
+        batches: Vec<RecordBatch>,
+    ) -> Result<WriteValidator<BatchesValidated>> {
+        let mut schema = Cow::Borrowed(self.state.db_schema.as_ref());
+        let mut valid_batches = Vec::with_capacity(batches.len());
+        for (i, batch) in batches.into_iter().enumerate() {
+            if batch.num_rows() == 0 {
+                continue;
+            }
+            let valid_batch = validate_batch(
+                &mut schema,
+                table_name,
+                batch,
+                self.state.time_bounds.as_ref(),
+            )
+            .map_err(|e| Error::InvalidArrowWrite(format!("batch {}: {e}", i + 1)))?;
+            valid_batches.push(valid_batch);
+        }
+
+        let catalog_ops = match schema {
+            Cow::Owned(schema) => self
+                .state
+                .catalog
+                .replace_database(self.state.sequence, Arc::new(schema))?,
+            Cow::Borrowed(_) => vec![],
+        };
+
+        Ok(WriteValidator {
+            state: BatchesValidated {
+                catalog: self.state,
+                table_name: table_name.to_string(),
+                batches: valid_batches,
+                catalog_ops,
+            },
+        })
+    }
+}
+
+/// Validate a record batch written to the table against the table's schema, updating the
+/// schema with the new table or columns of the batch, and returning the error message for the
+/// batch if it's invalid
+fn validate_batch(
+    db_schema: &mut Cow<'_, DatabaseSchema>,
+    table_name: &str,
+    batch: RecordBatch,
+    time_bounds: Option<&TimeBounds>,
+) -> Result<ValidBatch, String> {
+    let times = batch_times(&batch)?;
+    if let Some(bounds) = time_bounds {
+        for (row, time) in times.iter().enumerate() {
+            bounds
+                .check(Some(*time))
+                .map_err(|e| format!("row {}: {e}", row + 1))?;
+        }
+    }
+
+    let table_def = db_schema.get_table(table_name);
+    let series_key: Option<Vec<String>> = table_def
+        .and_then(|t| t.schema().series_key())
+        .map(|sk| sk.into_iter().map(ToString::to_string).collect());
+    let mut columns = Vec::with_capacity(batch.num_columns());
+    let mut new_columns = vec![];
+    let mut widened_fields = vec![];
+    for (field, values) in batch.schema().fields().iter().zip(batch.columns()) {
+        let name = field.name();
+        if name == TIME_COLUMN_NAME {
+            continue;
+        }
+        let write_type = arrow_column_type(values.data_type()).ok_or_else(|| {
+            format!(
+                "column {name} has type {}, which can't be written",
+                values.data_type()
+            )
+        })?;
+        let in_series_key = series_key.as_ref().is_some_and(|sk| sk.contains(name));
+
+        let column_type = match table_def.and_then(|t| t.field_type_by_name(name)) {
+            Some(existing) if existing == write_type => existing,
+            // strings can be written to tags, for clients that don't dictionary encode them:
+            Some(InfluxColumnType::Tag)
+                if write_type == InfluxColumnType::Field(InfluxFieldType::String) =>
+            {
+                InfluxColumnType::Tag
+            }
+            Some(existing) => {
+                let Some(widened) =
+                    table_def.and_then(|t| table_widened_field_type(t, existing, write_type))
+                else {
+                    return Err(format!(
+                        "column {name} has type {existing} in table {table_name}, but the \
+                        write has type {write_type}"
+                    ));
+                };
+                if widened != existing {
+                    widened_fields.push(name.to_string());
+                }
+                write_type
+            }
+            None => {
+                if series_key.is_some() && write_type == InfluxColumnType::Tag {
+                    return Err(format!(
+                        "column {name} is a tag, but isn't a member of the series key of table \
+                        {table_name}"
+                    ));
+                }
+                new_columns.push((name.to_string(), write_type));
+                write_type
+            }
+        };
+        if in_series_key && values.null_count() > 0 {
+            return Err(format!(
+                "column {name} is a member of the series key, so can't contain nulls"
+            ));
+        }
+
+        let values = match column_type {
+            InfluxColumnType::Tag | InfluxColumnType::Field(InfluxFieldType::String) => {
+                cast(values, &DataType::Utf8).map_err(|e| e.to_string())?
+            }
+            _ => Arc::clone(values),
+        };
+        columns.push(ValidColumn {
+            name: name.to_string(),
+            column_type,
+            series_key: in_series_key,
+            values,
+        });
+    }
+
+    if let Some(series_key) = &series_key {
+        let missing: Vec<&str> = series_key
+            .iter()
+            .filter(|sk| !columns.iter().any(|c| &c.name == *sk))
+            .map(String::as_str)
+            .collect();
+        if !missing.is_empty() {
+            return Err(format!(
+                "the write is missing the series key columns [{}] of table {table_name}",
+                missing.join(", ")
+            ));
+        }
+    }
+
+    if table_def.is_none() {
+        if db_schema.strict_schema() {
+            return Err(format!(
+                "table {table_name} has not been declared and the database uses a strict schema"
+            ));
+        }
+        // Always add time last on new table:
+        new_columns.push((TIME_COLUMN_NAME.to_string(), InfluxColumnType::Timestamp));
+        let table = TableDefinition::new(table_name, new_columns, Option::<Vec<String>>::None);
+        db_schema
+            .to_mut()
+            .tables
+            .insert(table_name.to_string(), table);
+    } else {
+        if !new_columns.is_empty() {
+            if db_schema.strict_schema() {
+                return Err(format!(
+                    "the columns [{}] have not been declared for table {table_name} and the \
+                    database uses a strict schema",
+                    new_columns
+                        .iter()
+                        .map(|(name, _)| name.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ));
+            }
+            // unwrap is safe as the table exists:
+            let t = db_schema.to_mut().tables.get_mut(table_name).unwrap();
+            t.add_columns(new_columns);
+        }
+        if !widened_fields.is_empty() {
+            let t = db_schema.to_mut().tables.get_mut(table_name).unwrap();
+            for field_name in widened_fields {
+                t.widen_field_to_float(&field_name);
+            }
+        }
+    }
+
+    Ok(ValidBatch {
+        batch,
+        times,
+        columns,
+    })
+}
+
+/// The type of the column that values of the given Arrow type are written to, or `None` if they
+/// can't be written
+fn arrow_column_type(data_type: &DataType) -> Option<InfluxColumnType> {
+    let column_type = match data_type {
+        DataType::Dictionary(_, value_type) if value_type.as_ref() == &DataType::Utf8 => {
+            InfluxColumnType::Tag
+        }
+        DataType::Utf8 | DataType::LargeUtf8 => InfluxColumnType::Field(InfluxFieldType::String),
+        DataType::Int64 => InfluxColumnType::Field(InfluxFieldType::Integer),
+        DataType::UInt64 => InfluxColumnType::Field(InfluxFieldType::UInteger),
+        DataType::Float64 => InfluxColumnType::Field(InfluxFieldType::Float),
+        DataType::Boolean => InfluxColumnType::Field(InfluxFieldType::Boolean),
+        _ => return None,
+    };
+    Some(column_type)
+}
+
+/// The times of the rows of the batch in nanoseconds, from its time column, which can be a
+/// timestamp of any unit
+fn batch_times(batch: &RecordBatch) -> Result<Vec<i64>, String> {
+    let time = batch
+        .column_by_name(TIME_COLUMN_NAME)
+        .ok_or_else(|| format!("the batch has no {TIME_COLUMN_NAME} column"))?;
+    let DataType::Timestamp(unit, _) = time.data_type() else {
+        return Err(format!(
+            "the {TIME_COLUMN_NAME} column must be a timestamp, not {}",
+            time.data_type()
+        ));
+    };
+    if time.null_count() > 0 {
+        return Err(format!("the {TIME_COLUMN_NAME} column can't contain nulls"));
+    }
+    let multiplier = match unit {
+        TimeUnit::Second => 1_000_000_000,
+        TimeUnit::Millisecond => 1_000_000,
+        TimeUnit::Microsecond => 1_000,
+        TimeUnit::Nanosecond => 1,
+    };
+    // casting to an integer keeps the values as they are, ignoring any time zone:
+    let times = cast(time, &DataType::Int64).map_err(|e| e.to_string())?;
+    times
+        .as_primitive::<Int64Type>()
+        .values()
+        .iter()
+        .map(|t| {
+            t.checked_mul(multiplier)
+                .ok_or_else(|| format!("timestamp {t} is out of range"))
+        })
+        .collect()
+}
+
+/// Check that the timestamp of a line is inside the write's time bounds, if it has any
+fn check_line_time(
+    time_bounds: Option<&TimeBounds>,
+    line_number: usize,
+    timestamp: Option<i64>,
+    raw_line: impl FnOnce() -> String,
+) -> Result<(), WriteLineError> {
+    time_bounds
+        .map_or(Ok(()), |bounds| bounds.check(timestamp))
+        .map_err(|error_message| WriteLineError {
+            original_line: raw_line(),
+            line_number,
+            error_message,
+        })
 }
 
 /// Validate an individual line of v3 line protocol and update the database
@@ -203,6 +660,7 @@ fn validate_v3_line<'a>(
             });
         }
         let mut columns = Vec::with_capacity(line.column_count() + 1);
+        let mut widened_fields = vec![];
         match (table_def.schema().series_key(), &line.series.series_key) {
             (Some(s), Some(l)) => {
                 let l = l.iter().map(|sk| sk.0.as_str()).collect::<Vec<&str>>();
@@ -247,6 +705,14 @@ fn validate_v3_line<'a>(
             if let Some(schema_col_type) = table_def.field_type_by_name(field_name) {
                 let field_col_type = influx_column_type_from_field_value(field_val);
                 if field_col_type != schema_col_type {
+                    if let Some(widened) =
+                        table_widened_field_type(table_def, schema_col_type, field_col_type)
+                    {
+                        if widened != schema_col_type {
+                            widened_fields.push(field_name.to_string());
+                        }
+                        continue;
+                    }
                     let field_name = field_name.to_string();
                     return Err(WriteLineError {
                         original_line: raw_line.to_string(),
@@ -267,10 +733,27 @@ fn validate_v3_line<'a>(
             }
         }
         if !columns.is_empty() {
+            if db_schema.strict_schema() {
+                return Err(undeclared_columns_error(
+                    table_name,
+                    &columns,
+                    line_number,
+                    raw_line,
+                ));
+            }
             let t = db_schema.to_mut().tables.get_mut(table_name).unwrap();
             t.add_columns(columns);
         }
+        if !widened_fields.is_empty() {
+            let t = db_schema.to_mut().tables.get_mut(table_name).unwrap();
+            for field_name in widened_fields {
+                t.widen_field_to_float(&field_name);
+            }
+        }
     } else {
+        if db_schema.strict_schema() {
+            return Err(undeclared_table_error(table_name, line_number, raw_line));
+        }
         let mut columns = Vec::new();
         let mut key = Vec::new();
         if let Some(series_key) = &line.series.series_key {
@@ -326,6 +809,7 @@ fn validate_v1_line<'a>(
         }
         // This table already exists, so update with any new columns if present:
         let mut columns = Vec::with_capacity(line.column_count() + 1);
+        let mut widened_fields = vec![];
         if let Some(tag_set) = &line.series.tag_set {
             for (tag_key, _) in tag_set {
                 if !table_def.column_exists(tag_key) {
@@ -338,6 +822,14 @@ fn validate_v1_line<'a>(
             if let Some(schema_col_type) = table_def.field_type_by_name(field_name) {
                 let field_col_type = influx_column_type_from_field_value(field_val);
                 if field_col_type != schema_col_type {
+                    if let Some(widened) =
+                        table_widened_field_type(table_def, schema_col_type, field_col_type)
+                    {
+                        if widened != schema_col_type {
+                            widened_fields.push(field_name.to_string());
+                        }
+                        continue;
+                    }
                     let field_name = field_name.to_string();
                     return Err(WriteLineError {
                         original_line: line.to_string(),
@@ -358,11 +850,33 @@ fn validate_v1_line<'a>(
             }
         }
         if !columns.is_empty() {
+            if db_schema.strict_schema() {
+                return Err(undeclared_columns_error(
+                    table_name,
+                    &columns,
+                    line_number,
+                    &line.to_string(),
+                ));
+            }
             // unwrap is safe due to the surrounding if let condition:
             let t = db_schema.to_mut().tables.get_mut(table_name).unwrap();
             t.add_columns(columns);
         }
+        if !widened_fields.is_empty() {
+            // unwrap is safe due to the surrounding if let condition:
+            let t = db_schema.to_mut().tables.get_mut(table_name).unwrap();
+            for field_name in widened_fields {
+                t.widen_field_to_float(&field_name);
+            }
+        }
     } else {
+        if db_schema.strict_schema() {
+            return Err(undeclared_table_error(
+                table_name,
+                line_number,
+                &line.to_string(),
+            ));
+        }
         // This is a new table, so build up its columns:
         let mut columns = Vec::new();
         if let Some(tag_set) = &line.series.tag_set {
@@ -393,6 +907,54 @@ fn validate_v1_line<'a>(
     Ok(line)
 }
 
+/// The type that a field in the table is widened to when written with a value of a conflicting
+/// type, or `None` if the table does not widen its fields, or the types cannot be reconciled
+fn table_widened_field_type(
+    table_def: &TableDefinition,
+    schema_col_type: InfluxColumnType,
+    field_col_type: InfluxColumnType,
+) -> Option<InfluxColumnType> {
+    table_def
+        .widens_numeric_fields()
+        .then(|| widened_field_type(schema_col_type, field_col_type))
+        .flatten()
+}
+
+/// The error for a write to a table that was not declared in a database with a strict schema
+fn undeclared_table_error(table_name: &str, line_number: usize, raw_line: &str) -> WriteLineError {
+    WriteLineError {
+        original_line: raw_line.to_string(),
+        line_number: line_number + 1,
+        error_message: format!(
+            "write to table {table_name} was rejected, the table has not been declared and the \
+            database uses a strict schema"
+        ),
+    }
+}
+
+/// The error for a write with columns that were not declared for its table, in a database with a
+/// strict schema
+fn undeclared_columns_error(
+    table_name: &str,
+    columns: &[(String, InfluxColumnType)],
+    line_number: usize,
+    raw_line: &str,
+) -> WriteLineError {
+    WriteLineError {
+        original_line: raw_line.to_string(),
+        line_number: line_number + 1,
+        error_message: format!(
+            "write to table {table_name} was rejected, the columns [{columns}] have not been \
+            declared and the database uses a strict schema",
+            columns = columns
+                .iter()
+                .map(|(name, _)| name.as_str())
+                .collect::<Vec<_>>()
+                .join(", "),
+        ),
+    }
+}
+
 /// Result of conversion from line protocol to valid segmented data
 /// for the buffer.
 #[derive(Debug, Default)]
@@ -453,12 +1015,15 @@ impl<'lp> WriteValidator<LinesParsed<'lp, v3::ParsedLine<'lp>>> {
                 database_name: self.state.catalog.db_name.clone(),
                 segment_start,
                 table_batches: table_batch_map.table_batches,
-                wal_op: WalOp::LpWrite(LpWriteOp {
-                    db_name: self.state.catalog.db_name.to_string(),
-                    lp: table_batch_map.lines.join("\n"),
-                    default_time: ingest_time.timestamp_nanos(),
-                    precision,
-                }),
+                wal_ops: wal_ops_for_write(
+                    &self.state.catalog_ops,
+                    [WalOp::LpWrite(LpWriteOp {
+                        db_name: self.state.catalog.db_name.to_string(),
+                        lp: table_batch_map.lines.join("\n"),
+                        default_time: ingest_time.timestamp_nanos(),
+                        precision,
+                    })],
+                ),
                 starting_catalog_sequence_number: self.state.catalog.sequence,
             })
             .collect();
@@ -473,6 +1038,23 @@ impl<'lp> WriteValidator<LinesParsed<'lp, v3::ParsedLine<'lp>>> {
     }
 }
 
+/// The ops written to the WAL for the write to a segment, which are the catalog changes made for
+/// the write, followed by the write itself
+///
+/// The catalog ops are written to every segment that the write spans, so that each segment can
+/// be replayed on its own.
+fn wal_ops_for_write(
+    catalog_ops: &[CatalogOp],
+    writes: impl IntoIterator<Item = WalOp>,
+) -> Vec<WalOp> {
+    catalog_ops
+        .iter()
+        .cloned()
+        .map(WalOp::Catalog)
+        .chain(writes)
+        .collect()
+}
+
 fn convert_v3_parsed_line<'a>(
     line: v3::ParsedLine<'_>,
     raw_line: &'a str,
@@ -566,12 +1148,15 @@ impl<'lp> WriteValidator<LinesParsed<'lp, ParsedLine<'lp>>> {
                 database_name: self.state.catalog.db_name.clone(),
                 segment_start,
                 table_batches: table_batches.table_batches,
-                wal_op: WalOp::LpWrite(LpWriteOp {
-                    db_name: self.state.catalog.db_name.to_string(),
-                    lp: table_batches.lines.join("\n"),
-                    default_time: ingest_time.timestamp_nanos(),
-                    precision,
-                }),
+                wal_ops: wal_ops_for_write(
+                    &self.state.catalog_ops,
+                    [WalOp::LpWrite(LpWriteOp {
+                        db_name: self.state.catalog.db_name.to_string(),
+                        lp: table_batches.lines.join("\n"),
+                        default_time: ingest_time.timestamp_nanos(),
+                        precision,
+                    })],
+                ),
                 starting_catalog_sequence_number: self.state.catalog.sequence,
             })
             .collect();
@@ -652,6 +1237,115 @@ fn convert_v1_parsed_line<'a>(
     table_batch_map.lines.push(raw_line);
 }
 
+impl WriteValidator<BatchesValidated> {
+    /// Convert the validated record batches to a [`ValidatedLines`] which will be buffered and
+    /// written to the WAL, if configured.
+    ///
+    /// The rows of each batch are split into the segments their times fall in, and the rows for
+    /// each segment are written to its WAL as a record batch of their own. This only fails if the
+    /// rows can't be encoded for the WAL.
+    pub(crate) fn convert_batches_to_buffer(
+        self,
+        ingest_time: Time,
+        segment_duration: SegmentDuration,
+    ) -> Result<ValidatedLines> {
+        let db_name = self.state.catalog.db_name;
+        let table_name = self.state.table_name;
+        let mut segment_writes: HashMap<Time, (TableBatch, Vec<WalOp>)> = HashMap::new();
+        let mut line_count = 0;
+        let mut field_count = 0;
+        let mut index_count = 0;
+
+        for valid_batch in self.state.batches {
+            line_count += valid_batch.times.len();
+            let mut segment_rows: HashMap<Time, Vec<u32>> = HashMap::new();
+            for (row, &time) in valid_batch.times.iter().enumerate() {
+                let row_data = valid_batch.row(row);
+                for field in &row_data.fields {
+                    match field.value {
+                        FieldData::Timestamp(_) => (),
+                        FieldData::Key(_) | FieldData::Tag(_) => index_count += 1,
+                        _ => field_count += 1,
+                    }
+                }
+
+                let segment_start = segment_duration.start_time(time / 1_000_000_000);
+                segment_rows
+                    .entry(segment_start)
+                    .or_default()
+                    .push(row as u32);
+                segment_writes
+                    .entry(segment_start)
+                    .or_default()
+                    .0
+                    .rows
+                    .push(row_data);
+            }
+
+            let spans_segments = segment_rows.len() > 1;
+            for (segment_start, rows) in segment_rows {
+                let batch = if spans_segments {
+                    take_record_batch(&valid_batch.batch, &UInt32Array::from(rows))?
+                } else {
+                    valid_batch.batch.clone()
+                };
+                let write = ArrowWriteOp::new(
+                    db_name.as_str(),
+                    table_name.as_str(),
+                    ingest_time.timestamp_nanos(),
+                    &batch,
+                )?;
+                segment_writes
+                    .entry(segment_start)
+                    .or_default()
+                    .1
+                    .push(WalOp::ArrowWrite(write));
+            }
+        }
+
+        let valid_segmented_data = segment_writes
+            .into_iter()
+            .map(
+                |(segment_start, (table_batch, writes))| ValidSegmentedData {
+                    database_name: db_name.clone(),
+                    segment_start,
+                    table_batches: HashMap::from([(table_name.clone(), table_batch)]),
+                    wal_ops: wal_ops_for_write(&self.state.catalog_ops, writes),
+                    starting_catalog_sequence_number: self.state.catalog.sequence,
+                },
+            )
+            .collect();
+
+        Ok(ValidatedLines {
+            line_count,
+            field_count,
+            index_count,
+            errors: vec![],
+            valid_segmented_data,
+        })
+    }
+
+    /// Convert the validated record batches to the rows of the table in each segment their
+    /// times fall in, keyed by the start time of the segment, along with the changes made to
+    /// the catalog for them. This is used for imports, whose rows are persisted directly rather
+    /// than buffered and written to the WAL.
+    pub(crate) fn convert_batches_to_segment_rows(
+        self,
+        segment_duration: SegmentDuration,
+    ) -> (Vec<CatalogOp>, BTreeMap<Time, Vec<Row>>) {
+        let mut segment_rows: BTreeMap<Time, Vec<Row>> = BTreeMap::new();
+        for valid_batch in &self.state.batches {
+            for (row, &time) in valid_batch.times.iter().enumerate() {
+                segment_rows
+                    .entry(segment_duration.start_time(time / 1_000_000_000))
+                    .or_default()
+                    .push(valid_batch.row(row));
+            }
+        }
+        (self.state.catalog_ops, segment_rows)
+    }
+}
+
 fn apply_precision_to_timestamp(precision: Precision, ts: i64) -> i64 {
     let multiplier = match precision {
         Precision::Auto => match crate::guess_precision(ts) {
@@ -673,12 +1367,25 @@ fn apply_precision_to_timestamp(precision: Precision, ts: i64) -> i64 {
 
 #[cfg(test)]
 mod tests {
-    use std::sync::Arc;
+    use std::{sync::Arc, time::Duration};
 
+    use arrow::{
+        array::{
+            ArrayRef, DictionaryArray, Float64Array, Int64Array, StringArray, TimestampSecondArray,
+        },
+        datatypes::Int32Type,
+        record_batch::RecordBatch,
+    };
     use data_types::NamespaceName;
     use iox_time::Time;
 
-    use crate::{catalog::Catalog, write_buffer::Error, Precision, SegmentDuration};
+    use schema::{InfluxColumnType, InfluxFieldType};
+
+    use crate::{
+        catalog::{Catalog, DatabaseSchema},
+        write_buffer::{Error, FieldData, WriteTimeWindow},
+        Precision, SegmentDuration, WalOp,
+    };
 
     use super::WriteValidator;
 
@@ -708,4 +1415,195 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn write_validator_strict_schema() -> Result<(), Error> {
+        let namespace = NamespaceName::new("test").unwrap();
+        let catalog = Arc::new(Catalog::new());
+        let mut db_schema = DatabaseSchema::new("test");
+        db_schema.strict_schema = true;
+        db_schema
+            .create_table(
+                "cpu",
+                &["host".to_string()],
+                &[("usage".to_string(), InfluxFieldType::Float)],
+                None,
+                false,
+            )
+            .unwrap();
+        catalog.create_database(db_schema).unwrap();
+
+        let result = WriteValidator::initialize(namespace.clone(), Arc::clone(&catalog))?
+            .v1_parse_lines_and_update_schema(
+                "cpu,host=a usage=1 1\n\
+                cpu,host=a,region=b usage=1 2\n\
+                mem,host=a usage=1 3",
+                true,
+            )?
+            .convert_lines_to_buffer(
+                Time::from_timestamp_nanos(0),
+                SegmentDuration::new_5m(),
+                Precision::Auto,
+            );
+
+        assert_eq!(result.line_count, 1);
+        assert_eq!(result.errors.len(), 2);
+        assert_eq!(result.errors[0].line_number, 2);
+        assert!(result.errors[0].error_message.contains("columns [region]"));
+        assert_eq!(result.errors[1].line_number, 3);
+        assert!(result.errors[1]
+            .error_message
+            .contains("write to table mem was rejected"));
+
+        // the schema was not changed by the rejected writes:
+        let db_schema = catalog.db_schema("test").unwrap();
+        assert!(!db_schema.table_exists("mem"));
+        assert_eq!(3, db_schema.get_table("cpu").unwrap().num_columns());
+
+        Ok(())
+    }
+
+    #[test]
+    fn write_validator_time_window() -> Result<(), Error> {
+        let namespace = NamespaceName::new("test").unwrap();
+        let catalog = Arc::new(Catalog::new());
+        let window = WriteTimeWindow {
+            max_write_age: Some(Duration::from_secs(3600)),
+            max_write_future: Some(Duration::from_secs(60)),
+        };
+        let ingest_time = Time::from_timestamp(7200, 0).unwrap();
+
+        let result = WriteValidator::initialize(namespace.clone(), Arc::clone(&catalog))?
+            .with_time_window(window, ingest_time, Precision::Second)
+            .v1_parse_lines_and_update_schema(
+                "cpu,host=a usage=1 3600\n\
+                cpu,host=a usage=1 3599\n\
+                cpu,host=a usage=1 7260\n\
+                mem,host=a usage=1 7261\n\
+                cpu,host=a usage=1",
+                true,
+            )?
+            .convert_lines_to_buffer(ingest_time, SegmentDuration::new_5m(), Precision::Second);
+
+        assert_eq!(result.line_count, 3);
+        assert_eq!(result.errors.len(), 2);
+        assert_eq!(result.errors[0].line_number, 2);
+        assert_eq!(
+            result.errors[0].error_message,
+            "timestamp 1970-01-01T00:59:59+00:00 is more than the max write age of 1h before the \
+            server time"
+        );
+        assert_eq!(result.errors[1].line_number, 4);
+        assert_eq!(
+            result.errors[1].error_message,
+            "timestamp 1970-01-01T02:01:01+00:00 is more than the max write future of 1m after \
+            the server time"
+        );
+        // the rejected lines didn't change the schema:
+        assert!(!catalog.db_schema("test").unwrap().table_exists("mem"));
+
+        // without accepting partial writes, the whole write is rejected:
+        let result = WriteValidator::initialize(namespace, catalog)?
+            .with_time_window(window, ingest_time, Precision::Second)
+            .v1_parse_lines_and_update_schema("cpu,host=a usage=1 3599", false);
+        assert!(
+            matches!(&result, Err(Error::ParseError(e)) if e.error_message.contains("max write age"))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn write_validator_arrow_batches() -> Result<(), Error> {
+        let namespace = NamespaceName::new("test").unwrap();
+        let catalog = Arc::new(Catalog::new());
+        let batch = RecordBatch::try_from_iter([
+            (
+                "host",
+                Arc::new(DictionaryArray::<Int32Type>::from_iter(["a", "b", "a"])) as ArrayRef,
+            ),
+            (
+                "usage",
+                Arc::new(Float64Array::from(vec![Some(0.5), None, Some(1.5)])) as ArrayRef,
+            ),
+            (
+                "time",
+                // the last row is in the next 5 minute segment:
+                Arc::new(TimestampSecondArray::from(vec![1, 2, 301])) as ArrayRef,
+            ),
+        ])
+        .unwrap();
+
+        let result = WriteValidator::initialize(namespace.clone(), Arc::clone(&catalog))?
+            .validate_batches_and_update_schema("cpu", vec![batch])?
+            .convert_batches_to_buffer(Time::from_timestamp_nanos(0), SegmentDuration::new_5m())?;
+
+        assert_eq!(result.line_count, 3);
+        assert_eq!(result.field_count, 2);
+        assert_eq!(result.index_count, 3);
+        assert!(result.errors.is_empty());
+
+        let table = catalog.db_schema("test").unwrap();
+        let table = table.get_table("cpu").unwrap();
+        assert_eq!(
+            table.field_type_by_name("host"),
+            Some(InfluxColumnType::Tag)
+        );
+        assert_eq!(
+            table.field_type_by_name("usage"),
+            Some(InfluxColumnType::Field(InfluxFieldType::Float))
+        );
+
+        // the rows are split between the segments, each of which logs its rows to its WAL:
+        let mut segments = result.valid_segmented_data;
+        segments.sort_by_key(|s| s.segment_start);
+        assert_eq!(segments.len(), 2);
+        let rows = &segments[0].table_batches["cpu"].rows;
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[1].time, 2_000_000_000);
+        assert_eq!(rows[1].fields.len(), 2);
+        assert_eq!(rows[1].fields[0].value, FieldData::Tag("b".to_string()));
+        assert!(matches!(segments[0].wal_ops[0], WalOp::Catalog(_)));
+        let WalOp::ArrowWrite(write) = &segments[1].wal_ops[1] else {
+            panic!("expected an arrow write, got {:?}", segments[1].wal_ops);
+        };
+        assert_eq!(write.table_name, "cpu");
+        let batches = write.record_batches().unwrap();
+        assert_eq!(batches.len(), 1);
+        assert_eq!(batches[0].num_rows(), 1);
+
+        // strings can be written to tags, but a field can't change type:
+        let batch = RecordBatch::try_from_iter([
+            ("host", Arc::new(StringArray::from(vec!["c"])) as ArrayRef),
+            ("usage", Arc::new(Int64Array::from(vec![1])) as ArrayRef),
+            (
+                "time",
+                Arc::new(TimestampSecondArray::from(vec![3])) as ArrayRef,
+            ),
+        ])
+        .unwrap();
+        let result = WriteValidator::initialize(namespace.clone(), Arc::clone(&catalog))?
+            .validate_batches_and_update_schema("cpu", vec![batch]);
+        assert!(
+            matches!(&result, Err(Error::InvalidArrowWrite(e)) if e.contains("column usage has type")),
+            "{:?}",
+            result.err()
+        );
+
+        // batches must have a time column:
+        let batch = RecordBatch::try_from_iter([(
+            "host",
+            Arc::new(StringArray::from(vec!["c"])) as ArrayRef,
+        )])
+        .unwrap();
+        let result = WriteValidator::initialize(namespace, catalog)?
+            .validate_batches_and_update_schema("cpu", vec![batch]);
+        assert!(
+            matches!(&result, Err(Error::InvalidArrowWrite(e)) if e == "batch 1: the batch has no time column"),
+            "{:?}",
+            result.err()
+        );
+
+        Ok(())
+    }
 }
@@ -67,6 +67,16 @@ impl<S> Rewritten<S> {
     }
 }
 
+impl Rewritten<Statement> {
+    /// The name of the measurement being dropped, if this is a `DROP MEASUREMENT` statement
+    pub fn drop_measurement(&self) -> Option<String> {
+        match &self.statement {
+            Statement::DropMeasurement(s) => Some(s.name.as_str().to_string()),
+            _ => None,
+        }
+    }
+}
+
 impl From<Rewritten<Statement>> for Statement {
     fn from(r: Rewritten<Statement>) -> Self {
         r.to_statement()
@@ -552,4 +562,21 @@ mod tests {
         }
         .assert();
     }
+
+    #[test]
+    fn drop_measurement() {
+        assert_eq!(
+            Some("cpu".to_string()),
+            parse_single("DROP MEASUREMENT cpu").drop_measurement()
+        );
+        assert_eq!(
+            Some("cpu load".to_string()),
+            parse_single(r#"DROP MEASUREMENT "cpu load""#).drop_measurement()
+        );
+        assert_eq!(
+            Some(r#"cpu "1""#.to_string()),
+            parse_single(r#"DROP MEASUREMENT "cpu \"1\"""#).drop_measurement()
+        );
+        assert_eq!(None, parse_single("SHOW DATABASES").drop_measurement());
+    }
 }